@@ -2,22 +2,31 @@
 
 use std::{
     borrow::Cow,
+    cell::Cell,
     cmp::{max, min},
     collections::{BTreeMap, HashMap, VecDeque},
+    env,
+    ffi::{CStr, CString, OsString},
     fmt::{Debug, Display, Formatter},
     fs,
     fs::{File, create_dir_all},
     hash::BuildHasherDefault,
+    hint::black_box,
     io,
-    io::{BorrowedBuf, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
-    mem::MaybeUninit,
+    io::{BorrowedBuf, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    mem::{MaybeUninit, offset_of},
     os::{
         fd::{AsFd, OwnedFd},
-        unix::fs::FileExt,
+        unix::{
+            ffi::{OsStrExt, OsStringExt},
+            fs::{DirBuilderExt, FileExt, MetadataExt, OpenOptionsExt, PermissionsExt},
+        },
     },
-    path::{Path, PathBuf},
-    str,
+    path::{Component, Path, PathBuf},
+    slice, str,
     sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
 
 use arrayvec::ArrayVec;
@@ -37,32 +46,43 @@ use rand_xoshiro::{
 };
 use regex::bytes::Regex;
 use ringboard_sdk::{
-    ClientError, DatabaseReader, EntryReader, Kind,
+    BucketUsage, ClientError, DatabaseReader, Entry, EntryReader, Kind,
     api::{
-        AddRequest, GarbageCollectRequest, MoveToFrontRequest, RemoveRequest, SwapRequest,
-        connect_to_paste_server, connect_to_server, connect_to_server_with, send_paste_buffer,
+        AddAlternateRequest, AddRequest, GarbageCollectRequest, MoveToFrontRequest, PasteTarget,
+        RemoveRequest, SetExpiryRequest, SetLabelRequest, SwapRequest, WipeRequest,
+        connect_to_paste_server, connect_to_server, connect_to_server_for_bulk,
+        connect_to_server_with, send_paste_buffer,
     },
-    config::{X11Config, X11V1Config, x11_config_file},
+    config::{X11Config, X11V1Config, x11_chord_keysyms, x11_config_file},
     core::{
-        BucketAndIndex, Error as CoreError, IoErr, NUM_BUCKETS, SendQuitAndWait, acquire_lock_file,
-        bucket_to_length, copy_file_range_all, create_tmp_file,
+        EntryKey, Error as CoreError, IoErr, NUM_BUCKETS, PathView, RingAndIndex, Timestamp,
+        URI_PREFIX, abstract_socket_name, bucket_to_length, copy_to_memfd, create_tmp_file,
+        direct_file_name,
         dirs::{data_dir, paste_socket_file, socket_file},
+        open_buckets,
         protocol::{
-            AddResponse, GarbageCollectResponse, IdNotFoundError, MimeType, MoveToFrontResponse,
-            RemoveResponse, Response, RingKind, SwapResponse, decompose_id,
+            AddAlternateResponse, AddResponse, GarbageCollectResponse, IdNotFoundError,
+            InvalidIdError, Label, MimeType, MoveToFrontResponse, RemoveResponse, Response,
+            RingKind, SetExpiryResponse, SetLabelResponse, SwapResponse, WipeResponse,
+            checked_decompose_id, decompose_id, mime_type_from_bytes,
         },
         read_at_to_end,
-        ring::Mmap,
-        size_to_bucket,
+        ring::{self, Mmap, Ring},
+        sniff_content_kind, socket_addr,
     },
     duplicate_detection::DuplicateDetector,
-    search::{CaselessQuery, EntryLocation, Query, QueryResult},
+    is_text_mime, preview, read_direct_file_at,
+    search::{CaselessQuery, Query, QueryResult},
 };
 use rustc_hash::FxHasher;
 use rustix::{
-    fs::{AtFlags, CWD, MemfdFlags, Mode, OFlags, StatxFlags, memfd_create, openat, statx},
+    fs::{
+        Access, AtFlags, CWD, MemfdFlags, Mode, OFlags, StatxFlags, XattrFlags, access,
+        copy_file_range, fgetxattr, flistxattr, fsetxattr, getxattr, memfd_create, openat, statx,
+    },
+    io::{Errno, dup},
     net::{RecvFlags, SendFlags, SocketAddrUnix, SocketFlags},
-    stdio::stdin,
+    stdio::{stdin, stdout},
 };
 use serde::{Deserialize, Serialize, Serializer, ser::SerializeSeq};
 use thiserror::Error;
@@ -96,28 +116,42 @@ enum Cmd {
     ///
     /// The entry bytes will be outputted to stdout.
     #[command(aliases = ["g", "at", "gimme"])]
-    Get(EntryAction),
+    Get(Get),
 
     /// Searches the Ringboard database for entries matching a query.
     #[command(aliases = ["f", "find", "query"])]
     Search(Search),
 
+    /// Count the entries in each ring.
+    ///
+    /// Unlike `debug stats`, entry contents are never read so this stays fast
+    /// no matter how large the history grows.
+    #[command(alias = "len")]
+    Count(Count),
+
     /// Add an entry to the database.
     ///
     /// Prints the ID of the newly added entry.
     #[command(aliases = ["a", "new", "create", "copy"])]
     Add(Add),
 
+    /// Copy an existing entry to the clipboard.
+    ///
+    /// A clipboard watcher (X11 or Wayland) must be running to hand the entry
+    /// to other apps.
+    #[command(alias = "p")]
+    Paste(Paste),
+
     /// Favorite an entry.
     #[command(alias = "star")]
-    Favorite(EntryAction),
+    Favorite(Favorite),
 
     /// Unfavorite an entry.
     #[command(alias = "unstar")]
     Unfavorite(EntryAction),
 
     /// Move an entry to the front, making it the most recent entry.
-    MoveToFront(EntryAction),
+    MoveToFront(MoveToFront),
 
     /// Swap the positions of two entries.
     ///
@@ -129,20 +163,60 @@ enum Cmd {
     /// swapping it into place, and deleting the swapped out entry.
     Swap(Swap),
 
+    /// Attach a human readable label to an entry.
+    ///
+    /// Labels are shown alongside the entry in the clipboard viewers and can
+    /// be searched for using mime type searches.
+    #[command(aliases = ["l", "name", "tag"])]
+    Label(LabelEntry),
+
+    /// Label a favorite so it can be recalled by name.
+    ///
+    /// Labeled favorites act as a named snippet store: pass `--label` to
+    /// `get` or `paste` to look a favorite up by its label instead of its ID.
+    #[command(alias = "name-favorite")]
+    RenameFavorite(LabelEntry),
+
+    /// Attach an alternate representation to an entry.
+    ///
+    /// Alternates let an entry carry the same content in several formats, such
+    /// as the HTML and plain text versions of copied rich text. Adding an
+    /// alternate with a mime type the entry already has replaces it.
+    #[command(alias = "alt")]
+    AddAlternate(AddAlternate),
+
     /// Delete an entry from the database.
     #[command(aliases = ["r", "del", "delete", "destroy", "yeet"])]
-    Remove(EntryAction),
+    Remove(Remove),
 
     /// Wipe the entire database.
     ///
+    /// Pass `--main` or `--favorites` to clear only that ring while keeping
+    /// the other one, e.g. to drop transient history but keep your favorites.
+    ///
     /// WARNING: this operation is irreversible. ALL DATA WILL BE LOST.
     #[command(alias = "nuke")]
-    Wipe,
+    Wipe(Wipe),
 
     /// Migrate from other clipboard managers to Ringboard.
     #[command(alias = "migrate")]
     Import(Import),
 
+    /// Back up the raw database files to a self-contained archive.
+    ///
+    /// Unlike a dump, the archive preserves entry IDs, labels, alternates, and
+    /// the bucket layout so `restore-archive` recreates the database exactly,
+    /// e.g. when migrating to another machine. Entries copied while the export
+    /// is running may be missing from the archive or make it inconsistent.
+    #[command(alias = "backup")]
+    Export(Export),
+
+    /// Replace the database with one saved by `export`.
+    ///
+    /// The server must be stopped while the archive is restored.
+    #[command(alias = "restore")]
+    RestoreArchive(RestoreArchive),
+
     /// Run garbage collection on the database.
     ///
     /// Prints the amount of freed space.
@@ -154,6 +228,14 @@ enum Cmd {
     #[command(subcommand)]
     Configure(Configure),
 
+    /// Diagnose common problems with the Ringboard installation.
+    ///
+    /// Checks that the database directory is usable, that the filesystem
+    /// supports the features Ringboard relies on, and that the server is
+    /// reachable. Each failed check is printed alongside a hint for fixing it.
+    #[command(aliases = ["check", "diagnose"])]
+    Doctor,
+
     /// Debugging tools for developers.
     #[command(aliases = ["d", "dev"])]
     #[command(subcommand)]
@@ -176,13 +258,42 @@ struct ConfigureX11 {
     #[clap(default_value_t = true)]
     #[clap(action = ArgAction::Set)]
     auto_paste: bool,
+
+    /// Also save the PRIMARY selection (highlighted text) to the clipboard
+    /// history.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    #[clap(action = ArgAction::Set)]
+    capture_primary: bool,
+
+    /// The key chord sent to the previously focused application when
+    /// auto-pasting, e.g. `shift+insert` or `ctrl+shift+v`.
+    #[clap(long)]
+    #[clap(default_value = "shift+insert")]
+    #[clap(value_parser = paste_key)]
+    paste_key: String,
+
+    /// Don't save copied text that looks like a password, API key, or other
+    /// secret to the clipboard history.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    #[clap(action = ArgAction::Set)]
+    ignore_secrets: bool,
+}
+
+fn paste_key(s: &str) -> Result<String, String> {
+    if x11_chord_keysyms(s).is_some() {
+        Ok(s.to_string())
+    } else {
+        Err("expected a key optionally prefixed with modifiers, e.g. `ctrl+shift+v`".to_string())
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum Dev {
     /// Print statistics about the Ringboard database.
     #[command(aliases = ["nerd", "kowalski-analysis"])]
-    Stats,
+    Stats(Stats),
 
     /// Dump the database contents for analysis.
     ///
@@ -191,17 +302,22 @@ enum Dev {
     ///{n}  {
     ///{n}    "id": int64,
     ///{n}    "kind": "Human" | "Bytes",
-    ///{n}    "data": (UTF-8 | base64) string
+    ///{n}    "data": (UTF-8 | base64) string,
+    ///{n}    "content": "text" | "uri" | "json" | "xml" | "image" | "binary"
     ///{n}  },
     ///{n}  ...
     ///{n}]
     ///
+    /// With `--checksum`, the last object is instead
+    /// `{"entries": int64, "fnv1a64": hex string}` which import uses to detect
+    /// truncated or corrupted dumps.
+    ///
     /// Note that `$ ringboard import json` expects a JSON stream (wherein each
     /// object appears on its own line instead of being in a list). To import an
     /// export, you can convert the JSON array to a stream with `$ ... | jq -c
     /// .[]`.
     #[command(alias = "export")]
-    Dump,
+    Dump(Dump),
 
     /// Generate a pseudo-random database for testing and performance tuning
     /// purposes.
@@ -209,9 +325,60 @@ enum Dev {
 
     /// Spam the server with random commands.
     Fuzz(Fuzz),
+
+    /// Measure add, get, and search throughput.
+    ///
+    /// Random entries are added to the database before being read back and
+    /// searched, so point the server at a scratch data directory to avoid
+    /// polluting your clipboard history.
+    Bench(Bench),
+}
+
+#[derive(Args, Debug)]
+struct Stats {
+    /// Keep recomputing the statistics at this interval (for example `500ms`
+    /// or `5s`), redrawing the screen each time.
+    ///
+    /// The interval defaults to one second when omitted.
+    #[arg(long, value_name = "INTERVAL")]
+    #[arg(num_args = 0..=1, default_missing_value = "1s")]
+    #[arg(value_parser = humantime::parse_duration)]
+    watch: Option<Duration>,
+}
+
+#[derive(Args, Debug)]
+struct Dump {
+    /// Pretty print the JSON array with one field per line.
+    ///
+    /// This is convenient for small exports that will be read or edited by
+    /// hand, but indentation bloats the output considerably. Prefer the default
+    /// compact form for large exports, especially if they will be piped
+    /// through `jq` which must buffer and parse the whole array.
+    #[clap(short, long)]
+    #[clap(default_value_t = false)]
+    pretty: bool,
+
+    /// Only dump entries added to the same ring after the given entry ID.
+    ///
+    /// This is intended for incremental backups: save the ID of the last
+    /// exported entry and pass it in on the next run. Note that entries are
+    /// only dumped from the ring containing the given ID and that the ID must
+    /// not have been overwritten by the ring wrapping around in the meantime.
+    #[clap(long)]
+    since: Option<u64>,
+
+    /// Append a checksum over all dumped entries.
+    ///
+    /// Importing a dump verifies its checksum if present. Entries are imported
+    /// as they are read, so verification failures are only reported after the
+    /// preceding entries have been added.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    checksum: bool,
 }
 
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
 struct Add {
     /// A file containing the data to be added to the entry.
     ///
@@ -234,16 +401,232 @@ struct Add {
     #[clap(short, long)]
     #[clap(default_value_t = false)]
     copy: bool,
+
+    /// Stream STDIN into a temporary file before handing it to the server.
+    ///
+    /// By default, the server reads STDIN directly, blocking it until the
+    /// writer is done. Use this option when piping in large or slowly produced
+    /// data so the server receives the complete entry at once. Data is copied
+    /// in bounded chunks, so memory usage does not grow with the input size.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    stdin_chunked: bool,
+
+    /// Guess the mime type from the entry's contents instead of its file name.
+    ///
+    /// Images are recognized by their magic bytes. If the contents are
+    /// inconclusive, the mime type is guessed from the file extension as
+    /// usual. When reading from STDIN, this implies `--stdin-chunked`.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    mime_from_content: bool,
+
+    /// Don't add the entry if it is empty or only contains whitespace.
+    ///
+    /// This is useful for scripts that pipe in command output which may be
+    /// blank. When reading from STDIN, this implies `--stdin-chunked`.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    no_copy_if_empty: bool,
+
+    /// Automatically remove the entry after this duration (for example `30s`
+    /// or `5min`).
+    ///
+    /// Useful for sensitive transient data such as one-time passwords. The
+    /// server removes the entry shortly after it expires, even if it was
+    /// moved or favorited in the meantime.
+    #[clap(long)]
+    #[arg(value_parser = humantime::parse_duration)]
+    expire: Option<Duration>,
+}
+
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct Paste {
+    /// The entry ID or its `ringboard://` URI.
+    #[arg(required_unless_present = "label", value_parser = entry_id)]
+    id: Option<u64>,
+
+    /// Paste the most recently used favorite with this label instead of an
+    /// entry ID.
+    #[arg(long, conflicts_with = "id")]
+    label: Option<Label>,
+
+    /// Paste the entry into the focused window after copying it.
+    #[clap(short, long)]
+    trigger: bool,
 }
 
 #[derive(Args, Debug)]
 #[command(arg_required_else_help = true)]
 struct EntryAction {
-    /// The entry ID.
-    #[arg(required = true)]
+    /// The entry ID or its `ringboard://` URI.
+    #[arg(required = true, value_parser = entry_id)]
     id: u64,
 }
 
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct MoveToFront {
+    /// The entry ID or its `ringboard://` URI.
+    #[arg(required_unless_present = "batch", value_parser = entry_id)]
+    id: Option<u64>,
+
+    /// Move several entries to the front such that they end up in the listed
+    /// order, i.e. the first ID becomes the most recent entry.
+    #[arg(long, num_args = 1.., value_name = "ID", conflicts_with = "id")]
+    #[arg(value_parser = entry_id)]
+    batch: Vec<u64>,
+}
+
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct Favorite {
+    /// The entry ID or its `ringboard://` URI.
+    #[arg(required_unless_present = "all_matching", value_parser = entry_id)]
+    id: Option<u64>,
+
+    /// Favorite every entry in the main ring containing this plain-text query.
+    #[arg(long, conflicts_with = "id")]
+    all_matching: Option<String>,
+
+    /// Don't ask for confirmation before favoriting matches.
+    #[arg(short, long, conflicts_with = "id")]
+    yes: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct Get {
+    /// The entry ID or its `ringboard://` URI.
+    ///
+    /// Several comma-separated IDs may be given, in which case they are output
+    /// just like `--entries` except that each entry is preceded by an
+    /// `--- ENTRY <id> ---` header and entries are separated by a newline by
+    /// default.
+    #[arg(required_unless_present_any = ["entries", "label"])]
+    #[arg(value_delimiter = ',', value_parser = entry_id)]
+    ids: Vec<u64>,
+
+    /// Get the most recently used favorite with this label instead of an
+    /// entry ID.
+    #[arg(long, conflicts_with_all = ["ids", "entries"])]
+    label: Option<Label>,
+
+    /// Output several entries one after the other instead of a single entry.
+    ///
+    /// When several entries are requested, IDs which aren't found are reported
+    /// and skipped unless `--strict` is passed.
+    #[arg(long, value_delimiter = ',', num_args = 1.., conflicts_with = "ids")]
+    #[arg(value_parser = entry_id)]
+    entries: Vec<u64>,
+
+    /// Output this string between each of the entries.
+    #[arg(long)]
+    separator: Option<String>,
+
+    /// Output a NUL byte between each of the entries.
+    ///
+    /// Text essentially never contains NUL bytes, so tools such as `xargs -0`
+    /// can split the output even when entries span multiple lines.
+    #[arg(short = '0', long, conflicts_with = "separator")]
+    #[clap(default_value_t = false)]
+    null_separated: bool,
+
+    /// Fail if any of the entries aren't found instead of skipping them.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    strict: bool,
+
+    /// Only output the bytes in `start:end`, with `end` being exclusive.
+    ///
+    /// Either bound may be omitted to use the start or end of the entry.
+    #[arg(long, value_parser = byte_range)]
+    range: Option<ByteRange>,
+
+    /// Output the entry's alternate representation with this mime type
+    /// instead of its primary data.
+    #[clap(short, long, short_alias = 't', alias = "target")]
+    mime_type: Option<MimeType>,
+
+    /// Also overwrite the system clipboard with the entry.
+    ///
+    /// The whole entry is copied regardless of `--range`.
+    #[clap(short, long, conflicts_with = "entries")]
+    #[clap(default_value_t = false)]
+    copy: bool,
+}
+
+fn entry_id(s: &str) -> Result<u64, String> {
+    if s.starts_with(URI_PREFIX) {
+        RingAndIndex::from_uri(s)
+            .map(RingAndIndex::id)
+            .ok_or_else(|| format!("expected a URI of the form `{URI_PREFIX}<id>`"))
+    } else {
+        let id = s
+            .parse()
+            .map_err(|_| format!("expected an entry ID or `{URI_PREFIX}<id>` URI"))?;
+        RingAndIndex::from_id(id)
+            .map(RingAndIndex::id)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ByteRange {
+    start: Option<u64>,
+    end: Option<u64>,
+}
+
+fn byte_range(s: &str) -> Result<ByteRange, String> {
+    let Some((start, end)) = s.split_once(':') else {
+        return Err("expected a range of the form `start:end`".to_string());
+    };
+    let parse = |bound: &str| {
+        if bound.is_empty() {
+            Ok(None)
+        } else {
+            bound
+                .parse()
+                .map(Some)
+                .map_err(|e| format!("{e}: {bound:?}"))
+        }
+    };
+
+    let range = ByteRange {
+        start: parse(start)?,
+        end: parse(end)?,
+    };
+    if let ByteRange {
+        start: Some(start),
+        end: Some(end),
+    } = range
+        && start > end
+    {
+        return Err(format!("start {start} is after end {end}"));
+    }
+    Ok(range)
+}
+
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct Remove {
+    /// The entry ID or its `ringboard://` URI.
+    #[arg(required_unless_present = "older_than", value_parser = entry_id)]
+    id: Option<u64>,
+
+    /// Remove all entries in the main ring older than this duration (for
+    /// example `30days` or `12h`).
+    ///
+    /// Ringboard does not record when entries were added, so ages are inferred
+    /// from the entries stored in their own files: the newest such entry older
+    /// than the duration and every entry behind it are removed. Favorites are
+    /// never removed.
+    #[arg(long, conflicts_with = "id")]
+    #[arg(value_parser = humantime::parse_duration)]
+    older_than: Option<Duration>,
+}
+
 #[derive(Args, Debug)]
 #[command(arg_required_else_help = true)]
 struct Search {
@@ -256,9 +639,58 @@ struct Search {
     #[arg(conflicts_with = "regex")]
     ignore_case: bool,
 
+    /// Only search entries in this ring.
+    #[arg(long)]
+    ring: Option<SearchRing>,
+
+    /// Print the number of matching entries instead of the matches themselves.
+    #[arg(short, long)]
+    count: bool,
+
+    /// Stop after finding this many matching entries.
+    #[arg(short, long)]
+    limit: Option<usize>,
+
+    /// Only match entries whose mime type matches this glob, e.g. `image/*`.
+    ///
+    /// Plain text entries without a mime type are treated as `text/plain`.
+    /// Only text entries are searched for the query, so omit it to list
+    /// binary entries such as images.
+    #[arg(short, long, value_name = "GLOB", value_parser = mime_glob)]
+    mime: Option<Regex>,
+
     /// The query string to search for.
-    #[arg(required = true)]
-    query: String,
+    ///
+    /// May be omitted when searching by `--mime` to match every entry.
+    #[arg(required_unless_present = "mime")]
+    query: Option<String>,
+}
+
+fn mime_glob(s: &str) -> Result<Regex, String> {
+    let pattern = regex::escape(s).replace(r"\*", ".*").replace(r"\?", ".");
+    Regex::new(&format!("^{pattern}$")).map_err(|e| e.to_string())
+}
+
+#[derive(Args, Debug)]
+struct Count {
+    /// Print the counts as a JSON object for scripting.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+enum SearchRing {
+    Favorites,
+    Main,
+}
+
+impl From<SearchRing> for RingKind {
+    fn from(value: SearchRing) -> Self {
+        match value {
+            SearchRing::Favorites => Self::Favorites,
+            SearchRing::Main => Self::Main,
+        }
+    }
 }
 
 #[derive(Args, Debug)]
@@ -273,22 +705,129 @@ struct Swap {
     id2: u64,
 }
 
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct LabelEntry {
+    /// The entry ID.
+    #[arg(required = true)]
+    id: u64,
+
+    /// The label. Pass an empty string to remove an existing label.
+    #[arg(required = true)]
+    label: Label,
+}
+
+#[derive(Args, Debug)]
+#[command(arg_required_else_help = true)]
+struct AddAlternate {
+    /// The entry ID.
+    #[arg(required = true)]
+    id: u64,
+
+    /// A file containing the alternate's data.
+    ///
+    /// A value of `-` may be supplied to indicate that data should be read from
+    /// STDIN.
+    #[arg(value_hint = ValueHint::FilePath)]
+    #[clap(default_value = "-")]
+    data_file: PathBuf,
+
+    /// The alternate's mime type.
+    #[clap(short, long, short_alias = 't', alias = "target")]
+    mime_type: MimeType,
+}
+
 #[derive(Args, Debug)]
 #[command(arg_required_else_help = true)]
 struct Import {
     /// The existing clipboard to import.
-    #[arg(required = true)]
+    #[arg(required_unless_present = "favorites_from")]
     #[arg(requires_if("rb", "database"))]
     #[arg(requires_if("ring", "database"))]
     #[arg(requires_if("ringboard", "database"))]
     #[arg(requires_if("json", "database"))]
-    from: ImportClipboard,
+    #[arg(requires_if("copy-q", "database"))]
+    #[arg(requires_if("copyq", "database"))]
+    #[arg(requires_if("cq", "database"))]
+    from: Option<ImportClipboard>,
 
     /// The existing clipboard's database location.
     ///
     /// This will be automatically inferred by default.
     #[clap(value_hint = ValueHint::AnyPath)]
     database: Option<PathBuf>,
+
+    /// Add every snippet in a file to the favorites ring.
+    ///
+    /// The file may contain one snippet per line or JSON objects in the same
+    /// format as the dump command. Use `-` to read from stdin.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    #[arg(conflicts_with_all = ["from", "database"])]
+    favorites_from: Option<PathBuf>,
+
+    /// Check the database for corruption without importing it.
+    ///
+    /// Only Gnome Clipboard History databases can be validated.
+    #[arg(long)]
+    #[arg(conflicts_with = "favorites_from")]
+    validate: bool,
+
+    /// Replace an imported entry's mime type with another one.
+    ///
+    /// Rules are of the form `old=new` and the first matching rule wins. An
+    /// empty mime type refers to plain text, so `STRING=` maps the legacy X11
+    /// `STRING` target to plain text.
+    #[arg(long = "map-mime", value_name = "OLD=NEW", value_parser = mime_mapping)]
+    #[arg(conflicts_with = "validate")]
+    mime_mappings: Vec<MimeMapping>,
+}
+
+#[derive(Args, Debug)]
+struct Export {
+    /// The archive file to create.
+    ///
+    /// A value of `-` may be supplied to indicate that the archive should be
+    /// written to STDOUT.
+    #[arg(value_hint = ValueHint::FilePath)]
+    output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct RestoreArchive {
+    /// The archive file created by `export`.
+    ///
+    /// A value of `-` may be supplied to indicate that the archive should be
+    /// read from STDIN.
+    #[arg(value_hint = ValueHint::FilePath)]
+    archive: PathBuf,
+
+    /// Replace the existing database instead of refusing to restore over it.
+    #[arg(short, long)]
+    force: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct MimeMapping {
+    from: MimeType,
+    to: MimeType,
+}
+
+fn mime_mapping(s: &str) -> Result<MimeMapping, String> {
+    let Some((from, to)) = s.split_once('=') else {
+        return Err("expected a rule of the form `old=new`".to_string());
+    };
+    let parse = |mime: &str| mime_type_from_bytes(mime.as_bytes()).map_err(|e| e.to_string());
+    Ok(MimeMapping {
+        from: parse(from)?,
+        to: parse(to)?,
+    })
+}
+
+fn map_mime(mappings: &[MimeMapping], mime_type: MimeType) -> MimeType {
+    mappings
+        .iter()
+        .find(|MimeMapping { from, .. }| *from == mime_type)
+        .map_or(mime_type, |MimeMapping { to, .. }| *to)
 }
 
 #[derive(ValueEnum, Copy, Clone, Debug)]
@@ -305,12 +844,35 @@ enum ImportClipboard {
     #[value(aliases = ["gp", "gpaste"])]
     GPaste,
 
+    /// [CopyQ](https://github.com/hluk/CopyQ)
+    ///
+    /// CopyQ's binary tab files aren't read directly: the database argument
+    /// must be a tab-separated export with one item per line holding the mime
+    /// type, the item's comma-separated tags, and its contents. Text is escaped
+    /// with `\\`, `\t`, `\r`, and `\n` while other contents are base64 encoded.
+    /// Tagged items are added to the favorites. The Ringboard README lists the
+    /// `copyq eval` command which produces this export.
+    // Make sure to update the Import::from requires_ifs when changing aliases
+    #[value(aliases = ["cq", "copyq"])]
+    CopyQ,
+
     /// A sequence of JSON objects in the same format as the dump command.
     // Make sure to update the Import::from requires_ifs when changing aliases
     #[value(aliases = ["rb", "ring", "ringboard"])]
     Json,
 }
 
+#[derive(Args, Debug)]
+struct Wipe {
+    /// Only wipe the favorites ring.
+    #[arg(long, conflicts_with = "main")]
+    favorites: bool,
+
+    /// Only wipe the main ring, keeping favorites intact.
+    #[arg(long)]
+    main: bool,
+}
+
 #[derive(Args, Debug)]
 struct GarbageCollect {
     /// The maximum amount of garbage (in bytes) that is tolerable.
@@ -320,6 +882,11 @@ struct GarbageCollect {
     #[arg(short, long)]
     #[arg(default_value_t = 0)]
     max_wasted_bytes: u64,
+
+    /// Report how much space was reclaimed from each bucket and how many
+    /// direct files were removed.
+    #[arg(short, long)]
+    verbose: bool,
 }
 
 #[derive(Args, Debug)]
@@ -368,16 +935,54 @@ struct Fuzz {
     verbose: bool,
 }
 
+#[derive(Args, Debug)]
+struct Bench {
+    /// The RNG seed.
+    #[clap(long)]
+    #[clap(default_value = "42")]
+    seed: u64,
+
+    /// The number of random entries to add.
+    #[clap(short, long = "entries", alias = "num-entries")]
+    #[clap(value_parser = si_number::< u32 >)]
+    #[clap(default_value = "10_000")]
+    num_entries: u32,
+
+    /// The mean entry size.
+    #[clap(short, long)]
+    #[clap(value_parser = si_number::< u32 >)]
+    #[clap(default_value = "512")]
+    mean_size: u32,
+
+    /// The coefficient of variation of the entry size.
+    #[clap(short, long)]
+    #[clap(value_parser = si_number::< u32 >)]
+    #[clap(default_value = "10")]
+    cv_size: u32,
+
+    /// The number of random entry reads to perform.
+    #[clap(short, long)]
+    #[clap(value_parser = si_number::< u32 >)]
+    #[clap(default_value = "100_000")]
+    gets: u32,
+
+    /// The number of searches to perform.
+    #[clap(short, long)]
+    #[clap(value_parser = si_number::< u32 >)]
+    #[clap(default_value = "100")]
+    searches: u32,
+}
+
 #[derive(Error, Debug)]
 enum CliError {
     #[error("{0}")]
     Core(#[from] CoreError),
     #[error("{0}")]
     Sdk(#[from] ClientError),
-    #[error("failed to delete or copy files")]
-    Fuc(#[from] fuc_engine::Error),
     #[error("database not found")]
     DatabaseNotFound(PathBuf),
+    #[error("clipboard watcher not running")]
+    PasteServerNotFound(PathBuf),
     #[error("JSON (de)serialization failed")]
     SerdeJson(#[from] serde_json::Error),
     #[error("Quick XML (de)serialization failed")]
@@ -392,6 +997,20 @@ enum CliError {
     Regex(#[from] regex::Error),
     #[error("internal search error")]
     InternalSearchError,
+    #[error("invalid entry ID in entry ending on line {line}")]
+    InvalidImportId { line: u64, error: InvalidIdError },
+    #[error("dump checksum mismatch")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("unexpected entry after the dump checksum in entry ending on line {line}")]
+    RecordAfterChecksum { line: u64 },
+    #[error("server is running")]
+    ServerRunning(PathBuf),
+    #[error("database already exists")]
+    DatabaseNotEmpty(PathBuf),
+    #[error("archive version mismatch")]
+    ArchiveVersionMismatch { archive: u8, supported: u8 },
+    #[error("{failures} check(s) failed")]
+    ChecksFailed { failures: u32 },
 }
 
 #[derive(Error, Debug)]
@@ -408,22 +1027,47 @@ fn main() -> error_stack::Result<(), Wrapper> {
         let wrapper = Wrapper::W(e.to_string());
         match e {
             CliError::Core(e) => e.into_report(wrapper),
-            CliError::Fuc(fuc_engine::Error::Io { error, context }) => Report::new(error)
-                .attach_printable(context)
-                .change_context(wrapper),
             CliError::Sdk(e) => e.into_report(wrapper),
             CliError::DatabaseNotFound(db) => Report::new(wrapper)
                 .attach_printable(
                     "Make sure to run the Ringboard server or fix the XDG_DATA_HOME path.",
                 )
                 .attach_printable(format!("Expected database directory: {:?}", db.display())),
-            CliError::Fuc(e) => Report::new(e).change_context(wrapper),
+            CliError::PasteServerNotFound(socket) => Report::new(wrapper)
+                .attach_printable(
+                    "Start a clipboard watcher (X11 or Wayland) or set PASTE_SOCK to the socket \
+                     the watcher uses.",
+                )
+                .attach_printable(format!("Expected paste socket: {socket:?}")),
             CliError::SerdeJson(e) => Report::new(e).change_context(wrapper),
             CliError::QuickXml(e) => Report::new(e).change_context(wrapper),
             CliError::QuickXmlDe(e) => Report::new(e).change_context(wrapper),
             CliError::QuickXmlAttr(e) => Report::new(e).change_context(wrapper),
             CliError::Toml(e) => Report::new(e).change_context(wrapper),
             CliError::Regex(e) => Report::new(e).change_context(wrapper),
+            CliError::InvalidImportId { line: _, error } => {
+                Report::new(error).change_context(wrapper)
+            }
+            CliError::ChecksumMismatch { expected, actual } => Report::new(wrapper)
+                .attach_printable(format!("Expected: {expected}"))
+                .attach_printable(format!("Actual: {actual}"))
+                .attach_printable("The dump was likely truncated or modified."),
+            CliError::RecordAfterChecksum { line: _ } => Report::new(wrapper)
+                .attach_printable("The checksum must be the last object in a dump."),
+            CliError::ServerRunning(lock) => Report::new(wrapper)
+                .attach_printable("Stop the Ringboard server before restoring an archive.")
+                .attach_printable(format!("Server lock file: {lock:?}")),
+            CliError::DatabaseNotEmpty(db) => Report::new(wrapper)
+                .attach_printable("Pass --force to replace it with the archive's contents.")
+                .attach_printable(format!("Database directory: {db:?}")),
+            CliError::ArchiveVersionMismatch { archive, supported } => Report::new(wrapper)
+                .attach_printable(format!(
+                    "The archive contains database version {archive} but this build uses \
+                     version {supported}."
+                ))
+                .attach_printable("Restore it with a matching version of Ringboard."),
+            CliError::ChecksFailed { failures: _ } => Report::new(wrapper)
+                .attach_printable("Follow the hints of the failed checks above and rerun the doctor."),
             CliError::InternalSearchError => Report::new(wrapper).attach_printable(
                 "Please report this bug at https://github.com/SUPERCILEX/clipboard-history/issues/new",
             ),
@@ -442,55 +1086,293 @@ fn run() -> Result<(), CliError> {
 
     let server_addr = {
         let socket_file = socket_file();
-        SocketAddrUnix::new(&socket_file)
-            .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))?
+        socket_addr(&socket_file)?
     };
     match cmd {
         Cmd::Get(data) => get(data),
         Cmd::Search(data) => search(data),
+        Cmd::Count(data) => count(data),
         Cmd::Add(data) => add(connect_to_server(&server_addr)?, data),
-        Cmd::Favorite(data) => move_to_front(
+        Cmd::Paste(data) => paste(data),
+        Cmd::Favorite(Favorite {
+            id: Some(id),
+            all_matching: _,
+            yes: _,
+        }) => move_to_front(
             connect_to_server(&server_addr)?,
-            data,
+            EntryAction { id },
             Some(RingKind::Favorites),
         ),
+        Cmd::Favorite(Favorite {
+            id: None,
+            all_matching: Some(query),
+            yes,
+        }) => favorite_all_matching(connect_to_server(&server_addr)?, &query, yes),
         Cmd::Unfavorite(data) => {
             move_to_front(connect_to_server(&server_addr)?, data, Some(RingKind::Main))
         }
-        Cmd::MoveToFront(data) => move_to_front(connect_to_server(&server_addr)?, data, None),
+        Cmd::MoveToFront(MoveToFront {
+            id: Some(id),
+            batch: _,
+        }) => move_to_front(connect_to_server(&server_addr)?, EntryAction { id }, None),
+        Cmd::MoveToFront(MoveToFront { id: None, batch }) => {
+            move_to_front_batch(connect_to_server(&server_addr)?, &batch)
+        }
         Cmd::Swap(data) => swap(connect_to_server(&server_addr)?, data),
-        Cmd::Remove(data) => remove(connect_to_server(&server_addr)?, data),
-        Cmd::Wipe => wipe(),
+        Cmd::Label(data) => label(connect_to_server(&server_addr)?, data),
+        Cmd::RenameFavorite(data) => rename_favorite(connect_to_server(&server_addr)?, data),
+        Cmd::AddAlternate(data) => add_alternate(connect_to_server(&server_addr)?, data),
+        Cmd::Remove(Remove {
+            id: Some(id),
+            older_than: _,
+        }) => remove(connect_to_server(&server_addr)?, EntryAction { id }),
+        Cmd::Remove(Remove {
+            id: None,
+            older_than: Some(older_than),
+        }) => remove_older_than(connect_to_server(&server_addr)?, older_than),
+        Cmd::Favorite(Favorite {
+            id: None,
+            all_matching: None,
+            yes: _,
+        })
+        | Cmd::Remove(Remove {
+            id: None,
+            older_than: None,
+        }) => unreachable!(),
+        Cmd::Wipe(data) => wipe(connect_to_server(&server_addr)?, data),
         Cmd::GarbageCollect(data) => garbage_collect(connect_to_server(&server_addr)?, data),
-        Cmd::Import(data) => import(connect_to_server(&server_addr)?, data),
+        Cmd::Import(Import {
+            from: Some(ImportClipboard::GnomeClipboardHistory),
+            database,
+            favorites_from: _,
+            validate: true,
+            mime_mappings: _,
+        }) => validate_gch(database),
+        Cmd::Import(data) => import(
+            connect_to_server_for_bulk(&server_addr, SocketFlags::empty())?,
+            data,
+        ),
+        Cmd::Export(data) => export(data),
+        Cmd::RestoreArchive(data) => restore_archive(data),
         Cmd::Configure(Configure::X11(data)) => configure_x11(data),
-        Cmd::Debug(Dev::Stats) => stats(),
-        Cmd::Debug(Dev::Dump) => dump(),
-        Cmd::Debug(Dev::Generate(data)) => generate(connect_to_server(&server_addr)?, data),
+        Cmd::Doctor => doctor(&server_addr),
+        Cmd::Debug(Dev::Stats(data)) => stats(data),
+        Cmd::Debug(Dev::Dump(data)) => dump(data),
+        Cmd::Debug(Dev::Generate(data)) => generate(
+            connect_to_server_for_bulk(&server_addr, SocketFlags::empty())?,
+            data,
+        ),
         Cmd::Debug(Dev::Fuzz(data)) => fuzz(&server_addr, data),
+        Cmd::Debug(Dev::Bench(data)) => bench(
+            connect_to_server_for_bulk(&server_addr, SocketFlags::empty())?,
+            data,
+        ),
     }
 }
 
 fn open_db() -> Result<(DatabaseReader, EntryReader), CliError> {
-    let mut database = data_dir();
-    if !database
-        .try_exists()
-        .map_io_err(|| format!("Failed to check that database exists: {database:?}"))?
-    {
-        return Err(CliError::DatabaseNotFound(database));
-    }
+    open_db_with(DatabaseReader::open)
+}
 
-    Ok((
-        DatabaseReader::open(&mut database)?,
-        EntryReader::open(&mut database)?,
-    ))
+/// Like [`open_db`], but ignores entries added while the caller is scanning.
+fn open_db_snapshot() -> Result<(DatabaseReader, EntryReader), CliError> {
+    open_db_with(DatabaseReader::open_snapshot)
+}
+
+fn open_db_with(
+    open: impl FnOnce(&mut PathBuf) -> Result<DatabaseReader, CoreError>,
+) -> Result<(DatabaseReader, EntryReader), CliError> {
+    let mut database = existing_data_dir()?;
+    Ok((open(&mut database)?, EntryReader::open(&mut database)?))
+}
+
+fn existing_data_dir() -> Result<PathBuf, CliError> {
+    let database = data_dir();
+    if !database
+        .try_exists()
+        .map_io_err(|| format!("Failed to check that database exists: {database:?}"))?
+    {
+        return Err(CliError::DatabaseNotFound(database));
+    }
+    Ok(database)
 }
 
-fn get(EntryAction { id }: EntryAction) -> Result<(), CliError> {
+fn get(
+    Get {
+        ids,
+        label,
+        entries,
+        separator,
+        null_separated,
+        strict,
+        range,
+        mime_type,
+        copy,
+    }: Get,
+) -> Result<(), CliError> {
     let (database, mut reader) = open_db()?;
-    let entry = database.get_raw(id)?;
-    io::copy(&mut *entry.to_file(&mut reader)?, &mut io::stdout().lock())
-        .map_io_err(|| "Failed to write entry to stdout")?;
+    let mut out = io::stdout().lock();
+
+    let entry = if let Some(label) = label {
+        Some(favorite_by_label(&database, &mut reader, &label)?)
+    } else if let [id] = *ids {
+        Some(database.get_raw(id)?)
+    } else {
+        None
+    };
+    if let Some(entry) = entry {
+        write_entry(entry, &mut reader, range, mime_type, &mut out)?;
+        if copy {
+            copy_to_clipboard(entry, &mut reader, false)?;
+        }
+        return Ok(());
+    }
+
+    if copy {
+        return Err(io::Error::from(ErrorKind::InvalidInput))
+            .map_io_err(|| "Only a single entry can be copied.")?;
+    }
+    let headers = !ids.is_empty();
+    let ids = if headers { ids } else { entries };
+    let skip_missing = !strict && ids.len() > 1;
+    let separator = if null_separated {
+        Some(&b"\0"[..])
+    } else if let Some(separator) = &separator {
+        Some(separator.as_bytes())
+    } else if headers {
+        Some(&b"\n"[..])
+    } else {
+        None
+    };
+    let mut first = true;
+    for id in ids {
+        let entry = match database.get_raw(id) {
+            Err(e) if skip_missing => {
+                eprintln!("Skipping entry {id}: {e}");
+                continue;
+            }
+            r => r?,
+        };
+        if !first && let Some(separator) = separator {
+            out.write_all(separator)
+                .map_io_err(|| "Failed to write separator to stdout")?;
+        }
+        first = false;
+
+        if headers {
+            writeln!(out, "--- ENTRY {id} ---")
+                .map_io_err(|| "Failed to write entry header to stdout")?;
+        }
+        write_entry(entry, &mut reader, range, mime_type, &mut out)?;
+    }
+    Ok(())
+}
+
+fn write_entry(
+    entry: Entry,
+    reader: &mut EntryReader,
+    range: Option<ByteRange>,
+    mime_type: Option<MimeType>,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    let alternate;
+    let loaded;
+    let mut file: &File = if let Some(mime_type) = mime_type {
+        let Some((_, file)) = entry
+            .alternates(reader)?
+            .into_iter()
+            .find(|(alternate, _)| *alternate == mime_type)
+        else {
+            return Err(io::Error::from(ErrorKind::NotFound)).map_io_err(|| {
+                format!(
+                    "Entry {} has no alternate with mime type {mime_type:?}.",
+                    entry.id()
+                )
+            })?;
+        };
+        alternate = file;
+        &alternate
+    } else {
+        loaded = entry.to_file(reader)?;
+        &loaded
+    };
+
+    let Some(ByteRange { start, end }) = range else {
+        io::copy(&mut file, out).map_io_err(|| "Failed to write entry to stdout")?;
+        return Ok(());
+    };
+
+    let len = file
+        .metadata()
+        .map_io_err(|| "Failed to get entry size.")?
+        .len();
+    let start = start.unwrap_or(0);
+    let end = end.unwrap_or(len);
+    if start > end || end > len {
+        return Err(io::Error::from(ErrorKind::InvalidInput)).map_io_err(|| {
+            format!("Range {start}:{end} is out of bounds for an entry of {len} bytes.")
+        })?;
+    }
+
+    file.seek(SeekFrom::Start(start))
+        .map_io_err(|| "Failed to seek to start of range.")?;
+    io::copy(&mut file.take(end - start), out).map_io_err(|| "Failed to write entry to stdout")?;
+    Ok(())
+}
+
+fn count(Count { json }: Count) -> Result<(), CliError> {
+    #[derive(Serialize, Default, Debug)]
+    struct RingCounts {
+        entries: u32,
+        bucketed: u32,
+        files: u32,
+    }
+
+    #[derive(Serialize, Debug)]
+    struct Counts {
+        main: RingCounts,
+        favorites: RingCounts,
+    }
+
+    fn count_ring(entries: impl Iterator<Item = Entry>) -> RingCounts {
+        let mut counts = RingCounts::default();
+        for entry in entries {
+            counts.entries += 1;
+            match entry.kind() {
+                Kind::Bucket(_) => counts.bucketed += 1,
+                Kind::File => counts.files += 1,
+            }
+        }
+        counts
+    }
+
+    let database = DatabaseReader::open_snapshot(&mut existing_data_dir()?)?;
+    let counts = Counts {
+        main: count_ring(database.main()),
+        favorites: count_ring(database.favorites()),
+    };
+
+    let mut out = io::stdout().lock();
+    if json {
+        serde_json::to_writer(&mut out, &counts)?;
+        writeln!(out).map_io_err(|| "Failed to write to stdout.")?;
+    } else {
+        for (
+            name,
+            RingCounts {
+                entries,
+                bucketed,
+                files,
+            },
+        ) in [("Main", counts.main), ("Favorites", counts.favorites)]
+        {
+            writeln!(
+                out,
+                "{name}: {entries} entries ({bucketed} bucketed, {files} files)"
+            )
+            .map_io_err(|| "Failed to write to stdout.")?;
+        }
+    }
     Ok(())
 }
 
@@ -498,13 +1380,20 @@ fn search(
     Search {
         regex,
         ignore_case,
+        ring,
+        count,
+        limit,
+        mime,
         query,
     }: Search,
 ) -> Result<(), CliError> {
     const PREFIX_CONTEXT: usize = 40;
     const CONTEXT_WINDOW: usize = 100;
 
-    let (mut database, reader) = open_db()?;
+    let ring = ring.map(RingKind::from);
+    let in_ring = |entry: &Entry| ring.is_none_or(|ring| entry.ring() == ring);
+
+    let (database, reader) = open_db_snapshot()?;
     let mut output = io::stdout().lock();
     let mut print_entry = |entry_id,
                            buf: &[u8],
@@ -522,105 +1411,160 @@ fn search(
             }
         )
         .map_io_err(|| "Failed to write to stdout.")?;
+        // Only text is searched, so binary entries are only listed when filtering by
+        // mime type.
+        if !is_text_mime(mime_type) {
+            return output
+                .write_all(b"(binary data)\n\n")
+                .map_io_err(|| "Failed to write to stdout.");
+        }
 
         let bold_start = start.min(PREFIX_CONTEXT);
-        let (prefix, suffix) = buf.split_at(bold_start);
-        let (middle, suffix) = suffix.split_at((end - start).min(suffix.len()));
-        let mut no_empty_write = |buf: &[u8]| -> Result<(), CoreError> {
+        let mut highlight = (bold_start, bold_start + (end - start));
+        let preview = preview(buf, CONTEXT_WINDOW, Some(&mut highlight));
+        let (prefix, suffix) = preview.split_at(highlight.0);
+        let (middle, suffix) = suffix.split_at(highlight.1 - highlight.0);
+        let mut no_empty_write = |buf: &str| -> Result<(), CoreError> {
             if !buf.is_empty() {
                 output
-                    .write_all(buf)
+                    .write_all(buf.as_bytes())
                     .map_io_err(|| "Failed to write to stdout.")?;
             }
             Ok(())
         };
 
         no_empty_write(prefix)?;
-        no_empty_write(b"\x1b[1m")?;
+        no_empty_write("\x1b[1m")?;
         no_empty_write(middle)?;
-        no_empty_write(b"\x1b[0m")?;
+        no_empty_write("\x1b[0m")?;
         no_empty_write(suffix)?;
-        no_empty_write(b"\n\n")?;
+        no_empty_write("\n\n")?;
 
         Ok(())
     };
 
-    let reader = Arc::new(reader);
-    let (result_stream, threads) = {
-        // TODO https://github.com/rust-lang/rust-clippy/issues/13227
-        #[allow(clippy::redundant_locals)]
-        let query = query;
-        ringboard_sdk::search(
-            if regex {
-                Query::Regex(Regex::new(&query)?)
-            } else if ignore_case {
-                Query::PlainIgnoreCase(CaselessQuery::new(query))
-            } else {
-                Query::Plain(query.as_bytes())
-            },
-            reader.clone(),
-        )
+    let (results, mut reader) = match query.as_deref() {
+        None | Some("") => (None, reader),
+        Some(query) => {
+            let (results, reader) = run_search(
+                if regex {
+                    Query::Regex(Regex::new(query)?)
+                } else if ignore_case {
+                    Query::PlainIgnoreCase(CaselessQuery::new(query))
+                } else {
+                    Query::Plain(query.as_bytes())
+                },
+                reader,
+            )?;
+            (Some(results), reader)
+        }
     };
-    let mut results = BTreeMap::<BucketAndIndex, (u16, u16)>::new();
+
+    let mut matches = 0;
+    for entry in database.favorites().chain(database.main()).filter(in_ring) {
+        if limit.is_some_and(|limit| matches >= limit) {
+            break;
+        }
+        let (start, end) = match &results {
+            None => (0, 0),
+            Some(results) => {
+                let Some(&range) = results.get(&entry.key()) else {
+                    continue;
+                };
+                range
+            }
+        };
+        // Direct entries are only opened if their mime type or contents are needed.
+        let file = match entry.kind() {
+            Kind::File if mime.is_some() || !count => entry.to_stored_file(&reader)?,
+            Kind::Bucket(_) | Kind::File => None,
+        };
+        let mime_type = file
+            .as_ref()
+            .map(|file| file.mime_type())
+            .transpose()?
+            .unwrap_or_default();
+        if let Some(mime) = &mime {
+            let mime_type = if mime_type.is_empty() {
+                "text/plain"
+            } else {
+                &mime_type
+            };
+            if !mime.is_match(mime_type.as_bytes()) {
+                continue;
+            }
+        }
+        matches += 1;
+        if count {
+            continue;
+        }
+        let prefix_start = start.saturating_sub(PREFIX_CONTEXT);
+
+        if let Some(file) = file {
+            let mut buf = [MaybeUninit::uninit(); CONTEXT_WINDOW];
+            let mut buf = BorrowedBuf::from(buf.as_mut_slice());
+            if is_text_mime(&mime_type) {
+                read_direct_file_at(&file, buf.unfilled(), u64::try_from(prefix_start).unwrap())?;
+            }
+
+            print_entry(entry.id(), buf.filled(), &mime_type, start, end)?;
+        } else {
+            let bytes = entry.to_slice(&mut reader)?;
+            print_entry(
+                entry.id(),
+                &bytes[prefix_start..(prefix_start + CONTEXT_WINDOW).min(bytes.len())],
+                &mime_type,
+                start,
+                end,
+            )?;
+        }
+    }
+
+    if count {
+        writeln!(output, "{matches}").map_io_err(|| "Failed to write to stdout.")?;
+    }
+    Ok(())
+}
+
+/// The match range of each matching entry.
+type SearchMatches = HashMap<EntryKey, (usize, usize), BuildHasherDefault<FxHasher>>;
+
+/// Collects the match range of every entry matching the query.
+fn run_search(query: Query, reader: EntryReader) -> Result<(SearchMatches, EntryReader), CliError> {
+    let reader = Arc::new(reader);
+    let (result_stream, threads) = ringboard_sdk::search(query, reader.clone());
+    let mut results = SearchMatches::default();
     for result in result_stream {
         let QueryResult {
             location,
             start,
             end,
         } = result?;
-        match location {
-            EntryLocation::Bucketed { bucket, index } => {
-                results.insert(
-                    BucketAndIndex::new(bucket, index),
-                    (u16::try_from(start).unwrap(), u16::try_from(end).unwrap()),
-                );
-            }
-            EntryLocation::File { entry_id } => {
-                let entry = unsafe { database.get(entry_id)? };
-                let file = entry.to_file_raw(&reader)?.unwrap();
-
-                let mut buf = [MaybeUninit::uninit(); CONTEXT_WINDOW];
-                let mut buf = BorrowedBuf::from(buf.as_mut_slice());
-                read_at_to_end(
-                    &*file,
-                    buf.unfilled(),
-                    u64::try_from(start.saturating_sub(PREFIX_CONTEXT)).unwrap(),
-                )
-                .map_io_err(|| format!("failed to read from direct entry {entry_id}."))?;
-
-                print_entry(entry_id, buf.filled(), &file.mime_type()?, start, end)?;
-            }
-        }
+        results.insert(location.key().map_err(CoreError::from)?, (start, end));
     }
     for thread in threads {
         thread.join().map_err(|_| CliError::InternalSearchError)?;
     }
-    let mut reader = Arc::into_inner(reader).unwrap();
+    Ok((results, Arc::into_inner(reader).unwrap()))
+}
 
-    for entry in database.favorites().chain(database.main()) {
-        let Kind::Bucket(bucket) = entry.kind() else {
-            continue;
-        };
-        let Some(&(start, end)) = results.get(&BucketAndIndex::new(
-            size_to_bucket(bucket.size()),
-            bucket.index(),
-        )) else {
-            continue;
-        };
-        let (start, end) = (usize::from(start), usize::from(end));
+fn sniff_mime_type(file: &File) -> Result<Option<MimeType>, CliError> {
+    let mut buf = [MaybeUninit::uninit(); 512];
+    let mut buf = BorrowedBuf::from(buf.as_mut_slice());
+    read_at_to_end(file, buf.unfilled(), 0).map_io_err(|| "Failed to read entry contents.")?;
+    let data = buf.filled();
 
-        let bytes = entry.to_slice(&mut reader)?;
-        let prefix_start = start.saturating_sub(PREFIX_CONTEXT);
-        print_entry(
-            entry.id(),
-            &bytes[prefix_start..(prefix_start + CONTEXT_WINDOW).min(bytes.len())],
-            &bytes.mime_type()?,
-            start,
-            end,
-        )?;
+    // Short image signatures (e.g. "BM" or "P1") are common text prefixes.
+    let is_text = match str::from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if is_text {
+        return Ok(None);
     }
-
-    Ok(())
+    Ok(image::guess_format(data)
+        .ok()
+        .and_then(|format| MimeType::from(format.to_mime_type()).ok()))
 }
 
 fn add(
@@ -630,26 +1574,65 @@ fn add(
         favorite,
         mime_type,
         copy,
+        stdin_chunked,
+        mime_from_content,
+        no_copy_if_empty,
+        expire,
     }: Add,
 ) -> Result<(), CliError> {
-    let AddResponse::Success { id } = {
+    let expires_at = expire
+        .map(|expire| {
+            Timestamp::now()
+                .checked_add(expire)
+                .ok_or_else(|| io::Error::from(ErrorKind::InvalidInput))
+                .map_io_err(|| format!("Expiry duration is too long: {expire:?}"))
+        })
+        .transpose()?;
+    let response = {
         let file = if data_file == Path::new("-") {
-            None
+            if stdin_chunked || mime_from_content || no_copy_if_empty {
+                let mut file = File::from(
+                    memfd_create(c"ringboard_stdin", MemfdFlags::empty())
+                        .map_io_err(|| "Failed to create data entry file.")?,
+                );
+                io::copy(&mut io::stdin().lock(), &mut file)
+                    .map_io_err(|| "Failed to copy stdin to entry file.")?;
+                file.rewind()
+                    .map_io_err(|| "Failed to reset entry file offset.")?;
+                Some(file)
+            } else {
+                None
+            }
         } else {
             Some(
                 File::open(&data_file)
                     .map_io_err(|| format!("Failed to open file: {data_file:?}"))?,
             )
         };
+        if no_copy_if_empty
+            && let Some(file) = &file
+            && Mmap::from(file)
+                .map_io_err(|| format!("Failed to mmap file: {data_file:?}"))?
+                .iter()
+                .all(u8::is_ascii_whitespace)
+        {
+            println!("Skipped empty entry.");
+            return Ok(());
+        }
+        let sniffed_mime_type = match &file {
+            Some(file) if mime_from_content && mime_type.is_none() => sniff_mime_type(file)?,
+            _ => None,
+        };
 
         AddRequest::response(
-            server,
+            &server,
             if favorite {
                 RingKind::Favorites
             } else {
                 RingKind::Main
             },
             mime_type
+                .or(sniffed_mime_type)
                 .or_else(|| {
                     mime_guess::from_path(data_file)
                         .first_raw()
@@ -659,23 +1642,87 @@ fn add(
             file.as_ref().map_or(stdin(), |file| file.as_fd()),
         )?
     };
+    let AddResponse::Success { id } = response else {
+        return Err(ClientError::RateLimited.into());
+    };
 
     println!("Entry added: {id}");
 
+    if let Some(expires_at) = expires_at {
+        let SetExpiryResponse { error } =
+            SetExpiryRequest::response(&server, id, Some(expires_at))?;
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+    }
+
     if copy {
         let (mut database, mut reader) = open_db()?;
         let entry = unsafe { database.get(id)? };
+        copy_to_clipboard(entry, &mut reader, false)?;
+    }
 
-        let paste_server = {
-            let socket_file = paste_socket_file();
-            let addr = SocketAddrUnix::new(&socket_file)
-                .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))?;
-            connect_to_paste_server(&addr)?
-        };
+    Ok(())
+}
 
-        send_paste_buffer(paste_server, entry, &mut reader, false)?;
+fn paste(Paste { id, label, trigger }: Paste) -> Result<(), CliError> {
+    let (database, mut reader) = open_db()?;
+    let entry = if let Some(label) = label {
+        favorite_by_label(&database, &mut reader, &label)?
+    } else {
+        database.get_raw(id.unwrap())?
+    };
+    copy_to_clipboard(entry, &mut reader, trigger)
+}
+
+/// Favorites are few enough that scanning them beats maintaining an index
+/// which would have to be kept in sync with every label change.
+fn favorite_by_label(
+    database: &DatabaseReader,
+    reader: &mut EntryReader,
+    label: &Label,
+) -> Result<Entry, CliError> {
+    if label.is_empty() {
+        return Err(io::Error::from(ErrorKind::InvalidInput))
+            .map_io_err(|| "Cannot look up favorites by an empty label.")?;
     }
+    for entry in database.favorites().rev() {
+        if entry.label(reader)? == *label {
+            return Ok(entry);
+        }
+    }
+    Err(io::Error::from(ErrorKind::NotFound))
+        .map_io_err(|| format!("No favorite is labeled {label:?}."))?
+}
 
+fn copy_to_clipboard(
+    entry: Entry,
+    reader: &mut EntryReader,
+    trigger_paste: bool,
+) -> Result<(), CliError> {
+    let paste_server = {
+        let socket_file = paste_socket_file();
+        let addr = socket_addr(&socket_file)?;
+        match connect_to_paste_server(&addr) {
+            Err(ClientError::Core(CoreError::Io { error, context: _ }))
+                if matches!(
+                    error.kind(),
+                    ErrorKind::NotFound | ErrorKind::ConnectionRefused
+                ) =>
+            {
+                return Err(CliError::PasteServerNotFound(socket_file));
+            }
+            r => r?,
+        }
+    };
+
+    send_paste_buffer(
+        paste_server,
+        entry,
+        reader,
+        trigger_paste,
+        PasteTarget::default(),
+    )?;
     Ok(())
 }
 
@@ -696,6 +1743,93 @@ fn move_to_front(
     Ok(())
 }
 
+fn move_to_front_batch(server: impl AsFd, ids: &[u64]) -> Result<(), CliError> {
+    let mut new_ids = Vec::with_capacity(ids.len());
+    let mut recv = |flags| {
+        unsafe { MoveToFrontRequest::recv(&server, flags) }.and_then(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| match value {
+                MoveToFrontResponse::Success { id } => {
+                    new_ids.push(id);
+                    Ok(())
+                }
+                MoveToFrontResponse::Error(e) => Err(e.into()),
+            },
+        )
+    };
+    let mut pending_requests = 0;
+    // Move the last entry first so the first entry ends up the most recent.
+    for &id in ids.iter().rev() {
+        pipeline_request(
+            |flags| MoveToFrontRequest::send(&server, id, None, flags),
+            &mut recv,
+            &mut pending_requests,
+        )?;
+    }
+    drain_requests(recv, 0, &mut pending_requests)?;
+
+    for id in new_ids.iter().rev() {
+        println!("Entry moved: {id}");
+    }
+    Ok(())
+}
+
+fn favorite_all_matching(server: impl AsFd, query: &str, yes: bool) -> Result<(), CliError> {
+    let (database, reader) = open_db_snapshot()?;
+    let (results, _) = run_search(Query::Plain(query.as_bytes()), reader)?;
+    // Entries that are already favorites would just be bumped to the front.
+    let matches = database
+        .main()
+        .filter(|entry| results.contains_key(&entry.key()))
+        .map(|entry| entry.id())
+        .collect::<Vec<_>>();
+    if matches.is_empty() {
+        println!("No entries to favorite.");
+        return Ok(());
+    }
+
+    if !yes {
+        let Answer::Yes = ask::ask(
+            format!("Favorite {} entries? [y/N] ", matches.len()),
+            Answer::No,
+            &mut io::stdin(),
+            &mut io::stdout(),
+        )
+        .map_io_err(|| "Failed to ask for confirmation.")?
+        else {
+            println!("Aborting.");
+            std::process::exit(1)
+        };
+    }
+
+    let recv = |flags| {
+        unsafe { MoveToFrontRequest::recv(&server, flags) }.and_then(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| match value {
+                MoveToFrontResponse::Success { id: _ } => Ok(()),
+                MoveToFrontResponse::Error(e) => Err(e.into()),
+            },
+        )
+    };
+    let mut pending_requests = 0;
+    // Favorite the oldest match first so the matches keep their relative order.
+    for &id in &matches {
+        pipeline_request(
+            |flags| MoveToFrontRequest::send(&server, id, Some(RingKind::Favorites), flags),
+            recv,
+            &mut pending_requests,
+        )?;
+    }
+    drain_requests(recv, 0, &mut pending_requests)?;
+
+    println!("Favorited {} entries.", matches.len());
+    Ok(())
+}
+
 fn swap(server: OwnedFd, Swap { id1, id2 }: Swap) -> Result<(), CliError> {
     let SwapResponse { error1, error2 } = SwapRequest::response(server, id1, id2)?;
     if let Some(e) = error1 {
@@ -708,6 +1842,48 @@ fn swap(server: OwnedFd, Swap { id1, id2 }: Swap) -> Result<(), CliError> {
     Ok(())
 }
 
+fn label(server: OwnedFd, LabelEntry { id, label }: LabelEntry) -> Result<(), CliError> {
+    let SetLabelResponse { error } = SetLabelRequest::response(server, id, label)?;
+    if let Some(e) = error {
+        return Err(e.into());
+    }
+    println!("Labeled.");
+
+    Ok(())
+}
+
+fn rename_favorite(server: OwnedFd, data: LabelEntry) -> Result<(), CliError> {
+    let (ring, _) = decompose_id(data.id)?;
+    if ring != RingKind::Favorites {
+        return Err(io::Error::from(ErrorKind::InvalidInput))
+            .map_io_err(|| format!("Entry {} is not a favorite.", data.id))?;
+    }
+    label(server, data)
+}
+
+fn add_alternate(
+    server: OwnedFd,
+    AddAlternate {
+        id,
+        data_file,
+        mime_type,
+    }: AddAlternate,
+) -> Result<(), CliError> {
+    let AddAlternateResponse { error } = if data_file == Path::new("-") {
+        AddAlternateRequest::response(server, id, mime_type, stdin())?
+    } else {
+        let file =
+            File::open(&data_file).map_io_err(|| format!("Failed to open file: {data_file:?}"))?;
+        AddAlternateRequest::response(server, id, mime_type, file)?
+    };
+    if let Some(e) = error {
+        return Err(e.into());
+    }
+    println!("Alternate added.");
+
+    Ok(())
+}
+
 fn remove(server: OwnedFd, EntryAction { id }: EntryAction) -> Result<(), CliError> {
     let RemoveResponse { error } = RemoveRequest::response(server, id)?;
     if let Some(e) = error {
@@ -718,57 +1894,547 @@ fn remove(server: OwnedFd, EntryAction { id }: EntryAction) -> Result<(), CliErr
     Ok(())
 }
 
-fn wipe() -> Result<(), CliError> {
-    let Answer::Yes = ask::ask(
-        "⚠️ Are you sure you want to delete your entire clipboard history? ⚠️ [y/N] ",
-        Answer::No,
-        &mut io::stdin(),
-        &mut io::stdout(),
-    )
-    .map_io_err(|| "Failed to ask for confirmation.")?
+fn remove_older_than(server: impl AsFd, older_than: Duration) -> Result<(), CliError> {
+    let Some(cutoff) = Timestamp::now().checked_sub(older_than) else {
+        println!("Pruned 0 entries.");
+        return Ok(());
+    };
+    let (database, reader) = open_db()?;
+
+    let recv = |flags| {
+        unsafe { RemoveRequest::recv(&server, flags) }.and_then(
+            |Response {
+                 sequence_number: _,
+                 value: RemoveResponse { error },
+             }| { error.map_or_else(|| Ok(()), |e| Err(e.into())) },
+        )
+    };
+    let mut pending_requests = 0;
+    let mut expired = false;
+    let mut num_pruned = 0;
+    for entry in database.main().rev() {
+        if !expired && entry.kind() == Kind::File {
+            let mut file_name = [MaybeUninit::uninit(); 14];
+            let file_name = direct_file_name(&mut file_name, entry.ring(), entry.index());
+            // Moving an entry renames its file which updates the ctime, so the ctime
+            // tracks when the entry was placed in its current position. Stat the file
+            // in the database rather than loading the entry since compressed entries
+            // are decompressed into fresh files.
+            let ctime = statx(
+                reader.direct(),
+                file_name,
+                AtFlags::empty(),
+                StatxFlags::CTIME,
+            )
+            .map_io_err(|| format!("Failed to statx direct file: {file_name:?}"))?
+            .stx_ctime;
+            let ctime = Timestamp::from_secs(u64::try_from(ctime.tv_sec).unwrap_or_default());
+            expired = ctime < cutoff;
+        }
+        if expired {
+            num_pruned += 1;
+            pipeline_request(
+                |flags| RemoveRequest::send(&server, entry.id(), flags),
+                recv,
+                &mut pending_requests,
+            )?;
+        }
+    }
+
+    drain_requests(recv, 0, &mut pending_requests)?;
+    println!("Pruned {num_pruned} entries.");
+    Ok(())
+}
+
+fn wipe(server: impl AsFd, Wipe { favorites, main }: Wipe) -> Result<(), CliError> {
+    let (from, prompt) = if favorites {
+        (
+            Some(RingKind::Favorites),
+            "⚠️ Are you sure you want to delete all of your favorites? ⚠️ [y/N] ",
+        )
+    } else if main {
+        (
+            Some(RingKind::Main),
+            "⚠️ Are you sure you want to delete your history but keep your favorites? ⚠️ [y/N] ",
+        )
+    } else {
+        (
+            None,
+            "⚠️ Are you sure you want to delete your entire clipboard history? ⚠️ [y/N] ",
+        )
+    };
+    let Answer::Yes = ask::ask(prompt, Answer::No, &mut io::stdin(), &mut io::stdout())
+        .map_io_err(|| "Failed to ask for confirmation.")?
     else {
         println!("Aborting.");
         std::process::exit(1)
     };
 
-    let mut data_dir = data_dir();
+    let WipeResponse { entries_removed } = WipeRequest::response(server, from)?;
+    println!("Wiped {entries_removed} entries.");
 
-    data_dir.push(".nuke.server.lock");
-    let mut extra_buffer = data_dir.clone();
-    data_dir.pop();
+    Ok(())
+}
 
-    data_dir.push("server.lock");
-    acquire_lock_file(
-        &mut false,
-        CWD,
-        data_dir.parent().unwrap(),
-        &extra_buffer,
-        &data_dir,
-        SendQuitAndWait,
+/// Archives start with [`ARCHIVE_MAGIC`], the archive version, and the ring
+/// [`Header`](ring::Header) version, followed by one record per directory and
+/// regular file in the database. Each record is a tag, the little endian `u16`
+/// length of its path relative to the database directory, the path, and a
+/// `u32` mode. File records continue with a `u32` count of `user.*` extended
+/// attributes (each a `u8` length prefixed name and `u32` length prefixed
+/// value) and the `u64` length of the contents followed by the contents. An
+/// [`ARCHIVE_RECORD_END`] tag ends the archive.
+const ARCHIVE_MAGIC: [u8; 4] = *b"RBAR";
+const ARCHIVE_VERSION: u8 = 0;
+const ARCHIVE_RECORD_END: u8 = 0;
+const ARCHIVE_RECORD_DIR: u8 = 1;
+const ARCHIVE_RECORD_FILE: u8 = 2;
+
+/// The xattrs the server stores as the NUL separated fields of a direct
+/// entry's metadata file when the filesystem can't hold them.
+const METADATA_FILE_XATTRS: [&[u8]; 4] = [
+    b"user.mime_type",
+    b"user.label",
+    b"user.expires_at",
+    b"user.secret",
+];
+
+fn export(Export { output }: Export) -> Result<(), CliError> {
+    let database = existing_data_dir()?;
+    let to_stdout = output == Path::new("-");
+    let out = if to_stdout {
+        File::from(dup(stdout()).map_io_err(|| "Failed to duplicate STDOUT.")?)
+    } else {
+        File::create(&output).map_io_err(|| format!("Failed to create archive: {output:?}"))?
+    };
+
+    let (num_files, num_bytes) = write_archive(&database, out)?;
+    if !to_stdout {
+        println!("Exported {num_files} files ({num_bytes} bytes) to {output:?}.");
+    }
+    Ok(())
+}
+
+/// Returns the number of files and bytes archived.
+fn write_archive(database: &Path, out: File) -> Result<(u64, u64), CliError> {
+    let ring_version = {
+        let path = database.join(RingKind::Main.file_name());
+        let mut header = [0; size_of::<ring::Header>()];
+        File::open(&path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .map_io_err(|| format!("Failed to read ring header: {path:?}"))?;
+        if header[..ring::MAGIC.len()] != ring::MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Not a Ringboard database.",
+            ))
+            .map_io_err(|| format!("Ring file has invalid magic header: {path:?}"))?;
+        }
+        header[offset_of!(ring::Header, version)]
+    };
+
+    let mut out = BufWriter::new(out);
+    out.write_all(&ARCHIVE_MAGIC)
+        .and_then(|()| out.write_all(&[ARCHIVE_VERSION, ring_version]))
+        .map_io_err(|| "Failed to write archive header.")?;
+
+    let (mut num_files, mut num_bytes) = (0, 0);
+    export_dir(
+        database,
+        Path::new(""),
+        &mut out,
+        &mut num_files,
+        &mut num_bytes,
     )?;
-    data_dir.pop();
+    out.write_all(&[ARCHIVE_RECORD_END])
+        .and_then(|()| out.flush())
+        .map_io_err(|| "Failed to finish archive.")?;
+    Ok((num_files, num_bytes))
+}
 
-    extra_buffer.pop();
-    extra_buffer.set_extension("tmp");
-    match fs::rename(&data_dir, &extra_buffer) {
-        Err(e) if e.kind() == ErrorKind::NotFound => {
-            println!("Nothing to delete");
-            return Ok(());
+fn export_dir(
+    database: &Path,
+    dir: &Path,
+    out: &mut BufWriter<File>,
+    num_files: &mut u64,
+    num_bytes: &mut u64,
+) -> Result<(), CliError> {
+    let path = database.join(dir);
+    let mut children = fs::read_dir(&path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .map_io_err(|| format!("Failed to read directory: {path:?}"))?;
+    children.sort_by_key(fs::DirEntry::file_name);
+
+    for child in children {
+        let relative = dir.join(child.file_name());
+        // The lock belongs to whichever server is running on this machine.
+        if relative == Path::new("server.lock") {
+            continue;
         }
-        r => r,
+        let path = child.path();
+        let metadata = child
+            .metadata()
+            .map_io_err(|| format!("Failed to stat file: {path:?}"))?;
+
+        if metadata.is_dir() {
+            write_archive_record(out, ARCHIVE_RECORD_DIR, &relative, &metadata)?;
+            export_dir(database, &relative, out, num_files, num_bytes)?;
+        } else if metadata.is_file() {
+            let file = File::open(&path).map_io_err(|| format!("Failed to open file: {path:?}"))?;
+            let xattrs = user_xattrs(&file)
+                .map_io_err(|| format!("Failed to read extended attributes: {path:?}"))?;
+            let len = metadata.len();
+
+            write_archive_record(out, ARCHIVE_RECORD_FILE, &relative, &metadata)?;
+            let mut write = || {
+                out.write_all(&archive_len::<u32>(xattrs.len())?.to_le_bytes())?;
+                for (name, value) in &xattrs {
+                    out.write_all(&[archive_len::<u8>(name.as_bytes().len())?])?;
+                    out.write_all(name.as_bytes())?;
+                    out.write_all(&archive_len::<u32>(value.len())?.to_le_bytes())?;
+                    out.write_all(value)?;
+                }
+                out.write_all(&len.to_le_bytes())?;
+                out.flush()
+            };
+            write().map_io_err(|| format!("Failed to write archive record: {path:?}"))?;
+            copy_exact(&file, out.get_ref(), len)
+                .map_io_err(|| format!("Failed to copy file into archive: {path:?}"))?;
+
+            *num_files += 1;
+            *num_bytes += len;
+        }
+    }
+    Ok(())
+}
+
+fn write_archive_record(
+    out: &mut BufWriter<File>,
+    tag: u8,
+    path: &Path,
+    metadata: &fs::Metadata,
+) -> Result<(), CliError> {
+    let path_bytes = path.as_os_str().as_bytes();
+    out.write_all(&[tag])
+        .and_then(|()| out.write_all(&archive_len::<u16>(path_bytes.len())?.to_le_bytes()))
+        .and_then(|()| out.write_all(path_bytes))
+        .and_then(|()| out.write_all(&(metadata.mode() & 0o7777).to_le_bytes()))
+        .map_io_err(|| format!("Failed to write archive record: {path:?}"))
+        .map_err(CliError::from)
+}
+
+fn archive_len<T: TryFrom<usize>>(len: usize) -> io::Result<T> {
+    T::try_from(len).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{len} bytes is too long to store in the archive."),
+        )
+    })
+}
+
+fn user_xattrs(file: &File) -> rustix::io::Result<Vec<(CString, Vec<u8>)>> {
+    let len = match flistxattr(file, &mut []) {
+        Err(Errno::NOTSUP) => return Ok(Vec::new()),
+        r => r?,
+    };
+    let mut names = vec![0; len];
+    let len = flistxattr(file, &mut names)?;
+    let names = unsafe { slice::from_raw_parts(names.as_ptr().cast::<u8>(), len) };
+
+    let mut xattrs = Vec::new();
+    for name in names.split_inclusive(|&b| b == 0) {
+        let Ok(name) = CStr::from_bytes_with_nul(name) else {
+            continue;
+        };
+        if !name.to_bytes().starts_with(b"user.") {
+            continue;
+        }
+        let mut value = vec![0; fgetxattr(file, name, &mut [])?];
+        let len = fgetxattr(file, name, &mut value)?;
+        value.truncate(len);
+        xattrs.push((name.to_owned(), value));
+    }
+    Ok(xattrs)
+}
+
+/// Copies exactly `len` bytes between the current offsets of the files, in the
+/// kernel when possible.
+fn copy_exact(from: &File, mut to: &File, len: u64) -> io::Result<()> {
+    let mut remaining = len;
+    while remaining > 0 {
+        match copy_file_range(
+            from,
+            None,
+            to,
+            None,
+            usize::try_from(remaining).unwrap_or(usize::MAX),
+        ) {
+            Ok(0) => break,
+            Ok(copied) => remaining -= u64::try_from(copied).unwrap(),
+            // Pipes, appending outputs, and some filesystems can't copy in the kernel.
+            // Both offsets have advanced past whatever was copied so far.
+            Err(Errno::INVAL | Errno::XDEV | Errno::BADF | Errno::NOSYS | Errno::OPNOTSUPP) => {
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    if io::copy(&mut from.take(remaining), &mut to)? == remaining {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "File shrank while being copied.",
+        ))
+    }
+}
+
+fn restore_archive(RestoreArchive { archive, force }: RestoreArchive) -> Result<(), CliError> {
+    let database = data_dir();
+    let lock = database.join("server.lock");
+    if lock
+        .try_exists()
+        .map_io_err(|| format!("Failed to check for server lock: {lock:?}"))?
+    {
+        return Err(CliError::ServerRunning(lock));
+    }
+    let non_empty = match fs::read_dir(&database) {
+        Ok(mut dir) => dir.next().is_some(),
+        Err(e) if e.kind() == ErrorKind::NotFound => false,
+        Err(e) => {
+            return Err(e).map_io_err(|| format!("Failed to read directory: {database:?}"))?;
+        }
+    };
+    if non_empty && !force {
+        return Err(CliError::DatabaseNotEmpty(database));
+    }
+
+    let mut archive = if archive == Path::new("-") {
+        File::from(dup(stdin()).map_io_err(|| "Failed to duplicate STDIN.")?)
+    } else {
+        File::open(&archive).map_io_err(|| format!("Failed to open archive: {archive:?}"))?
+    };
+
+    // Extract next to the database so a bad archive leaves the old one intact.
+    let staging = {
+        let mut staging = database.clone().into_os_string();
+        staging.push(".restore");
+        PathBuf::from(staging)
+    };
+    match fs::remove_dir_all(&staging) {
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        r => r.map_io_err(|| format!("Failed to remove old staging directory: {staging:?}"))?,
+    }
+    create_dir_all(&staging)
+        .map_io_err(|| format!("Failed to create staging directory: {staging:?}"))?;
+    let num_files = read_archive(&mut archive, &staging)?;
+
+    if non_empty {
+        fs::remove_dir_all(&database)
+            .map_io_err(|| format!("Failed to remove old database: {database:?}"))?;
     }
-    .map_io_err(|| format!("Failed to rename dir: {data_dir:?} -> {extra_buffer:?}"))?;
+    fs::rename(&staging, &database)
+        .map_io_err(|| format!("Failed to move restored database into place: {database:?}"))?;
 
-    fuc_engine::remove_dir_all(extra_buffer)?;
-    println!("Wiped.");
+    println!("Restored {num_files} files to {database:?}.");
+    Ok(())
+}
+
+fn read_archive_array<const N: usize>(archive: &mut File) -> Result<[u8; N], CliError> {
+    let mut buf = [0; N];
+    archive
+        .read_exact(&mut buf)
+        .map_io_err(|| "Failed to read archive.")?;
+    Ok(buf)
+}
+
+fn read_archive_bytes(archive: &mut File, len: usize) -> Result<Vec<u8>, CliError> {
+    let mut buf = vec![0; len];
+    archive
+        .read_exact(&mut buf)
+        .map_io_err(|| "Failed to read archive.")?;
+    Ok(buf)
+}
+
+/// Extracts the archive into the existing `staging` directory, returning the
+/// number of files restored.
+fn read_archive(archive: &mut File, staging: &Path) -> Result<u64, CliError> {
+    let [m0, m1, m2, m3, version, ring_version] = read_archive_array(archive)?;
+    if [m0, m1, m2, m3] != ARCHIVE_MAGIC {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Not a Ringboard archive.",
+        ))
+        .map_io_err(|| "Archive has invalid magic header.")?;
+    }
+    for (archive, supported) in [(version, ARCHIVE_VERSION), (ring_version, ring::VERSION)] {
+        if archive != supported {
+            return Err(CliError::ArchiveVersionMismatch { archive, supported });
+        }
+    }
+
+    let mut num_files = 0;
+    loop {
+        let [tag] = read_archive_array(archive)?;
+        if tag == ARCHIVE_RECORD_END {
+            return Ok(num_files);
+        }
+
+        let path = {
+            let len = u16::from_le_bytes(read_archive_array(archive)?);
+            PathBuf::from(OsString::from_vec(read_archive_bytes(
+                archive,
+                usize::from(len),
+            )?))
+        };
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Archive paths must stay inside the database.",
+            ))
+            .map_io_err(|| format!("Invalid archive path: {path:?}"))?;
+        }
+        let mode = u32::from_le_bytes(read_archive_array(archive)?);
+        let target = staging.join(&path);
+
+        match tag {
+            ARCHIVE_RECORD_DIR => match fs::DirBuilder::new().mode(mode).create(&target) {
+                // Storing metadata in files may have created the directory early.
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    fs::set_permissions(&target, fs::Permissions::from_mode(mode))
+                }
+                r => r,
+            }
+            .map_io_err(|| format!("Failed to create directory: {target:?}"))?,
+            ARCHIVE_RECORD_FILE => {
+                let file = File::options()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&target)
+                    .map_io_err(|| format!("Failed to create file: {target:?}"))?;
+
+                let num_xattrs = u32::from_le_bytes(read_archive_array(archive)?);
+                let mut xattrs = Vec::new();
+                for _ in 0..num_xattrs {
+                    let [len] = read_archive_array(archive)?;
+                    let name = read_archive_bytes(archive, usize::from(len))?;
+                    let len = u32::from_le_bytes(read_archive_array(archive)?);
+                    let value = read_archive_bytes(archive, usize::try_from(len).unwrap())?;
+                    xattrs.push((name, value));
+                }
+                restore_xattrs(&file, &xattrs, staging, &path)?;
+
+                let len = u64::from_le_bytes(read_archive_array(archive)?);
+                copy_exact(archive, &file, len)
+                    .map_io_err(|| format!("Failed to copy file out of archive: {target:?}"))?;
+                file.set_permissions(fs::Permissions::from_mode(mode))
+                    .map_io_err(|| format!("Failed to set file permissions: {target:?}"))?;
+                num_files += 1;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Unknown archive record.",
+                ))
+                .map_io_err(|| format!("Invalid record tag {tag} for path {path:?}"))?;
+            }
+        }
+    }
+}
+
+/// Sets the file's xattrs, falling back to the server's metadata files for
+/// direct entries when the filesystem can't store them.
+fn restore_xattrs(
+    file: &File,
+    xattrs: &[(Vec<u8>, Vec<u8>)],
+    staging: &Path,
+    path: &Path,
+) -> Result<(), CliError> {
+    let error = match xattrs
+        .iter()
+        .try_for_each(|(name, value)| fsetxattr(file, name.as_slice(), value, XattrFlags::empty()))
+    {
+        Ok(()) => return Ok(()),
+        Err(e @ (Errno::NOTSUP | Errno::NOSPC | Errno::DQUOT)) => e,
+        Err(e) => {
+            return Err(e).map_io_err(|| format!("Failed to set extended attributes: {path:?}"))?;
+        }
+    };
+    let (Ok(file_name), Some(true)) = (
+        path.strip_prefix("direct"),
+        path.parent().map(|dir| dir == Path::new("direct")),
+    ) else {
+        return Err(error).map_io_err(|| format!("Failed to set extended attributes: {path:?}"))?;
+    };
 
+    let mut fields = [&[][..]; METADATA_FILE_XATTRS.len()];
+    for (name, value) in xattrs {
+        if let Some(field) = METADATA_FILE_XATTRS.iter().position(|n| n == name) {
+            fields[field] = value;
+        } else if name == b"user.compression" {
+            return Err(error).map_io_err(|| {
+                format!(
+                    "Compressed entries can only be restored to filesystems with extended \
+                     attribute support: {path:?}"
+                )
+            })?;
+        }
+    }
+    let len = fields
+        .iter()
+        .rposition(|f| !f.is_empty())
+        .map_or(1, |i| i + 1);
+    let metadata = fields[..len].join(&0);
+
+    let metadata_dir = staging.join("metadata");
+    match fs::DirBuilder::new().mode(0o700).create(&metadata_dir) {
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(()),
+        r => r,
+    }
+    .map_io_err(|| format!("Failed to create directory: {metadata_dir:?}"))?;
+    let metadata_file = metadata_dir.join(file_name);
+    File::options()
+        .write(true)
+        .create_new(true)
+        .mode(0o400)
+        .open(&metadata_file)
+        .and_then(|mut f| f.write_all(&metadata))
+        .map_io_err(|| format!("Failed to write metadata file: {metadata_file:?}"))?;
     Ok(())
 }
 
 fn garbage_collect(
     server: OwnedFd,
-    GarbageCollect { max_wasted_bytes }: GarbageCollect,
+    GarbageCollect {
+        max_wasted_bytes,
+        verbose,
+    }: GarbageCollect,
 ) -> Result<(), CliError> {
+    fn snapshot() -> Result<([u64; NUM_BUCKETS], usize), CliError> {
+        let mut dir = data_dir();
+        let (_, bucket_lengths) = {
+            let mut buckets = PathView::new(&mut dir, "buckets");
+            open_buckets(|name| {
+                let file = PathView::new(&mut buckets, name);
+                openat(CWD, &*file, OFlags::PATH, Mode::empty())
+                    .map_io_err(|| format!("Failed to open bucket: {file:?}"))
+            })?
+        };
+        let direct_files = {
+            let direct = PathView::new(&mut dir, "direct");
+            fs::read_dir(&*direct)
+                .map_io_err(|| format!("Failed to read directory: {direct:?}"))?
+                .count()
+        };
+        Ok((bucket_lengths, direct_files))
+    }
+
+    let before = if verbose { Some(snapshot()?) } else { None };
+
     if max_wasted_bytes == 0 {
         let (database, mut reader) = open_db()?;
         let mut duplicates = DuplicateDetector::default();
@@ -801,80 +2467,222 @@ fn garbage_collect(
     let GarbageCollectResponse { bytes_freed } =
         GarbageCollectRequest::response(server, max_wasted_bytes)?;
     println!("{bytes_freed} bytes of garbage freed.");
+
+    if let Some((bucket_lengths_before, direct_files_before)) = before {
+        let (bucket_lengths, direct_files) = snapshot()?;
+        for (bucket, (before, after)) in bucket_lengths_before
+            .into_iter()
+            .zip(bucket_lengths)
+            .enumerate()
+        {
+            let bytes = before.saturating_sub(after);
+            if bytes == 0 {
+                continue;
+            }
+            let slot_size = bucket_to_length(bucket);
+            println!(
+                "Bucket with {slot_size} byte slots: freed {} slots ({bytes} bytes).",
+                bytes / u64::from(slot_size),
+            );
+        }
+        println!(
+            "Removed {} direct files.",
+            direct_files_before.saturating_sub(direct_files)
+        );
+    }
     Ok(())
 }
 
-fn import(server: OwnedFd, Import { from, database }: Import) -> Result<(), CliError> {
-    match from {
-        ImportClipboard::GnomeClipboardHistory => migrate_from_gch(server, database),
-        ImportClipboard::ClipboardIndicator => migrate_from_clipboard_indicator(server, database),
-        ImportClipboard::GPaste => migrate_from_gpaste(server, database),
-        ImportClipboard::Json => migrate_from_ringboard_export(server, database.unwrap()),
+fn import(
+    server: OwnedFd,
+    Import {
+        from,
+        database,
+        favorites_from,
+        validate,
+        mime_mappings,
+    }: Import,
+) -> Result<(), CliError> {
+    if let Some(file) = favorites_from {
+        return import_favorites(server, &file, &mime_mappings);
+    }
+    if validate {
+        Err(io::Error::from(ErrorKind::Unsupported))
+            .map_io_err(|| "Only Gnome Clipboard History databases can be validated.")?;
+    }
+
+    match from.unwrap() {
+        ImportClipboard::GnomeClipboardHistory => {
+            migrate_from_gch(server, database, &mime_mappings)
+        }
+        ImportClipboard::ClipboardIndicator => {
+            migrate_from_clipboard_indicator(server, database, &mime_mappings)
+        }
+        ImportClipboard::GPaste => migrate_from_gpaste(server, database, &mime_mappings),
+        ImportClipboard::CopyQ => migrate_from_copyq(server, &database.unwrap(), &mime_mappings),
+        ImportClipboard::Json => {
+            migrate_from_ringboard_export(server, database.unwrap(), &mime_mappings)
+        }
     }?;
     println!("Migration complete.");
     Ok(())
 }
 
-fn migrate_from_gch(server: OwnedFd, database: Option<PathBuf>) -> Result<(), CliError> {
-    const OP_TYPE_SAVE_TEXT: u8 = 1;
-    const OP_TYPE_DELETE_TEXT: u8 = 2;
-    const OP_TYPE_FAVORITE_ITEM: u8 = 3;
-    const OP_TYPE_UNFAVORITE_ITEM: u8 = 4;
-    const OP_TYPE_MOVE_ITEM_TO_END: u8 = 5;
+const GCH_OP_TYPE_SAVE_TEXT: u8 = 1;
+const GCH_OP_TYPE_DELETE_TEXT: u8 = 2;
+const GCH_OP_TYPE_FAVORITE_ITEM: u8 = 3;
+const GCH_OP_TYPE_UNFAVORITE_ITEM: u8 = 4;
+const GCH_OP_TYPE_MOVE_ITEM_TO_END: u8 = 5;
+
+/// A GCH operation where existing entries are identified by their zero indexed
+/// position in the sequence of saved entries.
+enum GchOp {
+    SaveText {
+        start: usize,
+        len: usize,
+    },
+    DeleteText {
+        gch_id: usize,
+    },
+    MoveToFront {
+        gch_id: usize,
+        to: Option<RingKind>,
+    },
+}
+
+/// Parses a Gnome Clipboard History operation log, bounds checking every read
+/// so corrupt or truncated logs are reported rather than panicking.
+struct GchOps<'a> {
+    bytes: &'a [u8],
+    i: usize,
+    num_saved: usize,
+}
 
-    fn generate_entry_file(database: impl AsFd, start: u64, len: usize) -> Result<File, CliError> {
-        let file = memfd_create(c"ringboard_import_gch", MemfdFlags::empty())
-            .map_io_err(|| "Failed to create data entry file.")?;
+impl<'a> GchOps<'a> {
+    const fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            i: 0,
+            num_saved: 0,
+        }
+    }
 
-        let result =
-            copy_file_range_all(database, Some(&mut start.clone()), &file, Some(&mut 0), len)
-                .map_io_err(|| "Failed to copy data to entry file.")?;
-        debug_assert_eq!(len, result);
+    fn corrupted<T>(offset: usize, problem: impl Display) -> Result<T, CliError> {
+        Err(io::Error::from(ErrorKind::InvalidData))
+            .map_io_err(|| format!("GCH database corrupted at offset {offset}: {problem}"))
+            .map_err(CliError::from)
+    }
+
+    fn next_op(&mut self) -> Result<GchOp, CliError> {
+        let offset = self.i;
+        let op = self.bytes[offset];
+        self.i += 1;
+
+        if op == GCH_OP_TYPE_SAVE_TEXT {
+            let Some(len) = self.bytes[self.i..].iter().position(|&b| b == 0) else {
+                return Self::corrupted(offset, "data was not NUL terminated");
+            };
+            let start = self.i;
+            self.i += 1 + len;
+            self.num_saved += 1;
+            return Ok(GchOp::SaveText { start, len });
+        }
+        let to = match op {
+            GCH_OP_TYPE_DELETE_TEXT => None,
+            GCH_OP_TYPE_FAVORITE_ITEM => Some(Some(RingKind::Favorites)),
+            GCH_OP_TYPE_UNFAVORITE_ITEM => Some(Some(RingKind::Main)),
+            GCH_OP_TYPE_MOVE_ITEM_TO_END => Some(None),
+            _ => return Self::corrupted(offset, format!("unknown operation {op}")),
+        };
+
+        let Some(&id) = self.bytes[self.i..].first_chunk::<4>() else {
+            return Self::corrupted(
+                offset,
+                format!(
+                    "expected a 4 byte entry ID but only {} bytes remain",
+                    self.bytes.len() - self.i
+                ),
+            );
+        };
+        self.i += 4;
+        // GCH uses one indexing
+        let gch_id = u32::from_le_bytes(id);
+        if gch_id == 0 || usize::try_from(gch_id).unwrap() > self.num_saved {
+            return Self::corrupted(
+                offset,
+                format!(
+                    "entry ID {gch_id} does not refer to one of the {} saved entries",
+                    self.num_saved
+                ),
+            );
+        }
+        let gch_id = usize::try_from(gch_id - 1).unwrap();
+        Ok(to.map_or(GchOp::DeleteText { gch_id }, |to| GchOp::MoveToFront {
+            gch_id,
+            to,
+        }))
+    }
+}
+
+impl Iterator for GchOps<'_> {
+    type Item = Result<GchOp, CliError>;
 
-        Ok(File::from(file))
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i >= self.bytes.len() {
+            return None;
+        }
+        let op = self.next_op();
+        if op.is_err() {
+            self.i = self.bytes.len();
+        }
+        Some(op)
     }
+}
 
-    let (bytes, database) = {
-        let database = database
-            .or_else(|| {
-                dirs::cache_dir().map(|mut f| {
-                    f.push("clipboard-history@alexsaveau.dev/database.log");
-                    f
-                })
+fn open_gch_database(database: Option<PathBuf>) -> Result<(Mmap, File), CliError> {
+    let database = database
+        .or_else(|| {
+            dirs::cache_dir().map(|mut f| {
+                f.push("clipboard-history@alexsaveau.dev/database.log");
+                f
             })
-            .ok_or_else(|| io::Error::from(ErrorKind::NotFound))
-            .map_io_err(|| "Failed to find Gnome Clipboard History database file")?;
+        })
+        .ok_or_else(|| io::Error::from(ErrorKind::NotFound))
+        .map_io_err(|| "Failed to find Gnome Clipboard History database file")?;
 
-        let file =
-            File::open(&database).map_io_err(|| format!("Failed to open file: {database:?}"))?;
-        (
-            Mmap::from(&file).map_io_err(|| format!("Failed to mmap file: {database:?}"))?,
-            file,
-        )
-    };
+    let file = File::open(&database).map_io_err(|| format!("Failed to open file: {database:?}"))?;
+    Ok((
+        Mmap::from(&file).map_io_err(|| format!("Failed to mmap file: {database:?}"))?,
+        file,
+    ))
+}
+
+fn validate_gch(database: Option<PathBuf>) -> Result<(), CliError> {
+    let (bytes, _) = open_gch_database(database)?;
+
+    let mut ops = GchOps::new(&bytes);
+    let mut num_ops = 0;
+    for op in &mut ops {
+        op?;
+        num_ops += 1;
+    }
+    println!(
+        "Validation complete: {num_ops} operations over {} entries.",
+        ops.num_saved
+    );
+    Ok(())
+}
+
+fn migrate_from_gch(
+    server: OwnedFd,
+    database: Option<PathBuf>,
+    mime_mappings: &[MimeMapping],
+) -> Result<(), CliError> {
+    let (bytes, database) = open_gch_database(database)?;
 
     let mut translation = Vec::new();
     let mut pending_adds = 0;
-    let mut i = 0;
-    while i < bytes.len() {
-        macro_rules! gch_id {
-            () => {{
-                let gch_id = u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
-                // GCH uses one indexing
-                usize::try_from(gch_id - 1).unwrap()
-            }};
-        }
-        macro_rules! get_translation {
-            () => {{
-                let gch_id = gch_id!();
-                if translation.len() <= gch_id {
-                    unsafe {
-                        drain_add_requests(&server, Some(&mut translation), &mut pending_adds)?;
-                    }
-                }
-                translation[gch_id]
-            }};
-        }
+    for op in GchOps::new(&bytes) {
         macro_rules! api_error {
             ($e:expr) => {
                 println!(
@@ -885,59 +2693,55 @@ fn migrate_from_gch(server: OwnedFd, database: Option<PathBuf>) -> Result<(), Cl
             };
         }
 
-        let op = bytes[i];
-        i += 1;
-        match op {
-            OP_TYPE_SAVE_TEXT => {
-                let raw_len = bytes[i..]
-                    .iter()
-                    .position(|&b| b == 0)
-                    .ok_or_else(|| io::Error::from(ErrorKind::InvalidData))
-                    .map_io_err(|| "GCH database corrupted: data was not NUL terminated")?;
-
-                let data = generate_entry_file(&database, u64::try_from(i).unwrap(), raw_len)?;
-                i += 1 + raw_len;
-
+        let op = op?;
+        let gch_id = match op {
+            GchOp::SaveText { start, len } => {
+                let data = copy_to_memfd(
+                    c"ringboard_import_gch",
+                    &database,
+                    u64::try_from(start).unwrap(),
+                    len,
+                )
+                .map(File::from)
+                .map_io_err(|| "Failed to copy data to entry file.")?;
                 unsafe {
                     pipeline_add_request(
                         &server,
                         data,
                         RingKind::Main,
-                        MimeType::new_const(),
+                        map_mime(mime_mappings, MimeType::new_const()),
                         Some(&mut translation),
                         &mut pending_adds,
                     )?;
                 }
+                continue;
+            }
+            GchOp::DeleteText { gch_id } | GchOp::MoveToFront { gch_id, .. } => gch_id,
+        };
+        if translation.len() <= gch_id {
+            unsafe {
+                drain_add_requests(&server, Some(&mut translation), &mut pending_adds)?;
             }
-            OP_TYPE_DELETE_TEXT => {
+        }
+
+        match op {
+            GchOp::SaveText { .. } => unreachable!(),
+            GchOp::DeleteText { gch_id } => {
                 if let RemoveResponse { error: Some(e) } =
-                    RemoveRequest::response(&server, get_translation!())?
+                    RemoveRequest::response(&server, translation[gch_id])?
                 {
                     api_error!(e);
                 }
-                i += 4;
             }
-            OP_TYPE_FAVORITE_ITEM | OP_TYPE_UNFAVORITE_ITEM | OP_TYPE_MOVE_ITEM_TO_END => {
-                match MoveToFrontRequest::response(&server, get_translation!(), match op {
-                    OP_TYPE_FAVORITE_ITEM => Some(RingKind::Favorites),
-                    OP_TYPE_UNFAVORITE_ITEM => Some(RingKind::Main),
-                    OP_TYPE_MOVE_ITEM_TO_END => None,
-                    _ => unreachable!(),
-                })? {
+            GchOp::MoveToFront { gch_id, to } => {
+                match MoveToFrontRequest::response(&server, translation[gch_id], to)? {
                     MoveToFrontResponse::Success { id } => {
-                        translation[gch_id!()] = id;
+                        translation[gch_id] = id;
                     }
                     MoveToFrontResponse::Error(e) => {
                         api_error!(e);
                     }
                 }
-                i += 4;
-            }
-            _ => {
-                Err(io::Error::from(ErrorKind::InvalidData)).map_io_err(|| {
-                    format!("GCH database corrupted: unknown operation {:?}", bytes[i])
-                })?;
-                unreachable!();
             }
         }
     }
@@ -948,6 +2752,7 @@ fn migrate_from_gch(server: OwnedFd, database: Option<PathBuf>) -> Result<(), Cl
 fn migrate_from_clipboard_indicator(
     server: OwnedFd,
     database: Option<PathBuf>,
+    mime_mappings: &[MimeMapping],
 ) -> Result<(), CliError> {
     #[derive(Deserialize)]
     struct Entry {
@@ -1036,7 +2841,7 @@ fn migrate_from_clipboard_indicator(
                 } else {
                     RingKind::Main
                 },
-                mimetype,
+                map_mime(mime_mappings, mimetype),
                 None,
                 &mut pending_adds,
             )?;
@@ -1046,7 +2851,11 @@ fn migrate_from_clipboard_indicator(
     unsafe { drain_add_requests(server, None, &mut pending_adds) }
 }
 
-fn migrate_from_gpaste(server: OwnedFd, database: Option<PathBuf>) -> Result<(), CliError> {
+fn migrate_from_gpaste(
+    server: OwnedFd,
+    database: Option<PathBuf>,
+    mime_mappings: &[MimeMapping],
+) -> Result<(), CliError> {
     #[derive(Deserialize, Debug)]
     struct History {
         #[serde(rename = "@version")]
@@ -1175,6 +2984,7 @@ fn migrate_from_gpaste(server: OwnedFd, database: Option<PathBuf>) -> Result<(),
             ),
         };
 
+        let mime = map_mime(mime_mappings, mime);
         unsafe {
             pipeline_add_request(&server, data, RingKind::Main, mime, None, &mut pending_adds)?;
         }
@@ -1183,8 +2993,134 @@ fn migrate_from_gpaste(server: OwnedFd, database: Option<PathBuf>) -> Result<(),
     unsafe { drain_add_requests(server, None, &mut pending_adds) }
 }
 
+/// A row of the tab-separated export described by [`ImportClipboard::CopyQ`].
+#[derive(Debug, PartialEq, Eq)]
+struct CopyQItem {
+    mime_type: MimeType,
+    favorite: bool,
+    data: Vec<u8>,
+}
+
+/// Returns [`None`] for malformed rows.
+fn parse_copyq_row(row: &[u8]) -> Option<CopyQItem> {
+    fn unescape(text: &[u8]) -> Option<Vec<u8>> {
+        let mut unescaped = Vec::with_capacity(text.len());
+        let mut bytes = text.iter();
+        while let Some(&b) = bytes.next() {
+            unescaped.push(if b == b'\\' {
+                match bytes.next()? {
+                    b'\\' => b'\\',
+                    b't' => b'\t',
+                    b'r' => b'\r',
+                    b'n' => b'\n',
+                    _ => return None,
+                }
+            } else {
+                b
+            });
+        }
+        Some(unescaped)
+    }
+
+    let mut columns = row.splitn(3, |&b| b == b'\t');
+    let (mime_type, tags, contents) = (columns.next()?, columns.next()?, columns.next()?);
+    let mime_type = MimeType::from(str::from_utf8(mime_type).ok()?).ok()?;
+
+    let data = if mime_type.is_empty() || mime_type.starts_with("text/") {
+        unescape(contents)?
+    } else {
+        use base64::{
+            Engine,
+            alphabet::STANDARD,
+            engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+        };
+
+        GeneralPurpose::new(
+            &STANDARD,
+            GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+        )
+        .decode(contents.trim_ascii_end())
+        .ok()?
+    };
+    let favorite = tags
+        .split(|&b| b == b',')
+        .any(|tag| !tag.trim_ascii().is_empty());
+
+    Some(CopyQItem {
+        mime_type,
+        favorite,
+        data,
+    })
+}
+
+fn migrate_from_copyq(
+    server: OwnedFd,
+    database: &Path,
+    mime_mappings: &[MimeMapping],
+) -> Result<(), CliError> {
+    fn generate_entry_file(data: &[u8]) -> Result<File, CliError> {
+        let file = File::from(
+            memfd_create(c"ringboard_copyq", MemfdFlags::empty())
+                .map_io_err(|| "Failed to create data entry file.")?,
+        );
+
+        file.write_all_at(data, 0)
+            .map_io_err(|| "Failed to copy data to entry file.")?;
+
+        Ok(file)
+    }
+
+    let history =
+        fs::read(database).map_io_err(|| format!("Failed to read CopyQ export: {database:?}"))?;
+
+    let mut pending_adds = 0;
+    // CopyQ lists the most recent items first.
+    for (line, row) in history
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+    {
+        let row = row.strip_suffix(b"\r").unwrap_or(row);
+        if row.is_empty() {
+            continue;
+        }
+
+        let Some(CopyQItem {
+            mime_type,
+            favorite,
+            data,
+        }) = parse_copyq_row(row)
+        else {
+            return Err(io::Error::from(ErrorKind::InvalidData))
+                .map_io_err(|| format!("Invalid CopyQ export row on line {}.", line + 1))?;
+        };
+        if data.is_empty() {
+            continue;
+        }
+
+        unsafe {
+            pipeline_add_request(
+                &server,
+                generate_entry_file(&data)?,
+                if favorite {
+                    RingKind::Favorites
+                } else {
+                    RingKind::Main
+                },
+                map_mime(mime_mappings, mime_type),
+                None,
+                &mut pending_adds,
+            )?;
+        }
+    }
+
+    unsafe { drain_add_requests(server, None, &mut pending_adds) }
+}
+
 #[allow(clippy::cast_precision_loss)]
-fn stats() -> Result<(), CliError> {
+fn stats(Stats { watch }: Stats) -> Result<(), CliError> {
     #[derive(Default, Debug)]
     struct RingStats {
         capacity: u32,
@@ -1197,15 +3133,6 @@ fn stats() -> Result<(), CliError> {
         owned_bytes: u64,
     }
 
-    #[derive(Default, Debug)]
-    struct BucketStats {
-        size_class: usize,
-
-        num_slots: u32,
-        used_slots: u32,
-        owned_bytes: u64,
-    }
-
     #[derive(Default, Debug)]
     struct DirectFileStats {
         owned_bytes: u64,
@@ -1216,7 +3143,7 @@ fn stats() -> Result<(), CliError> {
     #[derive(Default, Debug)]
     struct Stats {
         rings: HashMap<RingKind, RingStats, BuildHasherDefault<FxHasher>>,
-        buckets: [BucketStats; NUM_BUCKETS],
+        buckets: [BucketUsage; NUM_BUCKETS],
         direct_files: DirectFileStats,
     }
 
@@ -1265,23 +3192,15 @@ fn stats() -> Result<(), CliError> {
                     })
                     .field_with("buckets", |f| {
                         let mut buckets = f.debug_map();
-                        for &BucketStats {
-                            size_class,
-                            num_slots,
-                            used_slots,
-                            owned_bytes,
-                        } in &self.buckets
-                        {
-                            let length = bucket_to_length(size_class - 2);
-                            let used_bytes = u64::from(length) * u64::from(used_slots);
-                            let fragmentation = used_bytes - owned_bytes;
-                            buckets.key(&length).value_with(|f| {
+                        for bucket in &self.buckets {
+                            let fragmentation = bucket.fragmentation_bytes();
+                            buckets.key(&bucket.slot_length).value_with(|f| {
                                 f.debug_struct("Bucket")
-                                    .field("free_slots", &(num_slots - used_slots))
+                                    .field("free_slots", &bucket.free_slots())
                                     .field("fragmentation_bytes", &fragmentation)
                                     .field(
                                         "fragmentation_ratio",
-                                        &(fragmentation as f64 / used_bytes as f64),
+                                        &(fragmentation as f64 / bucket.used_bytes() as f64),
                                     )
                                     .finish()
                             });
@@ -1308,111 +3227,99 @@ fn stats() -> Result<(), CliError> {
         }
     }
 
-    let mut stats = Stats::default();
-    let Stats {
-        rings,
-        buckets,
-        direct_files:
-            DirectFileStats {
-                owned_bytes: direct_owned_bytes,
-                allocated_bytes,
-                mime_types,
-            },
-    } = &mut stats;
-
-    let (database, mut reader) = open_db()?;
-    let mut duplicates = DuplicateDetector::default();
-
-    for (
-        i,
-        (
-            BucketStats {
-                size_class,
-                num_slots,
-                used_slots: _,
-                owned_bytes: _,
-            },
-            mem,
-        ),
-    ) in buckets.iter_mut().zip(reader.buckets()).enumerate()
-    {
-        *size_class = i + 2;
-        *num_slots = u32::try_from(mem.len() / usize::from(bucket_to_length(i))).unwrap();
-    }
-
-    for ring_reader in [database.favorites(), database.main()] {
-        let mut ring_stats = RingStats::default();
-        let RingStats {
-            capacity,
-            len,
-            bucketed_entry_count,
-            file_entry_count,
-            num_duplicates,
-            min_entry_size,
-            max_entry_size,
-            owned_bytes: ring_owned_bytes,
-        } = &mut ring_stats;
-        *capacity = ring_reader.ring().capacity();
-        *len = ring_reader.ring().len();
-        *min_entry_size = u64::MAX;
-        let kind = ring_reader.kind();
-
-        for entry in ring_reader {
-            let entry_size;
-            let duplicate;
-
-            match entry.kind() {
-                Kind::Bucket(bucket) => {
-                    *bucketed_entry_count += 1;
-
-                    let BucketStats {
-                        size_class: _,
-                        num_slots: _,
-                        used_slots,
-                        owned_bytes,
-                    } = &mut buckets[usize::from(size_to_bucket(bucket.size()))];
-                    *used_slots += 1;
-
-                    entry_size = u64::from(bucket.size());
-                    *owned_bytes += entry_size;
+    let compute = || -> Result<Stats, CliError> {
+        let mut stats = Stats::default();
+        let Stats {
+            rings,
+            buckets,
+            direct_files:
+                DirectFileStats {
+                    owned_bytes: direct_owned_bytes,
+                    allocated_bytes,
+                    mime_types,
+                },
+        } = &mut stats;
 
-                    duplicate = duplicates.add_entry(&entry, &database, &mut reader)?;
+        let (database, mut reader) = open_db_snapshot()?;
+        let mut duplicates = DuplicateDetector::default();
+        *buckets = reader.bucket_usage(&database);
+
+        for ring_reader in [database.favorites(), database.main()] {
+            let mut ring_stats = RingStats::default();
+            let RingStats {
+                capacity,
+                len,
+                bucketed_entry_count,
+                file_entry_count,
+                num_duplicates,
+                min_entry_size,
+                max_entry_size,
+                owned_bytes: ring_owned_bytes,
+            } = &mut ring_stats;
+            *capacity = ring_reader.ring().capacity();
+            *len = ring_reader.ring().len();
+            *min_entry_size = u64::MAX;
+            let kind = ring_reader.kind();
+
+            for entry in ring_reader {
+                let entry_size;
+                let duplicate;
+
+                match entry.kind() {
+                    Kind::Bucket(bucket) => {
+                        *bucketed_entry_count += 1;
+                        entry_size = u64::from(bucket.size());
+
+                        duplicate = duplicates.add_entry(&entry, &database, &mut reader)?;
+                    }
+                    Kind::File => {
+                        *file_entry_count += 1;
+
+                        let file = entry.to_file(&mut reader)?;
+                        let stats = statx(
+                            &*file,
+                            c"",
+                            AtFlags::EMPTY_PATH,
+                            StatxFlags::SIZE | StatxFlags::BLOCKS,
+                        )
+                        .map_io_err(|| format!("Failed to statx file: {file:?}"))?;
+
+                        entry_size = stats.stx_size;
+                        *direct_owned_bytes += entry_size;
+                        *mime_types.entry(file.mime_type()?).or_default() += 1;
+                        *allocated_bytes += stats.stx_blocks * 512;
+
+                        duplicate = duplicates.add_entry(&entry, &database, &mut reader)?;
+                    }
                 }
-                Kind::File => {
-                    *file_entry_count += 1;
-
-                    let file = entry.to_file(&mut reader)?;
-                    let stats = statx(
-                        &*file,
-                        c"",
-                        AtFlags::EMPTY_PATH,
-                        StatxFlags::SIZE | StatxFlags::BLOCKS,
-                    )
-                    .map_io_err(|| format!("Failed to statx file: {file:?}"))?;
-
-                    entry_size = stats.stx_size;
-                    *direct_owned_bytes += entry_size;
-                    *mime_types.entry(file.mime_type()?).or_default() += 1;
-                    *allocated_bytes += stats.stx_blocks * 512;
 
-                    duplicate = duplicates.add_entry(&entry, &database, &mut reader)?;
+                *ring_owned_bytes += entry_size;
+                *min_entry_size = min(*min_entry_size, entry_size);
+                *max_entry_size = max(*max_entry_size, entry_size);
+                if duplicate {
+                    *num_duplicates += 1;
                 }
             }
 
-            *ring_owned_bytes += entry_size;
-            *min_entry_size = min(*min_entry_size, entry_size);
-            *max_entry_size = max(*max_entry_size, entry_size);
-            if duplicate {
-                *num_duplicates += 1;
-            }
+            rings.insert(kind, ring_stats);
         }
 
-        rings.insert(kind, ring_stats);
-    }
-
-    println!("{stats:#}");
+        Ok(stats)
+    };
 
-    Ok(())
+    let Some(interval) = watch else {
+        println!("{:#}", compute()?);
+        return Ok(());
+    };
+    let mut stdout = io::stdout().lock();
+    loop {
+        let stats = compute()?;
+        // Clear the screen and move the cursor back to the top left corner.
+        writeln!(stdout, "\x1b[2J\x1b[H{stats:#}")
+            .and_then(|()| stdout.flush())
+            .map_io_err(|| "Failed to write to stdout.")?;
+        thread::sleep(interval);
+    }
 }
 
 base64_serde_type!(
@@ -1430,6 +3337,9 @@ struct ExportEntry<'a> {
     #[serde(skip_serializing_if = "MimeType::is_empty")]
     #[serde(default)]
     mime_type: MimeType,
+    /// Informational only, so it is ignored on import.
+    #[serde(skip_deserializing)]
+    content: &'static str,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1439,28 +3349,148 @@ enum ExportData<'a> {
     Bytes(#[serde(with = "Base64Standard")] Cow<'a, [u8]>),
 }
 
-fn dump() -> Result<(), CliError> {
-    let (database, mut reader) = open_db()?;
-    let mut seq = serde_json::Serializer::new(io::stdout().lock());
-    let mut seq = seq.serialize_seq(None)?;
-    for entry in database.favorites().chain(database.main()) {
+impl ExportData<'_> {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            ExportData::Human(str) => str.as_bytes(),
+            ExportData::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, bound(deserialize = "'de: 'a"))]
+enum ExportRecord<'a> {
+    Entry(ExportEntry<'a>),
+    Checksum(ExportChecksum),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExportChecksum {
+    entries: u64,
+    fnv1a64: String,
+}
+
+/// Running checksum over exported entries.
+///
+/// FNV-1a is used because, unlike the std hashers, its output is guaranteed to
+/// never change.
+#[derive(Copy, Clone)]
+struct ExportHasher {
+    entries: u64,
+    hash: u64,
+}
+
+impl Default for ExportHasher {
+    fn default() -> Self {
+        Self {
+            entries: 0,
+            hash: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+}
+
+impl ExportHasher {
+    fn add(&mut self, id: u64, mime_type: &MimeType, data: &[u8]) {
+        self.entries += 1;
+        for bytes in [
+            &id.to_le_bytes()[..],
+            &u64::try_from(mime_type.len()).unwrap().to_le_bytes(),
+            mime_type.as_bytes(),
+            &u64::try_from(data.len()).unwrap().to_le_bytes(),
+            data,
+        ] {
+            for &b in bytes {
+                self.hash ^= u64::from(b);
+                self.hash = self.hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    fn finish(self) -> ExportChecksum {
+        ExportChecksum {
+            entries: self.entries,
+            fnv1a64: format!("{:016x}", self.hash),
+        }
+    }
+}
+
+fn dump(
+    Dump {
+        pretty,
+        since,
+        checksum,
+    }: Dump,
+) -> Result<(), CliError> {
+    let since = since.map(decompose_id).transpose()?;
+    let stdout = io::stdout().lock();
+    if pretty {
+        dump_to(&mut serde_json::Serializer::pretty(stdout), since, checksum)
+    } else {
+        dump_to(&mut serde_json::Serializer::new(stdout), since, checksum)
+    }
+}
+
+fn dump_to(
+    serializer: impl Serializer<Error = serde_json::Error>,
+    since: Option<(RingKind, u32)>,
+    checksum: bool,
+) -> Result<(), CliError> {
+    // Position of an entry in the ring relative to the oldest entry.
+    let age =
+        |ring: &Ring, write_head: u32, index: u32| (index + ring.len() - write_head) % ring.len();
+
+    let (database, mut reader) = open_db_snapshot()?;
+    if let Some((kind, index)) = since {
+        let ring_reader = match kind {
+            RingKind::Favorites => database.favorites(),
+            RingKind::Main => database.main(),
+        };
+        // Also guards the age computation against dividing by an empty ring.
+        if index >= ring_reader.ring().len() {
+            return Err(CoreError::from(IdNotFoundError::Entry(index)).into());
+        }
+    }
+    let mut hasher = ExportHasher::default();
+    let mut seq = serializer.serialize_seq(None)?;
+    for entry in [database.favorites(), database.main()]
+        .into_iter()
+        .filter(|rr| since.is_none_or(|(ring, _)| rr.kind() == ring))
+        .flat_map(|rr| {
+            let (ring, write_head) = (rr.ring(), rr.write_head());
+            let since = since.map(|(_, index)| age(ring, write_head, index));
+            rr.filter(move |entry| {
+                since.is_none_or(|since| age(ring, write_head, entry.index()) > since)
+            })
+        })
+    {
         let loaded = entry.to_slice(&mut reader)?;
         let mime_type = loaded.mime_type()?;
+        hasher.add(entry.id(), &mime_type, &loaded);
         seq.serialize_element(&ExportEntry {
             id: entry.id(),
             data: str::from_utf8(&loaded).map_or_else(
                 |_| ExportData::Bytes((&**loaded).into()),
                 |data| ExportData::Human(data.into()),
             ),
+            content: sniff_content_kind(&mime_type, &loaded).as_str(),
             mime_type,
         })?;
     }
+    if checksum {
+        seq.serialize_element(&hasher.finish())?;
+    }
 
     SerializeSeq::end(seq)?;
     Ok(())
 }
 
-fn migrate_from_ringboard_export(server: OwnedFd, dump_file: PathBuf) -> Result<(), CliError> {
+fn migrate_from_ringboard_export(
+    server: OwnedFd,
+    dump_file: PathBuf,
+    mime_mappings: &[MimeMapping],
+) -> Result<(), CliError> {
     fn generate_entry_file(tmp_file_unsupported: &mut bool, data: &[u8]) -> Result<File, CliError> {
         let file = File::from(
             create_tmp_file(
@@ -1480,45 +3510,190 @@ fn migrate_from_ringboard_export(server: OwnedFd, dump_file: PathBuf) -> Result<
         Ok(file)
     }
 
+    struct LineCounter<'a, R> {
+        inner: R,
+        lines: &'a Cell<u64>,
+    }
+
+    impl<R: Read> Read for LineCounter<'_, R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = self.inner.read(buf)?;
+            #[allow(clippy::naive_bytecount)]
+            let newlines = buf[..count].iter().filter(|&&b| b == b'\n').count();
+            self.lines
+                .set(self.lines.get() + u64::try_from(newlines).unwrap());
+            Ok(count)
+        }
+    }
+
     let mut pending_adds = 0;
     let mut cache = Default::default();
-    let mut process = |ExportEntry {
-                           id,
-                           data,
-                           mime_type,
-                       }|
-     -> Result<(), CliError> {
-        let data = generate_entry_file(&mut cache, match &data {
-            ExportData::Human(str) => str.as_bytes(),
-            ExportData::Bytes(bytes) => bytes,
-        })?;
+    let mut hasher = ExportHasher::default();
+    let mut verified = false;
+    let mut process = |line, record| -> Result<(), CliError> {
+        if verified {
+            return Err(CliError::RecordAfterChecksum { line });
+        }
+        let ExportEntry {
+            id,
+            data,
+            mime_type,
+            content: _,
+        } = match record {
+            ExportRecord::Entry(entry) => entry,
+            ExportRecord::Checksum(expected) => {
+                let actual = hasher.finish();
+                if expected.entries != actual.entries || expected.fnv1a64 != actual.fnv1a64 {
+                    return Err(CliError::ChecksumMismatch {
+                        expected: format!("{} ({} entries)", expected.fnv1a64, expected.entries),
+                        actual: format!("{} ({} entries)", actual.fnv1a64, actual.entries),
+                    });
+                }
+                verified = true;
+                return Ok(());
+            }
+        };
+        let (to, _) =
+            checked_decompose_id(id).map_err(|error| CliError::InvalidImportId { line, error })?;
+        hasher.add(id, &mime_type, data.as_bytes());
+        let data = generate_entry_file(&mut cache, data.as_bytes())?;
+        let mime_type = map_mime(mime_mappings, mime_type);
 
-        let (to, _) = decompose_id(id).unwrap_or_default();
         unsafe { pipeline_add_request(&server, data, to, mime_type, None, &mut pending_adds) }
     };
 
+    // Objects are self-delimiting so the deserializer stops reading at the closing
+    // brace, meaning the count points at the last line of the current entry.
+    let lines = Cell::new(1);
     if dump_file == Path::new("-") {
         drop(dump_file);
-        let iter =
-            serde_json::Deserializer::from_reader(io::stdin().lock()).into_iter::<ExportEntry>();
+        let iter = serde_json::Deserializer::from_reader(LineCounter {
+            inner: io::stdin().lock(),
+            lines: &lines,
+        })
+        .into_iter::<ExportRecord>();
         for result in iter {
-            process(result?)?;
+            process(lines.get(), result?)?;
         }
     } else {
         let dump =
             File::open(&dump_file).map_io_err(|| format!("Failed to open file: {dump_file:?}"))?;
         drop(dump_file);
 
-        let iter =
-            serde_json::Deserializer::from_reader(BufReader::new(dump)).into_iter::<ExportEntry>();
+        let iter = serde_json::Deserializer::from_reader(LineCounter {
+            inner: BufReader::new(dump),
+            lines: &lines,
+        })
+        .into_iter::<ExportRecord>();
         for result in iter {
-            process(result?)?;
+            process(lines.get(), result?)?;
         }
     };
 
     unsafe { drain_add_requests(server, None, &mut pending_adds) }
 }
 
+fn import_favorites(
+    server: impl AsFd,
+    file: &Path,
+    mime_mappings: &[MimeMapping],
+) -> Result<(), CliError> {
+    fn generate_entry_file(data: &[u8]) -> Result<File, CliError> {
+        let file = File::from(
+            memfd_create(c"ringboard_import_favorites", MemfdFlags::empty())
+                .map_io_err(|| "Failed to create data entry file.")?,
+        );
+        file.write_all_at(data, 0)
+            .map_io_err(|| "Failed to copy data to entry file.")?;
+        Ok(file)
+    }
+
+    let bytes = if file == Path::new("-") {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .map_io_err(|| "Failed to read stdin.")?;
+        bytes
+    } else {
+        fs::read(file).map_io_err(|| format!("Failed to read file: {file:?}"))?
+    };
+
+    let mut pending_adds = 0;
+    let mut count = 0;
+    let mut add = |data: &[u8], mime_type| -> Result<(), CliError> {
+        let data = generate_entry_file(data)?;
+        count += 1;
+        unsafe {
+            pipeline_add_request(
+                &server,
+                data,
+                RingKind::Favorites,
+                map_mime(mime_mappings, mime_type),
+                None,
+                &mut pending_adds,
+            )
+        }
+    };
+
+    match bytes.trim_ascii_start().first() {
+        Some(b'[') => {
+            for ExportEntry {
+                id: _,
+                data,
+                mime_type,
+                content: _,
+            } in serde_json::from_slice::<Vec<ExportEntry>>(&bytes)?
+            {
+                add(data.as_bytes(), mime_type)?;
+            }
+        }
+        Some(b'{') => {
+            for entry in serde_json::Deserializer::from_slice(&bytes).into_iter::<ExportEntry>() {
+                let ExportEntry {
+                    id: _,
+                    data,
+                    mime_type,
+                    content: _,
+                } = entry?;
+                add(data.as_bytes(), mime_type)?;
+            }
+        }
+        _ => {
+            for line in bytes.split(|&b| b == b'\n') {
+                if !line.trim_ascii().is_empty() {
+                    add(line, MimeType::new())?;
+                }
+            }
+        }
+    }
+
+    unsafe { drain_add_requests(&server, None, &mut pending_adds) }?;
+    println!("Added {count} favorites.");
+    Ok(())
+}
+
+fn generate_random_entry_file(
+    rng: &mut (impl RngCore + 'static),
+    len_distr: LogNormal<f64>,
+) -> Result<(File, u64), CliError> {
+    let mut file = File::from(
+        memfd_create(c"ringboard_gen", MemfdFlags::empty())
+            .map_io_err(|| "Failed to create data entry file.")?,
+    );
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    let len = len_distr.sample(rng).round().max(1.) as u64;
+    // TODO use adapter when it's available
+    let result = io::copy(&mut (rng as &mut dyn RngCore).take(len), &mut file)
+        .map_io_err(|| "Failed to write bytes to entry file.")?;
+    debug_assert_eq!(len, result);
+    file.seek(SeekFrom::Start(0))
+        .map_io_err(|| "Failed to reset entry file offset.")?;
+
+    Ok((file, len))
+}
+
 fn generate(
     server: OwnedFd,
     Generate {
@@ -1527,27 +3702,6 @@ fn generate(
         cv_size,
     }: Generate,
 ) -> Result<(), CliError> {
-    fn generate_random_entry_file(
-        rng: &mut (impl RngCore + 'static),
-        len_distr: LogNormal<f64>,
-    ) -> Result<(File, u64), CliError> {
-        let mut file = File::from(
-            memfd_create(c"ringboard_gen", MemfdFlags::empty())
-                .map_io_err(|| "Failed to create data entry file.")?,
-        );
-
-        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-        let len = len_distr.sample(rng).round().max(1.) as u64;
-        // TODO use adapter when it's available
-        let result = io::copy(&mut (rng as &mut dyn RngCore).take(len), &mut file)
-            .map_io_err(|| "Failed to write bytes to entry file.")?;
-        debug_assert_eq!(len, result);
-        file.seek(SeekFrom::Start(0))
-            .map_io_err(|| "Failed to reset entry file offset.")?;
-
-        Ok((file, len))
-    }
-
     struct GenerateRingKind(RingKind);
 
     impl Distribution<GenerateRingKind> for Standard {
@@ -1734,10 +3888,14 @@ fn fuzz(
             match kind {
                 ResponseKind::Add {
                     data: NoDebug(data),
-                    value: AddResponse::Success { id },
+                    value: AddResponse::Success { id } | AddResponse::Stored { id, .. },
                 } => {
                     database.insert(id, data);
                 }
+                ResponseKind::Add {
+                    data: _,
+                    value: AddResponse::RateLimited,
+                } => (),
                 ResponseKind::Move { move_id, value } => match value {
                     MoveToFrontResponse::Success { id } => {
                         let file = database.remove(&move_id).unwrap();
@@ -1912,7 +4070,7 @@ fn fuzz(
                             data: NoDebug(data),
                         });
                         pipeline_request!(|flags| AddRequest::send(
-                            server, kind, mime_type, &file, flags
+                            server, kind, mime_type, &file, 0, flags
                         ));
                     }
                     3 => {
@@ -1999,7 +4157,120 @@ fn fuzz(
     }
 }
 
-fn configure_x11(ConfigureX11 { auto_paste }: ConfigureX11) -> Result<(), CliError> {
+fn bench(
+    server: OwnedFd,
+    Bench {
+        seed,
+        num_entries,
+        mean_size,
+        cv_size,
+        gets,
+        searches,
+    }: Bench,
+) -> Result<(), CliError> {
+    fn report(name: &str, total: Duration, mut latencies: Vec<Duration>) {
+        let ops = latencies.len();
+        #[allow(clippy::cast_precision_loss)]
+        let throughput = ops as f64 / total.as_secs_f64();
+        print!("{name}: {ops} ops in {total:.2?} ({throughput:.0} ops/s)");
+        if latencies.is_empty() {
+            println!(".");
+            return;
+        }
+
+        latencies.sort_unstable();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        println!(
+            ", p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, max {:.2?}.",
+            percentile(50),
+            percentile(90),
+            percentile(99),
+            latencies.last().unwrap(),
+        );
+    }
+
+    let distr = LogNormal::from_mean_cv(f64::from(mean_size), f64::from(cv_size)).unwrap();
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+
+    let mut ids = Vec::with_capacity(usize::try_from(num_entries).unwrap());
+    {
+        let mut pending_adds = 0;
+        let start = Instant::now();
+        for _ in 0..num_entries {
+            let data = generate_random_entry_file(&mut rng, distr)?.0;
+            unsafe {
+                pipeline_add_request(
+                    &server,
+                    data,
+                    RingKind::Main,
+                    MimeType::new_const(),
+                    Some(&mut ids),
+                    &mut pending_adds,
+                )?;
+            }
+        }
+        unsafe { drain_add_requests(server, Some(&mut ids), &mut pending_adds) }?;
+        let total = start.elapsed();
+
+        // Adds are pipelined, so per-request latencies aren't meaningful.
+        #[allow(clippy::cast_precision_loss)]
+        let throughput = f64::from(num_entries) / total.as_secs_f64();
+        println!("Add: {num_entries} ops in {total:.2?} ({throughput:.0} ops/s).");
+    }
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let (mut database, mut reader) = open_db()?;
+
+    let mut latencies = Vec::with_capacity(usize::try_from(gets).unwrap());
+    let start = Instant::now();
+    for _ in 0..gets {
+        let id = ids[rng.gen_range(0..ids.len())];
+        let op_start = Instant::now();
+        let entry = unsafe { database.get(id)? };
+        black_box(&**entry.to_slice(&mut reader)?);
+        latencies.push(op_start.elapsed());
+    }
+    report("Get", start.elapsed(), latencies);
+
+    let mut queries = Vec::with_capacity(usize::try_from(searches).unwrap());
+    for _ in 0..searches {
+        let id = ids[rng.gen_range(0..ids.len())];
+        let entry = unsafe { database.get(id)? };
+        let data = entry.to_slice(&mut reader)?;
+        let len = rng.gen_range(1..=data.len().min(8));
+        let offset = rng.gen_range(0..=data.len() - len);
+        queries.push(data[offset..offset + len].to_vec());
+    }
+
+    let reader = Arc::new(reader);
+    let mut latencies = Vec::with_capacity(queries.len());
+    let start = Instant::now();
+    for query in &queries {
+        let op_start = Instant::now();
+        let (result_stream, threads) = ringboard_sdk::search(Query::Plain(query), reader.clone());
+        for result in result_stream {
+            black_box(result?);
+        }
+        for thread in threads {
+            thread.join().map_err(|_| CliError::InternalSearchError)?;
+        }
+        latencies.push(op_start.elapsed());
+    }
+    report("Search", start.elapsed(), latencies);
+
+    Ok(())
+}
+
+fn configure_x11(
+    ConfigureX11 {
+        auto_paste,
+        capture_primary,
+        paste_key,
+        ignore_secrets,
+    }: ConfigureX11,
+) -> Result<(), CliError> {
     let path = x11_config_file();
     {
         let parent = path.parent().unwrap();
@@ -2007,7 +4278,12 @@ fn configure_x11(ConfigureX11 { auto_paste }: ConfigureX11) -> Result<(), CliErr
     }
     let mut file = File::create(&path).map_io_err(|| format!("Failed to open file: {path:?}"))?;
 
-    let config = toml::to_string_pretty(&X11Config::V1(X11V1Config { auto_paste }))?;
+    let config = toml::to_string_pretty(&X11Config::V1(X11V1Config {
+        auto_paste,
+        capture_primary,
+        paste_key,
+        ignore_secrets,
+    }))?;
     file.write_all(config.as_bytes())
         .map_io_err(|| format!("Failed to write to config file: {path:?}"))?;
 
@@ -2015,6 +4291,134 @@ fn configure_x11(ConfigureX11 { auto_paste }: ConfigureX11) -> Result<(), CliErr
     Ok(())
 }
 
+fn doctor(server_addr: &SocketAddrUnix) -> Result<(), CliError> {
+    let mut failures = 0;
+    let mut check = |name: &str, result: Result<(), Cow<'static, str>>| match result {
+        Ok(()) => println!("[PASS] {name}"),
+        Err(hint) => {
+            failures += 1;
+            println!("[FAIL] {name}\n       {hint}");
+        }
+    };
+
+    let data_dir = data_dir();
+    let data_dir_exists = data_dir
+        .try_exists()
+        .map_io_err(|| format!("Failed to check that database exists: {data_dir:?}"))?;
+    check(
+        &format!("Database directory exists: {data_dir:?}"),
+        if data_dir_exists {
+            Ok(())
+        } else {
+            Err("Start the Ringboard server to create it or fix the XDG_DATA_HOME path.".into())
+        },
+    );
+    if data_dir_exists {
+        check(
+            "Database directory is writable",
+            access(
+                &data_dir,
+                Access::READ_OK | Access::WRITE_OK | Access::EXEC_OK,
+            )
+            .map_err(|e| {
+                format!("Fix the directory permissions so the current user owns it: {e}").into()
+            }),
+        );
+        check(
+            "Filesystem supports O_TMPFILE",
+            match openat(
+                CWD,
+                &data_dir,
+                OFlags::RDWR | OFlags::TMPFILE,
+                Mode::RUSR | Mode::WUSR,
+            ) {
+                Ok(_) => Ok(()),
+                Err(Errno::NOTSUP | Errno::ISDIR) => Err("Ringboard will fall back to named \
+                                                          scratch files which is slower but \
+                                                          otherwise harmless."
+                    .into()),
+                Err(e) => Err(format!("Failed to create temporary file: {e}").into()),
+            },
+        );
+        check(
+            "Filesystem supports extended attributes",
+            match getxattr(&data_dir, c"user.mime_type", &mut []) {
+                Ok(_) | Err(Errno::NODATA) => Ok(()),
+                Err(Errno::NOTSUP) => Err("Mime types will be stored in the metadata directory \
+                                           instead, which is slower but otherwise harmless."
+                    .into()),
+                Err(e) => Err(format!("Failed to read extended attributes: {e}").into()),
+            },
+        );
+    }
+
+    for (name, socket, hint) in [
+        (
+            "Server socket exists",
+            socket_file(),
+            "Start the Ringboard server or set RINGBOARD_SOCK to the socket the server uses.",
+        ),
+        (
+            "Paste socket exists",
+            paste_socket_file(),
+            "Start a clipboard watcher (X11 or Wayland) or set PASTE_SOCK to the socket the \
+             watcher uses.",
+        ),
+    ] {
+        check(
+            &format!("{name}: {socket:?}"),
+            if abstract_socket_name(&socket).is_some() || socket.exists() {
+                Ok(())
+            } else {
+                Err(hint.into())
+            },
+        );
+    }
+    check(
+        "Server is running and accepting connections",
+        match connect_to_server(server_addr) {
+            Ok(_) => Ok(()),
+            Err(ClientError::Core(CoreError::Io { error, context })) => Err(format!(
+                "Start the server (for example via `systemctl --user start ringboard-server`). \
+                 {context}: {error}"
+            )
+            .into()),
+            Err(e) => {
+                Err(format!("Upgrade the server and clients to matching versions: {e}").into())
+            }
+        },
+    );
+
+    let session_type = env::var("XDG_SESSION_TYPE").unwrap_or_default();
+    let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = env::var_os("DISPLAY").is_some();
+    check(
+        &format!(
+            "Session type detected: {}",
+            match (session_type.as_str(), wayland, x11) {
+                ("wayland", _, _) | (_, true, _) => "Wayland",
+                ("x11", _, _) | (_, false, true) => "X11",
+                _ => "unknown",
+            }
+        ),
+        if wayland || x11 {
+            Ok(())
+        } else {
+            Err(
+                "Neither WAYLAND_DISPLAY nor DISPLAY is set, so no clipboard watcher can run. \
+                 Make sure the watcher service imports the graphical session environment."
+                    .into(),
+            )
+        },
+    );
+
+    if failures > 0 {
+        return Err(CliError::ChecksFailed { failures });
+    }
+    println!("All checks passed.");
+    Ok(())
+}
+
 fn pipeline_request(
     mut send: impl FnMut(SendFlags) -> Result<(), ClientError>,
     mut recv: impl FnMut(RecvFlags) -> Result<(), ClientError>,
@@ -2075,16 +4479,17 @@ fn pipelined_add_recv<'a>(
     mut translation: Option<&'a mut Vec<u64>>,
 ) -> impl FnMut(RecvFlags) -> Result<(), ClientError> + 'a {
     move |flags| {
-        unsafe { AddRequest::recv(&server, flags) }.map(
-            |Response {
-                 sequence_number: _,
-                 value: AddResponse::Success { id },
-             }| {
-                if let Some(translation) = translation.as_deref_mut() {
-                    translation.push(id);
-                }
-            },
-        )
+        let Response {
+            sequence_number: _,
+            value: AddResponse::Success { id },
+        } = unsafe { AddRequest::recv(&server, flags) }?
+        else {
+            return Err(ClientError::RateLimited);
+        };
+        if let Some(translation) = translation.as_deref_mut() {
+            translation.push(id);
+        }
+        Ok(())
     }
 }
 
@@ -2097,7 +4502,7 @@ unsafe fn pipeline_add_request(
     pending_adds: &mut u32,
 ) -> Result<(), CliError> {
     pipeline_request(
-        |flags| AddRequest::send(server, to, mime_type, &data, flags),
+        |flags| AddRequest::send(server, to, mime_type, &data, 0, flags),
         pipelined_add_recv(server, translation),
         pending_adds,
     )
@@ -2126,4 +4531,92 @@ mod cli_tests {
     fn help_for_review() {
         supercilex_tests::help_for_review(Cli::command());
     }
+
+    #[test]
+    fn copyq_rows() {
+        assert_eq!(
+            parse_copyq_row(b"text/plain\tstar\ttab\\there\\\\back\\nline"),
+            Some(CopyQItem {
+                mime_type: MimeType::from("text/plain").unwrap(),
+                favorite: true,
+                data: b"tab\there\\back\nline".to_vec(),
+            })
+        );
+        assert_eq!(
+            parse_copyq_row(b"image/png\t \tUE5HREFUQQ=="),
+            Some(CopyQItem {
+                mime_type: MimeType::from("image/png").unwrap(),
+                favorite: false,
+                data: b"PNGDATA".to_vec(),
+            })
+        );
+        assert_eq!(
+            parse_copyq_row(b"\t\tplain"),
+            Some(CopyQItem {
+                mime_type: MimeType::new_const(),
+                favorite: false,
+                data: b"plain".to_vec(),
+            })
+        );
+
+        assert_eq!(parse_copyq_row(b"text/plain\tno contents"), None);
+        assert_eq!(parse_copyq_row(b"text/plain\t\tbad \\escape"), None);
+        assert_eq!(parse_copyq_row(b"image/png\t\tnot base64!"), None);
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        let root =
+            std::env::temp_dir().join(format!("ringboard-archive-test-{}", std::process::id()));
+        let (database, restored) = (root.join("database"), root.join("restored"));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(database.join("direct")).unwrap();
+        fs::create_dir_all(&restored).unwrap();
+
+        let mut header = [0; size_of::<ring::Header>()];
+        header[..ring::MAGIC.len()].copy_from_slice(&ring::MAGIC);
+        header[offset_of!(ring::Header, version)] = ring::VERSION;
+        fs::write(database.join(RingKind::Main.file_name()), header).unwrap();
+        fs::write(database.join("server.lock"), b"").unwrap();
+        let entry = database.join("direct/42");
+        fs::write(&entry, b"Hello, archive!").unwrap();
+        fs::set_permissions(&entry, fs::Permissions::from_mode(0o440)).unwrap();
+        let xattrs = fsetxattr(
+            File::open(&entry).unwrap(),
+            c"user.mime_type",
+            b"text/x-test",
+            XattrFlags::empty(),
+        )
+        .is_ok();
+
+        let mut archive = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(root.join("archive"))
+            .unwrap();
+        let (num_files, num_bytes) =
+            write_archive(&database, archive.try_clone().unwrap()).unwrap();
+        assert_eq!(num_files, 2);
+        assert_eq!(num_bytes, u64::try_from(header.len()).unwrap() + 15);
+
+        archive.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(read_archive(&mut archive, &restored).unwrap(), 2);
+
+        assert!(!restored.join("server.lock").exists());
+        assert_eq!(
+            fs::read(restored.join(RingKind::Main.file_name())).unwrap(),
+            header
+        );
+        let entry = restored.join("direct/42");
+        assert_eq!(fs::read(&entry).unwrap(), b"Hello, archive!");
+        assert_eq!(fs::metadata(&entry).unwrap().mode() & 0o7777, 0o440);
+        if xattrs {
+            let mut mime_type = [0; 16];
+            let len = getxattr(&entry, c"user.mime_type", &mut mime_type).unwrap();
+            assert_eq!(&mime_type[..len], b"text/x-test");
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -8,14 +8,15 @@ use std::{
 };
 
 use ringboard_core::{
-    AsBytes, IoErr, create_tmp_file, protocol,
+    AsBytes, IoErr, Timestamp, create_tmp_file, protocol,
     protocol::{
-        AddResponse, GarbageCollectResponse, MimeType, MoveToFrontResponse, RemoveResponse,
-        Request, Response, RingKind, SwapResponse,
+        AddAlternateResponse, AddResponse, GarbageCollectResponse, Label, ListResponse, MimeType,
+        MoveToFrontResponse, RemoveResponse, Request, Response, RingKind, SetExpiryResponse,
+        SetLabelResponse, SwapResponse, WipeResponse,
     },
 };
 use rustix::{
-    fs::{AtFlags, CWD, FileType, Mode, OFlags, StatxFlags, statx},
+    fs::{AtFlags, CWD, FileType, Mode, OFlags, SealFlags, StatxFlags, fcntl_add_seals, statx},
     net::{
         AddressFamily, RecvAncillaryBuffer, RecvFlags, SendAncillaryBuffer, SendAncillaryMessage,
         SendFlags, SocketAddrUnix, SocketFlags, SocketType, connect_unix, recvmsg, sendmsg,
@@ -55,6 +56,23 @@ pub fn connect_to_server(addr: &SocketAddrUnix) -> Result<OwnedFd, ClientError>
 pub fn connect_to_server_with(
     addr: &SocketAddrUnix,
     flags: SocketFlags,
+) -> Result<OwnedFd, ClientError> {
+    connect(addr, flags, 0)
+}
+
+/// Connect to the server as a client that adds entries in bulk, exempting it
+/// from the server's add rate limit.
+pub fn connect_to_server_for_bulk(
+    addr: &SocketAddrUnix,
+    flags: SocketFlags,
+) -> Result<OwnedFd, ClientError> {
+    connect(addr, flags, protocol::CONNECTION_FLAG_BULK)
+}
+
+fn connect(
+    addr: &SocketAddrUnix,
+    flags: SocketFlags,
+    connection_flags: u8,
 ) -> Result<OwnedFd, ClientError> {
     let socket = socket_with(AddressFamily::UNIX, SocketType::SEQPACKET, flags, None)
         .map_io_err(|| format!("Failed to create socket: {addr:?}"))?;
@@ -63,7 +81,7 @@ pub fn connect_to_server_with(
     {
         sendmsg(
             &socket,
-            &[IoSlice::new(&[protocol::VERSION])],
+            &[IoSlice::new(&[protocol::VERSION, connection_flags])],
             &mut SendAncillaryBuffer::default(),
             SendFlags::empty(),
         )
@@ -99,17 +117,28 @@ pub fn connect_to_paste_server(addr: &SocketAddrUnix) -> Result<OwnedFd, ClientE
     Ok(sock)
 }
 
-pub const PASTE_SERVER_PROTOCOL_VERSION: u8 = 2;
+pub const PASTE_SERVER_PROTOCOL_VERSION: u8 = 3;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct PasteCommand {
     version: u8,
     pub trigger_paste: bool,
+    pub target: PasteTarget,
     pub id: u64,
     pub mime: MimeType,
 }
 
+/// The selections a paste server should claim.
+#[repr(u8)]
+#[derive(Default, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PasteTarget {
+    #[default]
+    Both,
+    Clipboard,
+    Primary,
+}
+
 impl AsBytes for PasteCommand {}
 
 pub fn send_paste_buffer(
@@ -117,6 +146,7 @@ pub fn send_paste_buffer(
     entry: Entry,
     reader: &mut EntryReader,
     trigger_paste: bool,
+    target: PasteTarget,
 ) -> ringboard_core::Result<()> {
     let file = entry.to_file(reader)?;
     let mime = file.mime_type()?;
@@ -131,6 +161,7 @@ pub fn send_paste_buffer(
     let cmd = PasteCommand {
         version: PASTE_SERVER_PROTOCOL_VERSION,
         trigger_paste,
+        target,
         id: entry.id(),
         mime,
     };
@@ -164,7 +195,7 @@ impl AddRequest {
                 .into(),
         ) == FileType::RegularFile
         {
-            Self::response_add_unchecked(server, to, mime_type, data)
+            Self::response_add_unchecked(server, to, mime_type, data, 0)
         } else {
             let file = create_tmp_file(
                 &mut false,
@@ -185,7 +216,7 @@ impl AddRequest {
             file.seek(SeekFrom::Start(0))
                 .map_io_err(|| "Failed to reset intermediary data file offset.")?;
 
-            Self::response_add_unchecked(server, to, mime_type, &file)
+            Self::response_add_unchecked(server, to, mime_type, &file, 0)
         }
     }
 
@@ -194,8 +225,25 @@ impl AddRequest {
         to: RingKind,
         mime_type: MimeType,
         data: Data,
+        add_flags: u8,
+    ) -> Result<AddResponse, ClientError> {
+        Self::send(&server, to, mime_type, data, add_flags, SendFlags::empty())?;
+        unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| value,
+        )
+    }
+
+    pub fn response_add_sealed<Server: AsFd, Data: AsFd>(
+        server: Server,
+        to: RingKind,
+        mime_type: MimeType,
+        data: Data,
+        add_flags: u8,
     ) -> Result<AddResponse, ClientError> {
-        Self::send(&server, to, mime_type, data, SendFlags::empty())?;
+        Self::send_sealed(&server, to, mime_type, data, add_flags, SendFlags::empty())?;
         unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
             |Response {
                  sequence_number: _,
@@ -204,14 +252,54 @@ impl AddRequest {
         )
     }
 
+    /// `add_flags` is a combination of the `ADD_FLAG_*` constants in
+    /// [`protocol`].
     pub fn send<Server: AsFd, Data: AsFd>(
         server: Server,
         to: RingKind,
         mime_type: MimeType,
         data: Data,
+        add_flags: u8,
+        flags: SendFlags,
+    ) -> Result<(), ClientError> {
+        request_with_fd(
+            &server,
+            Request::Add {
+                to,
+                mime_type,
+                flags: add_flags,
+            },
+            data,
+            flags,
+        )
+    }
+
+    /// Seals the data, which must be a memfd created with
+    /// [`MemfdFlags::ALLOW_SEALING`](rustix::fs::MemfdFlags::ALLOW_SEALING),
+    /// before sending it so the server can skip copying it.
+    pub fn send_sealed<Server: AsFd, Data: AsFd>(
+        server: Server,
+        to: RingKind,
+        mime_type: MimeType,
+        data: Data,
+        add_flags: u8,
         flags: SendFlags,
     ) -> Result<(), ClientError> {
-        request_with_fd(&server, Request::Add { to, mime_type }, data, flags)
+        fcntl_add_seals(
+            &data,
+            SealFlags::SEAL | SealFlags::SHRINK | SealFlags::GROW | SealFlags::WRITE,
+        )
+        .map_io_err(|| "Failed to seal data file.")?;
+        request_with_fd(
+            &server,
+            Request::AddSealed {
+                to,
+                mime_type,
+                flags: add_flags,
+            },
+            data,
+            flags,
+        )
     }
 
     response!(AddResponse);
@@ -326,6 +414,156 @@ impl GarbageCollectRequest {
     response!(GarbageCollectResponse);
 }
 
+pub struct WipeRequest;
+
+impl WipeRequest {
+    pub fn response<Server: AsFd>(
+        server: Server,
+        from: Option<RingKind>,
+    ) -> Result<WipeResponse, ClientError> {
+        Self::send(&server, from, SendFlags::empty())?;
+        unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| value,
+        )
+    }
+
+    pub fn send<Server: AsFd>(
+        server: Server,
+        from: Option<RingKind>,
+        flags: SendFlags,
+    ) -> Result<(), ClientError> {
+        request(&server, Request::Wipe { from }, flags)
+    }
+
+    response!(WipeResponse);
+}
+
+pub struct SetLabelRequest;
+
+impl SetLabelRequest {
+    pub fn response<Server: AsFd>(
+        server: Server,
+        id: u64,
+        label: Label,
+    ) -> Result<SetLabelResponse, ClientError> {
+        Self::send(&server, id, label, SendFlags::empty())?;
+        unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| value,
+        )
+    }
+
+    pub fn send<Server: AsFd>(
+        server: Server,
+        id: u64,
+        label: Label,
+        flags: SendFlags,
+    ) -> Result<(), ClientError> {
+        request(&server, Request::SetLabel { id, label }, flags)
+    }
+
+    response!(SetLabelResponse);
+}
+
+pub struct AddAlternateRequest;
+
+impl AddAlternateRequest {
+    pub fn response<Server: AsFd, Data: AsFd>(
+        server: Server,
+        id: u64,
+        mime_type: MimeType,
+        data: Data,
+    ) -> Result<AddAlternateResponse, ClientError> {
+        Self::send(&server, id, mime_type, data, SendFlags::empty())?;
+        unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| value,
+        )
+    }
+
+    pub fn send<Server: AsFd, Data: AsFd>(
+        server: Server,
+        id: u64,
+        mime_type: MimeType,
+        data: Data,
+        flags: SendFlags,
+    ) -> Result<(), ClientError> {
+        request_with_fd(
+            &server,
+            Request::AddAlternate { id, mime_type },
+            data,
+            flags,
+        )
+    }
+
+    response!(AddAlternateResponse);
+}
+
+pub struct SetExpiryRequest;
+
+impl SetExpiryRequest {
+    pub fn response<Server: AsFd>(
+        server: Server,
+        id: u64,
+        expires_at: Option<Timestamp>,
+    ) -> Result<SetExpiryResponse, ClientError> {
+        Self::send(&server, id, expires_at, SendFlags::empty())?;
+        unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| value,
+        )
+    }
+
+    pub fn send<Server: AsFd>(
+        server: Server,
+        id: u64,
+        expires_at: Option<Timestamp>,
+        flags: SendFlags,
+    ) -> Result<(), ClientError> {
+        request(&server, Request::SetExpiry { id, expires_at }, flags)
+    }
+
+    response!(SetExpiryResponse);
+}
+
+pub struct ListRequest;
+
+impl ListRequest {
+    pub fn response<Server: AsFd>(
+        server: Server,
+        from: RingKind,
+        offset: u32,
+    ) -> Result<ListResponse, ClientError> {
+        Self::send(&server, from, offset, SendFlags::empty())?;
+        unsafe { Self::recv(&server, RecvFlags::empty()) }.map(
+            |Response {
+                 sequence_number: _,
+                 value,
+             }| value,
+        )
+    }
+
+    pub fn send<Server: AsFd>(
+        server: Server,
+        from: RingKind,
+        offset: u32,
+        flags: SendFlags,
+    ) -> Result<(), ClientError> {
+        request(&server, Request::List { from, offset }, flags)
+    }
+
+    response!(ListResponse);
+}
+
 fn request(server: impl AsFd, request: Request, flags: SendFlags) -> Result<(), ClientError> {
     request_with_ancillary(server, request, &mut SendAncillaryBuffer::default(), flags)
 }
@@ -10,6 +10,20 @@ pub fn x11_config_file() -> PathBuf {
     file
 }
 
+#[must_use]
+pub fn egui_config_file() -> PathBuf {
+    let mut file = config_file_dir();
+    file.push("egui.toml");
+    file
+}
+
+#[must_use]
+pub fn tui_config_file() -> PathBuf {
+    let mut file = config_file_dir();
+    file.push("tui.toml");
+    file
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "version")]
 pub enum X11Config {
@@ -27,12 +41,26 @@ impl Default for X11Config {
 pub struct X11V1Config {
     #[serde(default = "x11_auto_paste_")]
     pub auto_paste: bool,
+    #[serde(default)]
+    pub capture_primary: bool,
+    /// The key chord sent to the focused application when auto-pasting.
+    ///
+    /// Written as a key optionally prefixed with modifiers, e.g.
+    /// `"shift+insert"` or `"ctrl+shift+v"`.
+    #[serde(default = "x11_paste_key_")]
+    pub paste_key: String,
+    /// Drop copied text that looks like a secret instead of saving it.
+    #[serde(default)]
+    pub ignore_secrets: bool,
 }
 
 impl Default for X11V1Config {
     fn default() -> Self {
         Self {
             auto_paste: x11_auto_paste_(),
+            capture_primary: false,
+            paste_key: x11_paste_key_(),
+            ignore_secrets: false,
         }
     }
 }
@@ -40,3 +68,209 @@ impl Default for X11V1Config {
 const fn x11_auto_paste_() -> bool {
     true
 }
+
+fn x11_paste_key_() -> String {
+    "shift+insert".to_string()
+}
+
+/// Parses a key chord into the X11 keysyms to press, in order.
+///
+/// Returns [`None`] if the chord names an unknown key or modifier.
+#[must_use]
+pub fn x11_chord_keysyms(chord: &str) -> Option<Vec<u32>> {
+    let mut parts = chord.split('+');
+    let key = parts.next_back()?;
+
+    let mut keysyms = Vec::new();
+    for modifier in parts {
+        keysyms.push(match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => 0xffe3,
+            "shift" => 0xffe1,
+            "alt" => 0xffe9,
+            "super" => 0xffeb,
+            _ => return None,
+        });
+    }
+
+    let mut chars = key.chars();
+    keysyms.push(
+        if let Some(c) = chars.next()
+            && chars.next().is_none()
+            && c.is_ascii_graphic()
+        {
+            u32::from(c.to_ascii_lowercase())
+        } else {
+            match key.to_ascii_lowercase().as_str() {
+                "insert" => 0xff63,
+                "enter" | "return" => 0xff0d,
+                "tab" => 0xff09,
+                "space" => 0x20,
+                "backspace" => 0xff08,
+                "delete" => 0xffff,
+                "escape" => 0xff1b,
+                "home" => 0xff50,
+                "end" => 0xff57,
+                "pageup" => 0xff55,
+                "pagedown" => 0xff56,
+                "left" => 0xff51,
+                "up" => 0xff52,
+                "right" => 0xff53,
+                "down" => 0xff54,
+                f => match f.strip_prefix('f')?.parse::<u32>().ok()? {
+                    n @ 1..=24 => 0xffbe + n - 1,
+                    _ => return None,
+                },
+            }
+        },
+    );
+    Some(keysyms)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "version")]
+pub enum EguiConfig {
+    V1(EguiV1Config),
+}
+
+impl Default for EguiConfig {
+    fn default() -> Self {
+        Self::V1(EguiV1Config::default())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename = "v1")]
+pub struct EguiV1Config {
+    #[serde(default)]
+    pub theme: Theme,
+    /// The maximum height of image previews in logical pixels.
+    #[serde(default = "egui_image_preview_height_")]
+    pub image_preview_height: f32,
+    /// Shrink entry rows so more of them fit on screen.
+    #[serde(default)]
+    pub compact: bool,
+    /// List favorites among the main entries by when they were last used
+    /// instead of pinning them to the top.
+    #[serde(default)]
+    pub interleave_favorites: bool,
+    /// Shade every other row to make long lists easier to scan.
+    #[serde(default)]
+    pub striped_rows: bool,
+    /// Keep the window above other windows.
+    #[serde(default)]
+    pub always_on_top: bool,
+    /// Clear the search query after pasting a search result to go back to
+    /// the full list of entries.
+    #[serde(default)]
+    pub clear_search_on_paste: bool,
+    /// Hide the window when it loses focus as though escape had been pressed,
+    /// making it behave like a transient popup.
+    #[serde(default)]
+    pub close_on_focus_loss: bool,
+}
+
+impl Default for EguiV1Config {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            image_preview_height: egui_image_preview_height_(),
+            compact: false,
+            interleave_favorites: false,
+            striped_rows: false,
+            always_on_top: false,
+            clear_search_on_paste: false,
+            close_on_focus_loss: false,
+        }
+    }
+}
+
+const fn egui_image_preview_height_() -> f32 {
+    250.
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "version")]
+pub enum TuiConfig {
+    V1(TuiV1Config),
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self::V1(TuiV1Config::default())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename = "v1")]
+pub struct TuiV1Config {
+    #[serde(default)]
+    pub bindings: TuiKeyBindings,
+}
+
+/// Keys bound to each TUI action.
+///
+/// Keys are written as a single character (`"j"`), a named key (`"up"`,
+/// `"space"`, `"enter"`, `"f1"`, ...), or either prefixed with modifiers
+/// (`"ctrl+r"`, `"alt+shift+x"`).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TuiKeyBindings {
+    pub quit: Vec<String>,
+    pub paste: Vec<String>,
+    pub paste_primary: Vec<String>,
+    pub down: Vec<String>,
+    pub up: Vec<String>,
+    pub show_details: Vec<String>,
+    pub hide_details: Vec<String>,
+    pub toggle_details: Vec<String>,
+    pub scroll_details_down: Vec<String>,
+    pub scroll_details_up: Vec<String>,
+    pub search: Vec<String>,
+    pub regex_search: Vec<String>,
+    pub mime_search: Vec<String>,
+    pub favorite: Vec<String>,
+    pub delete: Vec<String>,
+    pub help: Vec<String>,
+    pub refresh: Vec<String>,
+    pub reset: Vec<String>,
+    pub switch_pane: Vec<String>,
+}
+
+impl Default for TuiKeyBindings {
+    fn default() -> Self {
+        fn keys(keys: &[&str]) -> Vec<String> {
+            keys.iter().map(|&k| k.to_string()).collect()
+        }
+
+        Self {
+            quit: keys(&["q", "ctrl+c"]),
+            paste: keys(&["enter"]),
+            paste_primary: keys(&["p"]),
+            down: keys(&["down", "j"]),
+            up: keys(&["up", "k"]),
+            show_details: keys(&["right", "l"]),
+            hide_details: keys(&["left", "h"]),
+            toggle_details: keys(&["space"]),
+            scroll_details_down: keys(&["J"]),
+            scroll_details_up: keys(&["K"]),
+            search: keys(&["/", "s"]),
+            regex_search: keys(&["x"]),
+            mime_search: keys(&["m"]),
+            favorite: keys(&["f"]),
+            delete: keys(&["d"]),
+            help: keys(&["?"]),
+            refresh: keys(&["r"]),
+            reset: keys(&["ctrl+r"]),
+            switch_pane: keys(&["tab"]),
+        }
+    }
+}
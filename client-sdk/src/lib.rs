@@ -1,9 +1,11 @@
 #![feature(core_io_borrowed_buf)]
+#![feature(borrowed_buf_init)]
 
 use std::borrow::Cow;
 
 pub use ring_reader::{
-    DatabaseReader, Entry, EntryReader, Kind, LoadedEntry, RingReader, is_text_mime,
+    BucketUsage, DatabaseReader, Entry, EntryReader, Interleaved, Kind, LoadedEntry, RingReader,
+    is_text_mime, preview, read_alternates, read_direct_file_at,
 };
 pub use ringboard_core as core;
 use ringboard_core::protocol::IdNotFoundError;
@@ -19,6 +21,8 @@ pub mod duplicate_detection;
 mod ring_reader;
 #[cfg(feature = "search")]
 pub mod search;
+#[cfg(feature = "thumbnails")]
+pub mod thumbnail;
 #[cfg(feature = "ui")]
 pub mod ui_actor;
 
@@ -30,6 +34,8 @@ pub enum ClientError {
     VersionMismatch { expected: u8, actual: u8 },
     #[error("invalid server response")]
     InvalidResponse { context: Cow<'static, str> },
+    #[error("rate limited by the server")]
+    RateLimited,
 }
 
 impl From<IdNotFoundError> for ClientError {
@@ -51,6 +57,10 @@ mod error_stack_compat {
                 Self::InvalidResponse { context } => Report::new(wrapper).attach_printable(context),
                 Self::VersionMismatch { expected, actual } => Report::new(wrapper)
                     .attach_printable(format!("Expected v{expected} but got v{actual}.")),
+                Self::RateLimited => Report::new(wrapper).attach_printable(
+                    "Too many entries were added too quickly. Try again later or raise the \
+                     server's --add-rate-limit.",
+                ),
             }
         }
     }
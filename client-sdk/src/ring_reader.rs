@@ -1,9 +1,13 @@
 use std::{
+    array,
     cmp::min,
+    ffi::CStr,
     fmt::{Debug, Formatter},
     fs::File,
     io,
-    io::{BorrowedBuf, ErrorKind},
+    io::{BorrowedBuf, BorrowedCursor, ErrorKind, Read, Seek},
+    iter,
+    iter::{Peekable, Rev},
     mem::MaybeUninit,
     ops::{Deref, DerefMut},
     os::{
@@ -12,18 +16,23 @@ use std::{
     },
     path::PathBuf,
     slice, str,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use arrayvec::ArrayVec;
 use ringboard_core::{
-    IoErr, NUM_BUCKETS, PathView, RingAndIndex, bucket_to_length, direct_file_name, open_buckets,
-    protocol::{IdNotFoundError, MimeType, RingKind, composite_id, decompose_id},
+    EntryKey, IoErr, NUM_BUCKETS, PathView, RingAndIndex, bucket_to_length, copy_to_memfd,
+    direct_file_name, is_compressed, open_buckets, parse_alternate_file_name,
+    protocol::{IdNotFoundError, Label, MimeType, RingKind, composite_id, decompose_id},
     read_at_to_end,
-    ring::{InitializedEntry, Mmap, Ring},
+    ring::{InitializedEntry, LastUsed, Mmap, Ring},
     size_to_bucket,
 };
 use rustix::{
-    fs::{CWD, MemfdFlags, Mode, OFlags, fgetxattr, memfd_create, openat},
+    fs::{
+        AtFlags, CWD, FileType, MemfdFlags, Mode, OFlags, RawDir, StatxFlags, fgetxattr,
+        memfd_create, openat, statx,
+    },
     io::Errno,
     path::Arg,
 };
@@ -41,6 +50,7 @@ struct RingIter {
     front: u32,
     back: u32,
     done: bool,
+    snapshot: bool,
 }
 
 impl RingIter {
@@ -73,12 +83,29 @@ impl RingIter {
                 || ring.next_head(self.front) == self.write_head
                 || self.back == self.write_head;
 
-            if let Some(entry) = Entry::from(ring, self.kind, advance(self)) {
+            let index = advance(self);
+            if let Some(entry) = Entry::from(ring, self.kind, index)
+                && !(self.snapshot && self.overwritten(ring, index))
+            {
                 break Some(entry);
             }
         }
     }
 
+    /// Whether the slot at `index` has been reused by an entry added after our
+    /// write head was captured.
+    ///
+    /// Must be checked after reading the entry so a concurrent write is either
+    /// observed here or didn't affect the entry.
+    fn overwritten(&self, ring: &Ring, index: u32) -> bool {
+        let (captured, live) = (self.write_head, ring.write_head());
+        if live >= captured {
+            (captured..live).contains(&index)
+        } else {
+            index >= captured || index < live
+        }
+    }
+
     fn size_hint(&self, ring: &Ring) -> (usize, Option<usize>) {
         let len = if self.front > self.back {
             ring.len() - self.front + self.back
@@ -94,16 +121,66 @@ impl RingIter {
 pub struct DatabaseReader {
     main: Ring,
     favorites: Ring,
+    main_last_used: LastUsed,
+    favorites_last_used: LastUsed,
+    snapshot: Option<WriteHeads>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct WriteHeads {
+    main: u32,
+    favorites: u32,
 }
 
 impl DatabaseReader {
     pub fn open(database: &mut PathBuf) -> Result<Self, ringboard_core::Error> {
+        let last_used = |database: &mut PathBuf, kind: RingKind| {
+            LastUsed::open(&*PathView::new(database, kind.last_used_file_name()))
+        };
         Ok(Self {
             main: RingReader::prepare_ring(database, RingKind::Main)?,
             favorites: RingReader::prepare_ring(database, RingKind::Favorites)?,
+            main_last_used: last_used(database, RingKind::Main)?,
+            favorites_last_used: last_used(database, RingKind::Favorites)?,
+            snapshot: None,
         })
     }
 
+    /// Open the database as of this moment: ring iterators are bounded by the
+    /// write heads captured here and skip entries added afterwards, giving
+    /// long scans a consistent view of a busy database.
+    ///
+    /// Entries evicted by later additions are skipped rather than replaced, so
+    /// the snapshot can only shrink. Moves, swaps, and removals are not
+    /// isolated.
+    pub fn open_snapshot(database: &mut PathBuf) -> Result<Self, ringboard_core::Error> {
+        let mut me = Self::open(database)?;
+        me.snapshot = Some(WriteHeads {
+            main: me.main.write_head(),
+            favorites: me.favorites.write_head(),
+        });
+        Ok(me)
+    }
+
+    /// Reopen the ring files to pick up server-side changes such as a ring
+    /// being grown or the database being wiped and recreated.
+    ///
+    /// Returns whether either ring's capacity or length changed.
+    ///
+    /// Snapshots are retaken as of the reopen.
+    pub fn reopen(&mut self, database: &mut PathBuf) -> Result<bool, ringboard_core::Error> {
+        let new = if self.snapshot.is_some() {
+            Self::open_snapshot(database)?
+        } else {
+            Self::open(database)?
+        };
+        let changed = [(&self.main, &new.main), (&self.favorites, &new.favorites)]
+            .into_iter()
+            .any(|(old, new)| old.capacity() != new.capacity() || old.len() != new.len());
+        *self = new;
+        Ok(changed)
+    }
+
     pub fn get_raw(&self, id: u64) -> Result<Entry, IdNotFoundError> {
         let (kind, id) = decompose_id(id)?;
         Entry::from(
@@ -117,6 +194,11 @@ impl DatabaseReader {
         .ok_or(IdNotFoundError::Entry(id))
     }
 
+    #[must_use]
+    pub fn get_by_uri(&self, uri: &str) -> Option<Entry> {
+        RingAndIndex::from_uri(uri).and_then(|rai| self.get_raw(rai.id()).ok())
+    }
+
     /// # Safety
     ///
     /// The ID must index into a ring whose length is greater than the index
@@ -135,6 +217,49 @@ impl DatabaseReader {
         self.get_raw(id)
     }
 
+    /// When the entry was last added or moved to the front, if known.
+    #[must_use]
+    pub fn last_used(&self, entry: Entry) -> Option<SystemTime> {
+        self.last_used_millis(entry)
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    fn last_used_millis(&self, entry: Entry) -> Option<u64> {
+        match entry.ring() {
+            RingKind::Main => &self.main_last_used,
+            RingKind::Favorites => &self.favorites_last_used,
+        }
+        .get(entry.index())
+    }
+
+    /// Merge favorites and main ring entries into a single list ordered from
+    /// most to least recently used.
+    ///
+    /// Both iterators must yield their entries newest first, such as
+    /// `database.main().rev()`. Entries with unknown times sort after those
+    /// with known times and favorites win ties, so databases without
+    /// recorded times keep listing favorites first.
+    #[must_use]
+    pub fn interleave<Favorites: Iterator<Item = Entry>, Main: Iterator<Item = Entry>>(
+        &self,
+        favorites: Favorites,
+        main: Main,
+    ) -> Interleaved<Favorites, Main> {
+        Interleaved {
+            database: self,
+            favorites: favorites.peekable(),
+            main: main.peekable(),
+        }
+    }
+
+    /// Every entry ordered from most to least recently used.
+    ///
+    /// See [`DatabaseReader::interleave`].
+    #[must_use]
+    pub fn by_recency(&self) -> Interleaved<Rev<RingReader>, Rev<RingReader>> {
+        self.interleave(self.favorites().rev(), self.main().rev())
+    }
+
     pub fn main_ring_mut(&mut self) -> &mut Ring {
         &mut self.main
     }
@@ -145,12 +270,63 @@ impl DatabaseReader {
 
     #[must_use]
     pub fn main(&self) -> RingReader {
-        RingReader::from_ring(&self.main, RingKind::Main)
+        match self.snapshot {
+            Some(WriteHeads { main, .. }) => {
+                RingReader::from_snapshot(&self.main, RingKind::Main, main)
+            }
+            None => RingReader::from_ring(&self.main, RingKind::Main),
+        }
     }
 
     #[must_use]
     pub fn favorites(&self) -> RingReader {
-        RingReader::from_ring(&self.favorites, RingKind::Favorites)
+        match self.snapshot {
+            Some(WriteHeads { favorites, .. }) => {
+                RingReader::from_snapshot(&self.favorites, RingKind::Favorites, favorites)
+            }
+            None => RingReader::from_ring(&self.favorites, RingKind::Favorites),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Interleaved<'a, Favorites: Iterator<Item = Entry>, Main: Iterator<Item = Entry>> {
+    database: &'a DatabaseReader,
+    favorites: Peekable<Favorites>,
+    main: Peekable<Main>,
+}
+
+impl<Favorites: Iterator<Item = Entry>, Main: Iterator<Item = Entry>> Iterator
+    for Interleaved<'_, Favorites, Main>
+{
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Self {
+            database,
+            favorites,
+            main,
+        } = self;
+        match (favorites.peek(), main.peek()) {
+            (Some(&favorite), Some(&entry))
+                if database.last_used_millis(entry) > database.last_used_millis(favorite) =>
+            {
+                main.next()
+            }
+            (Some(_), _) => favorites.next(),
+            (None, _) => main.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (favorites_min, favorites_max) = self.favorites.size_hint();
+        let (main_min, main_max) = self.main.size_hint();
+        (
+            favorites_min.saturating_add(main_min),
+            favorites_max
+                .zip(main_max)
+                .and_then(|(a, b)| a.checked_add(b)),
+        )
     }
 }
 
@@ -167,6 +343,15 @@ impl<'a> RingReader<'a> {
         Self::from_id(ring, kind, tail, tail)
     }
 
+    /// Iterate over the ring as it was when `write_head` was read, skipping
+    /// slots that have since been reused by new entries.
+    #[must_use]
+    pub fn from_snapshot(ring: &'a Ring, kind: RingKind, write_head: u32) -> Self {
+        let mut me = Self::from_id(ring, kind, write_head, write_head);
+        me.iter.snapshot = true;
+        me
+    }
+
     #[must_use]
     pub fn from_id(ring: &'a Ring, kind: RingKind, write_head: u32, id: u32) -> Self {
         let mut me = Self::from_uninit(ring, kind);
@@ -184,6 +369,7 @@ impl<'a> RingReader<'a> {
                 back: 0,
                 front: 0,
                 done: true,
+                snapshot: false,
             },
             ring,
         }
@@ -198,7 +384,7 @@ impl<'a> RingReader<'a> {
     }
 
     #[must_use]
-    pub const fn ring(&self) -> &Ring {
+    pub const fn ring(&self) -> &'a Ring {
         self.ring
     }
 
@@ -207,6 +393,25 @@ impl<'a> RingReader<'a> {
         self.iter.kind
     }
 
+    /// The write head this reader stops at.
+    #[must_use]
+    pub const fn write_head(&self) -> u32 {
+        self.iter.write_head
+    }
+
+    /// Look up an entry in this reader's ring by its ID in constant time.
+    ///
+    /// Returns `None` if the ID belongs to another ring or doesn't point at an
+    /// initialized entry.
+    #[must_use]
+    pub fn find(&self, id: u64) -> Option<Entry> {
+        let (kind, index) = decompose_id(id).ok()?;
+        if kind != self.kind() {
+            return None;
+        }
+        Entry::from(self.ring, kind, index)
+    }
+
     pub fn reset_to(&mut self, write_head: u32, start: u32) {
         let RingIter {
             kind: _,
@@ -214,6 +419,7 @@ impl<'a> RingReader<'a> {
             back,
             front,
             done,
+            snapshot: _,
         } = &mut self.iter;
 
         // Since the on-disk ring can be longer than our in-memory known length,
@@ -286,31 +492,43 @@ enum LoadedEntryFd {
     HackySelfReference(BorrowedFd<'static>),
 }
 
+/// Opens the entry's metadata file if it has one, in which case all of its
+/// metadata is stored there rather than in xattrs.
+fn open_metadata<MetadataFd: AsFd, MetadataPath: Arg + Copy + Debug>(
+    read_from_metadata: Option<(MetadataFd, MetadataPath)>,
+) -> Result<Option<(File, MetadataPath)>, ringboard_core::Error> {
+    let Some((metadata_dir, file_name)) = read_from_metadata else {
+        return Ok(None);
+    };
+    match openat(metadata_dir, file_name, OFlags::RDONLY, Mode::empty()) {
+        Err(Errno::NOENT) => Ok(None),
+        r => Ok(Some((
+            File::from(r.map_io_err(|| format!("Failed to open metadata file: {file_name:?}"))?),
+            file_name,
+        ))),
+    }
+}
+
 pub fn xattr_mime_type<Fd: AsFd, MetadataFd: AsFd, MetadataPath: Arg + Copy + Debug>(
     fd: Fd,
     read_from_metadata: Option<(MetadataFd, MetadataPath)>,
 ) -> Result<MimeType, ringboard_core::Error> {
     let mut mime_type = [MaybeUninit::uninit(); MimeType::new_const().capacity()];
     let mut mime_type = BorrowedBuf::from(mime_type.as_mut_slice());
-    if let Some((metadata_dir, file_name)) = read_from_metadata {
-        let metadata = File::from(
-            match openat(metadata_dir, file_name, OFlags::RDONLY, Mode::empty()) {
-                Err(Errno::NOENT) => return Ok(MimeType::new_const()),
-                r => r.map_io_err(|| format!("Failed to open metadata file: {file_name:?}"))?,
-            },
-        );
+    if let Some((metadata, file_name)) = open_metadata(read_from_metadata)? {
         read_at_to_end(&metadata, mime_type.unfilled(), 0)
             .map_io_err(|| format!("Failed to read metadata file: {file_name:?}"))?;
     } else {
         let mut mime_type = mime_type.unfilled();
-        mime_type.ensure_init();
-        let len = match fgetxattr(fd, c"user.mime_type", mime_type.init_mut()) {
-            Err(Errno::NODATA) => return Ok(MimeType::new_const()),
+        let len = match fgetxattr(fd, c"user.mime_type", mime_type.ensure_init()) {
+            Err(Errno::NODATA | Errno::NOTSUP) => return Ok(MimeType::new_const()),
             r => r.map_io_err(|| "Failed to read extended attributes.")?,
         };
-        mime_type.advance(len);
+        mime_type.advance_checked(len);
     };
-    let mime_type = str::from_utf8(mime_type.filled()).map_err(|e| ringboard_core::Error::Io {
+    // Metadata files store the label after the mime type, separated by a NUL byte.
+    let mime_type = mime_type.filled().split(|&b| b == 0).next().unwrap();
+    let mime_type = str::from_utf8(mime_type).map_err(|e| ringboard_core::Error::Io {
         error: io::Error::new(ErrorKind::InvalidInput, e),
         context: "Database corruption detected: invalid mime type detected".into(),
     })?;
@@ -318,6 +536,236 @@ pub fn xattr_mime_type<Fd: AsFd, MetadataFd: AsFd, MetadataPath: Arg + Copy + De
     Ok(MimeType::from(mime_type).unwrap())
 }
 
+pub fn xattr_label<Fd: AsFd, MetadataFd: AsFd, MetadataPath: Arg + Copy + Debug>(
+    fd: Fd,
+    read_from_metadata: Option<(MetadataFd, MetadataPath)>,
+) -> Result<Label, ringboard_core::Error> {
+    const METADATA_LEN: usize =
+        MimeType::new_const().capacity() + 1 + Label::new_const().capacity();
+
+    let mut metadata = [MaybeUninit::uninit(); METADATA_LEN];
+    let mut metadata = BorrowedBuf::from(metadata.as_mut_slice());
+    let mut xattr = [0; Label::new_const().capacity()];
+    let label = if let Some((file, file_name)) = open_metadata(read_from_metadata)? {
+        read_at_to_end(&file, metadata.unfilled(), 0)
+            .map_io_err(|| format!("Failed to read metadata file: {file_name:?}"))?;
+
+        // Metadata files are formatted as `{mime_type}\0{label}\0{expires_at}\0{secret}`.
+        let mut fields = metadata.filled().split(|&b| b == 0).skip(1);
+        let Some(label) = fields.next() else {
+            return Ok(Label::new_const());
+        };
+        label
+    } else {
+        let len = match fgetxattr(fd, c"user.label", &mut xattr) {
+            Err(Errno::NODATA | Errno::NOTSUP) => return Ok(Label::new_const()),
+            r => r.map_io_err(|| "Failed to read extended attributes.")?,
+        };
+        &xattr[..len]
+    };
+    let label = str::from_utf8(label).map_err(|e| ringboard_core::Error::Io {
+        error: io::Error::new(ErrorKind::InvalidInput, e),
+        context: "Database corruption detected: invalid label detected".into(),
+    })?;
+
+    Ok(Label::from(label).unwrap())
+}
+
+/// Whether the server tagged the entry as looking like a secret when it was
+/// added.
+pub fn xattr_secret<Fd: AsFd, MetadataFd: AsFd, MetadataPath: Arg + Copy + Debug>(
+    fd: Fd,
+    read_from_metadata: Option<(MetadataFd, MetadataPath)>,
+) -> Result<bool, ringboard_core::Error> {
+    const METADATA_LEN: usize = MimeType::new_const().capacity()
+        + 1
+        + Label::new_const().capacity()
+        + 1
+        + "18446744073709551615".len()
+        + 1
+        + 1;
+
+    if let Some((file, file_name)) = open_metadata(read_from_metadata)? {
+        let mut metadata = [MaybeUninit::uninit(); METADATA_LEN];
+        let mut metadata = BorrowedBuf::from(metadata.as_mut_slice());
+        read_at_to_end(&file, metadata.unfilled(), 0)
+            .map_io_err(|| format!("Failed to read metadata file: {file_name:?}"))?;
+
+        Ok(metadata.filled().split(|&b| b == 0).nth(3) == Some(b"1"))
+    } else {
+        let mut xattr = [0; 1];
+        let len = match fgetxattr(fd, c"user.secret", &mut xattr) {
+            Err(Errno::NODATA | Errno::NOTSUP) => return Ok(false),
+            r => r.map_io_err(|| "Failed to read extended attributes.")?,
+        };
+        Ok(xattr[..len] == *b"1")
+    }
+}
+
+/// Decompresses a direct allocation into a memfd if it was stored compressed.
+pub fn decompress_direct_file(file: &File) -> Result<Option<File>, ringboard_core::Error> {
+    if !is_compressed(file).map_io_err(|| "Failed to read compression attribute.")? {
+        return Ok(None);
+    }
+
+    let mut decompressed = File::from(
+        memfd_create(c"ringboard_decompressed", MemfdFlags::empty())
+            .map_io_err(|| "Failed to create decompressed entry file.")?,
+    );
+    zstd::stream::copy_decode(file, &mut decompressed)
+        .map_io_err(|| "Failed to decompress entry.")?;
+    decompressed
+        .rewind()
+        .map_io_err(|| "Failed to reset decompressed entry file offset.")?;
+    Ok(Some(decompressed))
+}
+
+/// Reads a direct allocation's contents starting at `offset` into `buf`,
+/// decompressing only as much of the file as is needed if it was stored
+/// compressed.
+pub fn read_direct_file_at(
+    file: &File,
+    mut buf: BorrowedCursor,
+    offset: u64,
+) -> Result<(), ringboard_core::Error> {
+    if !is_compressed(file).map_io_err(|| "Failed to read compression attribute.")? {
+        return read_at_to_end(file, buf, offset).map_io_err(|| "Failed to read entry.");
+    }
+
+    let mut file = file;
+    file.rewind()
+        .map_io_err(|| "Failed to reset compressed entry file offset.")?;
+    let mut decoder =
+        zstd::stream::read::Decoder::new(file).map_io_err(|| "Failed to create decoder.")?;
+    io::copy(&mut (&mut decoder).take(offset), &mut io::sink())
+        .map_io_err(|| "Failed to decompress entry.")?;
+    let mut data = Vec::with_capacity(buf.capacity());
+    decoder
+        .take(u64::try_from(buf.capacity()).unwrap())
+        .read_to_end(&mut data)
+        .map_io_err(|| "Failed to decompress entry.")?;
+    buf.append(&data);
+    Ok(())
+}
+
+const PREVIEW_HIGHLIGHT_CONTEXT: usize = 24;
+const EMPTY_PREVIEW: &str = "(empty)";
+
+/// Collapses up to `max_len` bytes of `data` into a single line for display.
+///
+/// Whitespace runs are replaced with a single space, truncation on either end
+/// is marked with an ellipsis, and invalid UTF-8 is replaced lossily. If a
+/// `highlight` byte range is provided, the preview starts shortly before it
+/// and the range is remapped to point into the returned string.
+///
+/// Empty data is previewed as `(empty)` so it never renders as a blank row.
+#[must_use]
+pub fn preview(data: &[u8], max_len: usize, highlight: Option<&mut (usize, usize)>) -> String {
+    preview_impl(data, max_len, highlight, true).unwrap()
+}
+
+/// Like [`preview`], but returns `None` if the previewed bytes are not valid
+/// UTF-8.
+#[cfg(feature = "ui")]
+pub fn utf8_preview(
+    data: &[u8],
+    max_len: usize,
+    highlight: Option<&mut (usize, usize)>,
+) -> Option<String> {
+    preview_impl(data, max_len, highlight, false)
+}
+
+fn preview_impl(
+    data: &[u8],
+    max_len: usize,
+    highlight: Option<&mut (usize, usize)>,
+    lossy: bool,
+) -> Option<String> {
+    if data.is_empty() {
+        if let Some(highlight) = highlight {
+            *highlight = (EMPTY_PREVIEW.len(), EMPTY_PREVIEW.len());
+        }
+        return Some(EMPTY_PREVIEW.to_owned());
+    }
+
+    let mut skip = highlight
+        .as_ref()
+        .map_or(0, |&&mut (start, _)| {
+            start.saturating_sub(PREVIEW_HIGHLIGHT_CONTEXT)
+        })
+        .min(data.len());
+    for &b in data[skip..].iter().take(3) {
+        // https://github.com/rust-lang/rust/blob/33422e72c8a66bdb5ee21246a948a1a02ca91674/library/core/src/num/mod.rs#L1090
+        #[allow(clippy::cast_possible_wrap)]
+        let is_utf8_char_boundary = (b as i8) >= -0x40;
+        if is_utf8_char_boundary {
+            break;
+        }
+        skip += 1;
+    }
+    let prefix_free = &data[skip..];
+    let window = &prefix_free[..min(prefix_free.len(), max_len)];
+    let truncated = window.len() != prefix_free.len();
+
+    let (highlight_start, highlight_end) = highlight
+        .as_ref()
+        .map_or((usize::MAX, usize::MAX), |&&mut (start, end)| {
+            (start.saturating_sub(skip), end.saturating_sub(skip))
+        });
+    let (mut mapped_start, mut mapped_end) = (None, None);
+
+    let mut preview = String::with_capacity(window.len());
+    if skip > 0 {
+        preview.push('…');
+    }
+    let mut prev_char_is_whitespace = false;
+    let mut offset = 0;
+    let mut chunks = window.utf8_chunks().peekable();
+    while let Some(chunk) = chunks.next() {
+        let invalid = chunk.invalid();
+        let incomplete_tail = truncated && chunks.peek().is_none();
+        let replacement = if invalid.is_empty() || incomplete_tail {
+            None
+        } else if lossy {
+            Some((char::REPLACEMENT_CHARACTER, invalid.len()))
+        } else {
+            return None;
+        };
+
+        for (c, len) in chunk
+            .valid()
+            .chars()
+            .map(|c| (c, c.len_utf8()))
+            .chain(replacement)
+        {
+            if offset >= highlight_start {
+                mapped_start.get_or_insert(preview.len());
+            }
+            if offset >= highlight_end {
+                mapped_end.get_or_insert(preview.len());
+            }
+            offset += len;
+
+            if (prev_char_is_whitespace || preview.is_empty()) && c.is_whitespace() {
+                continue;
+            }
+            preview.push(if c.is_whitespace() { ' ' } else { c });
+            prev_char_is_whitespace = c.is_whitespace();
+        }
+    }
+
+    if let Some(highlight) = highlight {
+        *highlight = (
+            mapped_start.unwrap_or(preview.len()),
+            mapped_end.unwrap_or(preview.len()),
+        );
+    }
+    if truncated {
+        preview.push('…');
+    }
+    Some(preview)
+}
+
 impl<T> LoadedEntry<'_, T> {
     pub fn into_inner(self) -> T {
         self.loaded
@@ -338,6 +786,36 @@ impl<T> LoadedEntry<'_, T> {
         )
     }
 
+    pub fn label(&self) -> Result<Label, ringboard_core::Error> {
+        let Some(fd) = self.backing_file() else {
+            return Ok(Label::new_const());
+        };
+
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        xattr_label(
+            fd,
+            self.metadata.map(|(metadata_dir, rai)| {
+                let file_name = direct_file_name(&mut file_name, rai.ring(), rai.index());
+                (metadata_dir, file_name)
+            }),
+        )
+    }
+
+    pub fn is_secret(&self) -> Result<bool, ringboard_core::Error> {
+        let Some(fd) = self.backing_file() else {
+            return Ok(false);
+        };
+
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        xattr_secret(
+            fd,
+            self.metadata.map(|(metadata_dir, rai)| {
+                let file_name = direct_file_name(&mut file_name, rai.ring(), rai.index());
+                (metadata_dir, file_name)
+            }),
+        )
+    }
+
     pub fn backing_file(&self) -> Option<BorrowedFd> {
         self.fd.as_ref().map(|fd| match fd {
             LoadedEntryFd::Owned(o) => o.as_fd(),
@@ -346,6 +824,14 @@ impl<T> LoadedEntry<'_, T> {
     }
 }
 
+impl<T: Deref<Target = [u8]>> LoadedEntry<'_, T> {
+    /// A single line preview of the entry's contents. See [`preview`].
+    #[must_use]
+    pub fn preview(&self, max_len: usize) -> String {
+        preview(self, max_len, None)
+    }
+}
+
 impl<T> Deref for LoadedEntry<'_, T> {
     type Target = T;
 
@@ -403,6 +889,14 @@ impl Entry {
         }
     }
 
+    #[must_use]
+    pub fn key(&self) -> EntryKey {
+        match self.kind() {
+            Kind::Bucket(entry) => EntryKey::bucketed(size_to_bucket(entry.size()), entry.index()),
+            Kind::File => EntryKey::file(self.rai),
+        }
+    }
+
     #[must_use]
     pub fn ring(&self) -> RingKind {
         self.rai.ring()
@@ -421,10 +915,46 @@ impl Entry {
     pub fn mime_type(&self, reader: &mut EntryReader) -> Result<MimeType, ringboard_core::Error> {
         match self.kind() {
             Kind::Bucket(_) => Ok(MimeType::new_const()),
-            Kind::File => self.to_file(reader)?.mime_type(),
+            Kind::File => self.to_stored_file(reader)?.unwrap().mime_type(),
+        }
+    }
+
+    pub fn label(&self, reader: &mut EntryReader) -> Result<Label, ringboard_core::Error> {
+        match self.kind() {
+            Kind::Bucket(_) => Ok(Label::new_const()),
+            Kind::File => self.to_stored_file(reader)?.unwrap().label(),
+        }
+    }
+
+    /// Bucketed entries are never tagged as secrets since the tag lives in
+    /// metadata only direct allocations have.
+    pub fn is_secret(&self, reader: &mut EntryReader) -> Result<bool, ringboard_core::Error> {
+        match self.kind() {
+            Kind::Bucket(_) => Ok(false),
+            Kind::File => self.to_stored_file(reader)?.unwrap().is_secret(),
         }
     }
 
+    pub fn preview(
+        &self,
+        reader: &mut EntryReader,
+        max_len: usize,
+    ) -> Result<String, ringboard_core::Error> {
+        Ok(self.to_slice(reader)?.preview(max_len))
+    }
+
+    /// The entry's alternate representations, e.g. the HTML version of
+    /// copied rich text, in no particular order.
+    pub fn alternates(
+        &self,
+        reader: &EntryReader,
+    ) -> Result<Vec<(MimeType, File)>, ringboard_core::Error> {
+        let (Kind::File, Some(alternates_dir)) = (self.kind(), &reader.alternates) else {
+            return Ok(Vec::new());
+        };
+        read_alternates(alternates_dir, self.ring(), self.index())
+    }
+
     pub fn to_slice<'a>(
         &self,
         reader: &'a mut EntryReader,
@@ -441,6 +971,35 @@ impl Entry {
         Ok(self.to_file_raw(reader)?.unwrap())
     }
 
+    /// Copies the entry's contents into a new anonymous file, e.g. to re-add
+    /// it elsewhere or under a different mime type.
+    ///
+    /// Direct allocations are copied in the kernel so large entries are never
+    /// read into userspace.
+    pub fn duplicate(&self, reader: &mut EntryReader) -> Result<File, ringboard_core::Error> {
+        let LoadedEntry {
+            loaded: file,
+            metadata: _,
+            fd,
+        } = self.to_file(reader)?;
+        if !matches!(fd, Some(LoadedEntryFd::HackySelfReference(_))) {
+            // Bucketed and decompressed entries are already private copies.
+            return Ok(file);
+        }
+
+        let len = statx(&file, c"", AtFlags::EMPTY_PATH, StatxFlags::SIZE)
+            .map_io_err(|| format!("Failed to statx direct file: {file:?}"))?
+            .stx_size;
+        copy_to_memfd(
+            c"ringboard_duplicate",
+            &file,
+            0,
+            usize::try_from(len).unwrap(),
+        )
+        .map(File::from)
+        .map_io_err(|| format!("Failed to duplicate direct file: {file:?}"))
+    }
+
     fn grow_bucket_if_needed(self, reader: &mut EntryReader) -> Result<(), ringboard_core::Error> {
         match self.kind() {
             Kind::Bucket(entry) => {
@@ -473,15 +1032,23 @@ impl Entry {
                 }))
             }
             Kind::File => {
-                let Some(file) = self.to_file_raw(reader)? else {
+                let Some(LoadedEntry {
+                    loaded: file,
+                    metadata,
+                    fd,
+                }) = self.to_file_raw(reader)?
+                else {
                     return Ok(None);
                 };
                 Ok(Some(LoadedEntry {
-                    loaded: Mmap::from(&*file)
+                    loaded: Mmap::from(&file)
                         .map_io_err(|| format!("Failed to mmap data file: {file:?}"))?
                         .into(),
-                    metadata: reader.metadata.as_ref().map(|m| (m.as_fd(), self.rai)),
-                    fd: Some(LoadedEntryFd::Owned(file.loaded.into())),
+                    metadata,
+                    fd: Some(match fd {
+                        Some(LoadedEntryFd::Owned(backing)) => LoadedEntryFd::Owned(backing),
+                        _ => LoadedEntryFd::Owned(file.into()),
+                    }),
                 }))
             }
         }
@@ -511,22 +1078,97 @@ impl Entry {
                 }))
             }
             Kind::File => {
-                let mut file_name = [MaybeUninit::uninit(); 14];
-                let file_name = direct_file_name(&mut file_name, self.ring(), self.index());
-
-                let file = openat(&reader.direct, file_name, OFlags::RDONLY, Mode::empty())
-                    .map_io_err(|| format!("Failed to open direct file: {file_name:?}"))
-                    .map(File::from)?;
-                Ok(Some(LoadedEntry {
-                    fd: Some(LoadedEntryFd::HackySelfReference(unsafe {
-                        BorrowedFd::borrow_raw(file.as_raw_fd())
-                    })),
-                    metadata: reader.metadata.as_ref().map(|m| (m.as_fd(), self.rai)),
+                let entry = self.to_stored_file(reader)?.unwrap();
+                let Some(decompressed) = decompress_direct_file(&entry)? else {
+                    return Ok(Some(entry));
+                };
+                let LoadedEntry {
                     loaded: file,
+                    metadata,
+                    fd: _,
+                } = entry;
+                Ok(Some(LoadedEntry {
+                    loaded: decompressed,
+                    metadata,
+                    fd: Some(LoadedEntryFd::Owned(file.into())),
                 }))
             }
         }
     }
+
+    /// Opens the entry's direct allocation as it is stored on disk, i.e.
+    /// without decompressing it.
+    ///
+    /// The entry's metadata can be read as usual, but its contents must be
+    /// read with [`read_direct_file_at`] or [`decompress_direct_file`].
+    /// Returns `None` for bucketed entries.
+    pub fn to_stored_file<'a>(
+        &self,
+        reader: &'a EntryReader,
+    ) -> Result<Option<LoadedEntry<'a, File>>, ringboard_core::Error> {
+        let Kind::File = self.kind() else {
+            return Ok(None);
+        };
+
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        let file_name = direct_file_name(&mut file_name, self.ring(), self.index());
+        let file = openat(&reader.direct, file_name, OFlags::RDONLY, Mode::empty())
+            .map_io_err(|| format!("Failed to open direct file: {file_name:?}"))
+            .map(File::from)?;
+        Ok(Some(LoadedEntry {
+            fd: Some(LoadedEntryFd::HackySelfReference(unsafe {
+                BorrowedFd::borrow_raw(file.as_raw_fd())
+            })),
+            metadata: reader.metadata.as_ref().map(|m| (m.as_fd(), self.rai)),
+            loaded: file,
+        }))
+    }
+}
+
+/// Opens the alternate representations of the entry at `index` in `ring`,
+/// given the database's `alternates` directory.
+///
+/// This lets processes that only know an entry's ID, such as paste servers,
+/// find its alternates without opening the whole database.
+pub fn read_alternates(
+    alternates_dir: impl AsFd,
+    ring: RingKind,
+    index: u32,
+) -> Result<Vec<(MimeType, File)>, ringboard_core::Error> {
+    let mut file_name = [MaybeUninit::uninit(); 14];
+    let file_name = direct_file_name(&mut file_name, ring, index);
+
+    let dir = match openat(
+        alternates_dir,
+        file_name,
+        OFlags::RDONLY | OFlags::DIRECTORY,
+        Mode::empty(),
+    ) {
+        Err(Errno::NOENT) => return Ok(Vec::new()),
+        r => r.map_io_err(|| format!("Failed to open alternates directory: {file_name:?}"))?,
+    };
+
+    let mut alternates = Vec::new();
+    let mut buf = [MaybeUninit::uninit(); 1024];
+    let mut iter = RawDir::new(&dir, &mut buf);
+    while let Some(alternate) = iter.next() {
+        let alternate = alternate
+            .map_io_err(|| format!("Failed to read alternates directory: {file_name:?}"))?;
+        let Some(mime_type) = alternate
+            .file_name()
+            .to_str()
+            .ok()
+            .and_then(parse_alternate_file_name)
+        else {
+            continue;
+        };
+
+        let name = alternate.file_name();
+        let file = openat(&dir, name, OFlags::RDONLY, Mode::empty())
+            .map_io_err(|| format!("Failed to open alternate file: {file_name:?}/{name:?}"))?;
+        alternates.push((mime_type, File::from(file)));
+    }
+    Ok(alternates)
 }
 
 #[derive(Debug)]
@@ -534,6 +1176,7 @@ pub struct EntryReader {
     buckets: [Mmap; NUM_BUCKETS],
     direct: OwnedFd,
     metadata: Option<OwnedFd>,
+    alternates: Option<OwnedFd>,
 }
 
 impl EntryReader {
@@ -550,6 +1193,13 @@ impl EntryReader {
                 r => Some(r.map_io_err(|| format!("Failed to open directory: {file:?}"))?),
             }
         };
+        let alternates_dir = {
+            let file = PathView::new(database_dir, "alternates");
+            match openat(CWD, &*file, OFlags::DIRECTORY | OFlags::PATH, Mode::empty()) {
+                Err(Errno::NOENT) => None,
+                r => Some(r.map_io_err(|| format!("Failed to open directory: {file:?}"))?),
+            }
+        };
 
         let buckets = {
             let mut buckets = PathView::new(database_dir, "buckets");
@@ -573,9 +1223,25 @@ impl EntryReader {
             buckets,
             direct: direct_dir,
             metadata: metadata_dir,
+            alternates: alternates_dir,
         })
     }
 
+    /// Reopen the bucket files and entry directories, remapping every bucket
+    /// at its current size.
+    ///
+    /// Returns whether any bucket's size changed.
+    pub fn reopen(&mut self, database_dir: &mut PathBuf) -> Result<bool, ringboard_core::Error> {
+        let new = Self::open(database_dir)?;
+        let changed = self
+            .buckets
+            .iter()
+            .zip(&new.buckets)
+            .any(|(old, new)| old.len() != new.len());
+        *self = new;
+        Ok(changed)
+    }
+
     #[must_use]
     pub fn buckets(&self) -> [&Mmap; NUM_BUCKETS] {
         let mut buckets = ArrayVec::new_const();
@@ -594,6 +1260,118 @@ impl EntryReader {
     pub fn metadata(&self) -> Option<BorrowedFd> {
         self.metadata.as_ref().map(OwnedFd::as_fd)
     }
+
+    /// Tally the slots of each bucket that are referenced by the database's
+    /// rings.
+    #[must_use]
+    pub fn bucket_usage(&self, database: &DatabaseReader) -> [BucketUsage; NUM_BUCKETS] {
+        let mut usage: [BucketUsage; NUM_BUCKETS] = array::from_fn(|i| {
+            let slot_length = bucket_to_length(i);
+            BucketUsage {
+                slot_length,
+                num_slots: u32::try_from(self.buckets[i].len() / usize::from(slot_length)).unwrap(),
+                ..BucketUsage::default()
+            }
+        });
+        for entry in database.favorites().chain(database.main()) {
+            let Kind::Bucket(bucket) = entry.kind() else {
+                continue;
+            };
+            let BucketUsage {
+                used_slots,
+                owned_bytes,
+                ..
+            } = &mut usage[usize::from(size_to_bucket(bucket.size()))];
+            *used_slots += 1;
+            *owned_bytes += u64::from(bucket.size());
+        }
+        usage
+    }
+
+    /// The number of bytes taken up by the buckets and the files in the
+    /// direct, metadata, and alternates directories.
+    pub fn disk_usage(&self) -> Result<u64, ringboard_core::Error> {
+        let mut size = self
+            .buckets
+            .iter()
+            .map(|bucket| u64::try_from(bucket.len()).unwrap())
+            .sum();
+        for dir in iter::once(&self.direct)
+            .chain(&self.metadata)
+            .chain(&self.alternates)
+        {
+            size += dir_size(dir, c".")?;
+        }
+        Ok(size)
+    }
+}
+
+fn dir_size(parent: impl AsFd, path: &CStr) -> Result<u64, ringboard_core::Error> {
+    let dir = match openat(
+        parent,
+        path,
+        OFlags::RDONLY | OFlags::DIRECTORY,
+        Mode::empty(),
+    ) {
+        Err(Errno::NOENT) => return Ok(0),
+        r => r.map_io_err(|| format!("Failed to open directory: {path:?}"))?,
+    };
+
+    let mut size = 0;
+    let mut buf = [MaybeUninit::uninit(); 8192];
+    let mut iter = RawDir::new(&dir, &mut buf);
+    while let Some(file) = iter.next() {
+        let file = file.map_io_err(|| format!("Failed to read directory: {path:?}"))?;
+        let name = file.file_name();
+        if name == c"." || name == c".." {
+            continue;
+        }
+
+        if file.file_type() == FileType::Directory {
+            size += dir_size(&dir, name)?;
+            continue;
+        }
+        size += match statx(&dir, name, AtFlags::SYMLINK_NOFOLLOW, StatxFlags::SIZE) {
+            // The entry was removed while we were looking.
+            Err(Errno::NOENT) => 0,
+            r => {
+                r.map_io_err(|| format!("Failed to statx file: {name:?}"))?
+                    .stx_size
+            }
+        };
+    }
+    Ok(size)
+}
+
+/// Slot usage of a single bucket as seen from the rings.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct BucketUsage {
+    /// The length of each slot in bytes.
+    pub slot_length: u16,
+    /// The number of slots backed by the bucket file.
+    pub num_slots: u32,
+    /// The number of slots referenced by an entry.
+    pub used_slots: u32,
+    /// The number of bytes actually stored in the used slots.
+    pub owned_bytes: u64,
+}
+
+impl BucketUsage {
+    #[must_use]
+    pub const fn free_slots(&self) -> u32 {
+        self.num_slots.saturating_sub(self.used_slots)
+    }
+
+    #[must_use]
+    pub fn used_bytes(&self) -> u64 {
+        u64::from(self.slot_length) * u64::from(self.used_slots)
+    }
+
+    /// The number of bytes wasted by entries being shorter than their slots.
+    #[must_use]
+    pub fn fragmentation_bytes(&self) -> u64 {
+        self.used_bytes() - self.owned_bytes
+    }
 }
 
 struct BucketTooShort {
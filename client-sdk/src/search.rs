@@ -1,5 +1,6 @@
 use std::{
     ffi::CStr,
+    fs::File,
     io,
     io::ErrorKind,
     mem::MaybeUninit,
@@ -20,7 +21,8 @@ use arrayvec::ArrayVec;
 use memchr::memmem::Finder;
 use regex::bytes::Regex;
 use ringboard_core::{
-    DIRECT_FILE_NAME_LEN, Error as CoreError, IoErr, bucket_to_length, ring::Mmap, size_to_bucket,
+    DIRECT_FILE_NAME_LEN, EntryKey, Error as CoreError, IoErr, bucket_to_length,
+    protocol::IdNotFoundError, ring::Mmap, size_to_bucket,
 };
 use rustix::{
     fs::{Mode, OFlags, RawDir, openat},
@@ -30,7 +32,7 @@ use thiserror::Error;
 
 use crate::{
     EntryReader,
-    ring_reader::{is_text_mime, xattr_mime_type},
+    ring_reader::{decompress_direct_file, is_text_mime, xattr_label, xattr_mime_type},
 };
 
 #[derive(Clone, Debug)]
@@ -59,6 +61,7 @@ pub enum Query<'a> {
     Plain(&'a [u8]),
     PlainIgnoreCase(CaselessQuery),
     Regex(Regex),
+    /// Matches against entry mime types and labels.
     Mimes(Regex),
 }
 
@@ -129,6 +132,15 @@ pub enum EntryLocation {
     File { entry_id: u64 },
 }
 
+impl EntryLocation {
+    pub fn key(self) -> Result<EntryKey, IdNotFoundError> {
+        match self {
+            Self::Bucketed { bucket, index } => Ok(EntryKey::bucketed(bucket, index)),
+            Self::File { entry_id } => EntryKey::from_id(entry_id),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CancellationToken {
     stop: Arc<AtomicBool>,
@@ -281,9 +293,11 @@ fn search_impl(
                         return Ok(());
                     }
 
-                    let data = Mmap::from(&fd).map_io_err(|| {
-                        format!("Failed to mmap direct allocation: {file_name:?}")
-                    })?;
+                    let fd = File::from(fd);
+                    let data = Mmap::from(decompress_direct_file(&fd)?.as_ref().unwrap_or(&fd))
+                        .map_io_err(|| {
+                            format!("Failed to mmap direct allocation: {file_name:?}")
+                        })?;
                     let file_name = <[u8; DIRECT_FILE_NAME_LEN]>::try_from(file_name.to_bytes())
                         .map_err(|_| CoreError::Io {
                             error: io::Error::new(
@@ -470,12 +484,15 @@ fn mime_search_impl(
                 &reader,
                 &token,
                 &sender,
-                |file_name, _fd, mime_type| {
-                    if mime_type.is_empty() {
+                |file_name, fd, mime_type| {
+                    let label = xattr_label(&fd, reader.metadata().map(|d| (d, file_name)))?;
+                    if mime_type.is_empty() && label.is_empty() {
                         return Ok(());
                     }
 
-                    if query.find(mime_type.as_bytes()).is_some() {
+                    if (!mime_type.is_empty() && query.find(mime_type.as_bytes()).is_some())
+                        || (!label.is_empty() && query.find(label.as_bytes()).is_some())
+                    {
                         let id = entry_id_from_direct_file_name(file_name.to_bytes())?;
                         sender.send(Ok(QueryResult {
                             location: EntryLocation::File { entry_id: id },
@@ -0,0 +1,77 @@
+use std::{
+    fs::File,
+    io,
+    io::{BufWriter, Cursor, Seek},
+    os::fd::AsFd,
+};
+
+use image::{DynamicImage, ImageFormat, ImageReader};
+use ringboard_core::{
+    IoErr, THUMBNAIL_MIME_TYPE,
+    protocol::{AddAlternateResponse, MimeType},
+};
+use rustix::fs::{MemfdFlags, memfd_create};
+
+use crate::{ClientError, api::AddAlternateRequest};
+
+/// Thumbnails are downscaled to fit within a square of this many pixels.
+pub const THUMBNAIL_MAX_DIMENSION: u32 = 512;
+
+#[must_use]
+pub fn fits_in_thumbnail(image: &DynamicImage) -> bool {
+    image.width() <= THUMBNAIL_MAX_DIMENSION && image.height() <= THUMBNAIL_MAX_DIMENSION
+}
+
+/// Encodes a PNG thumbnail of an image with the given mime type.
+///
+/// Returns `None` if the mime type isn't a decodable image or the image is
+/// already small enough to be its own thumbnail.
+pub fn generate_thumbnail(
+    mime_type: &str,
+    image: &[u8],
+) -> Result<Option<File>, ringboard_core::Error> {
+    let Some(format) = ImageFormat::from_mime_type(mime_type) else {
+        return Ok(None);
+    };
+    let image = ImageReader::with_format(Cursor::new(image), format)
+        .decode()
+        .map_err(io::Error::other)
+        .map_io_err(|| format!("Failed to decode {mime_type:?} image."))?;
+    if fits_in_thumbnail(&image) {
+        return Ok(None);
+    }
+    let thumbnail = image.thumbnail(THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_DIMENSION);
+
+    let mut file = File::from(
+        memfd_create(c"ringboard_thumbnail", MemfdFlags::empty())
+            .map_io_err(|| "Failed to create thumbnail file.")?,
+    );
+    thumbnail
+        .write_to(&mut BufWriter::new(&mut file), ImageFormat::Png)
+        .map_err(io::Error::other)
+        .map_io_err(|| "Failed to encode thumbnail.")?;
+    file.rewind()
+        .map_io_err(|| "Failed to reset thumbnail file offset.")?;
+    Ok(Some(file))
+}
+
+/// Generates a thumbnail for a newly added image entry and stores it as one of
+/// the entry's alternates so frontends can preview the image without decoding
+/// the original.
+pub fn store_thumbnail(
+    server: impl AsFd,
+    id: u64,
+    mime_type: &str,
+    image: &[u8],
+) -> Result<(), ClientError> {
+    let Some(thumbnail) = generate_thumbnail(mime_type, image)? else {
+        return Ok(());
+    };
+    let AddAlternateResponse { error } = AddAlternateRequest::response(
+        server,
+        id,
+        MimeType::from(THUMBNAIL_MIME_TYPE).unwrap(),
+        thumbnail,
+    )?;
+    error.map_or(Ok(()), |e| Err(e.into()))
+}
@@ -1,39 +1,49 @@
 use std::{
     array,
-    cmp::{Ordering, min},
+    cmp::Ordering,
     collections::{BinaryHeap, HashMap},
+    fs::File,
     hash::BuildHasherDefault,
-    io::BufReader,
+    io::{BufReader, Read, Seek, Write},
     iter::once,
     mem,
     os::fd::{AsFd, OwnedFd},
     path::PathBuf,
     str,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use image::{DynamicImage, ImageError, ImageReader};
 use regex::bytes::Regex;
 use ringboard_core::dirs::paste_socket_file;
 use rustc_hash::FxHasher;
-use rustix::net::SocketAddrUnix;
+use rustix::{
+    fs::{MemfdFlags, memfd_create},
+    net::SocketAddrUnix,
+};
 use thiserror::Error;
 
 use crate::{
     ClientError, DatabaseReader, Entry, EntryReader, Kind,
     api::{
-        MoveToFrontRequest, RemoveRequest, connect_to_paste_server, connect_to_server,
-        send_paste_buffer,
+        AddAlternateRequest, AddRequest, MoveToFrontRequest, PasteTarget, RemoveRequest,
+        SetLabelRequest, connect_to_paste_server, connect_to_server, send_paste_buffer,
     },
     core::{
-        BucketAndIndex, Error as CoreError, IoErr, RingAndIndex,
+        BucketAndIndex, ContentKind, Error as CoreError, IoErr, RingAndIndex, THUMBNAIL_MIME_TYPE,
         dirs::{data_dir, socket_file},
-        protocol::{IdNotFoundError, MoveToFrontResponse, RemoveResponse, RingKind, composite_id},
-        ring::{MAX_ENTRIES, Ring},
-        size_to_bucket,
+        protocol::{
+            AddAlternateResponse, AddResponse, IdNotFoundError, Label, MimeType,
+            MoveToFrontResponse, RemoveResponse, RingKind, SetLabelResponse, composite_id,
+        },
+        ring::{MAX_ENTRIES, Ring, entries_to_offset},
+        size_to_bucket, sniff_content_kind, socket_addr,
     },
+    ring_reader::{LoadedEntry, MmapOrSlice, utf8_preview},
     search,
     search::{CancellationToken, CaselessQuery, EntryLocation, Query, QueryResult},
+    thumbnail::{THUMBNAIL_MAX_DIMENSION, fits_in_thumbnail},
 };
 
 #[derive(Error, Debug)]
@@ -75,13 +85,43 @@ mod error_stack_compat {
 #[derive(Debug)]
 pub enum Command {
     LoadFirstPage,
-    GetDetails { id: u64, with_text: bool },
+    /// Whether pages merge favorites into the main ring by recency instead of
+    /// listing them first. Reloads the first page.
+    InterleaveFavorites(bool),
+    GetDetails {
+        id: u64,
+        with_text: bool,
+    },
     Favorite(u64),
     Unfavorite(u64),
     Delete(u64),
-    Search { query: Box<str>, kind: SearchKind },
-    LoadImage(u64),
-    Paste(u64),
+    /// Like [`Command::Delete`], but the entry's contents are sent back in a
+    /// [`Message::Trashed`] so that it can later be restored. Entries larger
+    /// than [`MAX_TRASHED_ENTRY_LEN`] are left alone and reported with
+    /// [`Message::TooLargeToTrash`] instead.
+    Trash(u64),
+    /// Re-adds a previously trashed entry to its original ring along with its
+    /// label and alternates. Expiry times are not restored.
+    Restore(Box<TrashedEntry>),
+    Search {
+        query: Box<str>,
+        kind: SearchKind,
+    },
+    /// Decodes an image entry. Thumbnails fit within
+    /// [`THUMBNAIL_MAX_DIMENSION`] and are read from the thumbnail the watchers
+    /// stored when the image was copied, falling back to downscaling the
+    /// original.
+    LoadImage {
+        id: u64,
+        thumbnail: bool,
+    },
+    /// Places the entry in the `target` selections. Unless `keep_open` is set,
+    /// the entry is also pasted into the focused window and the UI should exit.
+    Paste {
+        id: u64,
+        keep_open: bool,
+        target: PasteTarget,
+    },
 }
 
 #[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -99,20 +139,30 @@ pub enum Message {
     LoadedFirstPage {
         entries: Box<[UiEntry]>,
         default_focused_id: Option<u64>,
+        /// The number of bytes taken up by the database on disk.
+        database_size: Result<u64, CoreError>,
     },
     EntryDetails {
         id: u64,
         result: Result<DetailedEntry, CoreError>,
     },
     PendingSearch(CancellationToken),
+    PartialSearchResults(Box<[UiEntry]>),
     SearchResults(Box<[UiEntry]>),
     FavoriteChange(u64),
     Deleted(u64),
+    Trashed(Box<TrashedEntry>),
+    /// The entry wasn't deleted because it can't fit in the trash.
+    TooLargeToTrash(u64),
+    Restored(u64),
     LoadedImage {
         id: u64,
+        thumbnail: bool,
         image: DynamicImage,
     },
-    Pasted,
+    Pasted {
+        keep_open: bool,
+    },
 }
 
 #[derive(Debug)]
@@ -148,12 +198,46 @@ impl UiEntryCache {
     }
 }
 
+/// The largest entry, alternates included, that [`Command::Trash`] will hold
+/// in memory.
+pub const MAX_TRASHED_ENTRY_LEN: usize = 16 << 20;
+
+#[derive(Debug)]
+pub struct TrashedEntry {
+    pub id: u64,
+    pub ring: RingKind,
+    pub mime_type: MimeType,
+    pub label: Label,
+    pub data: Box<[u8]>,
+    pub alternates: Box<[(MimeType, Box<[u8]>)]>,
+}
+
+impl TrashedEntry {
+    /// The number of bytes held in memory by this entry.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.data.len()
+            + self
+                .alternates
+                .iter()
+                .map(|(_, data)| data.len())
+                .sum::<usize>()
+    }
+}
+
 #[derive(Debug)]
 pub struct DetailedEntry {
     pub mime_type: Box<str>,
+    pub content_kind: ContentKind,
+    pub label: Box<str>,
     pub full_text: Option<Box<str>>,
+    /// Up to [`BINARY_PREFIX_MAX_LEN`] leading bytes of entries whose text was
+    /// requested but which aren't valid UTF-8.
+    pub binary_prefix: Option<Box<[u8]>>,
 }
 
+pub const BINARY_PREFIX_MAX_LEN: usize = 4096;
+
 pub fn controller<E>(
     commands: impl IntoIterator<Item = Command>,
     mut send: impl FnMut(Message) -> Result<(), E>,
@@ -169,8 +253,7 @@ pub fn controller<E>(
 
         let server = {
             let socket_file = socket_file();
-            let addr = SocketAddrUnix::new(&socket_file)
-                .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))?;
+            let addr = socket_addr(&socket_file)?;
             connect_to_server(&addr)?
         };
 
@@ -198,7 +281,7 @@ pub fn controller<E>(
         }
     };
     let mut reader = Some(reader);
-    let mut cache = Default::default();
+    let mut state = ActorState::default();
 
     for command in once(Command::LoadFirstPage).chain(commands) {
         let result = handle_command(
@@ -214,7 +297,7 @@ pub fn controller<E>(
             &mut send,
             &mut database,
             &mut reader,
-            &mut cache,
+            &mut state,
         )
         .unwrap_or_else(|e| Some(Message::Error(e)));
 
@@ -227,6 +310,13 @@ pub fn controller<E>(
     }
 }
 
+#[derive(Default)]
+struct ActorState {
+    cache: SearchCache,
+    /// Whether favorites are mixed into the first page by recency.
+    interleave: bool,
+}
+
 fn handle_command<Server: AsFd, PasteServer: AsFd, E>(
     command: Command,
     server: impl FnOnce() -> Result<Server, ClientError>,
@@ -234,7 +324,7 @@ fn handle_command<Server: AsFd, PasteServer: AsFd, E>(
     send: impl FnMut(Message) -> Result<(), E>,
     database: &mut DatabaseReader,
     reader_: &mut Option<EntryReader>,
-    cache: &mut SearchCache,
+    ActorState { cache, interleave }: &mut ActorState,
 ) -> Result<Option<Message>, CommandError> {
     let shitty_refresh = |database: &mut DatabaseReader| {
         let run = |ring: &mut Ring| {
@@ -259,19 +349,24 @@ fn handle_command<Server: AsFd, PasteServer: AsFd, E>(
 
     let reader = reader_.as_mut().unwrap();
     match command {
-        Command::LoadFirstPage => {
+        Command::LoadFirstPage | Command::InterleaveFavorites(_) => {
+            if let Command::InterleaveFavorites(enabled) = command {
+                *interleave = enabled;
+            }
             shitty_refresh(database);
 
             let mut entries = Vec::with_capacity(100);
-            for entry in database
-                .favorites()
-                .rev()
-                .chain(database.main().rev().take(100))
-            {
+            let mut push = |entry| {
                 entries.push(ui_entry(entry, reader, None).unwrap_or_else(|e| UiEntry {
                     cache: UiEntryCache::Error(e),
                     entry,
                 }));
+            };
+            let (favorites, main) = (database.favorites().rev(), database.main().rev().take(100));
+            if *interleave {
+                database.interleave(favorites, main).for_each(&mut push);
+            } else {
+                favorites.chain(main).for_each(&mut push);
             }
             Ok(Some(Message::LoadedFirstPage {
                 entries: entries.into(),
@@ -284,23 +379,30 @@ fn handle_command<Server: AsFd, PasteServer: AsFd, E>(
                         .as_ref()
                         .map(Entry::id)
                 },
+                database_size: reader.disk_usage().map(|size| {
+                    size + entries_to_offset(database.main_ring_mut().len())
+                        + entries_to_offset(database.favorites_ring_mut().len())
+                }),
             }))
         }
         Command::GetDetails { id, with_text } => {
             let mut run = || {
                 let entry = unsafe { database.get(id)? };
-                if with_text {
-                    let loaded = entry.to_slice(reader)?;
-                    Ok(DetailedEntry {
-                        mime_type: (&*loaded.mime_type()?).into(),
-                        full_text: str::from_utf8(&loaded).map(Box::from).ok(),
-                    })
+                let loaded = entry.to_slice(reader)?;
+                let mime_type = loaded.mime_type()?;
+                let full_text = if with_text {
+                    str::from_utf8(&loaded).map(Box::from).ok()
                 } else {
-                    Ok(DetailedEntry {
-                        mime_type: (&*entry.mime_type(reader)?).into(),
-                        full_text: None,
-                    })
-                }
+                    None
+                };
+                Ok(DetailedEntry {
+                    content_kind: sniff_content_kind(&mime_type, &loaded),
+                    mime_type: (&*mime_type).into(),
+                    label: (&*loaded.label()?).into(),
+                    binary_prefix: (with_text && full_text.is_none())
+                        .then(|| loaded[..loaded.len().min(BINARY_PREFIX_MAX_LEN)].into()),
+                    full_text,
+                })
             };
             Ok(Some(Message::EntryDetails { id, result: run() }))
         }
@@ -322,6 +424,84 @@ fn handle_command<Server: AsFd, PasteServer: AsFd, E>(
             RemoveResponse { error: None } => Ok(Some(Message::Deleted(id))),
             RemoveResponse { error: Some(e) } => Err(e.into()),
         },
+        Command::Trash(id) => {
+            let entry = unsafe { database.get(id)? };
+            let trashed = {
+                let mut alternates = entry.alternates(reader)?;
+                alternates.retain(|(mime_type, _)| mime_type != THUMBNAIL_MIME_TYPE);
+                let loaded = entry.to_slice(reader)?;
+
+                let mut size = loaded.len();
+                for (_, file) in &alternates {
+                    let len = file
+                        .metadata()
+                        .map_io_err(|| "Failed to read alternate file size.")?
+                        .len();
+                    size = size.saturating_add(usize::try_from(len).unwrap_or(usize::MAX));
+                }
+                if size > MAX_TRASHED_ENTRY_LEN {
+                    return Ok(Some(Message::TooLargeToTrash(id)));
+                }
+
+                let alternates = alternates
+                    .into_iter()
+                    .map(|(mime_type, mut file)| {
+                        let mut data = Vec::new();
+                        file.read_to_end(&mut data)
+                            .map_io_err(|| "Failed to read alternate file.")?;
+                        Ok((mime_type, data.into_boxed_slice()))
+                    })
+                    .collect::<Result<_, CoreError>>()?;
+                TrashedEntry {
+                    id,
+                    ring: entry.ring(),
+                    mime_type: loaded.mime_type()?,
+                    label: loaded.label()?,
+                    data: (**loaded).into(),
+                    alternates,
+                }
+            };
+            match RemoveRequest::response(server()?, id)? {
+                RemoveResponse { error: None } => Ok(Some(Message::Trashed(trashed.into()))),
+                RemoveResponse { error: Some(e) } => Err(e.into()),
+            }
+        }
+        Command::Restore(trashed) => {
+            let TrashedEntry {
+                id: _,
+                ring,
+                mime_type,
+                label,
+                ref data,
+                ref alternates,
+            } = *trashed;
+            let server = server()?;
+
+            let AddResponse::Success { id } = AddRequest::response_add_unchecked(
+                &server,
+                ring,
+                mime_type,
+                restore_file(data)?,
+                0,
+            )?
+            else {
+                return Err(ClientError::RateLimited.into());
+            };
+            if !label.is_empty() {
+                let SetLabelResponse { error } = SetLabelRequest::response(&server, id, label)?;
+                if let Some(e) = error {
+                    return Err(e.into());
+                }
+            }
+            for (mime_type, data) in alternates {
+                let AddAlternateResponse { error } =
+                    AddAlternateRequest::response(&server, id, *mime_type, restore_file(data)?)?;
+                if let Some(e) = error {
+                    return Err(e.into());
+                }
+            }
+            Ok(Some(Message::Restored(id)))
+        }
         Command::Search { query, kind } => {
             shitty_refresh(database);
 
@@ -343,110 +523,119 @@ fn handle_command<Server: AsFd, PasteServer: AsFd, E>(
                 do_search(query, reader_, database, send, cache).into(),
             )))
         }
-        Command::LoadImage(id) => {
+        Command::LoadImage { id, thumbnail } => {
             let entry = unsafe { database.get(id)? };
             Ok(Some(Message::LoadedImage {
                 id,
-                image: ImageReader::new(BufReader::new(&*entry.to_file(reader)?))
-                    .with_guessed_format()
-                    .map_io_err(|| format!("Failed to guess image format for entry {id}."))?
-                    .decode()?,
+                thumbnail,
+                image: if thumbnail {
+                    load_thumbnail(entry, reader)?
+                } else {
+                    decode_image(&*entry.to_file(reader)?, id)?
+                },
             }))
         }
-        Command::Paste(id) => {
+        Command::Paste {
+            id,
+            keep_open,
+            target,
+        } => {
             let entry = unsafe { database.get(id)? };
             let paste_server = paste_server()?;
-            send_paste_buffer(paste_server, entry, reader, true)?;
-            Ok(Some(Message::Pasted))
+            // Triggering a paste while the UI stays focused would paste into the UI itself.
+            send_paste_buffer(paste_server, entry, reader, !keep_open, target)?;
+            Ok(Some(Message::Pasted { keep_open }))
         }
     }
 }
 
+fn decode_image(file: &File, id: u64) -> Result<DynamicImage, CommandError> {
+    Ok(ImageReader::new(BufReader::new(file))
+        .with_guessed_format()
+        .map_io_err(|| format!("Failed to guess image format for entry {id}."))?
+        .decode()?)
+}
+
+fn restore_file(data: &[u8]) -> Result<File, CoreError> {
+    let mut file = File::from(
+        memfd_create(c"ringboard_restore", MemfdFlags::empty())
+            .map_io_err(|| "Failed to create restored entry file.")?,
+    );
+    file.write_all(data)
+        .map_io_err(|| "Failed to write restored entry file.")?;
+    file.rewind()
+        .map_io_err(|| "Failed to reset restored entry file offset.")?;
+    Ok(file)
+}
+
+fn load_thumbnail(entry: Entry, reader: &mut EntryReader) -> Result<DynamicImage, CommandError> {
+    let id = entry.id();
+    if let Some((_, file)) = entry
+        .alternates(reader)?
+        .into_iter()
+        .find(|(mime_type, _)| mime_type.as_str() == THUMBNAIL_MIME_TYPE)
+    {
+        return decode_image(&file, id);
+    }
+
+    // Entries that weren't added by a watcher, e.g. through the CLI, have no stored
+    // thumbnail.
+    let image = decode_image(&*entry.to_file(reader)?, id)?;
+    if fits_in_thumbnail(&image) {
+        return Ok(image);
+    }
+    Ok(image.thumbnail(THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_DIMENSION))
+}
+
+/// Upper bound on the number of bytes of an entry that make it into its one
+/// line preview. Giant single-line entries (minified JSON, CSS, etc.) would
+/// otherwise make text layout in the UIs arbitrarily expensive, so only a
+/// bounded window is ever handed to them. The full contents remain available
+/// through [`Command::GetDetails`] and pasting.
+pub const ONE_LINER_MAX_LEN: usize = 250;
+
+/// Shown in place of the preview of entries tagged as secrets. Their contents
+/// are only revealed when details are requested or the entry is pasted.
+pub const SECRET_ONE_LINER: &str = "•••••••• (hidden secret)";
+
 fn ui_entry(
     entry: Entry,
     reader: &mut EntryReader,
+    highlight: Option<(usize, usize)>,
+) -> Result<UiEntry, CoreError> {
+    loaded_ui_entry(entry, &entry.to_slice(reader)?, highlight)
+}
+
+fn loaded_ui_entry(
+    entry: Entry,
+    loaded: &LoadedEntry<MmapOrSlice>,
     mut highlight: Option<(usize, usize)>,
 ) -> Result<UiEntry, CoreError> {
-    let loaded = entry.to_slice(reader)?;
     let mime_type = &*loaded.mime_type()?;
-    if mime_type.starts_with("image/") {
+    if sniff_content_kind(mime_type, loaded) == ContentKind::Image {
         return Ok(UiEntry {
             entry,
             cache: UiEntryCache::Image,
         });
     }
-
-    let prefix_free = if let Some((start, end)) = &mut highlight {
-        let mut l = &loaded[start.saturating_sub(24)..];
-        for &b in l.iter().take(3) {
-            // https://github.com/rust-lang/rust/blob/33422e72c8a66bdb5ee21246a948a1a02ca91674/library/core/src/num/mod.rs#L1090
-            #[allow(clippy::cast_possible_wrap)]
-            let is_utf8_char_boundary = (b as i8) >= -0x40;
-            if is_utf8_char_boundary {
-                break;
-            }
-            l = &l[1..];
-        }
-
-        let diff = loaded.len() - l.len();
-        *start -= diff;
-        *end -= diff;
-
-        l
-    } else {
-        &loaded
-    };
-    let suffix_free = &prefix_free[..min(prefix_free.len(), 250)];
-
-    Ok(match str::from_utf8(suffix_free) {
-        Ok(s) => Some(s),
-        Err(e) if e.error_len().is_none() => {
-            Some(unsafe { str::from_utf8_unchecked(&suffix_free[..e.valid_up_to()]) })
-        }
-        Err(_) => None,
-    }
-    .map_or_else(
-        || UiEntry {
+    if loaded.is_secret()? {
+        return Ok(UiEntry {
             entry,
-            cache: UiEntryCache::Binary {
-                mime_type: mime_type.into(),
+            cache: UiEntryCache::Text {
+                one_liner: SECRET_ONE_LINER.into(),
             },
-        },
-        |s| {
-            let mut one_liner = String::with_capacity(s.len());
-
-            if prefix_free.len() != loaded.len() {
-                one_liner.push('…');
-                if let Some((start, end)) = &mut highlight {
-                    *start += '…'.len_utf8();
-                    *end += '…'.len_utf8();
-                }
-            }
-            let mut prev_char_is_whitespace = false;
-            for c in s.chars() {
-                if (prev_char_is_whitespace || one_liner.is_empty()) && c.is_whitespace() {
-                    if let Some((start, end)) = &mut highlight {
-                        if one_liner.len() < *start {
-                            *start -= c.len_utf8();
-                        }
-                        if one_liner.len() < *end {
-                            *end -= c.len_utf8();
-                        }
-                    }
-                    continue;
-                }
-
-                one_liner.push(if c.is_whitespace() { ' ' } else { c });
-                prev_char_is_whitespace = c.is_whitespace();
-            }
-            if suffix_free.len() != prefix_free.len() {
-                one_liner.push('…');
-            }
-            if let Some((_, end)) = &mut highlight {
-                *end = min(*end, one_liner.len());
-            }
+        });
+    }
 
-            UiEntry {
+    Ok(
+        utf8_preview(loaded, ONE_LINER_MAX_LEN, highlight.as_mut()).map_or_else(
+            || UiEntry {
+                entry,
+                cache: UiEntryCache::Binary {
+                    mime_type: mime_type.into(),
+                },
+            },
+            |one_liner| UiEntry {
                 entry,
                 cache: if let Some((start, end)) = highlight {
                     UiEntryCache::HighlightedText {
@@ -459,9 +648,9 @@ fn ui_entry(
                         one_liner: one_liner.into(),
                     }
                 },
-            }
-        },
-    ))
+            },
+        ),
+    )
 }
 
 type SearchCache = (
@@ -470,7 +659,7 @@ type SearchCache = (
     Vec<SearchEntry>,
 );
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 struct SearchEntry {
     rai: RingAndIndex,
     start: usize,
@@ -505,13 +694,13 @@ fn do_search<E>(
     (cached_write_heads, reverse_index_cache, search_result_buf): &mut SearchCache,
 ) -> Vec<UiEntry> {
     const MAX_SEARCH_ENTRIES: usize = 256;
+    const PARTIAL_RESULTS_INTERVAL: Duration = Duration::from_millis(100);
 
     let reader = Arc::new(reader_.take().unwrap());
 
     let (result_stream, threads) = search(query, reader.clone());
-    let _ = send(Message::PendingSearch(
-        result_stream.cancellation_token().clone(),
-    ));
+    let token = result_stream.cancellation_token().clone();
+    let _ = send(Message::PendingSearch(token.clone()));
 
     if *cached_write_heads
         != Some((
@@ -546,6 +735,8 @@ fn do_search<E>(
         let ring = ring.ring();
         ring.prev_entry(ring.write_head())
     });
+    let mut results_changed = false;
+    let mut last_partial_results = Instant::now();
     for entry in result_stream.flatten().flat_map(
         |QueryResult {
              location,
@@ -580,9 +771,45 @@ fn do_search<E>(
             if entry < *results.peek().unwrap() {
                 results.pop();
                 results.push(entry);
+                results_changed = true;
             }
         } else {
             results.push(entry);
+            results_changed = true;
+        }
+
+        if results_changed
+            && last_partial_results.elapsed() >= PARTIAL_RESULTS_INTERVAL
+            && !token.is_cancelled()
+        {
+            let mut partial = results.iter().copied().collect::<Vec<_>>();
+            partial.sort_unstable();
+            // The reader is shared with the search threads so buckets can't be grown here.
+            // Entries that don't fit in the current mappings will show up in the final
+            // results.
+            let entries = partial
+                .iter()
+                .flat_map(|entry| resolve_search_entry(entry, database, write_heads))
+                .filter_map(|(entry, highlight)| {
+                    entry
+                        .to_slice_raw(&reader)
+                        .and_then(|loaded| {
+                            loaded
+                                .map(|loaded| loaded_ui_entry(entry, &loaded, highlight))
+                                .transpose()
+                        })
+                        .unwrap_or_else(|e| {
+                            Some(UiEntry {
+                                cache: UiEntryCache::Error(e),
+                                entry,
+                            })
+                        })
+                })
+                .collect::<Box<_>>();
+            let _ = send(Message::PartialSearchResults(entries));
+
+            results_changed = false;
+            last_partial_results = Instant::now();
         }
     }
 
@@ -596,30 +823,36 @@ fn do_search<E>(
     #[allow(clippy::iter_with_drain)] // https://github.com/rust-lang/rust-clippy/issues/8539
     let entries = results
         .drain(..)
-        .flat_map(|SearchEntry { rai, start, end }| -> Result<_, CoreError> {
-            let entry = {
-                let ring = rai.ring();
-                let index = write_heads[ring as usize].wrapping_sub(rai.index()) & MAX_ENTRIES;
-
-                let id = composite_id(ring, index);
-                unsafe { database.get(id) }?
-            };
-
-            Ok(ui_entry(
-                entry,
-                reader,
-                if start == end {
-                    None
-                } else {
-                    Some((start, end))
-                },
-            )
-            .unwrap_or_else(|e| UiEntry {
+        .flat_map(|entry| resolve_search_entry(&entry, database, write_heads))
+        .map(|(entry, highlight)| {
+            ui_entry(entry, reader, highlight).unwrap_or_else(|e| UiEntry {
                 cache: UiEntryCache::Error(e),
                 entry,
-            }))
+            })
         })
         .collect();
     *search_result_buf = results;
     entries
 }
+
+fn resolve_search_entry(
+    &SearchEntry { rai, start, end }: &SearchEntry,
+    database: &mut DatabaseReader,
+    write_heads: [u32; 2],
+) -> Result<(Entry, Option<(usize, usize)>), CoreError> {
+    let entry = {
+        let ring = rai.ring();
+        let index = write_heads[ring as usize].wrapping_sub(rai.index()) & MAX_ENTRIES;
+
+        let id = composite_id(ring, index);
+        unsafe { database.get(id) }?
+    };
+    Ok((
+        entry,
+        if start == end {
+            None
+        } else {
+            Some((start, end))
+        },
+    ))
+}
@@ -0,0 +1,15 @@
+use clipboard_history_client_sdk::preview;
+
+#[test]
+fn empty_entries_have_explicit_preview() {
+    assert_eq!(preview(b"", 250, None), "(empty)");
+
+    let mut highlight = (0, 0);
+    let preview = preview(b"", 250, Some(&mut highlight));
+    assert_eq!(highlight, (preview.len(), preview.len()));
+}
+
+#[test]
+fn blank_entries_are_not_empty() {
+    assert_eq!(preview(b" \n\t", 250, None), "");
+}
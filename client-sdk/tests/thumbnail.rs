@@ -0,0 +1,49 @@
+#![cfg(feature = "thumbnails")]
+
+use std::io::{BufReader, Cursor};
+
+use clipboard_history_client_sdk::thumbnail::{THUMBNAIL_MAX_DIMENSION, generate_thumbnail};
+use image::{ImageFormat, ImageReader, RgbImage};
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = Vec::new();
+    RgbImage::new(width, height)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+#[test]
+fn large_images_are_downscaled() {
+    let thumbnail = generate_thumbnail("image/png", &png(2048, 1024))
+        .unwrap()
+        .unwrap();
+
+    let thumbnail = ImageReader::new(BufReader::new(thumbnail))
+        .with_guessed_format()
+        .unwrap()
+        .decode()
+        .unwrap();
+    assert_eq!(
+        (thumbnail.width(), thumbnail.height()),
+        (THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_DIMENSION / 2)
+    );
+}
+
+#[test]
+fn small_images_are_their_own_thumbnail() {
+    assert!(
+        generate_thumbnail("image/png", &png(16, 16))
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn non_images_are_skipped() {
+    assert!(
+        generate_thumbnail("text/plain", b"hello")
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,153 @@
+use std::{
+    fmt::{Display, Formatter},
+    str,
+};
+
+use crate::is_image_mime;
+
+/// Only this many leading bytes are checked for UTF-8 validity so sniffing
+/// large entries stays cheap.
+const SNIFF_MAX_LEN: usize = 4096;
+
+/// A coarse classification of an entry's contents, finer grained than whether
+/// or not it is valid UTF-8.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ContentKind {
+    Text,
+    Uri,
+    Json,
+    /// XML or HTML markup.
+    Xml,
+    Image,
+    Binary,
+}
+
+impl ContentKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Uri => "uri",
+            Self::Json => "json",
+            Self::Xml => "xml",
+            Self::Image => "image",
+            Self::Binary => "binary",
+        }
+    }
+
+    #[must_use]
+    pub const fn is_text(self) -> bool {
+        match self {
+            Self::Text | Self::Uri | Self::Json | Self::Xml => true,
+            Self::Image | Self::Binary => false,
+        }
+    }
+}
+
+impl Display for ContentKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Classifies an entry using its mime type when that is conclusive and
+/// otherwise by looking at the data itself.
+///
+/// Sniffing is deliberately shallow: structured formats are recognized by
+/// their delimiters rather than parsed, so the result is a hint for display
+/// purposes rather than a guarantee that the data is well formed.
+#[must_use]
+pub fn sniff_content_kind(mime_type: &str, data: &[u8]) -> ContentKind {
+    let mime_type = mime_type.to_ascii_lowercase();
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    if is_image_mime(essence) || has_image_magic(data) {
+        return ContentKind::Image;
+    }
+
+    let prefix = &data[..data.len().min(SNIFF_MAX_LEN)];
+    if let Err(e) = str::from_utf8(prefix)
+        && (e.error_len().is_some() || prefix.len() == data.len())
+    {
+        return ContentKind::Binary;
+    }
+
+    match essence {
+        "application/json" => return ContentKind::Json,
+        "text/uri-list" | "x-special/gnome-copied-files" => return ContentKind::Uri,
+        "text/html" => return ContentKind::Xml,
+        m if m.ends_with("/xml") || m.ends_with("+xml") => return ContentKind::Xml,
+        _ => {}
+    }
+
+    let text = data.trim_ascii();
+    if looks_like_json(text) {
+        ContentKind::Json
+    } else if looks_like_xml(text) {
+        ContentKind::Xml
+    } else if looks_like_uri(text) {
+        ContentKind::Uri
+    } else {
+        ContentKind::Text
+    }
+}
+
+fn has_image_magic(data: &[u8]) -> bool {
+    const MAGICS: &[&[u8]] = &[
+        b"\x89PNG\r\n\x1a\n",
+        b"\xff\xd8\xff",
+        b"GIF87a",
+        b"GIF89a",
+        b"qoif",
+    ];
+
+    MAGICS.iter().any(|magic| data.starts_with(magic))
+        || (data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP"))
+}
+
+fn looks_like_json(text: &[u8]) -> bool {
+    let (Some(&first), Some(&last)) = (text.first(), text.last()) else {
+        return false;
+    };
+    let next = text[1..].trim_ascii_start().first().copied();
+    match (first, last) {
+        (b'{', b'}') => matches!(next, Some(b'"' | b'}')),
+        (b'[', b']') => matches!(
+            next,
+            Some(b'{' | b'[' | b'"' | b'-' | b'0'..=b'9' | b't' | b'f' | b'n' | b']')
+        ),
+        _ => false,
+    }
+}
+
+fn looks_like_xml(text: &[u8]) -> bool {
+    text.starts_with(b"<")
+        && text.ends_with(b">")
+        && text
+            .get(1)
+            .is_some_and(|&b| b.is_ascii_alphabetic() || b == b'?' || b == b'!')
+}
+
+fn looks_like_uri(text: &[u8]) -> bool {
+    const OPAQUE_SCHEMES: &[&[u8]] = &[b"mailto", b"tel", b"magnet", b"data", b"urn"];
+
+    if text.iter().any(u8::is_ascii_whitespace) {
+        return false;
+    }
+    let Some(colon) = text.iter().position(|&b| b == b':') else {
+        return false;
+    };
+    let (scheme, rest) = (&text[..colon], &text[colon + 1..]);
+    let valid_scheme = scheme.first().is_some_and(u8::is_ascii_alphabetic)
+        && scheme
+            .iter()
+            .all(|&b| b.is_ascii_alphanumeric() || b"+-.".contains(&b));
+    valid_scheme
+        && if rest.starts_with(b"//") {
+            rest.len() > 2
+        } else {
+            !rest.is_empty()
+                && OPAQUE_SCHEMES
+                    .iter()
+                    .any(|opaque| scheme.eq_ignore_ascii_case(opaque))
+        }
+}
@@ -1,10 +1,90 @@
 use std::{
-    env,
+    env, fs, io,
+    io::ErrorKind,
     path::{MAIN_SEPARATOR, PathBuf},
+    sync::OnceLock,
 };
 
+use crate::{IoErr, Result, abstract_socket_name};
+
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static SOCKET_FILE_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Overrides the database directory for the rest of the process, taking
+/// precedence over `RINGBOARD_DATA_DIR`.
+///
+/// Returns the path back if an override was already set.
+pub fn set_data_dir(dir: PathBuf) -> std::result::Result<(), PathBuf> {
+    DATA_DIR_OVERRIDE.set(dir)
+}
+
+/// Overrides the server socket for the rest of the process, taking precedence
+/// over `RINGBOARD_SOCK`.
+///
+/// Returns the path back if an override was already set.
+pub fn set_socket_file(file: PathBuf) -> std::result::Result<(), PathBuf> {
+    SOCKET_FILE_OVERRIDE.set(file)
+}
+
+/// Checks that user provided data directory and socket paths are usable.
+pub fn validate_overrides() -> Result<()> {
+    fn invalid(message: String) -> Result<()> {
+        Err(io::Error::from(ErrorKind::InvalidInput)).map_io_err(|| message)
+    }
+
+    if let Some(dir) = data_dir_override() {
+        if !dir.is_absolute() {
+            return invalid(format!("Data directory must be an absolute path: {dir:?}"));
+        }
+        match fs::metadata(&dir) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            r => {
+                if !r
+                    .map_io_err(|| format!("Failed to read data directory: {dir:?}"))?
+                    .is_dir()
+                {
+                    return invalid(format!("Data directory is not a directory: {dir:?}"));
+                }
+            }
+        }
+    }
+    if let Some(file) = socket_file_override() {
+        match abstract_socket_name(&file) {
+            Some([]) => {
+                return invalid(format!("Abstract socket name must not be empty: {file:?}"));
+            }
+            Some(_) => {}
+            None if !file.is_absolute() => {
+                return invalid(format!(
+                    "Socket file must be an absolute path or an @abstract name: {file:?}"
+                ));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn data_dir_override() -> Option<PathBuf> {
+    DATA_DIR_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| env::var_os("RINGBOARD_DATA_DIR").map(PathBuf::from))
+}
+
+fn socket_file_override() -> Option<PathBuf> {
+    SOCKET_FILE_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| env::var_os("RINGBOARD_SOCK").map(PathBuf::from))
+}
+
 #[must_use]
 pub fn data_dir() -> PathBuf {
+    if let Some(dir) = data_dir_override() {
+        return dir;
+    }
+
     let mut dir = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("/tmp/data"));
     dir.reserve("/clipboard-history/buckets/(1024, 2048]".len());
     dir.push("clipboard-history");
@@ -13,8 +93,8 @@ pub fn data_dir() -> PathBuf {
 
 #[must_use]
 pub fn socket_file() -> PathBuf {
-    if let Some(s) = env::var_os("RINGBOARD_SOCK") {
-        return PathBuf::from(s);
+    if let Some(s) = socket_file_override() {
+        return s;
     }
 
     let mut file = PathBuf::with_capacity("/tmp/.ringboard/username.ch".len());
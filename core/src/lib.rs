@@ -1,16 +1,22 @@
 #![feature(core_io_borrowed_buf)]
 
-use std::{borrow::Cow, io, num::ParseIntError};
+use std::{borrow::Cow, io, num::ParseIntError, path::PathBuf};
 
+pub use content::{ContentKind, sniff_content_kind};
+pub use secrets::{SECRET_SCAN_MAX_LEN, looks_like_secret};
 use thiserror::Error;
+pub use timestamp::Timestamp;
 pub use utils::*;
-pub use views::{BucketAndIndex, PathView, RingAndIndex, StringView};
+pub use views::{BucketAndIndex, EntryKey, PathView, RingAndIndex, StringView, URI_PREFIX};
 
 use crate::protocol::IdNotFoundError;
 
+mod content;
 pub mod dirs;
 pub mod protocol;
 pub mod ring;
+mod secrets;
+mod timestamp;
 mod utils;
 mod views;
 
@@ -30,6 +36,12 @@ pub enum Error {
     },
     #[error("ID not found")]
     IdNotFound(#[from] IdNotFoundError),
+    #[error("database version {version} is newer than supported version {supported}")]
+    UnsupportedDatabaseVersion {
+        version: u8,
+        supported: u8,
+        path: PathBuf,
+    },
 }
 
 pub trait IoErr<Out> {
@@ -72,6 +84,16 @@ mod error_stack_compat {
                 Self::IdNotFound(IdNotFoundError::Entry(id)) => {
                     Report::new(wrapper).attach_printable(format!("Unknown entry: {id}"))
                 }
+                Self::UnsupportedDatabaseVersion {
+                    version,
+                    supported,
+                    path,
+                } => Report::new(wrapper)
+                    .attach_printable(format!(
+                        "This database was created by a newer version of Ringboard (format \
+                         v{version} > v{supported}). Please upgrade Ringboard."
+                    ))
+                    .attach_printable(format!("Ring file: {path:?}")),
             }
         }
     }
@@ -1,10 +1,25 @@
-use std::ffi::CStr;
+use std::{
+    ffi::CStr,
+    fmt,
+    fmt::{Debug, Formatter},
+    str,
+};
 
 use arrayvec::ArrayString;
 
-use crate::AsBytes;
+use crate::{AsBytes, Timestamp, ring::MAX_ENTRIES};
 
-pub const VERSION: u8 = 0;
+pub const VERSION: u8 = 1;
+
+/// Sent after the [`VERSION`] when connecting to mark clients that
+/// legitimately add entries in bulk, such as imports. These clients are
+/// exempt from the server's add rate limit.
+pub const CONNECTION_FLAG_BULK: u8 = 1 << 0;
+
+/// Set in an add request's `flags` to have the server reply with
+/// [`AddResponse::Stored`] instead of [`AddResponse::Success`] so the client
+/// can check what was stored without reading the entry back.
+pub const ADD_FLAG_ECHO: u8 = 1 << 0;
 
 #[repr(u8)]
 #[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -31,6 +46,23 @@ impl RingKind {
         }
     }
 
+    /// The file holding the ring's [`LastUsed`](crate::ring::LastUsed) times.
+    #[must_use]
+    pub const fn last_used_file_name(&self) -> &'static str {
+        match self {
+            Self::Main => "main.last-used",
+            Self::Favorites => "favorites.last-used",
+        }
+    }
+
+    #[must_use]
+    pub const fn last_used_file_name_cstr(&self) -> &'static CStr {
+        match self {
+            Self::Main => c"main.last-used",
+            Self::Favorites => c"favorites.last-used",
+        }
+    }
+
     #[must_use]
     pub const fn default_max_entries(&self) -> u32 {
         match self {
@@ -45,14 +77,65 @@ impl RingKind {
 // enough while still letting the Request fit in two cache lines.
 pub type MimeType = ArrayString<96>;
 
+/// A human readable name attached to an entry, e.g. "API key".
+pub type Label = ArrayString<96>;
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub enum Request {
-    Add { to: RingKind, mime_type: MimeType },
-    MoveToFront { id: u64, to: Option<RingKind> },
-    Swap { id1: u64, id2: u64 },
-    Remove { id: u64 },
-    GarbageCollect { max_wasted_bytes: u64 },
+    Add {
+        to: RingKind,
+        mime_type: MimeType,
+        flags: u8,
+    },
+    MoveToFront {
+        id: u64,
+        to: Option<RingKind>,
+    },
+    Swap {
+        id1: u64,
+        id2: u64,
+    },
+    Remove {
+        id: u64,
+    },
+    GarbageCollect {
+        max_wasted_bytes: u64,
+    },
+    /// Deletes every entry in the given ring, or the whole database if no ring
+    /// is specified.
+    Wipe {
+        from: Option<RingKind>,
+    },
+    SetLabel {
+        id: u64,
+        label: Label,
+    },
+    List {
+        from: RingKind,
+        offset: u32,
+    },
+    /// Attaches the data in the accompanying file as another representation of
+    /// the entry. An existing representation with the same mime type is
+    /// replaced.
+    AddAlternate {
+        id: u64,
+        mime_type: MimeType,
+    },
+    /// Schedules the entry for removal once `expires_at` is reached. Passing
+    /// `None` keeps the entry forever.
+    SetExpiry {
+        id: u64,
+        expires_at: Option<Timestamp>,
+    },
+    /// Like [`Request::Add`], but the accompanying file is a memfd sealed
+    /// against writes and resizes. The server can then trust its length and
+    /// read it in place instead of first copying it into a scratchpad.
+    AddSealed {
+        to: RingKind,
+        mime_type: MimeType,
+        flags: u8,
+    },
 }
 
 const _: () = assert!(size_of::<Request>() <= 128);
@@ -68,7 +151,31 @@ pub struct Response<T> {
 #[derive(Copy, Clone, Debug)]
 #[must_use]
 pub enum AddResponse {
-    Success { id: u64 },
+    Success {
+        id: u64,
+    },
+    /// Sent instead of [`Self::Success`] for adds with [`ADD_FLAG_ECHO`] set.
+    Stored {
+        id: u64,
+        size: u64,
+        /// The mime type the entry was stored with, which is empty for plain
+        /// text.
+        mime_type: MimeType,
+    },
+    /// The client exceeded the server's add rate limit, so the entry was
+    /// dropped.
+    RateLimited,
+}
+
+impl AddResponse {
+    /// The ID of the added entry unless it was dropped.
+    #[must_use]
+    pub const fn id(&self) -> Option<u64> {
+        match *self {
+            Self::Success { id } | Self::Stored { id, .. } => Some(id),
+            Self::RateLimited => None,
+        }
+    }
 }
 
 #[repr(C)]
@@ -101,6 +208,74 @@ pub struct GarbageCollectResponse {
     pub bytes_freed: u64,
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct WipeResponse {
+    pub entries_removed: u64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct SetLabelResponse {
+    pub error: Option<IdNotFoundError>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct AddAlternateResponse {
+    pub error: Option<IdNotFoundError>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct SetExpiryResponse {
+    pub error: Option<IdNotFoundError>,
+}
+
+pub const LIST_PAGE_SIZE: usize = 32;
+
+/// A page of entries ordered from newest to oldest.
+///
+/// Pass [`Self::next_offset`] back in a [`Request::List`] to fetch the next
+/// page.
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[must_use]
+pub struct ListResponse {
+    pub entries: [ListEntry; LIST_PAGE_SIZE],
+    pub len: u8,
+    pub next_offset: Option<u32>,
+}
+
+impl ListResponse {
+    #[must_use]
+    pub fn entries(&self) -> &[ListEntry] {
+        &self.entries[..usize::from(self.len)]
+    }
+}
+
+impl Debug for ListResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListResponse")
+            .field("entries", &self.entries())
+            .field("next_offset", &self.next_offset)
+            .finish_non_exhaustive()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ListEntry {
+    pub id: u64,
+    pub ring: RingKind,
+    pub size: u64,
+    pub mime_type: MimeType,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, thiserror::Error, Debug)]
 pub enum IdNotFoundError {
@@ -124,6 +299,64 @@ pub fn decompose_id(id: u64) -> Result<(RingKind, u32), IdNotFoundError> {
     .map(|ring| (ring, u32::try_from(id & u64::from(u32::MAX)).unwrap()))
 }
 
+#[derive(Copy, Clone, thiserror::Error, Debug)]
+pub enum InvalidIdError {
+    #[error("ring {ring} is out of range")]
+    RingOutOfRange { ring: u32 },
+    #[error("entry index {index} exceeds the maximum of {}", MAX_ENTRIES - 1)]
+    IndexOverflow { index: u32 },
+}
+
+impl From<InvalidIdError> for IdNotFoundError {
+    fn from(value: InvalidIdError) -> Self {
+        match value {
+            InvalidIdError::RingOutOfRange { ring } => Self::Ring(ring),
+            InvalidIdError::IndexOverflow { index } => Self::Entry(index),
+        }
+    }
+}
+
+pub fn checked_composite_id(kind: RingKind, index: u32) -> Result<u64, InvalidIdError> {
+    if index >= MAX_ENTRIES {
+        return Err(InvalidIdError::IndexOverflow { index });
+    }
+    Ok(composite_id(kind, index))
+}
+
+pub fn checked_decompose_id(id: u64) -> Result<(RingKind, u32), InvalidIdError> {
+    let ring = match id >> 32 {
+        0 => RingKind::Favorites,
+        1 => RingKind::Main,
+        ring => {
+            return Err(InvalidIdError::RingOutOfRange {
+                ring: u32::try_from(ring).unwrap(),
+            });
+        }
+    };
+    let index = u32::try_from(id & u64::from(u32::MAX)).unwrap();
+    checked_composite_id(ring, index).map(|_| (ring, index))
+}
+
+#[derive(Copy, Clone, thiserror::Error, Debug)]
+pub enum InvalidMimeTypeError {
+    #[error("mime type is {len} bytes long, exceeding the maximum of {}", MimeType::new_const().capacity())]
+    TooLong { len: usize },
+    #[error("mime type is not valid UTF-8")]
+    NotUtf8,
+}
+
+/// Validate raw mime type bytes, such as X11 atom names, without going
+/// through an intermediate [`String`].
+pub fn mime_type_from_bytes(bytes: &[u8]) -> Result<MimeType, InvalidMimeTypeError> {
+    let mut mime_type = MimeType::new_const();
+    if bytes.len() > mime_type.capacity() {
+        return Err(InvalidMimeTypeError::TooLong { len: bytes.len() });
+    }
+    let bytes = str::from_utf8(bytes).map_err(|_| InvalidMimeTypeError::NotUtf8)?;
+    mime_type.push_str(bytes);
+    Ok(mime_type)
+}
+
 impl AsBytes for Request {}
 
 impl AsBytes for AddResponse {}
@@ -131,3 +364,8 @@ impl AsBytes for MoveToFrontResponse {}
 impl AsBytes for SwapResponse {}
 impl AsBytes for RemoveResponse {}
 impl AsBytes for GarbageCollectResponse {}
+impl AsBytes for WipeResponse {}
+impl AsBytes for SetLabelResponse {}
+impl AsBytes for ListResponse {}
+impl AsBytes for AddAlternateResponse {}
+impl AsBytes for SetExpiryResponse {}
@@ -13,7 +13,8 @@ use std::{
 };
 
 use rustix::{
-    fs::{AtFlags, CWD, Mode, OFlags, StatxFlags, openat, statx},
+    fs::{AtFlags, CWD, Mode, OFlags, StatxFlags, ftruncate, openat, statx},
+    io::pwrite,
     mm::{MapFlags, MremapFlags, ProtFlags, mmap, mremap, munmap},
     path::Arg,
 };
@@ -262,21 +263,31 @@ impl Ring {
         )
         .map_io_err(|| "Failed to mmap ring.")?;
 
+        let path = || {
+            let mut buf = [MaybeUninit::uninit(); 26];
+            fs::read_link(Path::new(OsStr::from_bytes(
+                proc_self_fd_buf(&mut buf, &fd).to_bytes(),
+            )))
+            .unwrap_or_else(|_| PathBuf::from("unknown"))
+        };
         if len < MAGIC.len()
             || unsafe { slice::from_raw_parts(mem.ptr().as_ptr(), MAGIC.len()) } != MAGIC
         {
-            let path = {
-                let mut buf = [MaybeUninit::uninit(); 26];
-                fs::read_link(Path::new(OsStr::from_bytes(
-                    proc_self_fd_buf(&mut buf, &fd).to_bytes(),
-                )))
-            }
-            .unwrap_or_else(|_| PathBuf::from("unknown"));
             return Err(Error::Io {
                 error: io::Error::new(ErrorKind::InvalidData, "Not a Ringboard database."),
-                context: format!("Ring file has invalid magic header: {path:?}").into(),
+                context: format!("Ring file has invalid magic header: {:?}", path()).into(),
             });
         }
+        if len > MAGIC.len() {
+            let version = unsafe { *mem.ptr().as_ptr().add(MAGIC.len()) };
+            if version > VERSION {
+                return Err(Error::UnsupportedDatabaseVersion {
+                    version,
+                    supported: VERSION,
+                    path: path(),
+                });
+            }
+        }
 
         Ok(Self {
             mem,
@@ -326,6 +337,64 @@ impl Ring {
         self.capacity
     }
 
+    /// The size of the ring file once it has been filled to capacity.
+    #[must_use]
+    pub fn capacity_bytes(&self) -> u64 {
+        entries_to_offset(self.capacity())
+    }
+
+    /// Grow the ring so it can hold up to `capacity` entries.
+    ///
+    /// `fd` must be a writable handle to the ring file. A ring that isn't full
+    /// yet simply appends the new slots as entries are added. A full ring is
+    /// zero-filled up to the new capacity so the new slots appear as
+    /// uninitialized entries, which sit between the newest and oldest entries
+    /// once the write head reaches them. If the write head has just wrapped
+    /// around, the header is rewritten to point at the first new slot so the
+    /// oldest entries aren't overwritten while there is room to spare.
+    ///
+    /// Entries never move and the file only grows, so readers that mapped the
+    /// old size keep seeing a valid ring: they never read past the length they
+    /// saw when opening it and treat a write head past their length as the end
+    /// of the ring.
+    pub fn grow<Fd: AsFd>(&mut self, fd: Fd, capacity: u32) -> Result<()> {
+        if capacity < self.capacity() || capacity > MAX_ENTRIES {
+            return Err(Error::Io {
+                error: io::Error::from(ErrorKind::InvalidInput),
+                context: format!(
+                    "Ring capacity must be between {} and {MAX_ENTRIES}, got {capacity}.",
+                    self.capacity()
+                )
+                .into(),
+            });
+        }
+        if capacity == self.capacity() {
+            return Ok(());
+        }
+
+        self.mem
+            .remap(usize::try_from(entries_to_offset(capacity)).unwrap())
+            .map_io_err(|| "Failed to remap ring.")?;
+        let old_capacity = self.capacity();
+        self.capacity = capacity;
+        if self.len() < old_capacity {
+            return Ok(());
+        }
+
+        ftruncate(&fd, self.capacity_bytes()).map_io_err(|| "Failed to extend ring file.")?;
+        self.len = capacity;
+        if self.write_head() == 0 {
+            pwrite(
+                &fd,
+                &old_capacity.to_le_bytes(),
+                u64::try_from(MAGIC.len() + size_of_val(&VERSION)).unwrap(),
+            )
+            .map_io_err(|| "Failed to update ring write head.")?;
+        }
+
+        Ok(())
+    }
+
     #[must_use]
     pub fn write_head(&self) -> u32 {
         let bytes = unsafe {
@@ -395,6 +464,46 @@ impl Ring {
     }
 }
 
+/// When each position in a ring was last filled by adding or moving an entry to
+/// the front, stored in a file beside the ring as little-endian unix
+/// milliseconds indexed by ring position.
+///
+/// Times stay with their position when entries are swapped so they always
+/// increase in ring order. The server sizes the file to the ring's capacity up
+/// front so readers never need to remap it. Zero means the time is unknown, for
+/// example because the entry was written by an older server.
+#[derive(Debug)]
+pub struct LastUsed {
+    mem: Option<Mmap>,
+}
+
+impl LastUsed {
+    /// Open a ring's last used times, treating a missing file as all unknown.
+    pub fn open<P: Arg + Copy + Debug>(path: P) -> Result<Self> {
+        let fd = match openat(CWD, path, OFlags::RDONLY, Mode::empty()) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self { mem: None }),
+            r => r.map_io_err(|| format!("Failed to open last used times: {path:?}"))?,
+        };
+        Ok(Self {
+            mem: Some(Mmap::from(&fd).map_io_err(|| "Failed to mmap last used times.")?),
+        })
+    }
+
+    /// The unix time in milliseconds at which the entry at `index` was last
+    /// used.
+    #[must_use]
+    pub fn get(&self, index: u32) -> Option<u64> {
+        let offset = usize::try_from(last_used_offset(index)).unwrap();
+        let bytes = self.mem.as_ref()?.get(offset..offset + size_of::<u64>())?;
+        Some(u64::from_le_bytes(bytes.try_into().unwrap())).filter(|&millis| millis != 0)
+    }
+}
+
+#[must_use]
+pub fn last_used_offset(index: u32) -> u64 {
+    u64::from(index) * u64::try_from(size_of::<u64>()).unwrap()
+}
+
 #[must_use]
 pub fn entries_to_offset(entries: u32) -> u64 {
     u64::from(entries) * u64::try_from(size_of::<RawEntry>()).unwrap()
@@ -0,0 +1,111 @@
+use std::str;
+
+/// Entries longer than this are never considered secrets so scanning stays
+/// cheap. Even large private keys fit comfortably.
+pub const SECRET_SCAN_MAX_LEN: usize = 8192;
+
+const MIN_TOKEN_LEN: usize = 24;
+const MAX_TOKEN_LEN: usize = 512;
+const MIN_TOKEN_ENTROPY: f64 = 4.;
+
+/// Prefixes of well known credential formats.
+const TOKEN_PREFIXES: &[&str] = &[
+    "AKIA",
+    "AIza",
+    "ghp_",
+    "gho_",
+    "ghs_",
+    "ghu_",
+    "github_pat_",
+    "glpat-",
+    "sk-",
+    "sk_live_",
+    "rk_live_",
+    "xoxb-",
+    "xoxp-",
+    "xapp-",
+    "npm_",
+    "pypi-",
+];
+
+/// Heuristically checks whether `data` looks like a credential such as an API
+/// key, access token, or private key.
+///
+/// The check is deliberately conservative: only a single whitespace free token
+/// or a PEM private key block can match, so prose, code, paths, and URLs
+/// never do. Tokens must either start with a well known credential prefix or
+/// look random: a high entropy mix of upper case letters, lower case letters,
+/// and digits.
+#[must_use]
+pub fn looks_like_secret(data: &[u8]) -> bool {
+    if data.len() > SECRET_SCAN_MAX_LEN {
+        return false;
+    }
+    let Ok(text) = str::from_utf8(data) else {
+        return false;
+    };
+    let text = text.trim();
+
+    if text.starts_with("-----BEGIN ") && text.contains("PRIVATE KEY-----") {
+        return true;
+    }
+
+    if !(MIN_TOKEN_LEN..=MAX_TOKEN_LEN).contains(&text.len())
+        || !text
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"+/=_-".contains(&b))
+    {
+        return false;
+    }
+    if TOKEN_PREFIXES.iter().any(|prefix| text.starts_with(prefix)) {
+        return true;
+    }
+
+    let bytes = text.as_bytes();
+    let count = |f: fn(&u8) -> bool| bytes.iter().filter(|b| f(b)).count();
+    count(u8::is_ascii_uppercase) >= 2
+        && count(u8::is_ascii_lowercase) >= 2
+        && count(u8::is_ascii_digit) >= 2
+        && class_changes(bytes) * 5 >= (bytes.len() - 1) * 2
+        && shannon_entropy(bytes) >= MIN_TOKEN_ENTROPY
+}
+
+/// The number of adjacent bytes that switch between upper case, lower case,
+/// digits, and symbols. Random tokens switch often while identifiers written
+/// in camel case only switch at word boundaries.
+fn class_changes(bytes: &[u8]) -> usize {
+    let class = |b: u8| {
+        if b.is_ascii_uppercase() {
+            0
+        } else if b.is_ascii_lowercase() {
+            1
+        } else if b.is_ascii_digit() {
+            2
+        } else {
+            3
+        }
+    };
+    bytes
+        .windows(2)
+        .filter(|pair| class(pair[0]) != class(pair[1]))
+        .count()
+}
+
+/// The average number of bits of information per byte.
+#[allow(clippy::cast_precision_loss)]
+fn shannon_entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0u32; 256];
+    for &b in bytes {
+        counts[usize::from(b)] += 1;
+    }
+
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = f64::from(count) / len;
+            -p * p.log2()
+        })
+        .sum()
+}
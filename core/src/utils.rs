@@ -9,9 +9,9 @@ use std::{
     mem::{MaybeUninit, size_of},
     os::{
         fd::{AsFd, OwnedFd, RawFd},
-        unix::fs::FileExt,
+        unix::{ffi::OsStrExt, fs::FileExt},
     },
-    path::Path,
+    path::{Path, PathBuf},
     ptr, slice,
     str::FromStr,
 };
@@ -21,11 +21,12 @@ use itoa::Integer;
 use rustix::{
     event::{PollFd, PollFlags, poll},
     fs::{
-        AtFlags, CWD, FlockOperation, Mode, OFlags, StatxFlags, copy_file_range, flock, linkat,
-        openat, statx, unlinkat,
+        AtFlags, CWD, FlockOperation, MemfdFlags, Mode, OFlags, SeekFrom, StatxFlags,
+        copy_file_range, fgetxattr, flock, linkat, memfd_create, openat, seek, sendfile, statx,
+        unlinkat,
     },
     io::{Errno, pread_uninit},
-    net::{AddressFamily, SocketAddrUnix, SocketType, bind_unix, listen, socket},
+    net::{AddressFamily, SocketAddrUnix, SocketType, bind_unix, connect_unix, listen, socket},
     path::{Arg, DecInt},
     process::{
         Pid, PidfdFlags, Signal, getpid, kill_process, pidfd_open, pidfd_send_signal,
@@ -35,7 +36,7 @@ use rustix::{
 
 use crate::{
     Error, IoErr, Result,
-    protocol::{RingKind, composite_id},
+    protocol::{MimeType, RingKind, composite_id},
 };
 
 #[must_use]
@@ -54,6 +55,49 @@ pub fn is_plaintext_mime(mime: &str) -> bool {
     TEXT_MIMES.iter().any(|b| mime.eq_ignore_ascii_case(b))
 }
 
+#[must_use]
+pub fn is_image_mime(mime: &str) -> bool {
+    const IMAGE_MIMES: &[&str] = &[
+        "image/png",
+        "image/jpeg",
+        "image/jpg",
+        "image/pjpeg",
+        "image/gif",
+        "image/webp",
+        "image/bmp",
+        "image/x-bmp",
+        "image/x-ms-bmp",
+        "image/tiff",
+        "image/x-icon",
+        "image/vnd.microsoft.icon",
+        "image/qoi",
+    ];
+
+    IMAGE_MIMES.iter().any(|b| mime.eq_ignore_ascii_case(b))
+}
+
+/// The mime type of the alternate holding a downscaled PNG copy of a large
+/// image entry, used to render list previews without decoding the original.
+pub const THUMBNAIL_MIME_TYPE: &str = "image/x-ringboard-thumbnail";
+
+/// Plain text direct allocations at least this large may be stored zstd
+/// compressed when the server is built with compression support.
+pub const COMPRESSION_THRESHOLD: u64 = 64 * 1024;
+
+/// Returns whether a direct allocation's contents are zstd compressed.
+///
+/// Compressed files are marked with a `user.compression` extended attribute,
+/// so files written before compression existed (or on filesystems without
+/// xattr support) are always uncompressed.
+pub fn is_compressed<Fd: AsFd>(fd: Fd) -> rustix::io::Result<bool> {
+    let mut compression = [0; 4];
+    match fgetxattr(fd, c"user.compression", &mut compression) {
+        Ok(len) => Ok(compression[..len] == *b"zstd"),
+        Err(Errno::NODATA | Errno::NOTSUP) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
 pub const NUM_BUCKETS: usize = 11;
 
 // The max composite ID is 2^40 (8 bit ring ID and 32 bit entry ID)
@@ -365,6 +409,35 @@ pub fn copy_file_range_all<InFd: AsFd, OutFd: AsFd>(
     Ok(total_copied)
 }
 
+/// Copies `len` bytes starting at `offset` in `fd` into a new memfd in the
+/// kernel, without reading them into userspace.
+pub fn copy_to_memfd<Fd: AsFd>(
+    name: &CStr,
+    fd: Fd,
+    mut offset: u64,
+    len: usize,
+) -> rustix::io::Result<OwnedFd> {
+    let memfd = memfd_create(name, MemfdFlags::empty())?;
+    match copy_file_range_all(&fd, Some(&mut offset.clone()), &memfd, Some(&mut 0), len) {
+        // Newer kernels refuse to copy_file_range across filesystems, so fall back to
+        // sendfile which is still an in-kernel copy.
+        Err(Errno::XDEV) => {
+            let mut copied = 0;
+            while copied < len {
+                match sendfile(&memfd, &fd, Some(&mut offset), len - copied)? {
+                    0 => break,
+                    n => copied += n,
+                }
+            }
+            seek(&memfd, SeekFrom::Start(0))?;
+        }
+        r => {
+            r?;
+        }
+    }
+    Ok(memfd)
+}
+
 pub fn open_buckets<F: FnMut(&str) -> Result<OwnedFd>>(
     mut open: F,
 ) -> Result<([OwnedFd; NUM_BUCKETS], [u64; NUM_BUCKETS])> {
@@ -424,26 +497,121 @@ pub fn direct_file_name(
     unsafe { CStr::from_ptr(buf.filled_mut().as_ptr().cast()) }
 }
 
+/// Encodes a mime type as the file name of one of an entry's alternate
+/// representations.
+///
+/// Names are prefixed with an underscore so that empty mime types and ones
+/// starting with a dot still produce regular file names. Characters which
+/// cannot appear in file names are percent-encoded.
+#[must_use]
+pub fn alternate_file_name(mime_type: &str) -> String {
+    let mut name = String::with_capacity(1 + mime_type.len());
+    name.push('_');
+    for c in mime_type.chars() {
+        match c {
+            '%' => name.push_str("%25"),
+            '/' => name.push_str("%2F"),
+            '\0' => name.push_str("%00"),
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+/// The inverse of [`alternate_file_name`].
+#[must_use]
+pub fn parse_alternate_file_name(name: &str) -> Option<MimeType> {
+    let mut rest = name.strip_prefix('_')?;
+    let mut mime_type = MimeType::new_const();
+    while let Some((unescaped, escaped)) = rest.split_once('%') {
+        mime_type.try_push_str(unescaped).ok()?;
+        let (code, tail) = escaped.split_at_checked(2)?;
+        mime_type
+            .try_push(match code {
+                "25" => '%',
+                "2F" => '/',
+                "00" => '\0',
+                _ => return None,
+            })
+            .ok()?;
+        rest = tail;
+    }
+    mime_type.try_push_str(rest).ok()?;
+    Some(mime_type)
+}
+
+/// Returns the abstract namespace name of a socket written as `@name`.
+///
+/// Abstract sockets live in the kernel rather than the filesystem, so they
+/// can't go stale and don't need a writable directory.
+#[must_use]
+pub fn abstract_socket_name(socket_file: &Path) -> Option<&[u8]> {
+    socket_file.as_os_str().as_bytes().strip_prefix(b"@")
+}
+
+pub fn socket_addr<P: AsRef<Path>>(socket_file: P) -> Result<SocketAddrUnix> {
+    let socket_file = socket_file.as_ref();
+    if let Some(name) = abstract_socket_name(socket_file) {
+        SocketAddrUnix::new_abstract_name(name)
+    } else {
+        SocketAddrUnix::new(socket_file)
+    }
+    .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))
+}
+
+/// Creates a Unix socket server listening on `socket_file`.
+///
+/// A stale socket file left behind by a crashed server is replaced, but an
+/// error is returned if another server is still listening on it.
 pub fn init_unix_server<P: AsRef<Path>>(socket_file: P, kind: SocketType) -> Result<OwnedFd> {
     let socket_file = socket_file.as_ref();
-    let addr = {
-        match fs::remove_file(socket_file) {
-            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
-            r => r,
-        }
-        .map_io_err(|| format!("Failed to remove old socket: {socket_file:?}"))?;
+    let addr = socket_addr(socket_file)?;
+    if abstract_socket_name(socket_file).is_some() {
+        // The kernel frees the name when its owner exits and refuses to bind it
+        // while in use, so there are no stale sockets to clean up.
+        return bind_unix_server(socket_file, &addr, kind);
+    }
+    if let Some(parent) = socket_file.parent() {
+        fs::create_dir_all(parent)
+            .map_io_err(|| format!("Failed to create socket directory: {parent:?}"))?;
+    }
 
-        if let Some(parent) = socket_file.parent() {
-            fs::create_dir_all(parent)
-                .map_io_err(|| format!("Failed to create socket directory: {parent:?}"))?;
+    {
+        let probe = socket(AddressFamily::UNIX, kind, None)
+            .map_io_err(|| format!("Failed to create socket: {socket_file:?}"))?;
+        match connect_unix(&probe, &addr) {
+            Err(Errno::NOENT) => {}
+            Err(Errno::CONNREFUSED) => {
+                // Nobody is listening, so the socket was left behind by a crash.
+                match fs::remove_file(socket_file) {
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                    r => r,
+                }
+                .map_io_err(|| format!("Failed to remove stale socket: {socket_file:?}"))?;
+            }
+            Ok(()) | Err(Errno::PROTOTYPE) => {
+                return Err(io::Error::from(ErrorKind::AddrInUse)).map_io_err(|| {
+                    format!("Another server is already listening on socket: {socket_file:?}")
+                });
+            }
+            Err(e) => {
+                return Err(e)
+                    .map_io_err(|| format!("Failed to check for a live socket: {socket_file:?}"));
+            }
         }
-        SocketAddrUnix::new(socket_file)
-            .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))?
-    };
+    }
 
+    bind_unix_server(socket_file, &addr, kind)
+}
+
+fn bind_unix_server(
+    socket_file: &Path,
+    addr: &SocketAddrUnix,
+    kind: SocketType,
+) -> Result<OwnedFd> {
     let socket = socket(AddressFamily::UNIX, kind, None)
         .map_io_err(|| format!("Failed to create socket: {socket_file:?}"))?;
-    bind_unix(&socket, &addr).map_io_err(|| format!("Failed to bind socket: {socket_file:?}"))?;
+    bind_unix(&socket, addr).map_io_err(|| format!("Failed to bind socket: {socket_file:?}"))?;
     if kind != SocketType::DGRAM {
         listen(&socket, -1)
             .map_io_err(|| format!("Failed to listen for clients: {socket_file:?}"))?;
@@ -451,6 +619,27 @@ pub fn init_unix_server<P: AsRef<Path>>(socket_file: P, kind: SocketType) -> Res
     Ok(socket)
 }
 
+/// Deletes a socket file once dropped so that clean shutdowns don't leave
+/// stale sockets behind.
+#[derive(Debug)]
+pub struct SocketFileGuard(PathBuf);
+
+impl SocketFileGuard {
+    #[must_use]
+    pub const fn new(socket_file: PathBuf) -> Self {
+        Self(socket_file)
+    }
+}
+
+impl Drop for SocketFileGuard {
+    fn drop(&mut self) {
+        if abstract_socket_name(&self.0).is_some() {
+            return;
+        }
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
 pub fn read_at_to_end<Fd: AsFd>(
     file: Fd,
     mut buf: BorrowedCursor,
@@ -460,15 +649,13 @@ pub fn read_at_to_end<Fd: AsFd>(
         if buf.capacity() == 0 {
             break Ok(());
         }
-        match {
-            let offset = offset + u64::try_from(buf.written()).unwrap();
-            pread_uninit(&file, buf.uninit_mut(), offset)
-        } {
+        let offset = offset + u64::try_from(buf.written()).unwrap();
+        match pread_uninit(&file, unsafe { buf.as_mut() }, offset) {
             Ok(([], _)) => break Ok(()),
             Ok((init, _)) => {
                 let n = init.len();
                 unsafe {
-                    buf.advance_unchecked(n);
+                    buf.advance(n);
                 }
             }
             Err(e) if e.kind() == ErrorKind::Interrupted => {}
@@ -476,3 +663,38 @@ pub fn read_at_to_end<Fd: AsFd>(
         }
     }
 }
+
+/// Reads the file starting at `offset` into an owned buffer, stopping at EOF
+/// or once `limit` bytes have been read.
+///
+/// This copies the contents, so prefer memory mapping the file when borrowing
+/// them is enough.
+pub fn read_at_to_vec<Fd: AsFd>(
+    file: Fd,
+    offset: u64,
+    limit: Option<usize>,
+) -> rustix::io::Result<Vec<u8>> {
+    const MIN_READ_SIZE: usize = 8192;
+
+    let mut data = Vec::new();
+    loop {
+        let remaining = limit.map_or(usize::MAX, |limit| limit - data.len());
+        if remaining == 0 {
+            break Ok(data);
+        }
+        data.reserve(remaining.min(data.len().max(MIN_READ_SIZE)));
+
+        let len = data.len();
+        let spare = data.spare_capacity_mut();
+        let wanted = spare.len().min(remaining);
+        let mut buf = BorrowedBuf::from(&mut spare[..wanted]);
+        read_at_to_end(&file, buf.unfilled(), offset + u64::try_from(len).unwrap())?;
+        let read = buf.filled().len();
+        unsafe {
+            data.set_len(len + read);
+        }
+        if read < wanted {
+            break Ok(data);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     fmt::{Debug, Formatter},
-    intrinsics::transmute,
+    mem::transmute,
 };
 
 pub use path::PathView;
@@ -11,6 +11,8 @@ use crate::{
     ring::MAX_ENTRIES,
 };
 
+pub const URI_PREFIX: &str = "ringboard://";
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct RingAndIndex(u32);
 
@@ -29,6 +31,19 @@ impl RingAndIndex {
         decompose_id(composite_id).map(|(ring, id)| Self::new(ring, id))
     }
 
+    /// Parses a `ringboard://{id}` URI.
+    #[must_use]
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        uri.strip_prefix(URI_PREFIX)
+            .and_then(|id| id.parse().ok())
+            .and_then(|id| Self::from_id(id).ok())
+    }
+
+    #[must_use]
+    pub fn to_uri(self) -> String {
+        format!("{URI_PREFIX}{}", self.id())
+    }
+
     #[must_use]
     pub fn ring(self) -> RingKind {
         let ring = u8::try_from(self.0 & u32::from(u8::MAX)).unwrap();
@@ -85,6 +100,78 @@ impl Debug for BucketAndIndex {
     }
 }
 
+/// Identifies the data backing an entry: bucketed entries are keyed by their
+/// bucket slot while direct files are keyed by their ring position.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct EntryKey(u64);
+
+impl EntryKey {
+    const FILE_TAG: u64 = 1 << u32::BITS;
+
+    #[must_use]
+    pub fn bucketed(bucket: u8, index: u32) -> Self {
+        BucketAndIndex::new(bucket, index).into()
+    }
+
+    #[must_use]
+    pub const fn file(rai: RingAndIndex) -> Self {
+        Self(Self::FILE_TAG | rai.0 as u64)
+    }
+
+    pub fn from_id(composite_id: u64) -> Result<Self, IdNotFoundError> {
+        RingAndIndex::from_id(composite_id).map(Self::file)
+    }
+
+    #[must_use]
+    pub fn bucket_and_index(self) -> Option<BucketAndIndex> {
+        if self.0 & Self::FILE_TAG == 0 {
+            Some(BucketAndIndex(self.raw()))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn ring_and_index(self) -> Option<RingAndIndex> {
+        if self.0 & Self::FILE_TAG == 0 {
+            None
+        } else {
+            Some(RingAndIndex(self.raw()))
+        }
+    }
+
+    #[must_use]
+    pub fn id(self) -> Option<u64> {
+        self.ring_and_index().map(RingAndIndex::id)
+    }
+
+    fn raw(self) -> u32 {
+        u32::try_from(self.0 & u64::from(u32::MAX)).unwrap()
+    }
+}
+
+impl From<BucketAndIndex> for EntryKey {
+    fn from(value: BucketAndIndex) -> Self {
+        Self(u64::from(value.0))
+    }
+}
+
+impl From<RingAndIndex> for EntryKey {
+    fn from(value: RingAndIndex) -> Self {
+        Self::file(value)
+    }
+}
+
+impl Debug for EntryKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(bai) = self.bucket_and_index() {
+            Debug::fmt(&bai, f)
+        } else {
+            Debug::fmt(&self.ring_and_index().unwrap(), f)
+        }
+    }
+}
+
 mod path {
     use std::{
         fmt::{Debug, Formatter},
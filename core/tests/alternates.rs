@@ -0,0 +1,29 @@
+use clipboard_history_core::{alternate_file_name, parse_alternate_file_name};
+
+#[test]
+fn alternate_file_name_round_trip() {
+    for mime_type in [
+        "",
+        "text/plain",
+        "text/html;charset=utf-8",
+        "application/x-100%",
+        ".hidden",
+        "a\0b",
+    ] {
+        let name = alternate_file_name(mime_type);
+
+        assert!(!name.contains(['/', '\0']), "{name:?}");
+        assert_eq!(
+            parse_alternate_file_name(&name).as_deref(),
+            Some(mime_type),
+            "{name:?}"
+        );
+    }
+}
+
+#[test]
+fn invalid_alternate_file_names() {
+    for name in ["", "text%2Fplain", "_text%2", "_text%41plain", ".alternate"] {
+        assert_eq!(parse_alternate_file_name(name), None, "{name:?}");
+    }
+}
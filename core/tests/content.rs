@@ -0,0 +1,61 @@
+use clipboard_history_core::{ContentKind, sniff_content_kind};
+
+#[test]
+fn data_is_sniffed() {
+    for (data, kind) in [
+        (&b"hello world"[..], ContentKind::Text),
+        (b"", ContentKind::Text),
+        (
+            b"https://github.com/SUPERCILEX/clipboard-history",
+            ContentKind::Uri,
+        ),
+        (b"  file:///home/user/notes.txt\n", ContentKind::Uri),
+        (b"mailto:someone@example.com", ContentKind::Uri),
+        (b"key: value", ContentKind::Text),
+        (b"C:\\Windows", ContentKind::Text),
+        (b"see https://example.com", ContentKind::Text),
+        (b"{\"a\": [1, 2]}", ContentKind::Json),
+        (b"[\n  1,\n  2\n]", ContentKind::Json),
+        (b"[link]", ContentKind::Text),
+        (b"{ foo }", ContentKind::Text),
+        (b"<?xml version=\"1.0\"?><a/>", ContentKind::Xml),
+        (b"<div>hi</div>", ContentKind::Xml),
+        (b"<3 you >", ContentKind::Text),
+        (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", ContentKind::Image),
+        (b"RIFF\0\0\0\0WEBPVP8 ", ContentKind::Image),
+        (b"\xff\xfe\x00\x01", ContentKind::Binary),
+    ] {
+        assert_eq!(sniff_content_kind("", data), kind, "{data:?}");
+    }
+}
+
+#[test]
+fn conclusive_mime_types_win() {
+    assert_eq!(
+        sniff_content_kind("image/png", b"not really"),
+        ContentKind::Image
+    );
+    assert_eq!(
+        sniff_content_kind("application/json; charset=utf-8", b"42"),
+        ContentKind::Json
+    );
+    assert_eq!(
+        sniff_content_kind("image/svg+xml", b"<svg/>"),
+        ContentKind::Xml
+    );
+    assert_eq!(
+        sniff_content_kind("text/uri-list", b"https://a.com\nhttps://b.com"),
+        ContentKind::Uri
+    );
+    assert_eq!(
+        sniff_content_kind("application/json", b"\xff\xfe"),
+        ContentKind::Binary
+    );
+}
+
+#[test]
+fn truncated_utf8_prefix_is_text() {
+    let mut data = vec![b'a'; 4095];
+    data.extend_from_slice("é".as_bytes());
+    assert_eq!(sniff_content_kind("", &data), ContentKind::Text);
+}
@@ -0,0 +1,70 @@
+use std::{
+    env, fs,
+    fs::File,
+    io::{Read, Seek},
+    process,
+};
+
+use clipboard_history_core::{copy_to_memfd, read_at_to_vec};
+
+fn disk_file(name: &str, data: &[u8]) -> File {
+    let path = env::temp_dir().join(format!("ringboard-{name}-{}", process::id()));
+    fs::write(&path, data).unwrap();
+    let file = File::open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    file
+}
+
+fn read_all(fd: impl Into<File>) -> Vec<u8> {
+    let mut data = Vec::new();
+    fd.into().read_to_end(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn copy_to_memfd_across_filesystems() {
+    let data = (0..=u8::MAX).cycle().take(100_000).collect::<Vec<_>>();
+    let source = disk_file("copy-all", &data);
+
+    assert_eq!(
+        read_all(copy_to_memfd(c"test", &source, 0, data.len()).unwrap()),
+        data
+    );
+    // The source's file offset is left untouched.
+    assert_eq!((&source).stream_position().unwrap(), 0);
+}
+
+#[test]
+fn copy_to_memfd_at_offset() {
+    let source = disk_file("copy-offset", b"0123456789");
+    let memfd = File::from(copy_to_memfd(c"test", &source, 0, 10).unwrap());
+
+    for source in [&source, &memfd] {
+        assert_eq!(
+            read_all(copy_to_memfd(c"test", source, 2, 5).unwrap()),
+            b"23456"
+        );
+    }
+}
+
+#[test]
+fn read_at_to_vec_reads_everything() {
+    let data = (0..=u8::MAX).cycle().take(100_000).collect::<Vec<_>>();
+    let source = disk_file("read-all", &data);
+
+    assert_eq!(read_at_to_vec(&source, 0, None).unwrap(), data);
+    assert_eq!(
+        read_at_to_vec(&source, 99_990, None).unwrap(),
+        data[99_990..]
+    );
+    assert!(read_at_to_vec(&source, 200_000, None).unwrap().is_empty());
+}
+
+#[test]
+fn read_at_to_vec_stops_at_limit() {
+    let source = disk_file("read-limit", b"0123456789");
+
+    assert_eq!(read_at_to_vec(&source, 2, Some(5)).unwrap(), b"23456");
+    assert_eq!(read_at_to_vec(&source, 8, Some(5)).unwrap(), b"89");
+    assert!(read_at_to_vec(&source, 0, Some(0)).unwrap().is_empty());
+}
@@ -0,0 +1,23 @@
+use clipboard_history_core::protocol::{InvalidMimeTypeError, mime_type_from_bytes};
+
+#[test]
+fn mime_type_from_valid_bytes() {
+    for mime_type in ["", "text/plain", "text/html;charset=utf-8", &"a".repeat(96)] {
+        assert_eq!(
+            mime_type_from_bytes(mime_type.as_bytes()).unwrap().as_str(),
+            mime_type
+        );
+    }
+}
+
+#[test]
+fn mime_type_from_invalid_bytes() {
+    assert!(matches!(
+        mime_type_from_bytes(&[b'a'; 97]),
+        Err(InvalidMimeTypeError::TooLong { len: 97 })
+    ));
+    assert!(matches!(
+        mime_type_from_bytes(b"text/\xff"),
+        Err(InvalidMimeTypeError::NotUtf8)
+    ));
+}
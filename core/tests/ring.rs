@@ -0,0 +1,105 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    os::unix::fs::FileExt,
+    process,
+};
+
+use clipboard_history_core::ring::{Entry, Header, MAGIC, RawEntry, Ring, entries_to_offset};
+
+fn ring_file(name: &str, write_head: u32, files: u32) -> File {
+    let path = env::temp_dir().join(format!("ringboard-{name}-{}", process::id()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let Header { magic, version, .. } = Header::default();
+    let mut header = Vec::from(magic);
+    header.push(version);
+    header.extend_from_slice(&write_head.to_le_bytes());
+    file.write_all_at(&header, 0).unwrap();
+    for i in 0..files {
+        file.write_all_at(
+            &RawEntry::from(Entry::File).to_le_bytes(),
+            entries_to_offset(i),
+        )
+        .unwrap();
+    }
+    file
+}
+
+fn write_head(file: &File) -> u32 {
+    let mut bytes = [0; 4];
+    file.read_exact_at(&mut bytes, u64::try_from(MAGIC.len() + 1).unwrap())
+        .unwrap();
+    u32::from_le_bytes(bytes)
+}
+
+#[test]
+fn grow_partial_ring_only_raises_capacity() {
+    let file = ring_file("grow-partial", 2, 2);
+    let mut ring = Ring::open_fd(4, &file).unwrap();
+
+    ring.grow(&file, 8).unwrap();
+
+    assert_eq!(ring.capacity(), 8);
+    assert_eq!(ring.len(), 2);
+    assert_eq!(file.metadata().unwrap().len(), entries_to_offset(2));
+    assert_eq!(ring.next_head(3), 4);
+    assert_eq!(write_head(&file), 2);
+}
+
+#[test]
+fn grow_full_ring_zero_fills_new_slots() {
+    let file = ring_file("grow-full", 1, 4);
+    let mut ring = Ring::open_fd(4, &file).unwrap();
+
+    ring.grow(&file, 6).unwrap();
+
+    assert_eq!(ring.len(), 6);
+    assert_eq!(file.metadata().unwrap().len(), ring.capacity_bytes());
+    assert!(matches!(ring.get(3), Some(Entry::File)));
+    assert!(matches!(ring.get(4), Some(Entry::Uninitialized)));
+    assert!(matches!(ring.get(5), Some(Entry::Uninitialized)));
+    // The oldest entry is still the next one overwritten.
+    assert_eq!(ring.write_head(), 1);
+}
+
+#[test]
+fn grow_wrapped_ring_moves_write_head_into_new_slots() {
+    let file = ring_file("grow-wrapped", 0, 4);
+    let mut ring = Ring::open_fd(4, &file).unwrap();
+
+    ring.grow(&file, 6).unwrap();
+
+    assert_eq!(ring.write_head(), 4);
+    assert_eq!(write_head(&file), 4);
+    assert!(matches!(ring.get(0), Some(Entry::File)));
+}
+
+#[test]
+fn grow_keeps_old_readers_valid() {
+    let file = ring_file("grow-readers", 0, 4);
+    let reader = Ring::open_fd(4, &file).unwrap();
+    let mut ring = Ring::open_fd(4, &file).unwrap();
+
+    ring.grow(&file, 6).unwrap();
+
+    assert_eq!(reader.len(), 4);
+    assert!(reader.get(4).is_none());
+    // A write head past the reader's length points just after its newest entry.
+    assert_eq!(reader.prev_entry(reader.write_head()), 3);
+}
+
+#[test]
+fn grow_rejects_shrinking() {
+    let file = ring_file("grow-shrink", 0, 4);
+    let mut ring = Ring::open_fd(4, &file).unwrap();
+
+    assert!(ring.grow(&file, 2).is_err());
+    assert_eq!(ring.capacity(), 4);
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clipboard_history_core::Timestamp;
+
+#[test]
+fn system_time_round_trip() {
+    let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_999);
+    let timestamp = Timestamp::from(time);
+    assert_eq!(timestamp, Timestamp::from_secs(1_700_000_000));
+    assert_eq!(
+        SystemTime::from(timestamp),
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    );
+}
+
+#[test]
+fn before_epoch_saturates() {
+    let time = UNIX_EPOCH - Duration::from_secs(1);
+    assert_eq!(Timestamp::from(time), Timestamp::UNIX_EPOCH);
+}
+
+#[test]
+fn arithmetic() {
+    let timestamp = Timestamp::from_secs(100);
+    assert_eq!(
+        timestamp.checked_add(Duration::from_millis(1500)),
+        Some(Timestamp::from_secs(101))
+    );
+    assert_eq!(timestamp.checked_sub(Duration::from_secs(101)), None);
+    assert_eq!(
+        Timestamp::from_secs(u64::MAX).checked_add(Duration::from_secs(1)),
+        None
+    );
+    assert_eq!(
+        timestamp.saturating_duration_since(Timestamp::from_secs(40)),
+        Duration::from_secs(60)
+    );
+    assert_eq!(
+        Timestamp::UNIX_EPOCH.saturating_duration_since(timestamp),
+        Duration::ZERO
+    );
+}
+
+#[test]
+fn string_round_trip() {
+    let timestamp = Timestamp::from_secs(1_700_000_000);
+    assert_eq!(timestamp.to_string(), "1700000000");
+    assert_eq!("1700000000".parse(), Ok(timestamp));
+    assert!("soon".parse::<Timestamp>().is_err());
+}
@@ -0,0 +1,49 @@
+use std::{env, io::ErrorKind, path::PathBuf, process};
+
+use clipboard_history_core::{Error, SocketFileGuard, init_unix_server};
+use rustix::net::SocketType;
+
+#[test]
+fn stale_sockets_are_replaced() {
+    let socket_file = env::temp_dir().join(format!("ringboard-stale-{}.sock", process::id()));
+    let guard = SocketFileGuard::new(socket_file.clone());
+
+    drop(init_unix_server(&socket_file, SocketType::DGRAM).unwrap());
+    assert!(socket_file.exists());
+    init_unix_server(&socket_file, SocketType::DGRAM).unwrap();
+
+    drop(guard);
+    assert!(!socket_file.exists());
+}
+
+#[test]
+fn live_sockets_are_kept() {
+    let socket_file = env::temp_dir().join(format!("ringboard-live-{}.sock", process::id()));
+    let _guard = SocketFileGuard::new(socket_file.clone());
+
+    let _server = init_unix_server(&socket_file, SocketType::SEQPACKET).unwrap();
+    let Err(Error::Io { error, context: _ }) =
+        init_unix_server(&socket_file, SocketType::SEQPACKET)
+    else {
+        panic!("Clobbered a live socket");
+    };
+    assert_eq!(error.kind(), ErrorKind::AddrInUse);
+    assert!(socket_file.exists());
+}
+
+#[test]
+fn abstract_sockets_are_exclusive() {
+    let socket_file = PathBuf::from(format!("@ringboard-abstract-{}", process::id()));
+    let guard = SocketFileGuard::new(socket_file.clone());
+
+    let _server = init_unix_server(&socket_file, SocketType::SEQPACKET).unwrap();
+    assert!(!socket_file.exists());
+    let Err(Error::Io { error, context: _ }) =
+        init_unix_server(&socket_file, SocketType::SEQPACKET)
+    else {
+        panic!("Bound an abstract socket twice");
+    };
+    assert_eq!(error.kind(), ErrorKind::AddrInUse);
+
+    drop(guard);
+}
@@ -0,0 +1,32 @@
+use clipboard_history_core::{
+    RingAndIndex, URI_PREFIX,
+    protocol::{RingKind, composite_id},
+};
+
+#[test]
+fn uri_round_trip() {
+    for ring in [RingKind::Favorites, RingKind::Main] {
+        for index in [0, 1, 42, 131_069] {
+            let rai = RingAndIndex::new(ring, index);
+            let uri = rai.to_uri();
+
+            assert_eq!(uri, format!("{URI_PREFIX}{}", composite_id(ring, index)));
+            assert_eq!(RingAndIndex::from_uri(&uri), Some(rai));
+        }
+    }
+}
+
+#[test]
+fn invalid_uris() {
+    for uri in [
+        "",
+        "ringboard://",
+        "ringboard://abc",
+        "ringboard://-1",
+        "file://0",
+        "0",
+        "ringboard://8589934592",
+    ] {
+        assert_eq!(RingAndIndex::from_uri(uri), None, "{uri}");
+    }
+}
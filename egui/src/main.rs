@@ -2,11 +2,15 @@
 #![allow(clippy::significant_drop_tightening)]
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
     env,
     error::Error,
+    fs,
+    fs::create_dir_all,
     hash::BuildHasherDefault,
-    str,
+    io,
+    io::ErrorKind,
+    mem, str,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
@@ -20,9 +24,9 @@ use eframe::{
     egui,
     egui::{
         CentralPanel, Event, FontId, FontTweak, Frame, Image, Key, Label, Margin, Modifiers,
-        PopupCloseBehavior, Pos2, Response, RichText, ScrollArea, Sense, Stroke, TextEdit,
-        TextFormat, ThemePreference, TopBottomPanel, Ui, Vec2, ViewportBuilder, ViewportCommand,
-        Widget,
+        PopupCloseBehavior, Pos2, Rect, Response, RichText, ScrollArea, Sense, Slider, Stroke,
+        TextEdit, TextFormat, ThemePreference, TopBottomPanel, Ui, Vec2, ViewportBuilder,
+        ViewportCommand, Widget, WindowLevel,
         text::{LayoutJob, LayoutSection},
     },
     epaint::FontFamily,
@@ -30,11 +34,15 @@ use eframe::{
 use itoa::Integer;
 use ringboard_sdk::{
     ClientError,
-    core::{Error as CoreError, protocol::RingKind},
+    api::PasteTarget,
+    config::{EguiConfig, EguiV1Config, Theme, egui_config_file},
+    core::{Error as CoreError, IoErr, URI_PREFIX, protocol::RingKind},
+    is_text_mime, preview,
     search::CancellationToken,
+    thumbnail::THUMBNAIL_MAX_DIMENSION,
     ui_actor::{
-        Command, CommandError, DetailedEntry, Message, SearchKind, UiEntry, UiEntryCache,
-        controller,
+        Command, CommandError, DetailedEntry, Message, ONE_LINER_MAX_LEN, SECRET_ONE_LINER,
+        SearchKind, TrashedEntry, UiEntry, UiEntryCache, controller,
     },
 };
 use rustc_hash::FxHasher;
@@ -124,8 +132,13 @@ fn main() -> Result<(), eframe::Error> {
                     ctx.add_image_loader(ringboard_loader.clone());
 
                     controller(&command_receiver, |m| {
-                        let r = if let Message::LoadedImage { id, image } = m {
-                            ringboard_loader.add(id, image);
+                        let r = if let Message::LoadedImage {
+                            id,
+                            thumbnail,
+                            image,
+                        } = m
+                        {
+                            ringboard_loader.add(id, thumbnail, image);
                             Ok(())
                         } else {
                             response_sender.send(m)
@@ -157,11 +170,23 @@ fn main() -> Result<(), eframe::Error> {
                 }
             });
 
+            let (config, config_error) = match load_config() {
+                Ok(config) => (config, None),
+                Err(e) => (EguiV1Config::default(), Some(e)),
+            };
             if env::var_os("THEME").as_deref().unwrap_or_default() == "light" {
                 cc.egui_ctx.set_theme(ThemePreference::Light);
+            } else {
+                cc.egui_ctx.set_theme(theme_preference(config.theme));
+            }
+            if config.always_on_top {
+                cc.egui_ctx
+                    .send_viewport_cmd(ViewportCommand::WindowLevel(WindowLevel::AlwaysOnTop));
             }
 
-            Ok(Box::new(App::start(command_sender, response_receiver)))
+            let mut app = App::start(command_sender, response_receiver, config);
+            app.state.ui.last_error = config_error.map(CommandError::from);
+            Ok(Box::new(app))
         }),
     );
 
@@ -179,7 +204,84 @@ struct App {
     requests: Sender<Command>,
     responses: Receiver<Message>,
 
+    config: EguiV1Config,
     state: State,
+    trash: Trash,
+}
+
+fn load_config() -> Result<EguiV1Config, CoreError> {
+    let path = egui_config_file();
+    let config = match fs::read_to_string(&path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(EguiV1Config::default()),
+        r => r.map_io_err(|| format!("Failed to read config: {path:?}"))?,
+    };
+    Ok(
+        match toml::from_str::<EguiConfig>(&config)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+            .map_io_err(|| format!("Failed to parse config: {path:?}"))?
+        {
+            EguiConfig::V1(c) => c,
+        },
+    )
+}
+
+fn save_config(config: &EguiV1Config) -> Result<(), CoreError> {
+    let path = egui_config_file();
+    {
+        let parent = path.parent().unwrap();
+        create_dir_all(parent).map_io_err(|| format!("Failed to create dir: {parent:?}"))?;
+    }
+
+    let config = toml::to_string_pretty(&EguiConfig::V1(config.clone()))
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+        .map_io_err(|| "Failed to serialize config.")?;
+    fs::write(&path, config).map_io_err(|| format!("Failed to write to config file: {path:?}"))
+}
+
+const IMAGE_PREVIEW_HEIGHT_STEP: f32 = 50.;
+const MIN_IMAGE_PREVIEW_HEIGHT: f32 = 50.;
+const MAX_IMAGE_PREVIEW_HEIGHT: f32 = 2000.;
+
+const MAX_TRASH_ENTRIES: usize = 32;
+const MAX_TRASH_BYTES: usize = 64 << 20;
+
+const fn theme_preference(theme: Theme) -> ThemePreference {
+    match theme {
+        Theme::System => ThemePreference::System,
+        Theme::Light => ThemePreference::Light,
+        Theme::Dark => ThemePreference::Dark,
+    }
+}
+
+/// Recently deleted entries, oldest first. This lives outside of [`State`] so
+/// that it survives reloads and the window being closed.
+#[derive(Default)]
+struct Trash {
+    entries: VecDeque<Box<TrashedEntry>>,
+    size: usize,
+}
+
+impl Trash {
+    fn push(&mut self, entry: Box<TrashedEntry>) {
+        self.size += entry.size();
+        self.entries.push_back(entry);
+        // Never evict the entry that was just deleted.
+        while self.entries.len() > 1
+            && (self.entries.len() > MAX_TRASH_ENTRIES || self.size > MAX_TRASH_BYTES)
+        {
+            self.remove(0);
+        }
+    }
+
+    fn pop(&mut self) -> Option<Box<TrashedEntry>> {
+        self.remove(self.entries.len().checked_sub(1)?)
+    }
+
+    fn remove(&mut self, index: usize) -> Option<Box<TrashedEntry>> {
+        let entry = self.entries.remove(index)?;
+        self.size -= entry.size();
+        Some(entry)
+    }
 }
 
 #[derive(Default)]
@@ -202,6 +304,9 @@ struct UiState {
 
     details_requested: Option<u64>,
     detailed_entry: Option<Result<DetailedEntry, CoreError>>,
+    /// Hover previews keyed by entry id where `None` means the text is still
+    /// being fetched.
+    peeks: HashMap<u64, Option<Box<str>>, BuildHasherDefault<FxHasher>>,
 
     query: String,
     search_highlighted_id: Option<u64>,
@@ -209,45 +314,117 @@ struct UiState {
     pending_search_token: Option<CancellationToken>,
     queued_searches: u32,
 
+    show_trash: bool,
+    show_settings: bool,
+    /// An entry that was too large for the trash and needs confirmation before
+    /// being deleted for good.
+    confirm_delete: Option<u64>,
+
     was_focused: bool,
     skip_first_focus: bool,
 
     uri_buf: UriBuf,
+    row_heights: Vec<f32>,
 }
 
-const URI_PREFIX: &str = "ringboard://";
+/// Appended to an entry's URI to load its thumbnail instead of the full image.
+const THUMBNAIL_URI_SUFFIX: &str = "#thumbnail";
 
 struct UriBuf {
-    buf: [u8; URI_PREFIX.len() + u64::MAX_STR_LEN],
+    buf: [u8; URI_PREFIX.len() + u64::MAX_STR_LEN + THUMBNAIL_URI_SUFFIX.len()],
 }
 
 impl UriBuf {
     fn format(&mut self, id: u64) -> &str {
+        self.format_with_suffix(id, "")
+    }
+
+    fn format_thumbnail(&mut self, id: u64) -> &str {
+        self.format_with_suffix(id, THUMBNAIL_URI_SUFFIX)
+    }
+
+    fn format_with_suffix(&mut self, id: u64, suffix: &str) -> &str {
         let mut buf = itoa::Buffer::new();
         let str = buf.format(id);
 
-        self.buf[URI_PREFIX.len()..][..str.len()].copy_from_slice(str.as_bytes());
-        unsafe { str::from_utf8_unchecked(&self.buf[..URI_PREFIX.len() + str.len()]) }
+        let uri = &mut self.buf[URI_PREFIX.len()..];
+        uri[..str.len()].copy_from_slice(str.as_bytes());
+        uri[str.len()..][..suffix.len()].copy_from_slice(suffix.as_bytes());
+        unsafe {
+            str::from_utf8_unchecked(&self.buf[..URI_PREFIX.len() + str.len() + suffix.len()])
+        }
     }
 }
 
 impl Default for UriBuf {
     fn default() -> Self {
-        Self {
-            buf: *b"ringboard://\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-        }
+        let mut buf = [0; URI_PREFIX.len() + u64::MAX_STR_LEN + THUMBNAIL_URI_SUFFIX.len()];
+        buf[..URI_PREFIX.len()].copy_from_slice(URI_PREFIX.as_bytes());
+        Self { buf }
     }
 }
 
 impl App {
-    fn start(requests: Sender<Command>, responses: Receiver<Message>) -> Self {
+    fn start(
+        requests: Sender<Command>,
+        responses: Receiver<Message>,
+        config: EguiV1Config,
+    ) -> Self {
         let mut state = State::default();
         state.ui.skip_first_focus = true;
+        if config.interleave_favorites {
+            let _ = requests.send(Command::InterleaveFavorites(true));
+        }
         Self {
             requests,
             responses,
 
+            config,
             state,
+            trash: Trash::default(),
+        }
+    }
+}
+
+impl App {
+    /// Applies and persists the settings that changed since `old`.
+    fn apply_config_changes(&mut self, ctx: &egui::Context, old: &EguiV1Config) {
+        if self.config == *old {
+            return;
+        }
+        let EguiV1Config {
+            theme,
+            image_preview_height: _,
+            compact,
+            interleave_favorites,
+            striped_rows: _,
+            always_on_top,
+            clear_search_on_paste: _,
+            close_on_focus_loss: _,
+        } = self.config;
+
+        if theme != old.theme {
+            ctx.set_theme(theme_preference(theme));
+        }
+        if always_on_top != old.always_on_top {
+            // Compositors without a notion of window levels (e.g. Wayland) ignore this.
+            ctx.send_viewport_cmd(ViewportCommand::WindowLevel(if always_on_top {
+                WindowLevel::AlwaysOnTop
+            } else {
+                WindowLevel::Normal
+            }));
+        }
+        if compact != old.compact {
+            // Measured heights are stale in the other density.
+            self.state.ui.row_heights.clear();
+        }
+        if interleave_favorites != old.interleave_favorites {
+            let _ = self
+                .requests
+                .send(Command::InterleaveFavorites(interleave_favorites));
+        }
+        if let Err(e) = save_config(&self.config) {
+            self.state.ui.last_error = Some(e.into());
         }
     }
 }
@@ -289,10 +466,20 @@ fn remove_old_images<'a, 'b>(
         .filter(|id| !new_ids.contains(id))
     {
         ctx.forget_image(uri_buf.format(dead_id.id()));
+        ctx.forget_image(uri_buf.format_thumbnail(dead_id.id()));
     }
 }
 
-fn handle_message(message: Message, State { entries, ui }: &mut State, ctx: &egui::Context) {
+fn handle_message(
+    message: Message,
+    State { entries, ui }: &mut State,
+    trash: &mut Trash,
+    &EguiV1Config {
+        clear_search_on_paste,
+        ..
+    }: &EguiV1Config,
+    ctx: &egui::Context,
+) {
     let UiEntries {
         loaded_entries,
         search_results,
@@ -303,14 +490,19 @@ fn handle_message(message: Message, State { entries, ui }: &mut State, ctx: &egu
         highlighted_id,
         details_requested,
         detailed_entry,
-        query: _,
+        peeks,
+        query,
         search_highlighted_id,
         search_kind: _,
         pending_search_token,
         queued_searches,
+        show_trash: _,
+        show_settings: _,
+        confirm_delete,
         was_focused: _,
         skip_first_focus: _,
         uri_buf,
+        row_heights: _,
     } = ui;
 
     let mut remove_old_images = |entries| {
@@ -332,18 +524,41 @@ fn handle_message(message: Message, State { entries, ui }: &mut State, ctx: &egu
         Message::LoadedFirstPage {
             entries,
             default_focused_id,
+            database_size: _,
         } => {
             remove_old_images(entries.iter().chain(&*search_results));
+            peeks.clear();
             *loaded_entries = entries;
             if highlighted_id.is_none() {
                 *highlighted_id = default_focused_id;
             }
         }
         Message::EntryDetails { id, result } => {
+            if let Some(peek @ None) = peeks.get_mut(&id) {
+                let text = result
+                    .as_ref()
+                    .ok()
+                    .and_then(|d| d.full_text.as_deref())
+                    .unwrap_or_default();
+                *peek = Some(match text.char_indices().nth(PEEK_MAX_CHARS) {
+                    Some((end, _)) => format!("{}…", &text[..end]).into(),
+                    None => text.into(),
+                });
+            }
             if *details_requested == Some(id) {
                 *detailed_entry = Some(result);
             }
         }
+        Message::PartialSearchResults(entries) => {
+            if let Some(token) = pending_search_token
+                && !token.is_cancelled()
+            {
+                if search_highlighted_id.is_none() {
+                    *search_highlighted_id = entries.first().map(|e| e.entry.id());
+                }
+                *search_results = entries;
+            }
+        }
         Message::SearchResults(entries) => {
             remove_old_images(entries.iter().chain(&*loaded_entries));
             *queued_searches = queued_searches.saturating_sub(1);
@@ -353,7 +568,15 @@ fn handle_message(message: Message, State { entries, ui }: &mut State, ctx: &egu
             }
         }
         Message::FavoriteChange(id) => *active_highlighted_id!(ui) = Some(id),
-        Message::Deleted(_) => {}
+        Message::Deleted(id) => {
+            peeks.remove(&id);
+        }
+        Message::Trashed(entry) => {
+            peeks.remove(&entry.id);
+            trash.push(entry);
+        }
+        Message::TooLargeToTrash(id) => *confirm_delete = Some(id),
+        Message::Restored(id) => *highlighted_id = Some(id),
         Message::LoadedImage { .. } => unreachable!(),
         Message::PendingSearch(token) => {
             if *queued_searches > 1 {
@@ -361,14 +584,68 @@ fn handle_message(message: Message, State { entries, ui }: &mut State, ctx: &egu
             }
             *pending_search_token = Some(token);
         }
-        Message::Pasted => ctx.send_viewport_cmd(ViewportCommand::Close),
+        Message::Pasted { keep_open } => {
+            if clear_search_on_paste && !query.is_empty() {
+                if let Some(token) = pending_search_token.take() {
+                    token.cancel();
+                }
+                remove_old_images(loaded_entries.iter().chain(&[]));
+                query.clear();
+                *search_results = Box::default();
+                *search_highlighted_id = None;
+            }
+            if !keep_open {
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+            }
+        }
     }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         for message in self.responses.try_iter() {
-            handle_message(message, &mut self.state, ctx);
+            handle_message(message, &mut self.state, &mut self.trash, &self.config, ctx);
+        }
+
+        let old_config = self.config.clone();
+        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::T)) {
+            let theme = &mut self.config.theme;
+            *theme = match theme {
+                Theme::System => Theme::Light,
+                Theme::Light => Theme::Dark,
+                Theme::Dark => Theme::System,
+            };
+        }
+        for (key, delta) in [
+            (Key::Equals, IMAGE_PREVIEW_HEIGHT_STEP),
+            (Key::Minus, -IMAGE_PREVIEW_HEIGHT_STEP),
+        ] {
+            if ctx.input_mut(|i| i.consume_key(Modifiers::ALT, key)) {
+                let height = &mut self.config.image_preview_height;
+                *height =
+                    (*height + delta).clamp(MIN_IMAGE_PREVIEW_HEIGHT, MAX_IMAGE_PREVIEW_HEIGHT);
+            }
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::ALT, Key::I)) {
+            self.config.interleave_favorites ^= true;
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::ALT, Key::C)) {
+            self.config.compact ^= true;
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::ALT, Key::P)) {
+            self.config.always_on_top ^= true;
+        }
+        if self.config.always_on_top && !self.state.ui.was_focused && ctx.input(|i| i.focused) {
+            // Window managers drop the window level when the window is hidden, so
+            // restore it when the window comes back.
+            ctx.send_viewport_cmd(ViewportCommand::WindowLevel(WindowLevel::AlwaysOnTop));
+        }
+        if self.config.close_on_focus_loss && self.state.ui.was_focused && !ctx.input(|i| i.focused)
+        {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::Comma)) {
+            self.state.ui.show_settings ^= true;
         }
 
         let up_pressed = ctx
@@ -393,14 +670,21 @@ impl eframe::App for App {
                 ..Margin::ZERO
             }))
             .show(ctx, |ui| {
+                if self.state.ui.show_settings {
+                    settings_ui(ui, &mut self.config);
+                    return;
+                }
                 main_ui(
                     ui,
                     &mut self.state,
+                    &mut self.trash,
                     &self.requests,
                     up_pressed,
                     down_pressed,
+                    &self.config,
                 );
             });
+        self.apply_config_changes(ctx, &old_config);
 
         #[cfg(not(feature = "wayland"))]
         if {
@@ -534,12 +818,30 @@ fn show_error(ui: &mut Ui, e: &dyn Error) {
     ui.label(format!("Details: {e:#?}"));
 }
 
+/// The height of a one line text entry: 16pt monospace text, the row's frame
+/// margins, and item spacing. Used to lay out rows that have never been shown.
+const ESTIMATED_ROW_HEIGHT: f32 = 32.;
+const COMPACT_ESTIMATED_ROW_HEIGHT: f32 = 22.;
+
+const ROW_MARGIN: f32 = 5.;
+const COMPACT_ROW_MARGIN: f32 = 2.;
+const ROW_FONT_SIZE: f32 = 16.;
+const COMPACT_ROW_FONT_SIZE: f32 = 13.;
+
 fn main_ui(
     ui: &mut Ui,
     state_: &mut State,
+    trash: &mut Trash,
     requests: &Sender<Command>,
     up_pressed: bool,
     down_pressed: bool,
+    &EguiV1Config {
+        image_preview_height,
+        compact,
+        interleave_favorites,
+        striped_rows,
+        ..
+    }: &EguiV1Config,
 ) {
     let State { entries, ui: state } = state_;
     let refresh = |state: &mut UiState| {
@@ -574,6 +876,19 @@ fn main_ui(
     if let Some(e) = &state.last_error {
         show_error(ui, e);
     }
+    if let Some(id) = state.confirm_delete {
+        ui.label("This entry is too large to keep in the trash, so deleting it can't be undone.");
+        ui.horizontal(|ui| {
+            if ui.button("Delete permanently").clicked() {
+                let _ = requests.send(Command::Delete(id));
+                state.confirm_delete = None;
+                refresh(state);
+            }
+            if ui.button("Cancel").clicked() {
+                state.confirm_delete = None;
+            }
+        });
+    }
 
     let mut try_scroll = false;
 
@@ -587,6 +902,19 @@ fn main_ui(
         refresh(&mut state_.ui);
         return;
     }
+    if ui.input_mut(|input| input.consume_key(Modifiers::CTRL, Key::Z))
+        && let Some(entry) = trash.pop()
+    {
+        let _ = requests.send(Command::Restore(entry));
+        refresh(state);
+    }
+    if ui.input_mut(|input| input.consume_key(Modifiers::CTRL, Key::D)) {
+        state.show_trash ^= true;
+    }
+    if state.show_trash {
+        trash_ui(ui, trash, state, requests, refresh);
+        return;
+    }
     let no_popups_open = ui.memory(|mem| !mem.any_popup_open());
     if !active_entries!(entries, state).is_empty() && no_popups_open {
         handle_arrow_keys(
@@ -597,10 +925,21 @@ fn main_ui(
             down_pressed,
         );
     }
-    if ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Enter))
-        && let Some(id) = *active_highlighted_id!(state)
+    if let Some(keep_open) = ui.input_mut(|input| {
+        if input.consume_key(Modifiers::SHIFT, Key::Enter) {
+            Some(true)
+        } else if input.consume_key(Modifiers::NONE, Key::Enter) {
+            Some(false)
+        } else {
+            None
+        }
+    }) && let Some(id) = *active_highlighted_id!(state)
     {
-        let _ = requests.send(Command::Paste(id));
+        let _ = requests.send(Command::Paste {
+            id,
+            keep_open,
+            target: PasteTarget::default(),
+        });
     }
     if let Some(UiEntry { entry, cache: _ }) = ui
         .input_mut(|input| {
@@ -622,7 +961,11 @@ fn main_ui(
         })
         .and_then(|idx| active_entries!(entries, state).get(idx))
     {
-        let _ = requests.send(Command::Paste(entry.id()));
+        let _ = requests.send(Command::Paste {
+            id: entry.id(),
+            keep_open: false,
+            target: PasteTarget::default(),
+        });
     }
 
     if active_entries!(entries, state).is_empty() {
@@ -642,15 +985,51 @@ fn main_ui(
         ui.input(|input| input.key_pressed(Key::Space)) && ui.memory(|mem| mem.focused().is_none());
 
     let usable_height_for_popup = ui.available_size().y - 50.;
-    ScrollArea::vertical().show(ui, |ui| {
-        let mut prev_was_favorites = false;
-        for (i, entry) in active_entries!(entries, state).iter().enumerate() {
-            let next_was_favorites = entry.entry.ring() == RingKind::Favorites;
-            if prev_was_favorites && !next_was_favorites {
-                ui.separator();
+    ScrollArea::vertical().show_viewport(ui, |ui, viewport| {
+        // Only lay out the rows that are on screen. Everything else is replaced by
+        // empty space sized using the row heights we measured the last time those
+        // rows were visible.
+        let active_entries = active_entries!(entries, state);
+        let mut row_heights = mem::take(&mut state.row_heights);
+        row_heights.resize(
+            active_entries.len(),
+            if compact {
+                COMPACT_ESTIMATED_ROW_HEIGHT
+            } else {
+                ESTIMATED_ROW_HEIGHT
+            },
+        );
+        let highlighted_id = *active_highlighted_id!(state);
+
+        let mut y = 0.;
+        let mut skipped = 0.;
+        for (i, entry) in active_entries.iter().enumerate() {
+            let height = row_heights[i];
+            if y + height < viewport.min.y || y > viewport.max.y {
+                if try_scroll && highlighted_id == Some(entry.entry.id()) {
+                    let max_rect = ui.max_rect();
+                    ui.scroll_to_rect(
+                        Rect::from_min_size(
+                            Pos2::new(max_rect.left(), max_rect.top() + y),
+                            Vec2::new(max_rect.width(), height),
+                        ),
+                        None,
+                    );
+                }
+                y += height;
+                skipped += height;
+                continue;
             }
-            prev_was_favorites = next_was_favorites;
+            ui.add_space(mem::take(&mut skipped));
 
+            let start = ui.cursor().top();
+            if !interleave_favorites
+                && i > 0
+                && active_entries[i - 1].entry.ring() == RingKind::Favorites
+                && entry.entry.ring() != RingKind::Favorites
+            {
+                ui.separator();
+            }
             entry_ui(
                 ui,
                 entry,
@@ -662,12 +1041,113 @@ fn main_ui(
                 try_popup,
                 no_popups_open,
                 usable_height_for_popup,
+                image_preview_height,
+                compact,
+                striped_rows,
                 i,
             );
+            row_heights[i] = ui.cursor().top() - start;
+            y += row_heights[i];
+        }
+        ui.add_space(skipped);
+
+        state.row_heights = row_heights;
+    });
+}
+
+fn settings_ui(
+    ui: &mut Ui,
+    EguiV1Config {
+        theme,
+        image_preview_height,
+        compact,
+        interleave_favorites,
+        striped_rows,
+        always_on_top,
+        clear_search_on_paste,
+        close_on_focus_loss,
+    }: &mut EguiV1Config,
+) {
+    ui.heading("Settings");
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("Theme");
+        ui.radio_value(theme, Theme::System, "System");
+        ui.radio_value(theme, Theme::Light, "Light");
+        ui.radio_value(theme, Theme::Dark, "Dark");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Image preview height");
+        ui.add(
+            Slider::new(
+                image_preview_height,
+                MIN_IMAGE_PREVIEW_HEIGHT..=MAX_IMAGE_PREVIEW_HEIGHT,
+            )
+            .step_by(f64::from(IMAGE_PREVIEW_HEIGHT_STEP))
+            .suffix(" px"),
+        );
+    });
+    ui.checkbox(compact, "Compact rows");
+    ui.checkbox(
+        interleave_favorites,
+        "List favorites among other entries by when they were last used",
+    );
+    ui.checkbox(striped_rows, "Shade alternating rows");
+    ui.checkbox(always_on_top, "Keep the window above other windows");
+    ui.checkbox(
+        clear_search_on_paste,
+        "Clear the search after pasting a result",
+    );
+    ui.checkbox(close_on_focus_loss, "Hide the window when it loses focus");
+    ui.separator();
+    ui.label(format!("Settings are saved to {:?}.", egui_config_file()));
+}
+
+fn trash_ui(
+    ui: &mut Ui,
+    trash: &mut Trash,
+    state: &mut UiState,
+    requests: &Sender<Command>,
+    mut refresh: impl FnMut(&mut UiState),
+) {
+    ui.heading("Recently deleted");
+    ui.separator();
+    if trash.entries.is_empty() {
+        ui.centered_and_justified(|ui| {
+            ui.label(RichText::new("Nothing to see here…").heading());
+        });
+        return;
+    }
+
+    let mut restore = None;
+    ScrollArea::vertical().show(ui, |ui| {
+        for (i, entry) in trash.entries.iter().enumerate().rev() {
+            let TrashedEntry {
+                mime_type, data, ..
+            } = &**entry;
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    restore = Some(i);
+                }
+                ui.add(
+                    Label::new(if is_text_mime(mime_type) {
+                        preview(data, ONE_LINER_MAX_LEN, None)
+                    } else {
+                        format!("{mime_type} ({} bytes)", data.len())
+                    })
+                    .truncate(),
+                );
+            });
         }
     });
+
+    if let Some(entry) = restore.and_then(|i| trash.remove(i)) {
+        let _ = requests.send(Command::Restore(entry));
+        refresh(state);
+    }
 }
 
+#[allow(clippy::fn_params_excessive_bools)]
 fn entry_ui(
     ui: &mut Ui,
     entry: &UiEntry,
@@ -679,6 +1159,9 @@ fn entry_ui(
     try_popup: bool,
     no_popups_open: bool,
     max_popup_height: f32,
+    image_preview_height: f32,
+    compact: bool,
+    striped: bool,
     index: usize,
 ) {
     macro_rules! response {
@@ -694,6 +1177,8 @@ fn entry_ui(
                 try_scroll,
                 try_popup,
                 max_popup_height,
+                compact,
+                striped,
                 index,
             )
         };
@@ -710,7 +1195,11 @@ fn entry_ui(
                 },
                 sections: {
                     let format = TextFormat {
-                        font_id: FontId::monospace(16.),
+                        font_id: FontId::monospace(if compact {
+                            COMPACT_ROW_FONT_SIZE
+                        } else {
+                            ROW_FONT_SIZE
+                        }),
                         color: ui.visuals().text_color(),
                         ..Default::default()
                     };
@@ -752,12 +1241,22 @@ fn entry_ui(
             };
             response!(Label::new(job).selectable(false))
         }
-        UiEntryCache::Image => response!(
-            Image::new(state.uri_buf.format(entry.entry.id()).to_owned())
-                .max_height(250.)
-                .max_width(ui.available_width() - 10.)
-                .fit_to_original_size(1.)
-        ),
+        UiEntryCache::Image => {
+            // Thumbnails would look blurry in previews taller than them.
+            let uri = if image_preview_height * ui.ctx().pixels_per_point()
+                <= THUMBNAIL_MAX_DIMENSION as f32
+            {
+                state.uri_buf.format_thumbnail(entry.entry.id())
+            } else {
+                state.uri_buf.format(entry.entry.id())
+            };
+            response!(
+                Image::new(uri.to_owned())
+                    .max_height(image_preview_height)
+                    .max_width(ui.available_width() - 10.)
+                    .fit_to_original_size(1.)
+            )
+        }
         UiEntryCache::Binary { mime_type } => response!(
             Label::new(format!("Unable to display format of type {mime_type:?}."))
                 .selectable(false)
@@ -768,7 +1267,11 @@ fn entry_ui(
         }
     };
     if response.clicked() && no_popups_open {
-        let _ = requests.send(Command::Paste(entry.entry.id()));
+        let _ = requests.send(Command::Paste {
+            id: entry.entry.id(),
+            keep_open: ui.input(|i| i.modifiers.shift),
+            target: PasteTarget::default(),
+        });
     }
 }
 
@@ -783,6 +1286,8 @@ fn row_ui(
     try_scroll: bool,
     try_popup: bool,
     max_popup_height: f32,
+    compact: bool,
+    striped: bool,
     index: usize,
 ) -> Response {
     if index < 10 && ui.input(|i| i.modifiers.ctrl) {
@@ -795,7 +1300,11 @@ fn row_ui(
 
     let entry_id = entry.id();
 
-    let frame_data = Frame::default().inner_margin(5.);
+    let frame_data = Frame::default().inner_margin(if compact {
+        COMPACT_ROW_MARGIN
+    } else {
+        ROW_MARGIN
+    });
     let mut frame = frame_data.begin(ui);
     frame.content_ui.add(widget);
     frame
@@ -816,10 +1325,17 @@ fn row_ui(
     }
     if *highlighted_id == Some(entry_id) {
         frame.frame.fill = ui.style().visuals.widgets.hovered.weak_bg_fill;
+    } else if striped && index % 2 == 1 {
+        frame.frame.fill = ui.style().visuals.faint_bg_color;
     }
     frame.paint(ui);
 
     let popup_id = ui.make_persistent_id(entry_id);
+    let response = if ui.memory(|mem| mem.is_popup_open(popup_id)) {
+        response
+    } else {
+        peek_ui(response, &mut state.peeks, requests, entry_id, cache)
+    };
     if response.secondary_clicked() || (try_popup && *highlighted_id == Some(entry_id)) {
         ui.memory_mut(|mem| mem.toggle_popup(popup_id));
     }
@@ -861,7 +1377,7 @@ fn row_ui(
                     }
                 }
                 if ui.button("Delete").clicked() {
-                    run(ui, Command::Delete(entry_id));
+                    run(ui, Command::Trash(entry_id));
 
                     let entries = active_entries!(entries, state);
                     *active_highlighted_id!(state) = entries
@@ -869,6 +1385,15 @@ fn row_ui(
                         .or_else(|| entries.get(index.saturating_sub(1)))
                         .map(|e| e.entry.id());
                 }
+                if ui.button("Copy id").clicked() {
+                    ui.ctx().copy_text(entry_id.to_string());
+                    ui.memory_mut(egui::Memory::close_popup);
+                }
+                if ui.button("Copy URI").clicked() {
+                    ui.ctx()
+                        .copy_text(state.uri_buf.format(entry_id).to_owned());
+                    ui.memory_mut(egui::Memory::close_popup);
+                }
             });
             ui.separator();
 
@@ -880,11 +1405,18 @@ fn row_ui(
                 }
                 Some(Ok(DetailedEntry {
                     mime_type,
+                    content_kind,
+                    label,
                     full_text,
+                    binary_prefix: _,
                 })) => {
+                    if !label.is_empty() {
+                        ui.label(format!("Label: {label}"));
+                    }
                     if !mime_type.is_empty() {
                         ui.label(format!("Mime type: {mime_type}"));
                     }
+                    ui.label(format!("Content: {content_kind}"));
                     ui.separator();
                     if let Some(full) = full_text {
                         ScrollArea::both()
@@ -915,6 +1447,48 @@ fn row_ui(
     response
 }
 
+const PEEK_MAX_CHARS: usize = 500;
+
+fn peek_ui(
+    response: Response,
+    peeks: &mut HashMap<u64, Option<Box<str>>, BuildHasherDefault<FxHasher>>,
+    requests: &Sender<Command>,
+    entry_id: u64,
+    cache: &UiEntryCache,
+) -> Response {
+    let (UiEntryCache::Text { one_liner } | UiEntryCache::HighlightedText { one_liner, .. }) =
+        cache
+    else {
+        return response;
+    };
+    // Peeking would reveal the full text of entries hidden as secrets.
+    if **one_liner == *SECRET_ONE_LINER {
+        return response;
+    }
+    if let Some(Some(peek)) = peeks.get(&entry_id)
+        && (peek.is_empty() || **peek == **one_liner)
+    {
+        return response;
+    }
+
+    // Only runs once the tooltip delay has elapsed so merely sweeping the
+    // pointer across the list doesn't spam the controller.
+    response.on_hover_ui(|ui| {
+        let peek = peeks.entry(entry_id).or_insert_with(|| {
+            let _ = requests.send(Command::GetDetails {
+                id: entry_id,
+                with_text: true,
+            });
+            None
+        });
+        if let Some(peek) = peek {
+            ui.label(RichText::new(&**peek).monospace());
+        } else {
+            ui.label("Loading…");
+        }
+    })
+}
+
 fn handle_arrow_keys(
     entries: &[UiEntry],
     highlighted_id: &mut Option<u64>,
@@ -958,7 +1532,6 @@ mod loader {
     use std::{
         collections::{HashMap, hash_map::Entry},
         hash::BuildHasherDefault,
-        str::FromStr,
         sync::{Arc, Mutex, mpsc::Sender},
     };
 
@@ -973,7 +1546,7 @@ mod loader {
     use ringboard_sdk::{core::RingAndIndex, ui_actor::Command};
     use rustc_hash::FxHasher;
 
-    use crate::URI_PREFIX;
+    use crate::THUMBNAIL_URI_SUFFIX;
 
     enum CachedImage {
         Queued,
@@ -982,7 +1555,15 @@ mod loader {
 
     pub struct RingboardLoader {
         requests: Sender<Command>,
-        cache: Mutex<HashMap<RingAndIndex, CachedImage, BuildHasherDefault<FxHasher>>>,
+        /// Keyed by entry and whether the image is its thumbnail.
+        cache: Mutex<HashMap<(RingAndIndex, bool), CachedImage, BuildHasherDefault<FxHasher>>>,
+    }
+
+    fn parse_uri(uri: &str) -> Option<(RingAndIndex, bool)> {
+        let (uri, thumbnail) = uri
+            .strip_suffix(THUMBNAIL_URI_SUFFIX)
+            .map_or((uri, false), |uri| (uri, true));
+        RingAndIndex::from_uri(uri).map(|id| (id, thumbnail))
     }
 
     impl RingboardLoader {
@@ -995,7 +1576,7 @@ mod loader {
             }
         }
 
-        pub fn add(&self, id: u64, image: DynamicImage) {
+        pub fn add(&self, id: u64, thumbnail: bool, image: DynamicImage) {
             let size = [image.width() as _, image.height() as _];
             let image_buffer = image.into_rgba8();
             let pixels = image_buffer.into_flat_samples();
@@ -1003,7 +1584,7 @@ mod loader {
                 return;
             };
             cache.insert(
-                RingAndIndex::from_id(id).unwrap(),
+                (RingAndIndex::from_id(id).unwrap(), thumbnail),
                 CachedImage::Computed(
                     ColorImage::from_rgba_unmultiplied(size, pixels.as_slice()).into(),
                 ),
@@ -1011,19 +1592,13 @@ mod loader {
         }
     }
 
-    fn uri_to_id(uri: &str) -> Option<RingAndIndex> {
-        uri.strip_prefix(URI_PREFIX)
-            .and_then(|id| u64::from_str(id).ok())
-            .and_then(|id| RingAndIndex::from_id(id).ok())
-    }
-
     impl ImageLoader for RingboardLoader {
         fn id(&self) -> &str {
             Self::ID
         }
 
         fn load(&self, _: &egui::Context, uri: &str, _: SizeHint) -> ImageLoadResult {
-            let Some(id) = uri_to_id(uri) else {
+            let Some(key @ (id, thumbnail)) = parse_uri(uri) else {
                 return Err(LoadError::NotSupported);
             };
 
@@ -1032,7 +1607,7 @@ mod loader {
                     "Ringboard loader lock poisoned.".to_string(),
                 ));
             };
-            match cache.entry(id) {
+            match cache.entry(key) {
                 Entry::Occupied(e) => match e.get() {
                     CachedImage::Queued => Ok(ImagePoll::Pending { size: None }),
                     CachedImage::Computed(image) => Ok(ImagePoll::Ready {
@@ -1040,7 +1615,10 @@ mod loader {
                     }),
                 },
                 Entry::Vacant(v) => {
-                    let _ = self.requests.send(Command::LoadImage(id.id()));
+                    let _ = self.requests.send(Command::LoadImage {
+                        id: id.id(),
+                        thumbnail,
+                    });
                     v.insert(CachedImage::Queued);
                     Ok(ImagePoll::Pending { size: None })
                 }
@@ -1048,10 +1626,10 @@ mod loader {
         }
 
         fn forget(&self, uri: &str) {
-            if let Some(id) = uri_to_id(uri)
+            if let Some(key) = parse_uri(uri)
                 && let Ok(mut cache) = self.cache.lock()
             {
-                cache.remove(&id);
+                cache.remove(&key);
             }
         }
 
@@ -1,17 +1,22 @@
 use std::{
     array,
-    cmp::{Reverse, min},
-    collections::BinaryHeap,
+    cmp::{Ordering, Reverse, max, min},
+    collections::{BinaryHeap, HashMap},
     ffi::CStr,
     fmt::Debug,
     fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
     io,
     io::{ErrorKind, ErrorKind::AlreadyExists, IoSlice, Read, Seek, SeekFrom, Write},
     mem,
     mem::{ManuallyDrop, MaybeUninit},
     ops::{Index, IndexMut},
-    os::{fd::OwnedFd, unix::fs::FileExt},
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::fs::FileExt,
+    },
     slice,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use arrayvec::{ArrayString, ArrayVec};
@@ -19,11 +24,14 @@ use bitcode::{Decode, Encode};
 use bitvec::{order::Lsb0, vec::BitVec};
 use log::{debug, error, info, trace, warn};
 use ringboard_core::{
-    IoErr, NUM_BUCKETS, RingAndIndex, bucket_to_length, copy_file_range_all, create_tmp_file,
-    direct_file_name, is_plaintext_mime, link_tmp_file, open_buckets,
+    IoErr, NUM_BUCKETS, RingAndIndex, SECRET_SCAN_MAX_LEN, Timestamp, alternate_file_name,
+    bucket_to_length, copy_file_range_all, create_tmp_file, direct_file_name, is_plaintext_mime,
+    link_tmp_file, looks_like_secret, open_buckets,
     protocol::{
-        AddResponse, GarbageCollectResponse, IdNotFoundError, MimeType, MoveToFrontResponse,
-        RemoveResponse, RingKind, SwapResponse, composite_id, decompose_id,
+        AddAlternateResponse, AddResponse, GarbageCollectResponse, IdNotFoundError, LIST_PAGE_SIZE,
+        Label, ListEntry, ListResponse, MimeType, MoveToFrontResponse, RemoveResponse, RingKind,
+        SetExpiryResponse, SetLabelResponse, SwapResponse, WipeResponse, composite_id,
+        decompose_id,
     },
     ring,
     ring::{Entry, Header, InitializedEntry, RawEntry, Ring, entries_to_offset},
@@ -31,12 +39,14 @@ use ringboard_core::{
 };
 use rustix::{
     fs::{
-        AtFlags, CWD, Mode, OFlags, RenameFlags, XattrFlags, fsetxattr, ftruncate, getxattr, mkdir,
-        openat, renameat, renameat_with, unlinkat,
+        AtFlags, CWD, Mode, OFlags, RawDir, RenameFlags, SealFlags, XattrFlags, fcntl_get_seals,
+        fgetxattr, fremovexattr, fsetxattr, fstat, ftruncate, getxattr, mkdir, mkdirat, openat,
+        renameat, renameat_with, sendfile, unlinkat,
     },
     io::Errno,
     path::Arg,
 };
+use smallvec::SmallVec;
 
 use crate::CliError;
 
@@ -102,12 +112,79 @@ impl RingWriter {
             )
             .map_io_err(|| format!("Failed to update Ringboard write head: {head}"))
     }
+
+    #[allow(clippy::needless_pass_by_ref_mut)]
+    fn clear(&mut self, len: u32) -> ringboard_core::Result<()> {
+        debug!("Clearing {len} entries.");
+        self.ring
+            .write_all_at(
+                &vec![0; usize::try_from(entries_to_offset(len) - entries_to_offset(0)).unwrap()],
+                entries_to_offset(0),
+            )
+            .map_io_err(|| "Failed to clear Ringboard database entries.")
+    }
 }
 
 #[derive(Debug)]
 struct WritableRing {
     writer: RingWriter,
     ring: Ring,
+    last_used: LastUsedWriter,
+}
+
+#[derive(Debug)]
+struct LastUsedWriter {
+    file: File,
+}
+
+impl LastUsedWriter {
+    fn open(kind: RingKind, capacity: u32) -> Result<Self, CliError> {
+        let path = kind.last_used_file_name_cstr();
+        let file = File::from(
+            openat(
+                CWD,
+                path,
+                OFlags::RDWR | OFlags::CREATE,
+                Mode::RUSR | Mode::WUSR,
+            )
+            .map_io_err(|| format!("Failed to open last used times: {path:?}"))?,
+        );
+
+        // Readers map the file once, so make room for every entry up front.
+        let len = ring::last_used_offset(capacity);
+        if file
+            .metadata()
+            .map_io_err(|| format!("Failed to stat last used times: {path:?}"))?
+            .len()
+            < len
+        {
+            file.set_len(len)
+                .map_io_err(|| format!("Failed to extend last used times: {path:?}"))?;
+        }
+
+        Ok(Self { file })
+    }
+
+    fn grow(&self, kind: RingKind, capacity: u32) -> Result<(), CliError> {
+        let path = kind.last_used_file_name_cstr();
+        self.file
+            .set_len(ring::last_used_offset(capacity))
+            .map_io_err(|| format!("Failed to extend last used times: {path:?}"))
+            .map_err(CliError::from)
+    }
+
+    fn touch(&self, at: u32) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        if let Err(e) = self.file.write_all_at(
+            &u64::try_from(now).unwrap_or(u64::MAX).to_le_bytes(),
+            ring::last_used_offset(at),
+        ) {
+            warn!("Failed to write last used time of position {at}: {e}");
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -131,15 +208,92 @@ impl IndexMut<RingKind> for Rings {
 pub struct Allocator {
     rings: Rings,
     data: AllocatorData,
+    expirations: Expirations,
+}
+
+/// Progress through a duplicate removal pass which may be split across several
+/// calls to [`Allocator::remove_duplicates`].
+#[derive(Default, Debug)]
+pub struct DuplicateScan {
+    ring: usize,
+    scanned: u32,
+    hashes: HashMap<u64, SmallVec<RingAndIndex, 1>>,
+}
+
+/// The location of an entry's bytes.
+enum EntryData<'a> {
+    Bucket {
+        file: &'a File,
+        offset: u64,
+        len: u64,
+    },
+    Direct {
+        file: File,
+        len: u64,
+    },
+}
+
+impl EntryData<'_> {
+    const fn parts(&self) -> (&File, u64, u64) {
+        match *self {
+            Self::Bucket { file, offset, len } => (file, offset, len),
+            Self::Direct { ref file, len } => (file, 0, len),
+        }
+    }
+
+    /// Small entries are hashed by content while large ones are only hashed by
+    /// length to avoid reading them in full.
+    fn hash(&self) -> Result<u64, CliError> {
+        let (file, offset, len) = self.parts();
+        let mut hasher = DefaultHasher::new();
+        if len >= 4096 {
+            len.hash(&mut hasher);
+        } else {
+            let mut buf = [0; 4096];
+            let buf = &mut buf[..usize::try_from(len).unwrap()];
+            file.read_exact_at(buf, offset)
+                .map_io_err(|| "Failed to read entry.")?;
+            buf.hash(&mut hasher);
+        }
+        Ok(hasher.finish())
+    }
+
+    fn same_contents(&self, other: &Self) -> io::Result<bool> {
+        let (file, offset, len) = self.parts();
+        let (other_file, other_offset, other_len) = other.parts();
+        if len != other_len {
+            return Ok(false);
+        }
+
+        let mut buf = [0; 4096];
+        let mut other_buf = [0; 4096];
+        let mut pos = 0;
+        while pos < len {
+            let n = usize::try_from(min(len - pos, 4096)).unwrap();
+            file.read_exact_at(&mut buf[..n], offset + pos)?;
+            other_file.read_exact_at(&mut other_buf[..n], other_offset + pos)?;
+            if buf[..n] != other_buf[..n] {
+                return Ok(false);
+            }
+            pos += u64::try_from(n).unwrap();
+        }
+        Ok(true)
+    }
 }
 
 #[derive(Debug)]
 struct AllocatorData {
     buckets: Buckets,
     direct_dir: OwnedFd,
+    /// Entries with a file in this directory store their metadata there
+    /// instead of in xattrs.
     metadata_dir: Option<OwnedFd>,
+    alternates_dir: OwnedFd,
     scratchpad: File,
     tmp_file_unsupported: bool,
+    xattr_unsupported: bool,
+    xattr_fallback_logged: bool,
+    detect_secrets: bool,
 }
 
 #[derive(Debug)]
@@ -262,6 +416,200 @@ impl FreeLists {
     }
 }
 
+/// Expiry times of entries keyed by their composite ID.
+///
+/// Removed entries aren't forgotten eagerly: sweeps re-read the expiry time of
+/// entries that look due before removing them.
+#[derive(Default, Debug)]
+struct Expirations {
+    entries: HashMap<u64, Timestamp>,
+    next: Option<Timestamp>,
+}
+
+#[derive(Encode, Decode, Default, Debug)]
+struct RawExpirations(Vec<(u64, u64)>);
+
+impl Expirations {
+    fn load(rings: &Rings, data: &AllocatorData) -> Result<Self, CliError> {
+        let file = match openat(CWD, c"expirations", OFlags::RDWR, Mode::empty()) {
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            r => Some(File::from(
+                r.map_io_err(|| "Failed to open expirations file.")?,
+            )),
+        };
+
+        if let Some(mut file) = file {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)
+                .map_io_err(|| "Failed to read expirations file.")?;
+
+            if !bytes.is_empty() {
+                file.set_len(0)
+                    .map_io_err(|| "Failed to truncate expirations file.")?;
+                match bitcode::decode(&bytes) {
+                    Ok(RawExpirations(entries)) => {
+                        let mut expirations = Self::default();
+                        for (id, expires_at) in entries {
+                            expirations.insert(id, Timestamp::from_secs(expires_at));
+                        }
+                        return Ok(expirations);
+                    }
+                    Err(e) => {
+                        error!("Corrupted expirations file.\nError: {e:?}");
+                    }
+                }
+            }
+            warn!("Reconstructing entry expiry times.");
+        }
+
+        let mut expirations = Self::default();
+        for kind in [RingKind::Favorites, RingKind::Main] {
+            let ring = &rings[kind].ring;
+            for id in (0..ring.len()).filter(|&id| ring.get(id) == Some(Entry::File)) {
+                match data.read_expiry(kind, id) {
+                    Ok(Some(expires_at)) => expirations.insert(composite_id(kind, id), expires_at),
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        "Ignoring expiry time of entry in {kind:?} ring at position {id}.\nError: \
+                         {e:?}"
+                    ),
+                }
+            }
+        }
+        Ok(expirations)
+    }
+
+    fn save(&self) -> Result<(), CliError> {
+        info!("Saving entry expiry times to disk.");
+        let file = openat(
+            CWD,
+            c"expirations",
+            OFlags::WRONLY | OFlags::CREATE,
+            Mode::RUSR | Mode::WUSR,
+        )
+        .map_io_err(|| "Failed to open expirations file.")?;
+        let bytes = bitcode::encode(&RawExpirations(
+            self.entries
+                .iter()
+                .map(|(&id, expires_at)| (id, expires_at.as_secs()))
+                .collect(),
+        ));
+        debug_assert!(!bytes.is_empty());
+        File::from(file)
+            .write_all_at(&bytes, 0)
+            .map_io_err(|| "Failed to write expirations.")?;
+        Ok(())
+    }
+
+    fn insert(&mut self, id: u64, expires_at: Timestamp) {
+        self.entries.insert(id, expires_at);
+        self.next = Some(self.next.map_or(expires_at, |t| min(t, expires_at)));
+    }
+
+    fn set(&mut self, id: u64, expires_at: Option<Timestamp>) {
+        if let Some(expires_at) = expires_at {
+            self.insert(id, expires_at);
+        } else {
+            self.entries.remove(&id);
+        }
+    }
+
+    fn move_(&mut self, from: u64, to: u64) {
+        let expires_at = self.entries.remove(&from);
+        self.set(to, expires_at);
+    }
+
+    fn swap(&mut self, id1: u64, id2: u64) {
+        let expires_at1 = self.entries.remove(&id1);
+        let expires_at2 = self.entries.remove(&id2);
+        self.set(id1, expires_at2);
+        self.set(id2, expires_at1);
+    }
+
+    /// Forgets and returns the IDs of entries due to expire by `now`.
+    fn take_due(&mut self, now: Timestamp) -> Vec<u64> {
+        let Self { entries, next } = self;
+        let mut due = Vec::new();
+        *next = None;
+        entries.retain(|&id, &mut expires_at| {
+            if expires_at <= now {
+                due.push(id);
+                false
+            } else {
+                *next = Some(next.map_or(expires_at, |t| min(t, expires_at)));
+                true
+            }
+        });
+        due
+    }
+}
+
+impl Buckets {
+    fn log_utilization(&self) {
+        for (bucket, (&slots, free_slots)) in self
+            .slot_counts
+            .iter()
+            .zip(&self.free_lists.lists.0)
+            .enumerate()
+        {
+            if slots == 0 {
+                continue;
+            }
+            let used = slots - u32::try_from(free_slots.len()).unwrap();
+            debug!(
+                "Bucket {bucket} with slots of {} bytes is using {used}/{slots} slots ({}%).",
+                bucket_to_length(bucket),
+                u64::from(used) * 100 / u64::from(slots),
+            );
+        }
+    }
+}
+
+fn validate_buckets(
+    rings: &Rings,
+    buckets: &[OwnedFd; NUM_BUCKETS],
+    lengths: [u64; NUM_BUCKETS],
+) -> Result<[u32; NUM_BUCKETS], CliError> {
+    let mut referenced_slots = [0; NUM_BUCKETS];
+    for ring in [RingKind::Favorites, RingKind::Main] {
+        let ring = &rings[ring].ring;
+        for entry in (0..ring.len()).filter_map(|i| ring.get(i)) {
+            if let Entry::Bucketed(entry) = entry {
+                let slots = &mut referenced_slots[usize::from(size_to_bucket(entry.size()))];
+                *slots = max(*slots, entry.index() + 1);
+            }
+        }
+    }
+
+    let mut slot_counts = [0; NUM_BUCKETS];
+    for (bucket, (file, len)) in buckets.iter().zip(lengths).enumerate() {
+        let slot_size = u64::from(bucket_to_length(bucket));
+        let slots = u32::try_from(len / slot_size).unwrap();
+        let referenced = referenced_slots[bucket];
+        if len % slot_size != 0 {
+            warn!(
+                "Bucket {bucket} with slots of {slot_size} bytes ends in a partial slot ({len} \
+                 bytes), ignoring it."
+            );
+        }
+
+        slot_counts[bucket] = if slots < referenced {
+            // Otherwise new allocations would be handed slots that are still in use.
+            error!(
+                "Bucket {bucket} with slots of {slot_size} bytes is missing or truncated: it has \
+                 {slots} slots but the rings reference {referenced}. Recreating the missing slots \
+                 as empty, their contents are lost."
+            );
+            ftruncate(file, u64::from(referenced) * slot_size)
+                .map_io_err(|| format!("Failed to extend bucket {bucket}."))?;
+            referenced
+        } else {
+            slots
+        };
+    }
+    Ok(slot_counts)
+}
+
 fn create_scratchpad(tmp_file_unsupported: &mut bool) -> ringboard_core::Result<File> {
     create_tmp_file(
         tmp_file_unsupported,
@@ -275,12 +623,45 @@ fn create_scratchpad(tmp_file_unsupported: &mut bool) -> ringboard_core::Result<
     .map_io_err(|| "Failed to create scratchpad file.")
 }
 
+/// Returns the length of a file whose contents can no longer change, or `None`
+/// if the client didn't actually seal it.
+fn sealed_len(file: &File) -> Result<Option<u64>, CliError> {
+    const REQUIRED: SealFlags = SealFlags::SHRINK
+        .union(SealFlags::GROW)
+        .union(SealFlags::WRITE);
+
+    match fcntl_get_seals(file) {
+        Ok(seals) if seals.contains(REQUIRED) => (),
+        Ok(_) | Err(Errno::INVAL) => return Ok(None),
+        Err(e) => Err(e).map_io_err(|| "Failed to get file seals.")?,
+    }
+    let len = fstat(file)
+        .map_io_err(|| "Failed to stat sealed file.")?
+        .st_size;
+    Ok(Some(u64::try_from(len).unwrap()))
+}
+
+fn is_secret(file: &File, size: u64) -> Result<bool, CliError> {
+    let mut data = [0; SECRET_SCAN_MAX_LEN];
+    let Some(data) = usize::try_from(size)
+        .ok()
+        .and_then(|size| data.get_mut(..size))
+    else {
+        return Ok(false);
+    };
+    file.read_exact_at(data, 0)
+        .map_io_err(|| "Failed to read entry data.")?;
+    Ok(looks_like_secret(data))
+}
+
 impl Allocator {
     pub fn open() -> Result<Self, CliError> {
         let open_ring = |kind: RingKind| -> Result<_, CliError> {
             let writer = RingWriter::open(kind.file_name_cstr())?;
+            let ring = Ring::open_fd(kind.default_max_entries(), &writer.ring)?;
             Ok(WritableRing {
-                ring: Ring::open_fd(kind.default_max_entries(), &writer.ring)?,
+                last_used: LastUsedWriter::open(kind, ring.capacity())?,
+                ring,
                 writer,
             })
         };
@@ -293,6 +674,7 @@ impl Allocator {
         };
         create_dir(c"direct")?;
         create_dir(c"buckets")?;
+        create_dir(c"alternates")?;
 
         let xattr_unsupported = matches!(
             getxattr(c"direct", c"user.mime_type", &mut []),
@@ -302,7 +684,7 @@ impl Allocator {
             create_dir(c"metadata")?;
         }
 
-        let (buckets, slot_counts) = {
+        let (buckets, bucket_lengths) = {
             let mut path = ArrayString::<{ "buckets/(1024, 2048]".len() + 1 }>::new_const();
             path.push_str("buckets/");
             open_buckets(|name| {
@@ -318,56 +700,134 @@ impl Allocator {
                 .map_io_err(|| format!("Failed to create bucket: {path:?}"))
             })?
         };
-        let slot_counts = {
-            let mut i = 0;
-            slot_counts.map(|len| {
-                let slots = u32::try_from(len >> (i + 2)).unwrap();
-                i += 1;
-                slots
-            })
-        };
-
         let open_dir = |name| {
             openat(CWD, name, OFlags::DIRECTORY | OFlags::PATH, Mode::empty())
                 .map_io_err(|| format!("Failed to open directory: {name:?}"))
         };
         let direct_dir = open_dir(c"direct")?;
-        let metadata_dir = if xattr_unsupported {
-            Some(open_dir(c"metadata")?)
-        } else {
-            None
+        let alternates_dir = open_dir(c"alternates")?;
+        // Even when xattrs are supported, some entries may have fallen back to
+        // metadata files after failing to write their xattrs.
+        let metadata_dir = match openat(
+            CWD,
+            c"metadata",
+            OFlags::DIRECTORY | OFlags::PATH,
+            Mode::empty(),
+        ) {
+            Err(Errno::NOENT) => None,
+            r => Some(r.map_io_err(|| "Failed to open directory: \"metadata\"")?),
         };
 
         let rings = Rings([favorites_ring, main_ring]);
+        let slot_counts = validate_buckets(&rings, &buckets, bucket_lengths)?;
         let free_lists = FreeLists::load(&rings)?;
         let mut tmp_file_unsupported = false;
         let scratchpad = create_scratchpad(&mut tmp_file_unsupported)?;
 
+        let buckets = Buckets {
+            files: buckets.map(File::from),
+            slot_counts,
+            free_lists,
+        };
+        buckets.log_utilization();
+
+        let data = AllocatorData {
+            buckets,
+            direct_dir,
+            metadata_dir,
+            alternates_dir,
+            scratchpad,
+            tmp_file_unsupported,
+            xattr_unsupported,
+            xattr_fallback_logged: false,
+            detect_secrets: false,
+        };
+        let expirations = Expirations::load(&rings, &data)?;
+
         Ok(Self {
             rings,
-            data: AllocatorData {
-                buckets: Buckets {
-                    files: buckets.map(File::from),
-                    slot_counts,
-                    free_lists,
-                },
-                direct_dir,
-                metadata_dir,
-                scratchpad,
-                tmp_file_unsupported,
-            },
+            data,
+            expirations,
         })
     }
 
+    /// Grows the main ring so it can hold up to `max_entries` entries.
+    ///
+    /// Rings can't be shrunk without invalidating entry IDs, so smaller values
+    /// are ignored.
+    pub fn grow_main_ring(&mut self, max_entries: u32) -> Result<(), CliError> {
+        let WritableRing {
+            writer,
+            ring,
+            last_used,
+        } = &mut self.rings[RingKind::Main];
+        match max_entries.cmp(&ring.capacity()) {
+            Ordering::Less => {
+                warn!(
+                    "Ignoring --max-entries {max_entries}: the main ring already holds up to {} \
+                     entries and cannot be shrunk.",
+                    ring.capacity()
+                );
+                return Ok(());
+            }
+            Ordering::Equal => return Ok(()),
+            Ordering::Greater => {}
+        }
+
+        info!(
+            "Growing main ring from {} to {max_entries} entries.",
+            ring.capacity()
+        );
+        // Extend the last used times first so they cover every slot readers can see.
+        last_used.grow(RingKind::Main, max_entries)?;
+        ring.grow(&writer.ring, max_entries)?;
+        Ok(())
+    }
+
+    /// Sets whether added plain text should be scanned for secrets, tagging
+    /// any matches so frontends can hide them.
+    pub fn set_detect_secrets(&mut self, detect_secrets: bool) {
+        if detect_secrets != self.data.detect_secrets {
+            info!(
+                "{} secret detection.",
+                if detect_secrets {
+                    "Enabling"
+                } else {
+                    "Disabling"
+                }
+            );
+        }
+        self.data.detect_secrets = detect_secrets;
+    }
+
     pub fn add(
         &mut self,
         fd: OwnedFd,
         to: RingKind,
         mime_type: &MimeType,
+        sealed: bool,
+        echo: bool,
     ) -> Result<AddResponse, CliError> {
-        let id = self.add_internal(to, |head, data| data.alloc(fd, mime_type, to, head))?;
-        Ok(AddResponse::Success {
-            id: composite_id(to, id),
+        let mut size = 0;
+        let id = self.add_internal(to, |head, data| {
+            let (entry, stored_size) = data.alloc(fd, mime_type, to, head, sealed)?;
+            size = stored_size;
+            Ok(entry)
+        })?;
+        let id = composite_id(to, id);
+        Ok(if echo {
+            AddResponse::Stored {
+                id,
+                size,
+                // Plain text is stored without a mime type.
+                mime_type: if is_plaintext_mime(mime_type) {
+                    MimeType::new()
+                } else {
+                    *mime_type
+                },
+            }
+        } else {
+            AddResponse::Success { id }
         })
     }
 
@@ -376,7 +836,11 @@ impl Allocator {
         to: RingKind,
         alloc: impl FnOnce(u32, &mut AllocatorData) -> Result<Entry, CliError>,
     ) -> Result<u32, CliError> {
-        let WritableRing { writer, ring } = &mut self.rings[to];
+        let WritableRing {
+            writer,
+            ring,
+            last_used: _,
+        } = &mut self.rings[to];
         let head = ring.write_head();
 
         if let Some(entry) = ring.get(head) {
@@ -390,8 +854,14 @@ impl Allocator {
             }
         }
         let entry = alloc(head, &mut self.data)?;
-        let WritableRing { writer, ring } = &mut self.rings[to];
-
+        let WritableRing {
+            writer,
+            ring,
+            last_used,
+        } = &mut self.rings[to];
+
+        // Written before the entry so readers never see an entry without its time.
+        last_used.touch(head);
         writer
             .write(entry, head)
             .map_err(CliError::from)
@@ -437,7 +907,11 @@ impl Allocator {
             Ok(r) => r,
         };
         let to = to.unwrap_or(from);
-        let WritableRing { writer, ring } = &mut self.rings[from];
+        let WritableRing {
+            writer,
+            ring,
+            last_used: _,
+        } = &mut self.rings[from];
 
         if from == to && ring.next_head(from_id) == ring.write_head() {
             return Ok(MoveToFrontResponse::Success {
@@ -450,6 +924,7 @@ impl Allocator {
                    &mut AllocatorData {
                        ref direct_dir,
                        ref metadata_dir,
+                       ref alternates_dir,
                        ..
                    }: &mut AllocatorData| {
             debug!(
@@ -477,36 +952,49 @@ impl Allocator {
                         },
                     )?;
                     if let Some(metadata_dir) = metadata_dir {
-                        renameat(metadata_dir, from_file_name, metadata_dir, to_file_name)
-                            .map_io_err(|| {
-                                format!(
-                                    "Failed to rename metadata file from {from_file_name:?} to \
-                                     {to_file_name:?}."
-                                )
-                            })
-                            .map_err(CliError::from)
-                            .map_err(|e| {
-                                if let Err(e2) =
-                                    renameat(direct_dir, to_file_name, direct_dir, from_file_name)
-                                        .map_io_err(|| {
-                                            format!(
-                                                "Failed to undo renaming direct allocation file \
-                                                 from {to_file_name:?} to {from_file_name:?}."
-                                            )
-                                        })
-                                        .map_err(CliError::from)
-                                {
-                                    CliError::Multiple(vec![e, e2])
-                                } else {
-                                    e
-                                }
-                            })?;
+                        rename_optional(
+                            metadata_dir,
+                            from_file_name,
+                            to_file_name,
+                            RenameFlags::empty(),
+                        )
+                        .map_io_err(|| {
+                            format!(
+                                "Failed to rename metadata file from {from_file_name:?} to \
+                                 {to_file_name:?}."
+                            )
+                        })
+                        .map_err(CliError::from)
+                        .map_err(|e| {
+                            if let Err(e2) =
+                                renameat(direct_dir, to_file_name, direct_dir, from_file_name)
+                                    .map_io_err(|| {
+                                        format!(
+                                            "Failed to undo renaming direct allocation file from \
+                                             {to_file_name:?} to {from_file_name:?}."
+                                        )
+                                    })
+                                    .map_err(CliError::from)
+                            {
+                                CliError::Multiple(vec![e, e2])
+                            } else {
+                                e
+                            }
+                        })?;
                     }
+                    move_alternates(
+                        alternates_dir,
+                        from_file_name,
+                        to_file_name,
+                        RenameFlags::empty(),
+                    )?;
                 }
             }
             Ok(from_entry)
         };
         let to_id = self.add_internal(to, run)?;
+        self.expirations
+            .move_(composite_id(from, from_id), composite_id(to, to_id));
         Ok(MoveToFrontResponse::Success {
             id: composite_id(to, to_id),
         })
@@ -573,42 +1061,42 @@ impl Allocator {
                         )
                     })?;
                 if let Some(metadata_dir) = &self.data.metadata_dir {
-                    renameat_with(
-                        metadata_dir,
-                        from_file_name,
-                        metadata_dir,
-                        to_file_name,
-                        flags,
-                    )
-                    .map_io_err(|| {
-                        format!(
-                            "Failed to swap metadata files between {from_file_name:?} and \
-                             {to_file_name:?}."
-                        )
-                    })
-                    .map_err(CliError::from)
-                    .map_err(|e| {
-                        if let Err(e2) = renameat_with(
-                            direct_dir,
-                            to_file_name,
-                            direct_dir,
-                            from_file_name,
-                            flags,
-                        )
+                    rename_optional(metadata_dir, from_file_name, to_file_name, flags)
                         .map_io_err(|| {
                             format!(
-                                "Failed to undo swapping direct allocation files between \
-                                 {to_file_name:?} and {from_file_name:?}."
+                                "Failed to swap metadata files between {from_file_name:?} and \
+                                 {to_file_name:?}."
                             )
                         })
                         .map_err(CliError::from)
-                        {
-                            CliError::Multiple(vec![e, e2])
-                        } else {
-                            e
-                        }
-                    })?;
+                        .map_err(|e| {
+                            if let Err(e2) = renameat_with(
+                                direct_dir,
+                                to_file_name,
+                                direct_dir,
+                                from_file_name,
+                                flags,
+                            )
+                            .map_io_err(|| {
+                                format!(
+                                    "Failed to undo swapping direct allocation files between \
+                                     {to_file_name:?} and {from_file_name:?}."
+                                )
+                            })
+                            .map_err(CliError::from)
+                            {
+                                CliError::Multiple(vec![e, e2])
+                            } else {
+                                e
+                            }
+                        })?;
                 }
+                move_alternates(
+                    &self.data.alternates_dir,
+                    from_file_name,
+                    to_file_name,
+                    flags,
+                )?;
             }
             (Entry::Bucketed(_), Entry::Bucketed(_) | Entry::Uninitialized)
             | (Entry::Uninitialized, Entry::Bucketed(_)) => {
@@ -616,6 +1104,8 @@ impl Allocator {
             }
             (Entry::Uninitialized, Entry::Uninitialized) => unreachable!(),
         }
+        self.expirations
+            .swap(composite_id(ring1, id1), composite_id(ring2, id2));
 
         Ok(SwapResponse {
             error1: None,
@@ -641,16 +1131,204 @@ impl Allocator {
         Ok(RemoveResponse { error: None })
     }
 
+    pub fn set_label(&mut self, id: u64, label: &Label) -> Result<SetLabelResponse, CliError> {
+        let (ring, id, entry) = match self.get_entry(id) {
+            Err(e) => return Ok(SetLabelResponse { error: Some(e) }),
+            Ok((_, id, Entry::Uninitialized)) => {
+                return Ok(SetLabelResponse {
+                    error: Some(IdNotFoundError::Entry(id)),
+                });
+            }
+            Ok(r) => r,
+        };
+        debug!("Labeling entry {entry:?} in {ring:?} ring at position {id} with {label:?}.");
+
+        match entry {
+            Entry::Uninitialized => unreachable!(),
+            Entry::Bucketed(_) if label.is_empty() => {
+                // Bucketed entries never have labels, so there's nothing to clear.
+                return Ok(SetLabelResponse { error: None });
+            }
+            Entry::Bucketed(bucket) => {
+                // Labels live next to the mime type which only direct allocations have.
+                self.data.promote_to_direct(bucket, ring, id)?;
+                self.rings[ring].writer.write(Entry::File, id)?;
+            }
+            Entry::File => {}
+        }
+        self.data.write_label(label, ring, id)?;
+
+        Ok(SetLabelResponse { error: None })
+    }
+
+    pub fn set_expiry(
+        &mut self,
+        id: u64,
+        expires_at: Option<Timestamp>,
+    ) -> Result<SetExpiryResponse, CliError> {
+        let (ring, id, entry) = match self.get_entry(id) {
+            Err(e) => return Ok(SetExpiryResponse { error: Some(e) }),
+            Ok((_, id, Entry::Uninitialized)) => {
+                return Ok(SetExpiryResponse {
+                    error: Some(IdNotFoundError::Entry(id)),
+                });
+            }
+            Ok(r) => r,
+        };
+        debug!(
+            "Setting expiry of entry {entry:?} in {ring:?} ring at position {id} to \
+             {expires_at:?}."
+        );
+
+        match entry {
+            Entry::Uninitialized => unreachable!(),
+            Entry::Bucketed(_) if expires_at.is_none() => {
+                // Bucketed entries never expire, so there's nothing to clear.
+                return Ok(SetExpiryResponse { error: None });
+            }
+            Entry::Bucketed(bucket) => {
+                // Expiry times live next to the mime type which only direct allocations have.
+                self.data.promote_to_direct(bucket, ring, id)?;
+                self.rings[ring].writer.write(Entry::File, id)?;
+            }
+            Entry::File => {}
+        }
+        self.data.write_expiry(expires_at, ring, id)?;
+        self.expirations.set(composite_id(ring, id), expires_at);
+
+        Ok(SetExpiryResponse { error: None })
+    }
+
+    /// When the next sweep for expired entries is due.
+    pub const fn next_expiry(&self) -> Option<Timestamp> {
+        self.expirations.next
+    }
+
+    /// Removes every entry whose expiry time is at or before `now`, reporting
+    /// the IDs of the removed entries to `removed`.
+    ///
+    /// Entries that fail to be removed are logged and skipped so one broken
+    /// entry can't take down the server.
+    pub fn remove_expired(&mut self, now: Timestamp, mut removed: impl FnMut(u64)) {
+        debug!("Sweeping for entries that expired by {now}.");
+        for id in self.expirations.take_due(now) {
+            let Ok((kind, index)) = decompose_id(id) else {
+                continue;
+            };
+            match self.remove_if_expired(kind, index, now) {
+                Ok(true) => removed(id),
+                Ok(false) => {}
+                Err(e) => warn!(
+                    "Failed to remove expired entry in {kind:?} ring at position {index}.\nError: \
+                     {e:?}"
+                ),
+            }
+        }
+    }
+
+    fn remove_if_expired(
+        &mut self,
+        kind: RingKind,
+        id: u32,
+        now: Timestamp,
+    ) -> Result<bool, CliError> {
+        if self.rings[kind].ring.get(id) != Some(Entry::File) {
+            return Ok(false);
+        }
+        // The entry may have been replaced since its expiry time was recorded.
+        let expires_at = match self.data.read_expiry(kind, id)? {
+            None => return Ok(false),
+            Some(expires_at) if expires_at > now => {
+                self.expirations.insert(composite_id(kind, id), expires_at);
+                return Ok(false);
+            }
+            Some(expires_at) => expires_at,
+        };
+
+        info!("Removing entry in {kind:?} ring at position {id} which expired at {expires_at}.");
+        self.rings[kind].writer.write(Entry::Uninitialized, id)?;
+        self.data.free(Entry::File, kind, id)?;
+        Ok(true)
+    }
+
+    pub fn add_alternate(
+        &mut self,
+        fd: OwnedFd,
+        id: u64,
+        mime_type: &MimeType,
+    ) -> Result<AddAlternateResponse, CliError> {
+        let (ring, id, entry) = match self.get_entry(id) {
+            Err(e) => return Ok(AddAlternateResponse { error: Some(e) }),
+            Ok((_, id, Entry::Uninitialized)) => {
+                return Ok(AddAlternateResponse {
+                    error: Some(IdNotFoundError::Entry(id)),
+                });
+            }
+            Ok(r) => r,
+        };
+        debug!(
+            "Adding {mime_type:?} alternate to entry {entry:?} in {ring:?} ring at position {id}."
+        );
+
+        match entry {
+            Entry::Uninitialized => unreachable!(),
+            Entry::Bucketed(bucket) => {
+                // Alternates are stored next to the direct allocation's file name.
+                self.data.promote_to_direct(bucket, ring, id)?;
+                self.rings[ring].writer.write(Entry::File, id)?;
+            }
+            Entry::File => {}
+        }
+        self.data.write_alternate(fd, mime_type, ring, id)?;
+
+        Ok(AddAlternateResponse { error: None })
+    }
+
+    pub fn list(&self, from: RingKind, offset: u32) -> Result<ListResponse, CliError> {
+        let ring = &self.rings[from].ring;
+        let len = ring.len();
+        let head = min(ring.write_head(), len);
+
+        let mut response = ListResponse {
+            entries: [ListEntry::default(); LIST_PAGE_SIZE],
+            len: 0,
+            next_offset: None,
+        };
+        let mut position = offset;
+        while position < len && usize::from(response.len) < LIST_PAGE_SIZE {
+            // Walk backwards from the write head so the newest entries come first.
+            let id = (head + len - 1 - position) % len;
+            position += 1;
+
+            let (size, mime_type) = match ring.get(id) {
+                None | Some(Entry::Uninitialized) => continue,
+                Some(Entry::Bucketed(bucket)) => (u64::from(bucket.size()), MimeType::new_const()),
+                Some(Entry::File) => self.data.direct_metadata(from, id)?,
+            };
+            response.entries[usize::from(response.len)] = ListEntry {
+                id: composite_id(from, id),
+                ring: from,
+                size,
+                mime_type,
+            };
+            response.len += 1;
+        }
+        if position < len {
+            response.next_offset = Some(position);
+        }
+
+        Ok(response)
+    }
+
     pub fn gc(&mut self, max_wasted_bytes: u64) -> Result<GarbageCollectResponse, CliError> {
         self.gc_(max_wasted_bytes)
             .map(|bytes_freed| GarbageCollectResponse { bytes_freed })
     }
 
-    fn gc_(&mut self, max_wasted_bytes: u64) -> Result<u64, CliError> {
-        const MIN_BYTES_TO_FREE: u64 = 1 << 14;
-
-        let wasted_bucket_bytes = self
-            .data
+    /// The number of bytes held by free bucket slots, i.e. the space a GC could
+    /// reclaim.
+    pub fn wasted_bucket_bytes(&self) -> u64 {
+        self.data
             .buckets
             .free_lists
             .lists
@@ -658,7 +1336,13 @@ impl Allocator {
             .iter()
             .enumerate()
             .map(|(i, v)| u64::try_from(v.len()).unwrap() * u64::from(bucket_to_length(i)))
-            .sum::<u64>();
+            .sum()
+    }
+
+    fn gc_(&mut self, max_wasted_bytes: u64) -> Result<u64, CliError> {
+        const MIN_BYTES_TO_FREE: u64 = 1 << 14;
+
+        let wasted_bucket_bytes = self.wasted_bucket_bytes();
         debug!(
             "GC requested with {max_wasted_bytes} bytes of max wasted space; found \
              {wasted_bucket_bytes} wasted bytes."
@@ -751,7 +1435,11 @@ impl Allocator {
             }
 
             for kind in [RingKind::Favorites, RingKind::Main] {
-                let WritableRing { writer: _, ring } = &self.rings[kind];
+                let WritableRing {
+                    writer: _,
+                    ring,
+                    last_used: _,
+                } = &self.rings[kind];
                 for i in 0..ring.len() {
                     match ring.get(i) {
                         Some(Entry::Bucketed(entry)) => {
@@ -809,7 +1497,11 @@ impl Allocator {
                         break;
                     }
 
-                    let WritableRing { writer, ring } = &mut self.rings[rai.ring()];
+                    let WritableRing {
+                        writer,
+                        ring,
+                        last_used: _,
+                    } = &mut self.rings[rai.ring()];
                     let size = match ring.get(rai.index()) {
                         Some(Entry::Bucketed(entry)) => entry.size(),
                         _ => unreachable!(),
@@ -882,11 +1574,139 @@ impl Allocator {
             bytes_freed += u64::try_from(drop_count).unwrap() * u64::from(bucket_size);
         }
         info!("GC freed {bytes_freed} bytes.");
+        self.data.buckets.log_utilization();
         Ok(bytes_freed)
     }
 
+    /// Removes entries whose contents duplicate a newer entry, examining at
+    /// most `budget` ring positions before returning so callers can spread the
+    /// work out. Favorites are kept over main ring entries.
+    ///
+    /// Returns whether the scan is complete. The scan is only valid as long as
+    /// nothing else modifies the database in between calls.
+    pub fn remove_duplicates(
+        &mut self,
+        scan: &mut DuplicateScan,
+        budget: u32,
+        mut removed: impl FnMut(u64),
+    ) -> Result<bool, CliError> {
+        for _ in 0..budget {
+            let Some(&kind) = [RingKind::Favorites, RingKind::Main].get(scan.ring) else {
+                return Ok(true);
+            };
+            let ring = &self.rings[kind].ring;
+            if scan.scanned >= ring.len() {
+                scan.ring += 1;
+                scan.scanned = 0;
+                continue;
+            }
+            // Go from newest to oldest so the most recent copy is the one that's kept.
+            let id = (ring.write_head() + ring.len() - 1 - scan.scanned) % ring.len();
+            scan.scanned += 1;
+
+            let Some(entry) = ring.get(id) else {
+                continue;
+            };
+            let Some(data) = self.data.open_entry(entry, kind, id)? else {
+                continue;
+            };
+            let candidates = scan.hashes.entry(data.hash()?).or_default();
+
+            let mut duplicate = false;
+            for &original in &*candidates {
+                let (original_kind, original_id) = (original.ring(), original.index());
+                let Some(original_entry) = self.rings[original_kind].ring.get(original_id) else {
+                    continue;
+                };
+                let Some(original) =
+                    self.data
+                        .open_entry(original_entry, original_kind, original_id)?
+                else {
+                    continue;
+                };
+                if data
+                    .same_contents(&original)
+                    .map_io_err(|| "Failed to compare entries.")?
+                {
+                    duplicate = true;
+                    break;
+                }
+            }
+            if !duplicate {
+                candidates.push(RingAndIndex::new(kind, id));
+                continue;
+            }
+            drop(data);
+
+            debug!("Removing duplicate entry in {kind:?} ring at position {id}.");
+            self.rings[kind].writer.write(Entry::Uninitialized, id)?;
+            self.data.free(entry, kind, id)?;
+            removed(composite_id(kind, id));
+        }
+        Ok(false)
+    }
+
+    pub fn wipe(&mut self, from: Option<RingKind>) -> Result<WipeResponse, CliError> {
+        let kinds = if let Some(kind) = &from {
+            info!("Wiping {kind:?} ring.");
+            slice::from_ref(kind)
+        } else {
+            info!("Wiping database.");
+            &[RingKind::Favorites, RingKind::Main]
+        };
+
+        let mut entries_removed = 0;
+        for &kind in kinds {
+            let WritableRing {
+                writer,
+                ring,
+                last_used: _,
+            } = &mut self.rings[kind];
+            for i in 0..ring.len() {
+                match ring.get(i) {
+                    Some(Entry::File) => {
+                        self.data.free_direct(kind, i)?;
+                        entries_removed += 1;
+                    }
+                    // The other ring's entries still live in the buckets, so slots must be
+                    // returned one by one instead of truncating the bucket files below.
+                    Some(entry @ Entry::Bucketed(_)) if from.is_some() => {
+                        self.data.free(entry, kind, i)?;
+                        entries_removed += 1;
+                    }
+                    Some(Entry::Bucketed(_)) => entries_removed += 1,
+                    Some(Entry::Uninitialized) | None => (),
+                }
+            }
+
+            // Zero the entries in place rather than truncating the ring so clients that
+            // have the ring mapped don't fault on stale lengths.
+            writer.clear(ring.len())?;
+            writer.set_write_head(0)?;
+        }
+
+        if from.is_none() {
+            let Buckets {
+                files,
+                slot_counts,
+                free_lists,
+            } = &mut self.data.buckets;
+            for ((file, slot_count), free_slots) in
+                files.iter().zip(slot_counts).zip(&mut free_lists.lists.0)
+            {
+                ftruncate(file, 0).map_io_err(|| "Failed to truncate bucket file.")?;
+                *slot_count = 0;
+                free_slots.clear();
+            }
+        }
+
+        info!("Wiped {entries_removed} entries.");
+        Ok(WipeResponse { entries_removed })
+    }
+
     pub fn shutdown(self) -> Result<(), CliError> {
-        self.data.buckets.free_lists.save()
+        self.data.buckets.free_lists.save()?;
+        self.expirations.save()
     }
 }
 
@@ -897,25 +1717,77 @@ impl AllocatorData {
         mime_type: &MimeType,
         to: RingKind,
         id: u32,
-    ) -> Result<Entry, CliError> {
+        sealed: bool,
+    ) -> Result<(Entry, u64), CliError> {
         debug!("Allocating entry to {to:?} ring at position {id} with mime type {mime_type:?}.");
 
-        let size = io::copy(&mut File::from(data), &mut self.scratchpad)
-            .map_io_err(|| "Failed to copy data to receiver file.")?;
-        debug!("Received {size} bytes.");
+        let mut data = File::from(data);
+        let sealed_len = if sealed { sealed_len(&data)? } else { None };
+        let (sealed, size) = if let Some(size) = sealed_len {
+            debug!("Reading {size} bytes in place from sealed file.");
+            (Some(data), size)
+        } else {
+            if sealed {
+                warn!("Client sent an unsealed file for a sealed add, copying it instead.");
+            }
+            let size = io::copy(&mut data, &mut self.scratchpad)
+                .map_io_err(|| "Failed to copy data to receiver file.")?;
+            debug!("Received {size} bytes.");
+            (None, size)
+        };
 
-        if is_plaintext_mime(mime_type) {
-            if size > 0 && size < 4096 {
-                self.alloc_bucket(u16::try_from(size).unwrap())
+        let entry = if is_plaintext_mime(mime_type) {
+            if self.detect_secrets && is_secret(sealed.as_ref().unwrap_or(&self.scratchpad), size)?
+            {
+                info!("Tagging entry in {to:?} ring at position {id} as a secret.");
+                // Only direct allocations have metadata to store the tag in.
+                self.copy_sealed_to_scratchpad(sealed.as_ref(), size)?;
+                let entry = self.alloc_direct(size, &MimeType::new_const(), to, id)?;
+                self.write_metadata(c"user.secret", 3, b"1", to, id)?;
+                entry
+            } else if size > 0 && size < 4096 {
+                self.alloc_bucket(u16::try_from(size).unwrap(), sealed.as_ref())?
             } else {
-                self.alloc_direct(size, &MimeType::new_const(), to, id)
+                self.copy_sealed_to_scratchpad(sealed.as_ref(), size)?;
+                self.alloc_direct(size, &MimeType::new_const(), to, id)?
             }
         } else {
-            self.alloc_direct(size, mime_type, to, id)
+            self.copy_sealed_to_scratchpad(sealed.as_ref(), size)?;
+            self.alloc_direct(size, mime_type, to, id)?
+        };
+        Ok((entry, size))
+    }
+
+    /// Direct allocations take ownership of the scratchpad, so sealed data
+    /// can't be used in place.
+    ///
+    /// This is the only copy a direct allocation makes: the scratchpad is an
+    /// unnamed file in the database directory which gets linked into place,
+    /// whereas sealed memfds live on the kernel's internal shmem mount and can
+    /// never be linked into another filesystem.
+    fn copy_sealed_to_scratchpad(
+        &mut self,
+        sealed: Option<&File>,
+        size: u64,
+    ) -> Result<(), CliError> {
+        let Some(sealed) = sealed else {
+            return Ok(());
+        };
+        let len = usize::try_from(size).unwrap();
+        let mut offset = 0;
+        let mut copied = 0;
+        while copied < len {
+            match sendfile(&self.scratchpad, sealed, Some(&mut offset), len - copied)
+                .map_io_err(|| "Failed to copy sealed data to scratchpad file.")?
+            {
+                0 => break,
+                n => copied += n,
+            }
         }
+        Ok(())
     }
 
-    fn alloc_bucket(&mut self, size: u16) -> Result<Entry, CliError> {
+    fn alloc_bucket(&mut self, size: u16, sealed: Option<&File>) -> Result<Entry, CliError> {
         debug!("Allocating {size} byte bucket slot.");
         let bucket = usize::from(size_to_bucket(size));
         let Buckets {
@@ -938,17 +1810,30 @@ impl AllocatorData {
             }
 
             let mut offset = u64::from(bucket_index) * u64::from(bucket_len);
-            copy_file_range_all(
-                &self.scratchpad,
-                Some(&mut 0),
-                &files[bucket],
-                Some(&mut offset),
-                usize::from(size),
-            )
-            .map_io_err(|| format!("Failed to copy data to bucket {bucket}."))?;
-            self.scratchpad
-                .seek(SeekFrom::Start(0))
-                .map_io_err(|| "Failed to reset scratchpad file offset.")?;
+            if let Some(sealed) = sealed {
+                // The kernel refuses to copy_file_range out of a memfd into another
+                // filesystem, but bucketed entries are small enough to bounce.
+                let mut buf = [0; 4096];
+                let buf = &mut buf[..usize::from(size)];
+                sealed
+                    .read_exact_at(buf, 0)
+                    .map_io_err(|| "Failed to read sealed data.")?;
+                files[bucket]
+                    .write_all_at(buf, offset)
+                    .map_io_err(|| format!("Failed to write data to bucket {bucket}."))?;
+            } else {
+                copy_file_range_all(
+                    &self.scratchpad,
+                    Some(&mut 0),
+                    &files[bucket],
+                    Some(&mut offset),
+                    usize::from(size),
+                )
+                .map_io_err(|| format!("Failed to copy data to bucket {bucket}."))?;
+                self.scratchpad
+                    .seek(SeekFrom::Start(0))
+                    .map_io_err(|| "Failed to reset scratchpad file offset.")?;
+            }
             if size < bucket_len {
                 files[bucket]
                     .write_all_at(
@@ -989,7 +1874,27 @@ impl AllocatorData {
         let mut file_name = [MaybeUninit::uninit(); 14];
         let file_name = direct_file_name(&mut file_name, to, id);
 
-        if let Some(metadata_dir) = &self.metadata_dir {
+        let in_metadata_file = if self.xattr_unsupported {
+            true
+        } else if mime_type.is_empty() {
+            false
+        } else {
+            match fsetxattr(
+                &data,
+                c"user.mime_type",
+                mime_type.as_bytes(),
+                XattrFlags::CREATE,
+            ) {
+                Ok(()) => false,
+                Err(e @ (Errno::NOSPC | Errno::DQUOT | Errno::NOTSUP)) => {
+                    self.fall_back_to_metadata_files(e)?;
+                    true
+                }
+                Err(e) => Err(e).map_io_err(|| "Failed to create mime type attribute.")?,
+            }
+        };
+        if in_metadata_file {
+            let metadata_dir = self.metadata_dir.as_ref().unwrap();
             let mut metadata = File::from(
                 openat(
                     metadata_dir,
@@ -1004,21 +1909,26 @@ impl AllocatorData {
                     format!("Failed to write to direct metadata file: {file_name:?}")
                 })?;
             }
-        } else if !mime_type.is_empty() {
-            fsetxattr(
-                &data,
-                c"user.mime_type",
-                mime_type.as_bytes(),
-                XattrFlags::CREATE,
-            )
-            .map_io_err(|| "Failed to create mime type attribute.")?;
         }
+        // Compressed files are tagged with an xattr, so there's nowhere to record
+        // compression for entries whose metadata lives in files.
+        #[cfg(feature = "compression")]
+        let data = if !in_metadata_file
+            && size >= ringboard_core::COMPRESSION_THRESHOLD
+            && (mime_type.is_empty() || mime_type.starts_with("text/"))
+            && let Some(compressed) = self.compress_direct(&data, size, &mime_type)?
+        {
+            compressed
+        } else {
+            data
+        };
 
         link_tmp_file(data, &self.direct_dir, file_name)
             .map_io_err(|| format!("Failed to materialize direct allocation: {file_name:?}"))
             .map_err(CliError::from)
             .map_err(|e| {
-                if let Some(metadata_dir) = &self.metadata_dir
+                if in_metadata_file
+                    && let Some(metadata_dir) = &self.metadata_dir
                     && let Err(e2) = unlinkat(metadata_dir, file_name, AtFlags::empty())
                         .map_io_err(|| {
                             format!(
@@ -1037,6 +1947,413 @@ impl AllocatorData {
         Ok(Entry::File)
     }
 
+    /// Creates the metadata directory for entries whose xattrs couldn't be
+    /// written even though the filesystem supports them, e.g. because the
+    /// xattr quota ran out.
+    fn fall_back_to_metadata_files(&mut self, error: Errno) -> Result<(), CliError> {
+        if !self.xattr_fallback_logged {
+            warn!(
+                "Failed to write extended attributes, storing metadata in files instead: {error}"
+            );
+            self.xattr_fallback_logged = true;
+        }
+        if self.metadata_dir.is_some() {
+            return Ok(());
+        }
+
+        match mkdir(c"metadata", Mode::RWXU) {
+            Err(Errno::EXIST) => Ok(()),
+            r => r,
+        }
+        .map_io_err(|| "Failed to create directory: \"metadata\"")?;
+        self.metadata_dir = Some(
+            openat(
+                CWD,
+                c"metadata",
+                OFlags::DIRECTORY | OFlags::PATH,
+                Mode::empty(),
+            )
+            .map_io_err(|| "Failed to open directory: \"metadata\"")?,
+        );
+        Ok(())
+    }
+
+    /// Opens the entry's metadata file if it has one, in which case all of its
+    /// metadata is stored there rather than in xattrs.
+    fn open_metadata(&self, file_name: &CStr) -> Result<Option<File>, CliError> {
+        let Some(metadata_dir) = &self.metadata_dir else {
+            return Ok(None);
+        };
+        match openat(metadata_dir, file_name, OFlags::RDONLY, Mode::empty()) {
+            Err(Errno::NOENT) => Ok(None),
+            r => Ok(Some(File::from(r.map_io_err(|| {
+                format!("Failed to open metadata file: {file_name:?}")
+            })?))),
+        }
+    }
+
+    fn promote_to_direct(
+        &mut self,
+        entry: InitializedEntry,
+        to: RingKind,
+        id: u32,
+    ) -> Result<(), CliError> {
+        let bucket = usize::from(size_to_bucket(entry.size()));
+        debug!(
+            "Promoting bucket {bucket} slot {} to direct allocation.",
+            entry.index()
+        );
+
+        let mut offset = u64::from(entry.index()) * u64::from(bucket_to_length(bucket));
+        copy_file_range_all(
+            &self.buckets.files[bucket],
+            Some(&mut offset),
+            &self.scratchpad,
+            Some(&mut 0),
+            usize::from(entry.size()),
+        )
+        .map_io_err(|| format!("Failed to copy data out of bucket {bucket}."))?;
+        self.alloc_direct(u64::from(entry.size()), &MimeType::new_const(), to, id)?;
+        self.buckets.free_lists.free(bucket, entry.index());
+
+        Ok(())
+    }
+
+    fn direct_metadata(&self, to: RingKind, id: u32) -> Result<(u64, MimeType), CliError> {
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        let file_name = direct_file_name(&mut file_name, to, id);
+
+        let file = openat(&self.direct_dir, file_name, OFlags::RDONLY, Mode::empty())
+            .map_io_err(|| format!("Failed to open direct allocation: {file_name:?}"))?;
+        let size = fstat(&file)
+            .map_io_err(|| format!("Failed to stat direct allocation: {file_name:?}"))?
+            .st_size;
+        let size = u64::try_from(size).unwrap();
+        #[cfg(feature = "compression")]
+        let size = if ringboard_core::is_compressed(&file)
+            .map_io_err(|| format!("Failed to read compression attribute: {file_name:?}"))?
+        {
+            // ZSTD_FRAMEHEADERSIZE_MAX
+            let mut header = [0; 18];
+            let len = rustix::io::pread(&file, &mut header, 0)
+                .map_io_err(|| format!("Failed to read direct allocation: {file_name:?}"))?;
+            zstd::zstd_safe::get_frame_content_size(&header[..len])
+                .ok()
+                .flatten()
+                .unwrap_or(size)
+        } else {
+            size
+        };
+
+        let mut mime_type = [0; MimeType::new_const().capacity()];
+        let len = if let Some(mut metadata) = self.open_metadata(file_name)? {
+            metadata
+                .read(&mut mime_type)
+                .map_io_err(|| format!("Failed to read metadata file: {file_name:?}"))?
+        } else {
+            match fgetxattr(&file, c"user.mime_type", &mut mime_type) {
+                Err(Errno::NODATA | Errno::NOTSUP) => 0,
+                r => {
+                    r.map_io_err(|| format!("Failed to read mime type attribute: {file_name:?}"))?
+                }
+            }
+        };
+        // Metadata files store the label after the mime type, separated by a NUL byte.
+        let mime_type = mime_type[..len].split(|&b| b == 0).next().unwrap();
+
+        Ok((
+            size,
+            str::from_utf8(mime_type)
+                .ok()
+                .and_then(|mime_type| MimeType::from(mime_type).ok())
+                .unwrap_or_default(),
+        ))
+    }
+
+    fn write_label(&self, label: &Label, to: RingKind, id: u32) -> Result<(), CliError> {
+        self.write_metadata(c"user.label", 1, label.as_bytes(), to, id)
+    }
+
+    fn write_expiry(
+        &self,
+        expires_at: Option<Timestamp>,
+        to: RingKind,
+        id: u32,
+    ) -> Result<(), CliError> {
+        let expires_at = expires_at.map(|t| t.to_string()).unwrap_or_default();
+        self.write_metadata(c"user.expires_at", 2, expires_at.as_bytes(), to, id)
+    }
+
+    /// Sets the attribute `name` of a direct allocation, or removes it if
+    /// `value` is empty. Without xattrs, the value is stored as the `field`th
+    /// NUL separated field of the metadata file.
+    fn write_metadata(
+        &self,
+        name: &CStr,
+        field: usize,
+        value: &[u8],
+        to: RingKind,
+        id: u32,
+    ) -> Result<(), CliError> {
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        let file_name = direct_file_name(&mut file_name, to, id);
+
+        let metadata = self.open_metadata(file_name)?;
+        if metadata.is_none() && !self.xattr_unsupported {
+            let file = openat(&self.direct_dir, file_name, OFlags::RDONLY, Mode::empty())
+                .map_io_err(|| format!("Failed to open direct allocation: {file_name:?}"))?;
+            if value.is_empty() {
+                match fremovexattr(&file, name) {
+                    Err(Errno::NODATA) => Ok(()),
+                    r => r,
+                }
+            } else {
+                fsetxattr(&file, name, value, XattrFlags::empty())
+            }
+            .map_io_err(|| format!("Failed to write {name:?} attribute: {file_name:?}"))?;
+            return Ok(());
+        }
+        let metadata_dir = self.metadata_dir.as_ref().unwrap();
+
+        // Metadata files are read-only and formatted as
+        // `{mime_type}\0{label}\0{expires_at}\0{secret}`, so build a replacement and swap it
+        // in.
+        let metadata = match metadata {
+            None => Vec::new(),
+            Some(mut file) => {
+                let mut metadata = Vec::new();
+                file.read_to_end(&mut metadata)
+                    .map_io_err(|| format!("Failed to read metadata file: {file_name:?}"))?;
+                metadata
+            }
+        };
+        let mut fields = metadata.split(|&b| b == 0).collect::<ArrayVec<_, 4>>();
+        while fields.len() <= field {
+            fields.push(&[]);
+        }
+        fields[field] = value;
+        while fields.len() > 1 && fields.last().is_some_and(|f| f.is_empty()) {
+            fields.pop();
+        }
+        let metadata = fields.join(&0);
+
+        let tmp_name = c".metadata";
+        create_tmp_replacement(metadata_dir, tmp_name)?
+            .write_all(&metadata)
+            .map_io_err(|| "Failed to write replacement metadata file.")?;
+        renameat(metadata_dir, tmp_name, metadata_dir, file_name)
+            .map_io_err(|| format!("Failed to replace metadata file: {file_name:?}"))?;
+
+        Ok(())
+    }
+
+    fn read_expiry(&self, to: RingKind, id: u32) -> Result<Option<Timestamp>, CliError> {
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        let file_name = direct_file_name(&mut file_name, to, id);
+
+        let mut metadata = Vec::new();
+        let expires_at = if let Some(mut file) = self.open_metadata(file_name)? {
+            file.read_to_end(&mut metadata)
+                .map_io_err(|| format!("Failed to read metadata file: {file_name:?}"))?;
+            match metadata.split(|&b| b == 0).nth(2) {
+                None => return Ok(None),
+                Some(expires_at) => expires_at,
+            }
+        } else {
+            let file = openat(&self.direct_dir, file_name, OFlags::RDONLY, Mode::empty())
+                .map_io_err(|| format!("Failed to open direct allocation: {file_name:?}"))?;
+            metadata.resize(u64::MAX.to_string().len(), 0);
+            let len = match fgetxattr(&file, c"user.expires_at", &mut metadata) {
+                Err(Errno::NODATA | Errno::NOTSUP) => return Ok(None),
+                r => r.map_io_err(|| format!("Failed to read expiry attribute: {file_name:?}"))?,
+            };
+            &metadata[..len]
+        };
+
+        if expires_at.is_empty() {
+            // Cleared expiry times are stored as empty fields.
+            return Ok(None);
+        }
+        let expires_at = str::from_utf8(expires_at).ok().and_then(|t| t.parse().ok());
+        if expires_at.is_none() {
+            warn!("Ignoring invalid expiry time of direct allocation {file_name:?}.");
+        }
+        Ok(expires_at)
+    }
+
+    fn write_alternate(
+        &self,
+        data: OwnedFd,
+        mime_type: &MimeType,
+        to: RingKind,
+        id: u32,
+    ) -> Result<(), CliError> {
+        let mut file_name = [MaybeUninit::uninit(); 14];
+        let file_name = direct_file_name(&mut file_name, to, id);
+
+        match mkdirat(&self.alternates_dir, file_name, Mode::RWXU) {
+            Err(Errno::EXIST) => {}
+            r => {
+                r.map_io_err(|| format!("Failed to create alternates directory: {file_name:?}"))?;
+            }
+        }
+        let dir = openat(
+            &self.alternates_dir,
+            file_name,
+            OFlags::DIRECTORY | OFlags::PATH,
+            Mode::empty(),
+        )
+        .map_io_err(|| format!("Failed to open alternates directory: {file_name:?}"))?;
+
+        // Write to a temporary file first so readers never see a partial alternate.
+        let tmp_name = c".alternate";
+        io::copy(
+            &mut File::from(data),
+            &mut create_tmp_replacement(&dir, tmp_name)?,
+        )
+        .map_io_err(|| "Failed to write alternate file.")?;
+
+        let alternate_name = alternate_file_name(mime_type);
+        renameat(&dir, tmp_name, &dir, &*alternate_name).map_io_err(|| {
+            format!("Failed to move alternate into place: {file_name:?}/{alternate_name}")
+        })?;
+
+        Ok(())
+    }
+
+    fn free_alternates(&self, file_name: &CStr) -> Result<(), CliError> {
+        let dir = match openat(
+            &self.alternates_dir,
+            file_name,
+            OFlags::RDONLY | OFlags::DIRECTORY,
+            Mode::empty(),
+        ) {
+            Err(Errno::NOENT) => return Ok(()),
+            r => r.map_io_err(|| format!("Failed to open alternates directory: {file_name:?}"))?,
+        };
+
+        let mut buf = [MaybeUninit::uninit(); 1024];
+        let mut iter = RawDir::new(&dir, &mut buf);
+        while let Some(alternate) = iter.next() {
+            let alternate = alternate
+                .map_io_err(|| format!("Failed to read alternates directory: {file_name:?}"))?;
+            let alternate = alternate.file_name();
+            if alternate == c"." || alternate == c".." {
+                continue;
+            }
+
+            unlinkat(&dir, alternate, AtFlags::empty()).map_io_err(|| {
+                format!("Failed to remove alternate file: {file_name:?}/{alternate:?}")
+            })?;
+        }
+        unlinkat(&self.alternates_dir, file_name, AtFlags::REMOVEDIR)
+            .map_io_err(|| format!("Failed to remove alternates directory: {file_name:?}"))?;
+
+        Ok(())
+    }
+
+    /// Returns a compressed copy of the entry with its xattrs in place, or
+    /// `None` if the entry should be stored as is.
+    ///
+    /// The frame records the entry's size so listings can report it without
+    /// decompressing.
+    #[cfg(feature = "compression")]
+    fn compress_direct(
+        &mut self,
+        data: &File,
+        size: u64,
+        mime_type: &MimeType,
+    ) -> Result<Option<File>, CliError> {
+        let compressed = create_scratchpad(&mut self.tmp_file_unsupported)?;
+        let mut data = data;
+        data.seek(SeekFrom::Start(0))
+            .map_io_err(|| "Failed to reset scratchpad file offset.")?;
+        let mut encoder = zstd::Encoder::new(&compressed, 0)
+            .and_then(|mut encoder| {
+                encoder.set_pledged_src_size(Some(size))?;
+                Ok(encoder)
+            })
+            .map_io_err(|| "Failed to create compressor.")?;
+        io::copy(&mut data.take(size), &mut encoder)
+            .and_then(|_| encoder.finish())
+            .map_io_err(|| "Failed to compress scratchpad file.")?;
+        let compressed_size = fstat(&compressed)
+            .map_io_err(|| "Failed to get compressed file size.")?
+            .st_size;
+        let compressed_size = u64::try_from(compressed_size).unwrap();
+
+        if compressed_size >= size {
+            debug!("Compression did not shrink the {size} byte entry, storing it as is.");
+            return Ok(None);
+        }
+
+        let xattrs = if mime_type.is_empty() {
+            Ok(())
+        } else {
+            fsetxattr(
+                &compressed,
+                c"user.mime_type",
+                mime_type.as_bytes(),
+                XattrFlags::CREATE,
+            )
+        }
+        .and_then(|()| {
+            fsetxattr(
+                &compressed,
+                c"user.compression",
+                b"zstd",
+                XattrFlags::CREATE,
+            )
+        });
+        match xattrs {
+            Ok(()) => {}
+            Err(e @ (Errno::NOSPC | Errno::DQUOT | Errno::NOTSUP)) => {
+                debug!("Failed to tag compressed entry, storing it as is: {e}");
+                return Ok(None);
+            }
+            Err(e) => Err(e).map_io_err(|| "Failed to create compression attribute.")?,
+        }
+
+        debug!("Compressed {size} byte entry to {compressed_size} bytes.");
+        Ok(Some(compressed))
+    }
+
+    fn open_entry(
+        &self,
+        entry: Entry,
+        to: RingKind,
+        id: u32,
+    ) -> Result<Option<EntryData<'_>>, CliError> {
+        match entry {
+            Entry::Uninitialized => Ok(None),
+            Entry::Bucketed(entry) => {
+                let bucket = usize::from(size_to_bucket(entry.size()));
+                Ok(Some(EntryData::Bucket {
+                    file: &self.buckets.files[bucket],
+                    offset: u64::from(entry.index()) * u64::from(bucket_to_length(bucket)),
+                    len: entry.size().into(),
+                }))
+            }
+            Entry::File => {
+                let mut file_name = [MaybeUninit::uninit(); 14];
+                let file_name = direct_file_name(&mut file_name, to, id);
+
+                let file = File::from(
+                    openat(&self.direct_dir, file_name, OFlags::RDONLY, Mode::empty()).map_io_err(
+                        || format!("Failed to open direct allocation: {file_name:?}"),
+                    )?,
+                );
+                let len = fstat(&file)
+                    .map_io_err(|| format!("Failed to stat direct allocation: {file_name:?}"))?
+                    .st_size;
+                Ok(Some(EntryData::Direct {
+                    file,
+                    len: u64::try_from(len).unwrap(),
+                }))
+            }
+        }
+    }
+
     fn free(&mut self, entry: Entry, to: RingKind, id: u32) -> Result<(), CliError> {
         debug!("Freeing entry in {to:?} ring at position {id}: {entry:?}");
         match entry {
@@ -1060,10 +2377,72 @@ impl AllocatorData {
         unlinkat(&self.direct_dir, file_name, AtFlags::empty())
             .map_io_err(|| format!("Failed to remove direct allocation file: {file_name:?}"))?;
         if let Some(metadata_dir) = &self.metadata_dir {
-            unlinkat(metadata_dir, file_name, AtFlags::empty())
-                .map_io_err(|| format!("Failed to remove metadata file: {file_name:?}"))?;
+            match unlinkat(metadata_dir, file_name, AtFlags::empty()) {
+                Err(Errno::NOENT) => Ok(()),
+                r => r,
+            }
+            .map_io_err(|| format!("Failed to remove metadata file: {file_name:?}"))?;
         }
+        self.free_alternates(file_name)?;
 
         Ok(())
     }
 }
+
+/// Alternates live in a directory named after their entry's direct allocation,
+/// so they must follow the allocation whenever it is renamed.
+fn move_alternates(
+    alternates_dir: &OwnedFd,
+    from: &CStr,
+    to: &CStr,
+    flags: RenameFlags,
+) -> Result<(), CliError> {
+    rename_optional(alternates_dir, from, to, flags)
+        .map_io_err(|| format!("Failed to move alternates from {from:?} to {to:?}."))
+        .map_err(CliError::from)
+}
+
+/// Creates a read-only file named `tmp_name` in `dir` to be renamed over the
+/// file it replaces.
+///
+/// A temporary file left behind by a failed write is removed first since it
+/// can't be reopened for writing.
+fn create_tmp_replacement(dir: impl AsFd, tmp_name: &CStr) -> Result<File, CliError> {
+    match unlinkat(&dir, tmp_name, AtFlags::empty()) {
+        Err(Errno::NOENT) => Ok(()),
+        r => r,
+    }
+    .map_io_err(|| format!("Failed to remove stale temporary file: {tmp_name:?}"))?;
+    let file = openat(
+        &dir,
+        tmp_name,
+        OFlags::CREATE | OFlags::EXCL | OFlags::WRONLY,
+        Mode::RUSR,
+    )
+    .map_io_err(|| format!("Failed to create temporary file: {tmp_name:?}"))?;
+    Ok(File::from(file))
+}
+
+/// Renames `from` to `to` within `dir` like [`renameat_with`], except that
+/// either file is allowed to be missing.
+fn rename_optional(
+    dir: &OwnedFd,
+    from: &CStr,
+    to: &CStr,
+    flags: RenameFlags,
+) -> rustix::io::Result<()> {
+    let result = match renameat_with(dir, from, dir, to, flags) {
+        Err(Errno::NOENT) if flags.contains(RenameFlags::EXCHANGE) => {
+            // At most one of the files exists.
+            match renameat(dir, from, dir, to) {
+                Err(Errno::NOENT) => renameat(dir, to, dir, from),
+                r => r,
+            }
+        }
+        r => r,
+    };
+    match result {
+        Err(Errno::NOENT) => Ok(()),
+        r => r,
+    }
+}
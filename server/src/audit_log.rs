@@ -0,0 +1,108 @@
+use std::{
+    fmt::{Display, Formatter},
+    fs::File,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use ringboard_core::{
+    IoErr, Timestamp,
+    protocol::{Label, RingKind},
+};
+
+use crate::CliError;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Mutation {
+    Add {
+        id: u64,
+    },
+    MoveToFront {
+        from: u64,
+        to: u64,
+    },
+    Swap {
+        id1: u64,
+        id2: u64,
+    },
+    Remove {
+        id: u64,
+    },
+    Expire {
+        id: u64,
+    },
+    Wipe {
+        from: Option<RingKind>,
+    },
+    SetLabel {
+        id: u64,
+        label: Label,
+    },
+    SetExpiry {
+        id: u64,
+        expires_at: Option<Timestamp>,
+    },
+}
+
+impl Display for Mutation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Add { id } => write!(f, "add {id}"),
+            Self::MoveToFront { from, to } => write!(f, "move {from} {to}"),
+            Self::Swap { id1, id2 } => write!(f, "swap {id1} {id2}"),
+            Self::Remove { id } => write!(f, "remove {id}"),
+            Self::Expire { id } => write!(f, "expire {id}"),
+            Self::Wipe { from: None } => write!(f, "wipe all"),
+            Self::Wipe {
+                from: Some(RingKind::Main),
+            } => write!(f, "wipe main"),
+            Self::Wipe {
+                from: Some(RingKind::Favorites),
+            } => write!(f, "wipe favorites"),
+            // Quoted so labels with whitespace can't break the one line per mutation format.
+            Self::SetLabel { id, ref label } => write!(f, "label {id} {:?}", label.as_str()),
+            Self::SetExpiry {
+                id,
+                expires_at: None,
+            } => write!(f, "persist {id}"),
+            Self::SetExpiry {
+                id,
+                expires_at: Some(expires_at),
+            } => write!(f, "expire-at {id} {expires_at}"),
+        }
+    }
+}
+
+/// An append-only text log with one `<unix millis> <mutation>` line per
+/// successful change to the database.
+#[derive(Debug)]
+pub struct AuditLog {
+    file: File,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self, CliError> {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_io_err(|| format!("Failed to open audit log: {path:?}"))?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, mutation: Mutation) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // Format up front so each entry is appended with a single write.
+        if let Err(e) = self
+            .file
+            .write_all(format!("{timestamp} {mutation}\n").as_bytes())
+        {
+            warn!("Failed to write to audit log: {e}");
+        }
+    }
+}
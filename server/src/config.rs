@@ -0,0 +1,263 @@
+use std::{
+    ffi::OsString,
+    fs,
+    iter::once,
+    path,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+use ringboard_core::{IoErr, abstract_socket_name, ring};
+
+use crate::{
+    CliError, allocator::Allocator, audit_log::AuditLog, idle_gc::IdleGc,
+    rate_limit::AddRateLimiter,
+};
+
+#[derive(Default, Clone, Debug)]
+pub struct Settings {
+    pub data_dir: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    pub audit_log: Option<PathBuf>,
+    pub add_rate_limit: Option<u32>,
+    pub max_entries: Option<u32>,
+    pub detect_secrets: bool,
+    pub idle_gc: bool,
+}
+
+/// Applies `--flag value` style arguments to `settings`, returning the config
+/// file if one was named.
+///
+/// Paths are made absolute since the server changes its working directory to
+/// the database. Relative paths are resolved against `relative_to` or the
+/// current directory if it is `None`.
+pub fn parse_args(
+    args: impl IntoIterator<Item = OsString>,
+    relative_to: Option<&Path>,
+    settings: &mut Settings,
+) -> Result<Option<PathBuf>, CliError> {
+    let mut config_file = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--add-rate-limit" {
+            let Some(limit) = args
+                .next()
+                .and_then(|value| value.to_str()?.parse().ok())
+                .filter(|&limit| limit > 0)
+            else {
+                return Err(CliError::InvalidArgs {
+                    context: "--add-rate-limit expects a positive number of adds per second".into(),
+                });
+            };
+            if settings.add_rate_limit.replace(limit).is_some() {
+                return Err(CliError::InvalidArgs {
+                    context: "Duplicate argument \"--add-rate-limit\"".into(),
+                });
+            }
+            continue;
+        }
+        if arg == "--max-entries" {
+            let Some(max_entries) = args
+                .next()
+                .and_then(|value| value.to_str()?.parse().ok())
+                .filter(|&max_entries| max_entries > 0 && max_entries <= ring::MAX_ENTRIES)
+            else {
+                return Err(CliError::InvalidArgs {
+                    context: format!(
+                        "--max-entries expects a number of entries between 1 and {}",
+                        ring::MAX_ENTRIES
+                    )
+                    .into(),
+                });
+            };
+            if settings.max_entries.replace(max_entries).is_some() {
+                return Err(CliError::InvalidArgs {
+                    context: "Duplicate argument \"--max-entries\"".into(),
+                });
+            }
+            continue;
+        }
+        if arg == "--detect-secrets" {
+            if settings.detect_secrets {
+                return Err(CliError::InvalidArgs {
+                    context: "Duplicate argument \"--detect-secrets\"".into(),
+                });
+            }
+            settings.detect_secrets = true;
+            continue;
+        }
+        if arg == "--idle-gc" {
+            if settings.idle_gc {
+                return Err(CliError::InvalidArgs {
+                    context: "Duplicate argument \"--idle-gc\"".into(),
+                });
+            }
+            settings.idle_gc = true;
+            continue;
+        }
+        // The server always runs in the foreground so that service managers
+        // track the right process: accept the flag for explicit unit files.
+        if arg == "--foreground" {
+            continue;
+        }
+        if arg == "--daemon" || arg == "--daemonize" {
+            return Err(CliError::InvalidArgs {
+                context: "The server never daemonizes: run it in the background through a service \
+                          manager such as systemd instead"
+                    .into(),
+            });
+        }
+
+        let slot = match arg.to_str() {
+            Some("--data-dir") => &mut settings.data_dir,
+            Some("--socket") => &mut settings.socket,
+            Some("--audit-log") => &mut settings.audit_log,
+            Some("--config") => &mut config_file,
+            _ => {
+                return Err(CliError::InvalidArgs {
+                    context: format!("Unknown argument: {arg:?}").into(),
+                });
+            }
+        };
+        let Some(value) = args.next() else {
+            return Err(CliError::InvalidArgs {
+                context: format!("Missing value for {arg:?}").into(),
+            });
+        };
+        let value = PathBuf::from(value);
+        let value = if arg == "--socket" && abstract_socket_name(&value).is_some() {
+            value
+        } else if let Some(dir) = relative_to {
+            dir.join(value)
+        } else {
+            path::absolute(&value).map_io_err(|| format!("Invalid path for {arg:?}: {value:?}"))?
+        };
+        if let Some(path) = slot.replace(value) {
+            return Err(CliError::InvalidArgs {
+                context: format!("Duplicate argument {arg:?}: {path:?}").into(),
+            });
+        }
+    }
+    Ok(config_file)
+}
+
+/// Layers the arguments in a config file on top of `settings`.
+///
+/// Config files contain one argument and its value per line, written exactly
+/// as they would be on the command line. Blank lines and lines starting with
+/// `#` are ignored. Relative paths are resolved against the config file's
+/// directory.
+pub fn read_config(path: &Path, mut settings: Settings) -> Result<Settings, CliError> {
+    let config =
+        fs::read_to_string(path).map_io_err(|| format!("Failed to read config file: {path:?}"))?;
+    let args = config
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .flat_map(|line| {
+            let (arg, value) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, None), |(arg, value)| (arg, Some(value.trim_start())));
+            once(arg).chain(value).map(OsString::from)
+        });
+    if parse_args(args, path.parent(), &mut settings)?.is_some() {
+        return Err(CliError::InvalidArgs {
+            context: format!("Config file {path:?} cannot name another config file").into(),
+        });
+    }
+    Ok(settings)
+}
+
+/// Tracks where the server's settings came from so they can be re-read while
+/// the server is running.
+#[derive(Debug)]
+pub struct Reloader {
+    command_line: Settings,
+    config_file: Option<PathBuf>,
+    current: Settings,
+}
+
+impl Reloader {
+    pub const fn new(
+        command_line: Settings,
+        config_file: Option<PathBuf>,
+        current: Settings,
+    ) -> Self {
+        Self {
+            command_line,
+            config_file,
+            current,
+        }
+    }
+
+    /// Re-reads the config file and applies the settings that can change at
+    /// runtime.
+    ///
+    /// The audit log is always reopened so it can be rotated. Settings which
+    /// would require a restart are logged and ignored.
+    pub fn reload(
+        &mut self,
+        audit_log: &mut Option<AuditLog>,
+        rate_limiter: &mut Option<AddRateLimiter>,
+        allocator: &mut Allocator,
+        idle_gc: &mut Option<IdleGc>,
+    ) -> Result<(), CliError> {
+        let Settings {
+            data_dir,
+            socket,
+            audit_log: audit_log_file,
+            add_rate_limit,
+            max_entries,
+            detect_secrets,
+            idle_gc: idle_gc_enabled,
+        } = match &self.config_file {
+            Some(path) => {
+                info!("Reloading config file {path:?}.");
+                read_config(path, self.command_line.clone())?
+            }
+            None => self.command_line.clone(),
+        };
+
+        if data_dir != self.current.data_dir {
+            warn!("Ignoring changed --data-dir {data_dir:?}: the server must be restarted.");
+        }
+        if socket != self.current.socket {
+            warn!("Ignoring changed --socket {socket:?}: the server must be restarted.");
+        }
+
+        *audit_log = audit_log_file
+            .as_deref()
+            .map(|path| {
+                info!("Recording mutations to audit log {path:?}.");
+                AuditLog::open(path)
+            })
+            .transpose()?;
+        match (rate_limiter.as_mut(), add_rate_limit) {
+            (Some(rate_limiter), Some(limit)) => rate_limiter.set_limit(limit),
+            (None, Some(limit)) => *rate_limiter = Some(AddRateLimiter::new(limit)),
+            (_, None) => *rate_limiter = None,
+        }
+        match add_rate_limit {
+            Some(limit) => info!("Limiting clients to {limit} adds per second."),
+            None => info!("Not limiting client adds."),
+        }
+
+        if let Some(max_entries) = max_entries {
+            allocator.grow_main_ring(max_entries)?;
+        }
+        allocator.set_detect_secrets(detect_secrets);
+        if idle_gc_enabled != idle_gc.is_some() {
+            *idle_gc = idle_gc_enabled.then(|| {
+                info!("Collecting garbage while idle.");
+                IdleGc::new()
+            });
+        }
+
+        self.current.audit_log = audit_log_file;
+        self.current.add_rate_limit = add_rate_limit;
+        self.current.max_entries = max_entries;
+        self.current.detect_secrets = detect_secrets;
+        self.current.idle_gc = idle_gc_enabled;
+        Ok(())
+    }
+}
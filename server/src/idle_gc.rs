@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use log::{debug, info};
+use ringboard_core::protocol::GarbageCollectResponse;
+
+use crate::{
+    CliError,
+    allocator::{Allocator, DuplicateScan},
+    audit_log::{AuditLog, Mutation},
+};
+
+/// How long the server must go without requests before collecting garbage.
+const IDLE_DELAY: Duration = Duration::from_secs(10 * 60);
+/// Free bucket space that must have accumulated before collecting garbage.
+const MIN_WASTED_BYTES: u64 = 1 << 20;
+/// Free bucket space to leave behind so the allocator has slots to reuse.
+const MAX_WASTED_BYTES_AFTER_GC: u64 = 1 << 18;
+/// Ring positions to scan for duplicates before yielding back to the reactor.
+const DUPLICATE_SCAN_BUDGET: u32 = 256;
+
+/// Removes duplicates and compacts the buckets once the server has been idle
+/// for a while and enough space is being wasted.
+///
+/// Work is done in small steps so the reactor can service requests in between,
+/// and any request abandons the current pass since it may have moved entries.
+#[derive(Debug)]
+pub struct IdleGc {
+    last_activity: Instant,
+    pending: bool,
+    scan: Option<DuplicateScan>,
+    num_duplicates: u32,
+}
+
+impl IdleGc {
+    pub fn new() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            pending: true,
+            scan: None,
+            num_duplicates: 0,
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+        self.pending = true;
+        if self.scan.take().is_some() {
+            debug!("Abandoning idle GC for incoming request.");
+        }
+    }
+
+    /// How long to wait before calling [`Self::run`] again, or `None` if there
+    /// is nothing to do until the next request.
+    pub fn delay(&self) -> Option<Duration> {
+        if !self.pending {
+            None
+        } else if self.scan.is_some() {
+            Some(Duration::ZERO)
+        } else {
+            Some(IDLE_DELAY.saturating_sub(self.last_activity.elapsed()))
+        }
+    }
+
+    pub fn run(
+        &mut self,
+        allocator: &mut Allocator,
+        audit_log: &mut Option<AuditLog>,
+    ) -> Result<(), CliError> {
+        if !self.pending || self.last_activity.elapsed() < IDLE_DELAY {
+            return Ok(());
+        }
+
+        let scan = match &mut self.scan {
+            Some(scan) => scan,
+            None => {
+                let wasted_bytes = allocator.wasted_bucket_bytes();
+                if wasted_bytes <= MIN_WASTED_BYTES {
+                    debug!("Skipping idle GC with only {wasted_bytes} wasted bytes.");
+                    self.pending = false;
+                    return Ok(());
+                }
+                info!("Server is idle with {wasted_bytes} wasted bytes, running GC.");
+                self.num_duplicates = 0;
+                self.scan.insert(DuplicateScan::default())
+            }
+        };
+
+        let done = allocator.remove_duplicates(scan, DUPLICATE_SCAN_BUDGET, |id| {
+            self.num_duplicates += 1;
+            if let Some(log) = audit_log {
+                log.record(Mutation::Remove { id });
+            }
+        })?;
+        if !done {
+            return Ok(());
+        }
+        self.scan = None;
+        self.pending = false;
+
+        let GarbageCollectResponse { bytes_freed } = allocator.gc(MAX_WASTED_BYTES_AFTER_GC)?;
+        info!(
+            "Idle GC removed {} duplicate entries and freed {bytes_freed} bytes.",
+            self.num_duplicates
+        );
+        Ok(())
+    }
+}
@@ -2,18 +2,41 @@
 #![feature(vec_into_raw_parts)]
 #![feature(let_chains)]
 
-use std::{borrow::Cow, collections::VecDeque, fs, path::PathBuf};
+use std::{borrow::Cow, collections::VecDeque, env, fs, path::PathBuf};
 
 use error_stack::Report;
 use log::info;
-use ringboard_core::{Error, IoErr, dirs::data_dir};
+use ringboard_core::{
+    Error, IoErr,
+    dirs::{data_dir, set_data_dir, set_socket_file, validate_overrides},
+};
 use rustix::process::{Pid, chdir};
 use thiserror::Error;
 
-use crate::{allocator::Allocator, startup::claim_server_ownership};
+use crate::{
+    allocator::Allocator,
+    audit_log::AuditLog,
+    config::{Reloader, Settings, parse_args, read_config},
+    idle_gc::IdleGc,
+    rate_limit::AddRateLimiter,
+    startup::claim_server_ownership,
+};
+
+/// Profiles the rest of the enclosing scope as a Tracy zone. Compiles to
+/// nothing when the `trace` feature is disabled.
+macro_rules! span {
+    ($name:literal) => {
+        #[cfg(feature = "trace")]
+        let _span = tracy_client::span!($name);
+    };
+}
 
 mod allocator;
+mod audit_log;
+mod config;
+mod idle_gc;
 mod io_uring;
+mod rate_limit;
 mod reactor;
 mod requests;
 mod send_msg_bufs;
@@ -34,6 +57,8 @@ enum CliError {
     Multiple(Vec<CliError>),
     #[error("internal error")]
     Internal { context: Cow<'static, str> },
+    #[error("invalid arguments")]
+    InvalidArgs { context: Cow<'static, str> },
 }
 
 #[derive(Error, Debug)]
@@ -79,6 +104,13 @@ fn into_report(cli_err: CliError) -> Report<Wrapper> {
             .attach_printable(
             "Please report this bug at https://github.com/SUPERCILEX/clipboard-history/issues/new",
         ),
+        CliError::InvalidArgs { context } => Report::new(wrapper)
+            .attach_printable(context)
+            .attach_printable(
+                "Usage: ringboard-server [--foreground] [--config <FILE>] [--data-dir <DIR>] \
+                 [--socket <FILE>] [--audit-log <FILE>] [--add-rate-limit <ADDS_PER_SEC>] \
+                 [--max-entries <N>] [--detect-secrets] [--idle-gc]",
+            ),
     }
 }
 
@@ -93,6 +125,38 @@ fn into_result(errs: Vec<CliError>) -> Result<(), CliError> {
 fn run() -> Result<(), CliError> {
     info!("Starting Ringboard server v{}.", env!("CARGO_PKG_VERSION"));
 
+    let mut command_line = Settings::default();
+    let config_file = parse_args(env::args_os().skip(1), None, &mut command_line)?;
+    let settings = match &config_file {
+        Some(path) => {
+            info!("Using config file {path:?}.");
+            read_config(path, command_line.clone())?
+        }
+        None => command_line.clone(),
+    };
+    if let Some(dir) = settings.data_dir.clone() {
+        set_data_dir(dir).unwrap();
+    }
+    if let Some(file) = settings.socket.clone() {
+        set_socket_file(file).unwrap();
+    }
+    validate_overrides()?;
+    let mut audit_log = settings
+        .audit_log
+        .as_deref()
+        .map(|path| {
+            info!("Recording mutations to audit log {path:?}.");
+            AuditLog::open(path)
+        })
+        .transpose()?;
+    let mut rate_limiter = settings.add_rate_limit.map(|limit| {
+        info!("Limiting clients to {limit} adds per second.");
+        AddRateLimiter::new(limit)
+    });
+    let mut idle_gc = settings.idle_gc.then(|| {
+        info!("Collecting garbage while idle.");
+        IdleGc::new()
+    });
     {
         let data_dir = data_dir();
         info!("Using database in {data_dir:?}.");
@@ -106,9 +170,19 @@ fn run() -> Result<(), CliError> {
     info!("Acquired server lock.");
 
     let mut allocator = Allocator::open()?;
+    if let Some(max_entries) = settings.max_entries {
+        allocator.grow_main_ring(max_entries)?;
+    }
+    allocator.set_detect_secrets(settings.detect_secrets);
     into_result(
         [
-            reactor::run(&mut allocator),
+            reactor::run(
+                &mut allocator,
+                &mut audit_log,
+                &mut rate_limiter,
+                &mut idle_gc,
+                Reloader::new(command_line, config_file, settings),
+            ),
             allocator.shutdown(),
             server_guard.shutdown(),
         ]
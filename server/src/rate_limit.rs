@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use crate::reactor::MAX_NUM_CLIENTS;
+
+/// A per-client token bucket limiting how quickly entries may be added.
+///
+/// Each client may burst up to `limit` adds, after which it is refilled at
+/// `limit` adds per second.
+#[derive(Debug)]
+pub struct AddRateLimiter {
+    limit: u32,
+    clients: [Bucket; MAX_NUM_CLIENTS as usize],
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Option<Instant>,
+    exempt: bool,
+}
+
+impl AddRateLimiter {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            clients: [Bucket {
+                tokens: f64::from(limit),
+                last_refill: None,
+                exempt: false,
+            }; MAX_NUM_CLIENTS as usize],
+        }
+    }
+
+    /// Changes the limit without resetting clients' budgets or exemptions.
+    pub fn set_limit(&mut self, limit: u32) {
+        self.limit = limit;
+        for Bucket { tokens, .. } in &mut self.clients {
+            *tokens = tokens.min(f64::from(limit));
+        }
+    }
+
+    pub fn connect(&mut self, client: u8, exempt: bool) {
+        self.clients[usize::from(client)] = Bucket {
+            tokens: f64::from(self.limit),
+            last_refill: None,
+            exempt,
+        };
+    }
+
+    pub fn try_add(&mut self, client: u8) -> bool {
+        let limit = f64::from(self.limit);
+        let Bucket {
+            tokens,
+            last_refill,
+            exempt,
+        } = &mut self.clients[usize::from(client)];
+        if *exempt {
+            return true;
+        }
+
+        let now = Instant::now();
+        if let Some(last_refill) = last_refill {
+            *tokens = limit.min(
+                now.duration_since(*last_refill)
+                    .as_secs_f64()
+                    .mul_add(limit, *tokens),
+            );
+        }
+        *last_refill = Some(now);
+
+        if *tokens < 1. {
+            return false;
+        }
+        *tokens -= 1.;
+        true
+    }
+}
@@ -11,12 +11,12 @@ use arrayvec::ArrayVec;
 use io_uring::{
     IoUring, SubmissionQueue,
     cqueue::{Entry, buffer_select, more},
-    opcode::{AcceptMulti, Close, PollAdd, RecvMsgMulti, SendMsg},
+    opcode::{AcceptMulti, Close, PollAdd, Read, RecvMsgMulti, SendMsg, Timeout, TimeoutRemove},
     squeue::{Flags, PushError},
-    types::Fixed,
+    types::{Fixed, TimeoutFlags, Timespec},
 };
-use log::{debug, info, trace, warn};
-use ringboard_core::{IoErr, dirs::socket_file, init_unix_server};
+use log::{debug, error, info, trace, warn};
+use ringboard_core::{IoErr, Timestamp, dirs::socket_file, init_unix_server};
 use rustix::{
     fs::{CWD, Mode, OFlags, openat},
     io::Errno,
@@ -26,7 +26,11 @@ use rustix::{
 use crate::{
     CliError,
     allocator::Allocator,
+    audit_log::{AuditLog, Mutation},
+    config::Reloader,
+    idle_gc::IdleGc,
     io_uring::{buf_ring::BufRing, register_buf_ring, types::RecvMsgOutMut},
+    rate_limit::AddRateLimiter,
     requests,
     send_msg_bufs::SendMsgBufs,
 };
@@ -42,6 +46,7 @@ struct Clients {
     pending_closes: u32,
     pending_recv: u32,
     pending_sends: u32,
+    bulk: u32,
 }
 
 impl Clients {
@@ -55,9 +60,19 @@ impl Clients {
         (self.pending_closes & (1 << id)) != 0
     }
 
-    fn set_connected(&mut self, id: u8) {
+    fn is_bulk(&self, id: u8) -> bool {
+        debug_assert!(id < MAX_NUM_CLIENTS);
+        (self.bulk & (1 << id)) != 0
+    }
+
+    fn set_connected(&mut self, id: u8, bulk: bool) {
         debug_assert!(id < MAX_NUM_CLIENTS);
         self.connections |= 1 << id;
+        if bulk {
+            self.bulk |= 1 << id;
+        } else {
+            self.bulk &= !(1 << id);
+        }
         self.pending_closes &= !(1 << id);
         self.pending_recv &= !(1 << id);
     }
@@ -122,6 +137,7 @@ fn setup_uring() -> Result<(IoUring, BuiltInFds), CliError> {
         libc::sigaddset(&mut set, libc::SIGTERM);
         libc::sigaddset(&mut set, libc::SIGQUIT);
         libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGHUP);
         libc::sigprocmask(libc::SIG_BLOCK, &set, ptr::null_mut());
 
         let fd = libc::signalfd(-1, &set, 0);
@@ -218,14 +234,23 @@ impl From<PushError> for CliError {
     }
 }
 
-pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
+pub fn run(
+    allocator: &mut Allocator,
+    audit_log: &mut Option<AuditLog>,
+    rate_limiter: &mut Option<AddRateLimiter>,
+    idle_gc: &mut Option<IdleGc>,
+    mut reloader: Reloader,
+) -> Result<(), CliError> {
     const REQ_TYPE_ACCEPT: u64 = 0;
     const REQ_TYPE_RECV: u64 = 1;
     const REQ_TYPE_CLOSE: u64 = 2;
     const REQ_TYPE_READ_SIGNALS: u64 = 3;
     const REQ_TYPE_SENDMSG: u64 = 4;
     const REQ_TYPE_LOW_MEM: u64 = 5;
-    const REQ_TYPE_MASK: u64 = 0b111;
+    const REQ_TYPE_EXPIRE: u64 = 6;
+    const REQ_TYPE_CANCEL_EXPIRE: u64 = 7;
+    const REQ_TYPE_IDLE_GC: u64 = 8;
+    const REQ_TYPE_MASK: u64 = 0b1111;
     const REQ_TYPE_SHIFT: u32 = REQ_TYPE_MASK.count_ones();
 
     let (mut uring, BuiltInFds([accept_fd, signal_handler_fd, low_mem_listener_fd])) =
@@ -267,6 +292,7 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                            remaining_submission_slots: usize,
                            submissions: &mut SubmissionQueue|
      -> Result<_, PushError> {
+        span!("respond");
         {
             let mut iter = bufs.drain_pending_sends(client, remaining_submission_slots);
             while let Some((token, msghdr)) = iter.next() {
@@ -305,18 +331,19 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
         Ok(())
     };
 
+    let mut signal_info = unsafe { mem::zeroed::<libc::signalfd_siginfo>() };
+    let read_signals = Read::new(
+        Fixed(signal_handler_fd),
+        (&raw mut signal_info).cast(),
+        u32::try_from(size_of::<libc::signalfd_siginfo>()).unwrap(),
+    )
+    .build()
+    .user_data(REQ_TYPE_READ_SIGNALS);
     {
-        let read_signals = PollAdd::new(
-            Fixed(signal_handler_fd),
-            u32::try_from(libc::POLLIN).unwrap(),
-        )
-        .build()
-        .user_data(REQ_TYPE_READ_SIGNALS);
-
         let mut submission = uring.submission();
         unsafe {
             submission
-                .push_multiple(&[accept.clone(), read_signals])
+                .push_multiple(&[accept.clone(), read_signals.clone()])
                 .unwrap();
             if low_mem_listener_fd > 0 {
                 submission.push(&poll_low_mem).unwrap();
@@ -326,6 +353,13 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
 
     info!("Server event loop started.");
 
+    let mut expiry_time;
+    let mut armed_expiry = None::<Timestamp>;
+    // Distinguishes the armed expiry timeout from ones it replaced.
+    let mut expiry_generation = 0u64;
+    let mut idle_gc_delay;
+    let mut idle_gc_armed = false;
+
     let mut sequence_number = 0;
     let mut client_buffers = [const { None::<BufRing> }; MAX_NUM_CLIENTS as usize];
     let mut send_bufs = SendMsgBufs::new();
@@ -389,6 +423,7 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                     unsafe { submissions.push(&recv) }?;
                 }
                 REQ_TYPE_RECV => 'recv: {
+                    span!("receive");
                     let fd = restore_fd(&entry);
                     debug!("Handling recv completion for client {fd}.");
                     match result {
@@ -441,6 +476,9 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                             debug!("Dropping spurious message for client {fd}.");
                             break 'recv;
                         }
+                        if let Some(idle_gc) = idle_gc {
+                            idle_gc.touch();
+                        }
 
                         if !clients.set_send_buffered(fd, true) {
                             clients_with_pending_sends.push(fd);
@@ -451,14 +489,30 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                                 msg.control_data,
                                 &mut send_bufs,
                                 allocator,
+                                audit_log,
+                                || {
+                                    let allowed = rate_limiter
+                                        .as_mut()
+                                        .is_none_or(|rate_limiter| rate_limiter.try_add(fd));
+                                    if !allowed {
+                                        warn!(
+                                            "Client {fd} exceeded the add rate limit, dropping \
+                                             entry."
+                                        );
+                                    }
+                                    allowed
+                                },
                                 &mut sequence_number,
                             )?
                         } else {
-                            let (version_valid, resp) =
+                            let (version_valid, bulk, resp) =
                                 requests::connect(msg.payload_data, &mut send_bufs);
                             if version_valid {
                                 info!("Client {fd} connected.");
-                                clients.set_connected(fd);
+                                clients.set_connected(fd, bulk);
+                                if let Some(rate_limiter) = rate_limiter {
+                                    rate_limiter.connect(fd, bulk);
+                                }
                             } else {
                                 clients.set_disconnected(fd);
                             }
@@ -479,6 +533,7 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                     }
                 }
                 REQ_TYPE_SENDMSG => {
+                    span!("sent");
                     let fd = restore_fd(&entry);
                     debug!("Handling sendmsg completion for client {fd}.");
 
@@ -553,14 +608,27 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                 }
                 REQ_TYPE_READ_SIGNALS => {
                     debug!("Handling read_signals completion.");
-                    let result = result.map_io_err(|| "Failed to poll for signals.")?;
-                    if (result & u32::try_from(libc::POLLIN).unwrap()) == 0 {
+                    let result = result.map_io_err(|| "Failed to read signals.")?;
+                    if result != u32::try_from(size_of::<libc::signalfd_siginfo>()).unwrap() {
                         return Err(CliError::Internal {
-                            context: format!("Unknown signal poll event received: {result}").into(),
+                            context: format!("Partial signal read of {result} bytes.").into(),
                         });
                     }
+                    if signal_info.ssi_signo != u32::try_from(libc::SIGHUP).unwrap() {
+                        break 'outer;
+                    }
 
-                    break 'outer;
+                    info!("Reloading configuration.");
+                    let was_rate_limited = rate_limiter.is_some();
+                    if let Err(e) = reloader.reload(audit_log, rate_limiter, allocator, idle_gc) {
+                        error!("Failed to reload configuration, keeping previous settings.\n{e:?}");
+                    }
+                    if !was_rate_limited && let Some(rate_limiter) = rate_limiter {
+                        for client in (0..MAX_NUM_CLIENTS).filter(|&c| clients.is_connected(c)) {
+                            rate_limiter.connect(client, clients.is_bulk(client));
+                        }
+                    }
+                    unsafe { submissions.push(&read_signals) }?;
                 }
                 REQ_TYPE_LOW_MEM => {
                     debug!("Handling low memory completion.");
@@ -583,10 +651,97 @@ pub fn run(allocator: &mut Allocator) -> Result<(), CliError> {
                         });
                     }
                 }
+                REQ_TYPE_EXPIRE => 'expire: {
+                    debug!("Handling expiry timeout completion.");
+                    if entry.user_data() >> REQ_TYPE_SHIFT != expiry_generation {
+                        debug!("Ignoring replaced expiry timeout.");
+                        break 'expire;
+                    }
+                    match result {
+                        Err(e) if e.raw_os_error() == Some(Errno::TIME.raw_os_error()) => {}
+                        r => {
+                            r.map_io_err(|| "Failed to wait for entries to expire.")?;
+                        }
+                    }
+                    armed_expiry = None;
+
+                    allocator.remove_expired(Timestamp::now(), |id| {
+                        if let Some(log) = audit_log {
+                            log.record(Mutation::Expire { id });
+                        }
+                    });
+                    if let Some(idle_gc) = idle_gc {
+                        idle_gc.touch();
+                    }
+                }
+                REQ_TYPE_CANCEL_EXPIRE => {
+                    debug!("Handling expiry timeout cancellation completion.");
+                    match result {
+                        Err(e) if e.raw_os_error() == Some(Errno::NOENT.raw_os_error()) => {
+                            // The timeout fired before it could be cancelled, so its
+                            // completion is ignored like a cancelled one.
+                        }
+                        r => {
+                            r.map_io_err(|| "Failed to cancel expiry timeout.")?;
+                        }
+                    }
+                }
+                REQ_TYPE_IDLE_GC => {
+                    debug!("Handling idle GC timeout completion.");
+                    match result {
+                        Err(e) if e.raw_os_error() == Some(Errno::TIME.raw_os_error()) => {}
+                        r => {
+                            r.map_io_err(|| "Failed to wait for the server to become idle.")?;
+                        }
+                    }
+                    idle_gc_armed = false;
+
+                    if let Some(idle_gc) = idle_gc {
+                        idle_gc.run(allocator, audit_log)?;
+                    }
+                }
                 _ => unreachable!(),
             }
         }
 
+        if !idle_gc_armed
+            && let Some(delay) = idle_gc.as_ref().and_then(IdleGc::delay)
+            && !submissions.is_full()
+        {
+            trace!("Scheduling idle GC in {delay:?}.");
+            idle_gc_delay = Timespec::from(delay);
+            let timeout = Timeout::new(&raw const idle_gc_delay)
+                .build()
+                .user_data(REQ_TYPE_IDLE_GC);
+            unsafe { submissions.push(&timeout) }?;
+            idle_gc_armed = true;
+        }
+
+        if let Some(next_expiry) = allocator.next_expiry()
+            && armed_expiry.is_none_or(|armed| next_expiry < armed)
+            && submissions.capacity() - submissions.len() >= 2
+        {
+            debug!("Scheduling expiry sweep at {next_expiry}.");
+            if armed_expiry.is_some() {
+                // Timeout updates don't support realtime clocks, so replace the timeout
+                // instead.
+                let cancel =
+                    TimeoutRemove::new(REQ_TYPE_EXPIRE | expiry_generation << REQ_TYPE_SHIFT)
+                        .build()
+                        .user_data(REQ_TYPE_CANCEL_EXPIRE);
+                unsafe { submissions.push(&cancel) }?;
+                expiry_generation += 1;
+            }
+
+            expiry_time = Timespec::new().sec(next_expiry.as_secs());
+            let timeout = Timeout::new(&raw const expiry_time)
+                .flags(TimeoutFlags::ABS | TimeoutFlags::REALTIME)
+                .build()
+                .user_data(REQ_TYPE_EXPIRE | expiry_generation << REQ_TYPE_SHIFT);
+            unsafe { submissions.push(&timeout) }?;
+            armed_expiry = Some(next_expiry);
+        }
+
         let mut remaining_sends = ArrayVec::<u8, { MAX_NUM_CLIENTS as usize }>::new_const();
         for (i, &client) in clients_with_pending_sends.iter().enumerate() {
             if !send_bufs.has_ready_block(client) {
@@ -4,17 +4,20 @@ use arrayvec::ArrayVec;
 use log::{debug, info, warn};
 use ringboard_core::{
     AsBytes, protocol,
-    protocol::{AddResponse, MimeType, Request, RingKind},
+    protocol::{
+        AddAlternateResponse, AddResponse, MimeType, MoveToFrontResponse, Request, RingKind,
+    },
 };
 use rustix::net::{AncillaryDrain, RecvAncillaryMessage};
 
 use crate::{
     CliError,
     allocator::Allocator,
+    audit_log::{AuditLog, Mutation},
     send_msg_bufs::{PendingBufAllocation, SendMsgBufs},
 };
 
-pub fn connect(payload: &[u8], send_bufs: &mut SendMsgBufs) -> (bool, PendingBufAllocation) {
+pub fn connect(payload: &[u8], send_bufs: &mut SendMsgBufs) -> (bool, bool, PendingBufAllocation) {
     debug!("Establishing client/server protocol connection.");
     let version = payload[0];
     let valid = version == protocol::VERSION;
@@ -24,6 +27,12 @@ pub fn connect(payload: &[u8], send_bufs: &mut SendMsgBufs) -> (bool, PendingBuf
             protocol::VERSION
         );
     }
+    let bulk = payload
+        .get(1)
+        .is_some_and(|&flags| flags & protocol::CONNECTION_FLAG_BULK != 0);
+    if bulk {
+        debug!("Client is a bulk client.");
+    }
 
     let response = send_bufs.init_buf(
         |_| (),
@@ -32,14 +41,18 @@ pub fn connect(payload: &[u8], send_bufs: &mut SendMsgBufs) -> (bool, PendingBuf
         },
     );
 
-    (valid, response)
+    (valid, bulk, response)
 }
 
+/// `add_allowed` is consulted once per add request so the caller can enforce
+/// its rate limit.
 pub fn handle(
     request_data: &[u8],
     control_data: &mut [u8],
     send_bufs: &mut SendMsgBufs,
     allocator: &mut Allocator,
+    audit_log: &mut Option<AuditLog>,
+    add_allowed: impl FnOnce() -> bool,
     sequence_number: &mut u64,
 ) -> Result<Option<PendingBufAllocation>, CliError> {
     if request_data.len() < size_of::<Request>() {
@@ -51,21 +64,105 @@ pub fn handle(
     macro_rules! reply {
         ($response:expr) => {{ Ok(Some(reply(send_bufs, *sequence_number, $response))) }};
     }
+    let mut audit = |mutation| {
+        if let Some(log) = audit_log {
+            log.record(mutation);
+        }
+    };
 
     info!("Processing request: {request:?}");
+    #[cfg(feature = "trace")]
+    let _span = match request {
+        Request::Add { .. } => tracy_client::span!("process add"),
+        Request::MoveToFront { .. } => tracy_client::span!("process move to front"),
+        Request::Swap { .. } => tracy_client::span!("process swap"),
+        Request::Remove { .. } => tracy_client::span!("process remove"),
+        Request::GarbageCollect { .. } => tracy_client::span!("process garbage collect"),
+        Request::Wipe { from: _ } => tracy_client::span!("process wipe"),
+        Request::SetLabel { .. } => tracy_client::span!("process set label"),
+        Request::SetExpiry { .. } => tracy_client::span!("process set expiry"),
+        Request::List { .. } => tracy_client::span!("process list"),
+        Request::AddAlternate { .. } => tracy_client::span!("process add alternate"),
+        Request::AddSealed { .. } => tracy_client::span!("process add sealed"),
+    };
     *sequence_number = sequence_number.wrapping_add(1);
     match *request {
-        Request::Add { to, ref mime_type } => {
-            reply!(add(control_data, allocator, to, mime_type)?)
+        Request::Add {
+            to,
+            ref mime_type,
+            flags,
+        }
+        | Request::AddSealed {
+            to,
+            ref mime_type,
+            flags,
+        } => {
+            if !add_allowed() {
+                // Close the received file descriptors.
+                for _ in unsafe { AncillaryDrain::parse(control_data) } {}
+                return reply!([AddResponse::RateLimited]);
+            }
+
+            let sealed = matches!(request, Request::AddSealed { .. });
+            let echo = flags & protocol::ADD_FLAG_ECHO != 0;
+            let responses = add(control_data, allocator, to, mime_type, sealed, echo)?;
+            for response in &responses {
+                if let Some(id) = response.id() {
+                    audit(Mutation::Add { id });
+                }
+            }
+            reply!(responses)
         }
         Request::MoveToFront { id, to } => {
-            reply!([allocator.move_to_front(id, to)?])
+            let response = allocator.move_to_front(id, to)?;
+            if let MoveToFrontResponse::Success { id: new_id } = response {
+                audit(Mutation::MoveToFront {
+                    from: id,
+                    to: new_id,
+                });
+            }
+            reply!([response])
+        }
+        Request::Swap { id1, id2 } => {
+            let response = allocator.swap(id1, id2)?;
+            if response.error1.is_none() && response.error2.is_none() {
+                audit(Mutation::Swap { id1, id2 });
+            }
+            reply!([response])
+        }
+        Request::Remove { id } => {
+            let response = allocator.remove(id)?;
+            if response.error.is_none() {
+                audit(Mutation::Remove { id });
+            }
+            reply!([response])
         }
-        Request::Swap { id1, id2 } => reply!([allocator.swap(id1, id2)?]),
-        Request::Remove { id } => reply!([allocator.remove(id)?]),
         Request::GarbageCollect { max_wasted_bytes } => {
             reply!([allocator.gc(max_wasted_bytes)?])
         }
+        Request::Wipe { from } => {
+            let response = allocator.wipe(from)?;
+            audit(Mutation::Wipe { from });
+            reply!([response])
+        }
+        Request::SetLabel { id, ref label } => {
+            let response = allocator.set_label(id, label)?;
+            if response.error.is_none() {
+                audit(Mutation::SetLabel { id, label: *label });
+            }
+            reply!([response])
+        }
+        Request::SetExpiry { id, expires_at } => {
+            let response = allocator.set_expiry(id, expires_at)?;
+            if response.error.is_none() {
+                audit(Mutation::SetExpiry { id, expires_at });
+            }
+            reply!([response])
+        }
+        Request::List { from, offset } => reply!([allocator.list(from, offset)?]),
+        Request::AddAlternate { id, ref mime_type } => {
+            reply!(add_alternate(control_data, allocator, id, mime_type)?)
+        }
     }
 }
 
@@ -93,16 +190,37 @@ fn add(
     allocator: &mut Allocator,
     kind: RingKind,
     mime_type: &MimeType,
-) -> Result<impl ExactSizeIterator<Item = AddResponse>, CliError> {
+    sealed: bool,
+    echo: bool,
+) -> Result<ArrayVec<AddResponse, 1>, CliError> {
+    let mut responses = ArrayVec::<_, 1>::new();
+
+    for message in unsafe { AncillaryDrain::parse(control_data) } {
+        if let RecvAncillaryMessage::ScmRights(received_fds) = message {
+            for fd in received_fds {
+                responses.push(allocator.add(fd, kind, mime_type, sealed, echo)?);
+            }
+        }
+    }
+
+    Ok(responses)
+}
+
+fn add_alternate(
+    control_data: &mut [u8],
+    allocator: &mut Allocator,
+    id: u64,
+    mime_type: &MimeType,
+) -> Result<ArrayVec<AddAlternateResponse, 1>, CliError> {
     let mut responses = ArrayVec::<_, 1>::new();
 
     for message in unsafe { AncillaryDrain::parse(control_data) } {
         if let RecvAncillaryMessage::ScmRights(received_fds) = message {
             for fd in received_fds {
-                responses.push(allocator.add(fd, kind, mime_type)?);
+                responses.push(allocator.add_alternate(fd, id, mime_type)?);
             }
         }
     }
 
-    Ok(responses.into_iter())
+    Ok(responses)
 }
@@ -0,0 +1,216 @@
+use std::{fs, io, io::ErrorKind};
+
+use ratatui::crossterm::event::{KeyCode, KeyModifiers};
+use ringboard_sdk::{
+    config::{TuiConfig, TuiKeyBindings, tui_config_file},
+    core::{Error as CoreError, IoErr},
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum KeyAction {
+    Quit,
+    Paste,
+    PastePrimary,
+    Down,
+    Up,
+    ShowDetails,
+    HideDetails,
+    ToggleDetails,
+    ScrollDetailsDown,
+    ScrollDetailsUp,
+    Search,
+    RegexSearch,
+    MimeSearch,
+    Favorite,
+    Delete,
+    Help,
+    Refresh,
+    Reset,
+    SwitchPane,
+}
+
+pub struct KeyBindings {
+    keys: Vec<(KeyCode, KeyModifiers, KeyAction)>,
+    pub help: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::new(&TuiKeyBindings::default()).unwrap()
+    }
+}
+
+impl KeyBindings {
+    pub fn load() -> Result<Self, CoreError> {
+        let path = tui_config_file();
+        let config = match fs::read_to_string(&path) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            r => r.map_io_err(|| format!("Failed to read config: {path:?}"))?,
+        };
+        let TuiConfig::V1(config) = toml::from_str::<TuiConfig>(&config)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+            .map_io_err(|| format!("Failed to parse config: {path:?}"))?;
+        Self::new(&config.bindings)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+            .map_io_err(|| format!("Invalid key binding in config: {path:?}"))
+    }
+
+    fn new(bindings: &TuiKeyBindings) -> Result<Self, String> {
+        let TuiKeyBindings {
+            quit,
+            paste,
+            paste_primary,
+            down,
+            up,
+            show_details,
+            hide_details,
+            toggle_details,
+            scroll_details_down,
+            scroll_details_up,
+            search,
+            regex_search,
+            mime_search,
+            favorite,
+            delete,
+            help,
+            refresh,
+            reset,
+            switch_pane,
+        } = bindings;
+
+        let mut keys = Vec::new();
+        for (specs, action) in [
+            (quit, KeyAction::Quit),
+            (paste, KeyAction::Paste),
+            (paste_primary, KeyAction::PastePrimary),
+            (down, KeyAction::Down),
+            (up, KeyAction::Up),
+            (show_details, KeyAction::ShowDetails),
+            (hide_details, KeyAction::HideDetails),
+            (toggle_details, KeyAction::ToggleDetails),
+            (scroll_details_down, KeyAction::ScrollDetailsDown),
+            (scroll_details_up, KeyAction::ScrollDetailsUp),
+            (search, KeyAction::Search),
+            (regex_search, KeyAction::RegexSearch),
+            (mime_search, KeyAction::MimeSearch),
+            (favorite, KeyAction::Favorite),
+            (delete, KeyAction::Delete),
+            (help, KeyAction::Help),
+            (refresh, KeyAction::Refresh),
+            (reset, KeyAction::Reset),
+            (switch_pane, KeyAction::SwitchPane),
+        ] {
+            for spec in specs {
+                let (code, modifiers) = parse_key(spec).ok_or_else(|| format!("{spec:?}"))?;
+                keys.push((code, modifiers, action));
+            }
+        }
+
+        let first = first_key;
+        let help = format!(
+            "Use {}{} to move, {}{} to (un)select, {} to search, {} to search with RegEx, {} to \
+             search mime types, {} to reload, {} to (un)favorite, {} to delete, {} to copy to the \
+             primary selection, {}/{} to scroll entry details, {} to switch panes in the split \
+             view.",
+            first(down),
+            first(up),
+            first(hide_details),
+            first(show_details),
+            first(search),
+            first(regex_search),
+            first(mime_search),
+            first(refresh),
+            first(favorite),
+            first(delete),
+            first(paste_primary),
+            first(scroll_details_down),
+            first(scroll_details_up),
+            first(switch_pane),
+        );
+
+        Ok(Self { keys, help })
+    }
+
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<KeyAction> {
+        let modifiers = normalize_modifiers(code, modifiers);
+        self.keys
+            .iter()
+            .find(|&&(c, m, _)| c == code && m == modifiers)
+            .map(|&(_, _, action)| action)
+    }
+}
+
+fn normalize_modifiers(code: KeyCode, modifiers: KeyModifiers) -> KeyModifiers {
+    if matches!(code, KeyCode::Char(_)) {
+        // Shift is already reflected in the character itself.
+        modifiers - KeyModifiers::SHIFT
+    } else {
+        modifiers
+    }
+}
+
+fn parse_key(spec: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let (modifiers, key) = match spec.rsplit_once('+') {
+        Some(("", "")) => ("", "+"),
+        Some((modifiers, "")) => (modifiers.strip_suffix('+')?, "+"),
+        Some((modifiers, key)) => (modifiers, key),
+        None => ("", spec),
+    };
+
+    let mut parsed_modifiers = KeyModifiers::NONE;
+    for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+        parsed_modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            "super" => KeyModifiers::SUPER,
+            _ => return None,
+        };
+    }
+
+    let mut chars = key.chars();
+    let code = if let Some(c) = chars.next()
+        && chars.next().is_none()
+    {
+        KeyCode::Char(c)
+    } else {
+        match key.to_ascii_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "enter" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f => KeyCode::F(f.strip_prefix('f')?.parse().ok()?),
+        }
+    };
+    let code = match code {
+        KeyCode::Char(c) if parsed_modifiers.contains(KeyModifiers::SHIFT) => {
+            KeyCode::Char(c.to_ascii_uppercase())
+        }
+        code => code,
+    };
+    Some((code, normalize_modifiers(code, parsed_modifiers)))
+}
+
+fn first_key(specs: &[String]) -> &str {
+    specs.first().map_or("(unbound)", |s| display_key(s))
+}
+
+fn display_key(spec: &str) -> &str {
+    match spec {
+        "up" => "↑",
+        "down" => "↓",
+        "left" => "←",
+        "right" => "→",
+        _ => spec,
+    }
+}
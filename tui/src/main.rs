@@ -1,10 +1,11 @@
 #![feature(let_chains)]
 
 use std::{
-    fmt::Write,
+    env,
+    fmt::{Display, Formatter, Write},
     fs::File,
     io,
-    io::BufWriter,
+    io::{BufWriter, ErrorKind},
     mem::ManuallyDrop,
     os::fd::FromRawFd,
     sync::{
@@ -20,7 +21,7 @@ use ratatui::{
     buffer::Buffer,
     crossterm::{
         ExecutableCommand, event,
-        event::{Event, KeyEvent, KeyEventKind, KeyModifiers},
+        event::{Event, KeyEvent, KeyEventKind},
         terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
     },
     layout::{Alignment, Constraint, Layout, Rect},
@@ -33,17 +34,27 @@ use ratatui::{
 };
 use ratatui_image::{StatefulImage, picker::Picker, protocol::StatefulProtocol};
 use ringboard_sdk::{
-    core::{Error as CoreError, IoErr, protocol::RingKind},
+    DatabaseReader, EntryReader,
+    api::PasteTarget,
+    core::{
+        Error as CoreError, IoErr,
+        dirs::data_dir,
+        protocol::{RingKind, decompose_id},
+    },
     search::CancellationToken,
     ui_actor::{
-        Command, CommandError, DetailedEntry, Message, SearchKind, UiEntry, UiEntryCache,
-        controller,
+        BINARY_PREFIX_MAX_LEN, Command, CommandError, DetailedEntry, Message, SearchKind, UiEntry,
+        UiEntryCache, controller,
     },
 };
 use rustix::stdio::raw_stdout;
 use thiserror::Error;
 use tui_textarea::{CursorMove, Input, Key, TextArea};
 
+use crate::keys::{KeyAction, KeyBindings};
+
+mod keys;
+
 #[cfg(feature = "trace")]
 #[global_allocator]
 static GLOBAL: tracy_client::ProfiledAllocator<std::alloc::System> =
@@ -70,9 +81,23 @@ struct App {
     requests: Sender<Command>,
     responses: Receiver<Action>,
     picker: Picker,
+    bindings: KeyBindings,
+    pick: bool,
     state: State,
 }
 
+#[derive(Copy, Clone)]
+enum PickOutput {
+    Id,
+    Content,
+}
+
+#[derive(Default)]
+struct Args {
+    pick: Option<PickOutput>,
+    split: bool,
+}
+
 #[derive(Default)]
 struct State {
     entries: UiEntries,
@@ -83,9 +108,12 @@ struct State {
 struct UiEntries {
     loaded_entries: Box<[UiEntry]>,
     search_results: Box<[UiEntry]>,
+    database_size: u64,
 
     loaded_state: ListState,
     search_state: ListState,
+    loaded_favorites_state: ListState,
+    search_favorites_state: ListState,
 }
 
 #[derive(Default)]
@@ -104,10 +132,37 @@ struct UiState {
     queued_searches: u32,
 
     show_help: bool,
+    focused_pane: Option<Pane>,
 
     cache: String,
 }
 
+/// A pane of the split view, which shows favorites separately from the main
+/// ring.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Pane {
+    Favorites,
+    Main,
+}
+
+impl Pane {
+    const fn other(self) -> Self {
+        match self {
+            Self::Favorites => Self::Main,
+            Self::Main => Self::Favorites,
+        }
+    }
+}
+
+impl From<RingKind> for Pane {
+    fn from(value: RingKind) -> Self {
+        match value {
+            RingKind::Favorites => Self::Favorites,
+            RingKind::Main => Self::Main,
+        }
+    }
+}
+
 struct SearchState {
     focused: bool,
     kind: SearchKind,
@@ -120,36 +175,49 @@ enum ImageState {
 
 macro_rules! active_entries {
     ($entries:expr, $state:expr) => {{
-        if $state.query.is_empty() {
-            &$entries.loaded_entries
-        } else {
-            &$entries.search_results
-        }
+        pane_entries(
+            if $state.query.is_empty() {
+                &$entries.loaded_entries
+            } else {
+                &$entries.search_results
+            },
+            $state.focused_pane,
+        )
     }};
 }
 
 macro_rules! active_list_state {
-    ($entries:expr, $state:expr) => {{
-        if $state.query.is_empty() {
-            &mut $entries.loaded_state
-        } else {
-            &mut $entries.search_state
+    ($entries:expr, $state:expr) => {{ active_list_state!($entries, $state, mut) }};
+    ($entries:expr, $state:expr, $($mut:tt)?) => {{
+        match ($state.query.is_empty(), $state.focused_pane) {
+            (true, Some(Pane::Favorites)) => &$($mut)? $entries.loaded_favorites_state,
+            (true, _) => &$($mut)? $entries.loaded_state,
+            (false, Some(Pane::Favorites)) => &$($mut)? $entries.search_favorites_state,
+            (false, _) => &$($mut)? $entries.search_state,
         }
     }};
 }
 
 macro_rules! selected_entry {
     ($entries:expr, $state:expr) => {{
-        if $state.query.is_empty() {
-            &$entries.loaded_state
-        } else {
-            &$entries.search_state
-        }
-        .selected()
-        .and_then(|selected| active_entries!($entries, $state).get(selected))
+        active_list_state!($entries, $state,)
+            .selected()
+            .and_then(|selected| active_entries!($entries, $state).get(selected))
     }};
 }
 
+/// Narrows `entries` down to those shown in `pane`, or all of them outside the
+/// split view.
+fn pane_entries(entries: &[UiEntry], pane: Option<Pane>) -> &[UiEntry] {
+    // Favorites aren't interleaved, so they always come before the main entries.
+    let favorites = entries.partition_point(|e| e.entry.ring() == RingKind::Favorites);
+    match pane {
+        None => entries,
+        Some(Pane::Favorites) => &entries[..favorites],
+        Some(Pane::Main) => &entries[favorites..],
+    }
+}
+
 #[derive(Error, Debug)]
 enum Wrapper {
     #[error("{0}")]
@@ -166,21 +234,82 @@ fn main() -> error_stack::Result<(), Wrapper> {
     })
 }
 
+fn parse_args() -> Result<Args, CoreError> {
+    let mut args = Args::default();
+    for arg in env::args_os().skip(1) {
+        match arg.to_str() {
+            Some("--pick") => args.pick = Some(PickOutput::Id),
+            Some("--pick-content") => args.pick = Some(PickOutput::Content),
+            Some("--split") => args.split = true,
+            _ => {
+                return Err(io::Error::from(ErrorKind::InvalidInput))
+                    .map_io_err(|| format!("Unknown argument: {arg:?}"));
+            }
+        }
+    }
+    Ok(args)
+}
+
 fn run() -> Result<(), CoreError> {
+    let Args { pick, split } = parse_args()?;
     let stdout = ManuallyDrop::new(unsafe { File::from_raw_fd(raw_stdout()) });
-    let mut stdout = BufWriter::new(&*stdout);
+    // Picked entries are written to stdout which is usually captured by the shell, so draw
+    // the UI on the controlling terminal instead.
+    let tty = if pick.is_some() {
+        Some(open_tty()?)
+    } else {
+        None
+    };
+    let mut output = BufWriter::new(tty.as_ref().unwrap_or(&*stdout));
 
-    let mut terminal = init_terminal(&mut stdout)?;
-    let r = App::init(&mut terminal).and_then(|app| app.run(terminal));
-    restore_terminal(&mut stdout)?;
-    r
+    let mut terminal = init_terminal(&mut output, pick.is_some())?;
+    let r = App::init(&mut terminal, pick.is_some(), split).and_then(|app| app.run(terminal));
+    restore_terminal(&mut output)?;
+    if let Some(id) = r?
+        && let Some(pick) = pick
+    {
+        print_picked(id, pick, &*stdout)?;
+    }
+    Ok(())
 }
 
-fn init_terminal(mut stdout: impl io::Write) -> Result<Terminal<impl Backend>, CoreError> {
+fn open_tty() -> Result<File, CoreError> {
+    File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_io_err(|| "Failed to open controlling terminal.")
+}
+
+fn print_picked(id: u64, pick: PickOutput, mut stdout: impl io::Write) -> Result<(), CoreError> {
+    match pick {
+        PickOutput::Id => writeln!(stdout, "{id}").map_io_err(|| "Failed to write to stdout."),
+        PickOutput::Content => {
+            let mut dir = data_dir();
+            let database = DatabaseReader::open(&mut dir)?;
+            let mut reader = EntryReader::open(&mut dir)?;
+
+            let entry = database.get_raw(id)?;
+            let file = entry.to_file(&mut reader)?;
+            io::copy(&mut &*file, &mut stdout).map_io_err(|| "Failed to write to stdout.")?;
+            Ok(())
+        }
+    }
+}
+
+fn init_terminal(
+    mut stdout: impl io::Write,
+    tty: bool,
+) -> Result<Terminal<impl Backend>, CoreError> {
     std::panic::set_hook({
         let hook = std::panic::take_hook();
         Box::new(move |info| {
-            if let Err(err) = restore_terminal(io::stdout()) {
+            let r = if tty {
+                open_tty().and_then(restore_terminal)
+            } else {
+                restore_terminal(io::stdout())
+            };
+            if let Err(err) = r {
                 eprintln!("Failed to restore terminal: {err}");
             }
             hook(info);
@@ -203,19 +332,35 @@ fn restore_terminal(mut stdout: impl io::Write) -> Result<(), CoreError> {
 }
 
 impl App {
-    fn init(terminal: &mut Terminal<impl Backend>) -> Result<Self, CoreError> {
+    fn init(
+        terminal: &mut Terminal<impl Backend>,
+        pick: bool,
+        split: bool,
+    ) -> Result<Self, CoreError> {
         let (command_sender, command_receiver) = mpsc::channel();
         let (response_sender, response_receiver) = mpsc::sync_channel(8);
         let mut state = State::default();
+        state.ui.focused_pane = split.then_some(Pane::Main);
+        let bindings = KeyBindings::load().unwrap_or_else(|e| {
+            state.ui.last_error = Some(e.into());
+            KeyBindings::default()
+        });
 
         AppWrapper {
             state: &mut state,
             requests: &command_sender,
+            help: &bindings.help,
         }
         .draw(terminal)
         .map_io_err(|| "Failed to write to terminal.")?;
 
-        let picker = Picker::from_query_stdio().unwrap_or_else(|_| Picker::from_fontsize((2, 4)));
+        // Querying the terminal goes through stdin and stdout which aren't the terminal we're
+        // drawing on when picking.
+        let picker = if pick {
+            Picker::from_fontsize((2, 4))
+        } else {
+            Picker::from_query_stdio().unwrap_or_else(|_| Picker::from_fontsize((2, 4)))
+        };
 
         thread::spawn({
             let sender = response_sender.clone();
@@ -235,6 +380,8 @@ impl App {
             requests: command_sender,
             responses: response_receiver,
             picker,
+            bindings,
+            pick,
 
             state,
         })
@@ -242,15 +389,18 @@ impl App {
 }
 
 impl App {
-    fn run(mut self, mut terminal: Terminal<impl Backend>) -> Result<(), CoreError> {
+    fn run(mut self, mut terminal: Terminal<impl Backend>) -> Result<Option<u64>, CoreError> {
         let Self {
             requests,
             responses,
             ref picker,
+            ref bindings,
+            pick,
             ref mut state,
         } = self;
 
         let mut local_state = Option::default();
+        let mut picked = None;
         for action in responses {
             if match action {
                 Action::Controller(message) => {
@@ -259,7 +409,9 @@ impl App {
                 Action::User(event) => handle_event(
                     event.map_io_err(|| "Failed to read terminal.")?,
                     state,
+                    bindings,
                     &requests,
+                    pick.then_some(&mut picked),
                 ),
             } {
                 break;
@@ -268,11 +420,12 @@ impl App {
             AppWrapper {
                 state,
                 requests: &requests,
+                help: &bindings.help,
             }
             .draw(&mut terminal)
             .map_io_err(|| "Failed to write to terminal.")?;
         }
-        Ok(())
+        Ok(picked)
     }
 }
 
@@ -286,8 +439,11 @@ fn handle_message(
     let UiEntries {
         loaded_entries,
         search_results,
+        database_size: db_size,
         loaded_state,
         search_state,
+        loaded_favorites_state: _,
+        search_favorites_state,
     } = entries;
     let UiState {
         details_requested,
@@ -309,16 +465,27 @@ fn handle_message(
         Message::LoadedFirstPage {
             entries: new_entries,
             default_focused_id,
+            database_size,
         } => {
             *loaded_entries = new_entries;
+            match database_size {
+                Ok(size) => *db_size = size,
+                Err(e) => *last_error = Some(e.into()),
+            }
             if loaded_state.selected().is_none() {
                 loaded_state.select(default_focused_id.and_then(|selected_id| {
-                    loaded_entries
+                    pane_entries(loaded_entries, ui.focused_pane.map(|_| Pane::Main))
                         .iter()
                         .position(|e| e.entry.id() == selected_id)
                 }));
             }
             if let Some(id) = pending_favorite_change.take() {
+                if let Some(pane) = &mut ui.focused_pane
+                    && let Ok((ring, _)) = decompose_id(id)
+                {
+                    // The entry moved to the other ring, so follow it.
+                    *pane = ring.into();
+                }
                 if let Some(index) = active_entries!(entries, ui)
                     .iter()
                     .position(|e| e.entry.id() == id)
@@ -335,12 +502,26 @@ fn handle_message(
                 *detailed_entry = Some(result);
             }
         }
+        Message::PartialSearchResults(entries) => {
+            if let Some(token) = pending_search_token
+                && !token.is_cancelled()
+            {
+                *search_results = entries;
+                for state in [&mut *search_state, &mut *search_favorites_state] {
+                    if state.selected().is_none() {
+                        state.select_first();
+                    }
+                }
+            }
+        }
         Message::SearchResults(entries) => {
             *queued_searches = queued_searches.saturating_sub(1);
             if pending_search_token.take().is_some() {
                 *search_results = entries;
-                if search_state.selected().is_none() {
-                    search_state.select_first();
+                for state in [&mut *search_state, &mut *search_favorites_state] {
+                    if state.selected().is_none() {
+                        state.select_first();
+                    }
                 }
             }
         }
@@ -351,7 +532,12 @@ fn handle_message(
         Message::Deleted(id) => {
             outstanding_request.take_if(|&mut req_id| req_id == id);
         }
-        Message::LoadedImage { id, image } => {
+        Message::Trashed(_) | Message::TooLargeToTrash(_) | Message::Restored(_) => unreachable!(),
+        Message::LoadedImage {
+            id,
+            thumbnail: _,
+            image,
+        } => {
             if let Some(ImageState::Requested(requested_id)) = ui.detail_image_state
                 && requested_id == id
             {
@@ -364,7 +550,7 @@ fn handle_message(
             }
             *pending_search_token = Some(token);
         }
-        Message::Pasted => return Ok(true),
+        Message::Pasted { keep_open: _ } => return Ok(true),
     }
     if ui.details_requested.is_some() {
         maybe_get_details(entries, ui, requests);
@@ -387,7 +573,13 @@ fn maybe_get_details(entries: &UiEntries, ui: &mut UiState, requests: &Sender<Co
     }
 }
 
-fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) -> bool {
+fn handle_event(
+    event: Event,
+    state: &mut State,
+    bindings: &KeyBindings,
+    requests: &Sender<Command>,
+    picked: Option<&mut Option<u64>>,
+) -> bool {
     let State { entries, ui } = state;
 
     let unselect = |ui: &mut UiState| {
@@ -419,7 +611,7 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
             state: _,
         }) => {
             if kind == KeyEventKind::Press {
-                use ratatui::crossterm::event::KeyCode::{Char, Down, Enter, Esc, Left, Right, Up};
+                use ratatui::crossterm::event::KeyCode::{Char, Down, Enter, Esc, Up};
                 match code {
                     Esc => {
                         if let Some(SearchState { focused, .. }) = &mut ui.search_state
@@ -440,10 +632,7 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                             && *focused
                         {
                             *focused = false;
-                        } else if let Some(&UiEntry { entry, cache: _ }) =
-                            selected_entry!(entries, ui)
-                        {
-                            let _ = requests.send(Command::Paste(entry.id()));
+                            return false;
                         }
                     }
                     _ => {}
@@ -481,19 +670,35 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                     } else if code == Up || code == Down {
                         *focused = false;
                     }
-                } else {
-                    match code {
-                        Char('q') => return true,
-                        Char('c') if modifiers == KeyModifiers::CONTROL => return true,
-                        Char(c @ '0'..='9') => {
-                            if let Some(UiEntry { entry, cache: _ }) = active_entries!(entries, ui)
-                                .get(usize::try_from(u32::from(c) - u32::from('0')).unwrap())
+                } else if let Char(c @ '0'..='9') = code {
+                    if let Some(UiEntry { entry, cache: _ }) = active_entries!(entries, ui)
+                        .get(usize::try_from(u32::from(c) - u32::from('0')).unwrap())
+                    {
+                        return paste(entry.id(), picked, requests);
+                    }
+                } else if let Some(action) = bindings.action(code, modifiers) {
+                    match action {
+                        KeyAction::Quit => return true,
+                        KeyAction::Paste => {
+                            if let Some(&UiEntry { entry, cache: _ }) = selected_entry!(entries, ui)
+                            {
+                                return paste(entry.id(), picked, requests);
+                            }
+                        }
+                        KeyAction::PastePrimary => {
+                            if let Some(&UiEntry { entry, cache: _ }) = selected_entry!(entries, ui)
                             {
-                                let _ = requests.send(Command::Paste(entry.id()));
+                                // Middle-click pastes the primary selection, so leave it to the
+                                // user rather than triggering a paste.
+                                let _ = requests.send(Command::Paste {
+                                    id: entry.id(),
+                                    keep_open: true,
+                                    target: PasteTarget::Primary,
+                                });
                             }
                         }
-                        Char('h') | Left => unselect(ui),
-                        Char('j') | Down => {
+                        KeyAction::HideDetails => unselect(ui),
+                        KeyAction::Down => {
                             let state = active_list_state!(entries, ui);
                             let len = active_entries!(entries, ui).len();
                             let next = state
@@ -501,10 +706,10 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                                 .map_or(0, |i| if i + 1 == len { 0 } else { i + 1 });
                             state.select(Some(next.min(len)));
                         }
-                        Char('J') => {
+                        KeyAction::ScrollDetailsDown => {
                             ui.detail_scroll = ui.detail_scroll.saturating_add(1);
                         }
-                        Char('k') | Up => {
+                        KeyAction::Up => {
                             let state = active_list_state!(entries, ui);
                             let len = active_entries!(entries, ui).len();
                             let previous = state.selected().map_or(usize::MAX, |i| {
@@ -518,21 +723,21 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                                 state.select(Some(previous.min(len)));
                             }
                         }
-                        Char('K') => {
+                        KeyAction::ScrollDetailsUp => {
                             ui.detail_scroll = ui.detail_scroll.saturating_sub(1);
                         }
-                        Char('l') | Right => maybe_get_details(entries, ui, requests),
-                        Char(' ') => {
+                        KeyAction::ShowDetails => maybe_get_details(entries, ui, requests),
+                        KeyAction::ToggleDetails => {
                             if ui.details_requested.is_some() {
                                 unselect(ui);
                             } else {
                                 maybe_get_details(entries, ui, requests);
                             }
                         }
-                        Char(c @ ('/' | 's' | 'x' | 'm')) => {
-                            let kind = match c {
-                                'x' => SearchKind::Regex,
-                                'm' => SearchKind::Mime,
+                        KeyAction::Search | KeyAction::RegexSearch | KeyAction::MimeSearch => {
+                            let kind = match action {
+                                KeyAction::RegexSearch => SearchKind::Regex,
+                                KeyAction::MimeSearch => SearchKind::Mime,
                                 _ => SearchKind::Plain,
                             };
                             ui.search_state = Some(SearchState {
@@ -541,7 +746,7 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                             });
                             search(ui, kind);
                         }
-                        Char('f') => {
+                        KeyAction::Favorite => {
                             if let Some(&UiEntry { entry, cache: _ }) = selected_entry!(entries, ui)
                                 && ui.outstanding_request != Some(entry.id())
                             {
@@ -557,7 +762,7 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                                 refresh(ui);
                             }
                         }
-                        Char('d') => {
+                        KeyAction::Delete => {
                             if let Some(&UiEntry { entry, cache: _ }) = selected_entry!(entries, ui)
                                 && ui.outstanding_request != Some(entry.id())
                             {
@@ -566,17 +771,27 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
                                 refresh(ui);
                             }
                         }
-                        Char('?') => {
+                        KeyAction::Help => {
                             ui.show_help ^= true;
                         }
-                        Char('r') => {
-                            if modifiers == KeyModifiers::CONTROL {
+                        KeyAction::SwitchPane => {
+                            if let Some(pane) = &mut ui.focused_pane {
+                                *pane = pane.other();
+                                let state = active_list_state!(entries, ui);
+                                if state.selected().is_none() {
+                                    state.select_first();
+                                }
+                            }
+                        }
+                        KeyAction::Refresh | KeyAction::Reset => {
+                            if action == KeyAction::Reset {
+                                let split = ui.focused_pane.is_some();
                                 *state = State::default();
+                                state.ui.focused_pane = split.then_some(Pane::Main);
                             }
                             refresh(&mut state.ui);
                             return false;
                         }
-                        _ => {}
                     }
                 }
             }
@@ -592,9 +807,24 @@ fn handle_event(event: Event, state: &mut State, requests: &Sender<Command>) ->
     false
 }
 
+/// Pastes the entry or, when picking, records it. Returns whether to quit.
+fn paste(id: u64, picked: Option<&mut Option<u64>>, requests: &Sender<Command>) -> bool {
+    if let Some(picked) = picked {
+        *picked = Some(id);
+        return true;
+    }
+    let _ = requests.send(Command::Paste {
+        id,
+        keep_open: false,
+        target: PasteTarget::default(),
+    });
+    false
+}
+
 struct AppWrapper<'a> {
     requests: &'a Sender<Command>,
     state: &'a mut State,
+    help: &'a str,
 }
 
 impl AppWrapper<'_> {
@@ -638,14 +868,59 @@ impl Widget for &mut AppWrapper<'_> {
             }
             .areas(main_area);
 
-        AppWrapper::render_title(header_area, buf);
+        self.render_title(header_area, buf);
         self.render_entries(entry_list_area, buf);
         if has_error {
             self.render_error(selected_entry_area, buf);
         } else {
             self.render_selected_entry(selected_entry_area, buf);
         }
-        AppWrapper::render_footer(footer_area, buf);
+        AppWrapper::render_footer(footer_area, buf, self.help);
+    }
+}
+
+struct HumanBytes(u64);
+
+impl Display for HumanBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+        let mut size = self.0;
+        let mut unit = 0;
+        while size >= 1024 * 10 && unit < UNITS.len() - 1 {
+            size /= 1024;
+            unit += 1;
+        }
+        write!(f, "{size} {}", UNITS[unit])
+    }
+}
+
+/// Formats `data` like `hexdump -C`: an offset, sixteen hex bytes, and their
+/// printable ASCII characters per line.
+fn hex_dump(out: &mut String, data: &[u8]) {
+    for (i, line) in data.chunks(16).enumerate() {
+        write!(out, "{:08x} ", i * 16).unwrap();
+        for j in 0..16 {
+            if j % 8 == 0 {
+                out.push(' ');
+            }
+            match line.get(j) {
+                Some(b) => write!(out, "{b:02x} ").unwrap(),
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                char::from(b)
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    if data.len() == BINARY_PREFIX_MAX_LEN {
+        out.push('…');
     }
 }
 
@@ -669,11 +944,55 @@ fn ui_entry_line(UiEntry { entry: _, cache }: &UiEntry) -> Line {
     }
 }
 
+fn render_entry_list(
+    entries: &[UiEntry],
+    state: &mut ListState,
+    title: &str,
+    focused: bool,
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let outer_block = Block::new()
+        .title_alignment(Alignment::Center)
+        .borders(Borders::TOP)
+        .border_style(if focused {
+            Style::new().bold()
+        } else {
+            Style::default()
+        })
+        .title(title);
+    let inner_block = Block::new().borders(Borders::NONE);
+    let inner_area = outer_block.inner(area);
+
+    outer_block.render(area, buf);
+
+    if entries.is_empty() {
+        Line::raw("Nothing to see here…")
+            .italic()
+            .render(inner_area, buf);
+    } else {
+        StatefulWidget::render(
+            List::new(entries.iter().map(ui_entry_line))
+                .block(inner_block)
+                .highlight_style(
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .add_modifier(Modifier::REVERSED),
+                )
+                .highlight_spacing(HighlightSpacing::Always),
+            inner_area,
+            buf,
+            state,
+        );
+    }
+}
+
 impl AppWrapper<'_> {
     fn render_entries(&mut self, area: Rect, buf: &mut Buffer) {
         let Self {
             state: State { entries, ui },
             requests: _,
+            help: _,
         } = self;
 
         let [search_area, entries_area] = Layout::vertical([
@@ -704,40 +1023,53 @@ impl AppWrapper<'_> {
             ui.query.render(search_area, buf);
         }
 
-        let outer_block = Block::new()
-            .title_alignment(Alignment::Center)
-            .borders(Borders::TOP)
-            .title("Entries");
-        let inner_block = Block::new().borders(Borders::NONE);
-        let inner_area = outer_block.inner(entries_area);
-
-        outer_block.render(entries_area, buf);
+        let (all_entries, main_state, favorites_state) = if ui.query.is_empty() {
+            (
+                &entries.loaded_entries,
+                &mut entries.loaded_state,
+                &mut entries.loaded_favorites_state,
+            )
+        } else {
+            (
+                &entries.search_results,
+                &mut entries.search_state,
+                &mut entries.search_favorites_state,
+            )
+        };
+        let Some(focused_pane) = ui.focused_pane else {
+            render_entry_list(all_entries, main_state, "Entries", false, entries_area, buf);
+            return;
+        };
 
-        if active_entries!(entries, ui).is_empty() {
-            Line::raw("Nothing to see here…")
-                .italic()
-                .render(inner_area, buf);
+        let [favorites_area, main_area] = if entries_area.width <= entries_area.height * 3 {
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
         } else {
-            StatefulWidget::render(
-                List::new(active_entries!(entries, ui).iter().map(ui_entry_line))
-                    .block(inner_block)
-                    .highlight_style(
-                        Style::default()
-                            .add_modifier(Modifier::BOLD)
-                            .add_modifier(Modifier::REVERSED),
-                    )
-                    .highlight_spacing(HighlightSpacing::Always),
-                inner_area,
-                buf,
-                active_list_state!(entries, ui),
-            );
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
         }
+        .areas(entries_area);
+        render_entry_list(
+            pane_entries(all_entries, Some(Pane::Favorites)),
+            favorites_state,
+            "Favorites",
+            focused_pane == Pane::Favorites,
+            favorites_area,
+            buf,
+        );
+        render_entry_list(
+            pane_entries(all_entries, Some(Pane::Main)),
+            main_state,
+            "Entries",
+            focused_pane == Pane::Main,
+            main_area,
+            buf,
+        );
     }
 
     fn render_selected_entry(&mut self, area: Rect, buf: &mut Buffer) {
         let Self {
             state: State { entries, ui },
             requests,
+            help: _,
         } = self;
         if area.is_empty() {
             return;
@@ -747,11 +1079,13 @@ impl AppWrapper<'_> {
         };
 
         let outer_block = {
-            let mime_type = ui
+            let (mime_type, label, content_kind) = ui
                 .detailed_entry
                 .as_ref()
                 .and_then(|r| r.as_ref().ok())
-                .map_or("", |d| &*d.mime_type);
+                .map_or(("", "", None), |d| {
+                    (&*d.mime_type, &*d.label, Some(d.content_kind))
+                });
 
             Block::new()
                 .borders(Borders::TOP)
@@ -768,6 +1102,12 @@ impl AppWrapper<'_> {
                         entry.id()
                     )
                     .unwrap();
+                    if !label.is_empty() {
+                        write!(ui.cache, "; {label}").unwrap();
+                    }
+                    if let Some(content_kind) = content_kind {
+                        write!(ui.cache, "; {content_kind}").unwrap();
+                    }
                     if mime_type.is_empty() {
                         write!(ui.cache, ")")
                     } else {
@@ -800,28 +1140,58 @@ impl AppWrapper<'_> {
             }
             if ui.detail_image_state.is_none() {
                 ui.detail_image_state = Some(ImageState::Requested(entry.id()));
-                let _ = requests.send(Command::LoadImage(entry.id()));
+                let _ = requests.send(Command::LoadImage {
+                    id: entry.id(),
+                    thumbnail: false,
+                });
             }
         } else {
-            Paragraph::new(ui.detailed_entry.as_ref().map_or("Loading…", |r| match r {
-                Ok(DetailedEntry {
+            let text = match &ui.detailed_entry {
+                None => "Loading…",
+                Some(Ok(DetailedEntry {
                     mime_type: _,
-                    full_text,
-                }) => full_text.as_deref().unwrap_or("Binary data."),
-                Err(_) => &error,
-            }))
-            .block(inner_block)
-            .wrap(Wrap { trim: false })
-            .scroll((ui.detail_scroll, 0))
-            .render(inner_area, buf);
+                    content_kind: _,
+                    label: _,
+                    full_text: Some(full_text),
+                    binary_prefix: _,
+                })) => full_text,
+                Some(Ok(DetailedEntry {
+                    mime_type: _,
+                    content_kind: _,
+                    label: _,
+                    full_text: None,
+                    binary_prefix: Some(prefix),
+                })) => {
+                    ui.cache.clear();
+                    hex_dump(&mut ui.cache, prefix);
+                    &ui.cache
+                }
+                Some(Ok(_)) => "Binary data.",
+                Some(Err(_)) => &error,
+            };
+            Paragraph::new(text)
+                .block(inner_block)
+                .wrap(Wrap { trim: false })
+                .scroll((ui.detail_scroll, 0))
+                .render(inner_area, buf);
         }
     }
 
-    fn render_title(area: Rect, buf: &mut Buffer) {
-        Paragraph::new(concat!("Ringboard v", env!("CARGO_PKG_VERSION")))
-            .bold()
-            .centered()
-            .render(area, buf);
+    fn render_title(&self, area: Rect, buf: &mut Buffer) {
+        let UiEntries {
+            loaded_entries,
+            database_size,
+            ..
+        } = &self.state.entries;
+        Paragraph::new(format!(
+            "Ringboard v{} · {} entries · {}",
+            env!("CARGO_PKG_VERSION"),
+            loaded_entries.len(),
+            HumanBytes(*database_size),
+        ))
+        .bold()
+        .centered()
+        .render(area, buf);
     }
 
     fn render_error(&self, area: Rect, buf: &mut Buffer) {
@@ -848,7 +1218,7 @@ impl AppWrapper<'_> {
             .render(inner_area, buf);
     }
 
-    fn render_footer(area: Rect, buf: &mut Buffer) {
+    fn render_footer(area: Rect, buf: &mut Buffer, help: &str) {
         if area.is_empty() {
             return;
         }
@@ -862,13 +1232,10 @@ impl AppWrapper<'_> {
 
         outer_block.render(area, buf);
 
-        Paragraph::new(
-            "Use ↓↑ to move, ←→ to (un)select, / to search, x to search with RegEx, m to search \
-             mime types, r to reload, f to (un)favorite, d to delete, J/K to scroll entry details.",
-        )
-        .wrap(Wrap { trim: true })
-        .block(inner_block)
-        .centered()
-        .render(inner_area, buf);
+        Paragraph::new(help)
+            .wrap(Wrap { trim: true })
+            .block(inner_block)
+            .centered()
+            .render(inner_area, buf);
     }
 }
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, mem};
 
 use ringboard_sdk::core::{is_plaintext_mime, protocol::MimeType};
 
@@ -19,6 +19,21 @@ pub struct BestMimeTypeFinder<Id> {
     seen: KnownSeenMimes<Id>,
     best_mime: MimeType,
     block_plain_text: bool,
+    alternates: Alternates<Id>,
+}
+
+/// Richer representations of the same content worth storing next to the best
+/// target so paste targets can ask for them, e.g. the HTML of copied rich text.
+const ALTERNATE_MIMES: [&str; 4] = ["text/html", "text/rtf", "application/rtf", "text/uri-list"];
+
+/// The alternate representations offered alongside a selection's best target.
+#[derive(Debug)]
+pub struct Alternates<Id>([Option<(Id, MimeType)>; ALTERNATE_MIMES.len()]);
+
+impl<Id> Default for Alternates<Id> {
+    fn default() -> Self {
+        Self([const { None }; ALTERNATE_MIMES.len()])
+    }
 }
 
 mod id {
@@ -45,8 +60,17 @@ mod id {
     }
 }
 
-impl<Id: id::AsId<Id: Eq>> BestMimeTypeFinder<Id> {
+impl<Id: id::AsId<Id: Eq> + Clone> BestMimeTypeFinder<Id> {
     pub fn add_mime(&mut self, mime: &MimeType, id: Id) {
+        let Alternates(alternates) = &mut self.alternates;
+        if let Some(i) = ALTERNATE_MIMES
+            .iter()
+            .position(|&m| mime.split(';').next() == Some(m))
+            && alternates[i].is_none()
+        {
+            alternates[i] = Some((id.clone(), *mime));
+        }
+
         let Self {
             seen:
                 KnownSeenMimes {
@@ -63,6 +87,7 @@ impl<Id: id::AsId<Id: Eq>> BestMimeTypeFinder<Id> {
                 },
             ref mut best_mime,
             block_plain_text,
+            alternates: _,
         } = *self;
 
         let target = if is_plaintext_mime(mime) {
@@ -125,6 +150,22 @@ impl<Id> BestMimeTypeFinder<Id> {
             .take()
             .map(|SeenMime { id, has_params: _ }| id)
     }
+
+    pub fn take_alternates(&mut self) -> Alternates<Id> {
+        mem::take(&mut self.alternates)
+    }
+}
+
+impl<Id> Alternates<Id> {
+    /// Takes the next alternate worth storing next to an entry saved as
+    /// `stored`, skipping the representation that was already stored.
+    pub fn pop(&mut self, stored: &MimeType) -> Option<(Id, MimeType)> {
+        let Self(alternates) = self;
+        alternates
+            .iter_mut()
+            .filter_map(Option::take)
+            .find(|(_, mime)| mime != stored)
+    }
 }
 
 impl<Id: Copy> BestMimeTypeFinder<Id> {
@@ -11,7 +11,7 @@ use ringboard_sdk::{
     core::{
         Error as CoreError, IoErr,
         dirs::data_dir,
-        protocol::{RingKind, composite_id, decompose_id},
+        protocol::{AddResponse, RingKind, composite_id, decompose_id},
         read_at_to_end,
         ring::Mmap,
     },
@@ -168,6 +168,32 @@ impl CopyDeduplication {
         Self::remember_(&mut self.main, &mut self.favorites, hash, id);
     }
 
+    /// Remembers the entry the server reports having added for data of `len`
+    /// bytes, returning the entry's ID or [`None`] if nothing was added.
+    ///
+    /// When the server echoes what it stored, the entry is only remembered if
+    /// the sizes agree since `hash` would otherwise describe different data.
+    pub fn remember_added(&mut self, hash: u64, len: u64, response: AddResponse) -> Option<u64> {
+        match response {
+            AddResponse::Success { id } => {
+                self.remember(hash, id);
+                Some(id)
+            }
+            AddResponse::Stored { id, size, .. } if size == len => {
+                self.remember(hash, id);
+                Some(id)
+            }
+            AddResponse::Stored { id, size, .. } => {
+                warn!(
+                    "Server stored {size} bytes for entry {id} but {len} were sent, not using it \
+                     for duplicate detection."
+                );
+                Some(id)
+            }
+            AddResponse::RateLimited => None,
+        }
+    }
+
     fn remember_<const A: usize, const B: usize>(
         main: &mut ArrayMap<A>,
         favorites: &mut ArrayMap<B>,
@@ -1,14 +1,28 @@
 use std::{
+    fs::File,
     io::IoSliceMut,
+    mem::offset_of,
     os::fd::{AsFd, OwnedFd},
 };
 
+use log::warn;
 use ringboard_sdk::{
     ClientError,
-    api::{PASTE_SERVER_PROTOCOL_VERSION, PasteCommand},
-    core::IoErr,
+    api::{PASTE_SERVER_PROTOCOL_VERSION, PasteCommand, PasteTarget},
+    core::{
+        IoErr, THUMBNAIL_MIME_TYPE,
+        dirs::data_dir,
+        protocol::{MimeType, decompose_id},
+        ring::Mmap,
+    },
+    read_alternates,
+    thumbnail::store_thumbnail,
+};
+use rustix::{
+    fs::{CWD, Mode, OFlags, openat},
+    io::Errno,
+    net::{RecvAncillaryBuffer, RecvAncillaryMessage::ScmRights, RecvFlags, recvmsg},
 };
-use rustix::net::{RecvAncillaryBuffer, RecvAncillaryMessage::ScmRights, RecvFlags, recvmsg};
 
 pub fn read_paste_command(
     paste_socket: impl AsFd,
@@ -36,6 +50,11 @@ pub fn read_paste_command(
         });
     }
     debug_assert!(!msg.flags.contains(RecvFlags::TRUNC));
+    if buf[offset_of!(PasteCommand, target)] > PasteTarget::Primary as u8 {
+        return Err(ClientError::InvalidResponse {
+            context: "Bad paste target.".into(),
+        });
+    }
 
     let mut data = None;
     for msg in ancillary.drain() {
@@ -52,3 +71,52 @@ pub fn read_paste_command(
         data,
     ))
 }
+
+/// Opens the alternate representations of the entry with the given `id` so
+/// paste servers can offer them next to its main mime type.
+///
+/// Thumbnails are skipped since they are a lossy copy for frontends. Failures
+/// are logged instead of returned as the entry can still be pasted without
+/// its alternates.
+#[must_use]
+pub fn read_paste_alternates(id: u64) -> Vec<(MimeType, File)> {
+    let read = || -> Result<_, ClientError> {
+        let (ring, index) = decompose_id(id)?;
+        let dir = data_dir().join("alternates");
+        let dir = match openat(CWD, &dir, OFlags::DIRECTORY | OFlags::PATH, Mode::empty()) {
+            Err(Errno::NOENT) => return Ok(Vec::new()),
+            r => r.map_io_err(|| format!("Failed to open directory: {dir:?}"))?,
+        };
+        Ok(read_alternates(dir, ring, index)?)
+    };
+
+    match read() {
+        Ok(mut alternates) => {
+            alternates.retain(|(mime_type, _)| mime_type.as_str() != THUMBNAIL_MIME_TYPE);
+            alternates
+        }
+        Err(e) => {
+            warn!("Failed to read alternates of entry {id}, pasting without them: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Stores a thumbnail of a newly added image entry so frontends can preview it
+/// without decoding the original.
+///
+/// Failures are logged instead of returned since frontends fall back to
+/// downscaling the original image.
+pub fn add_thumbnail(server: impl AsFd, id: u64, mime_type: &MimeType, data: impl AsFd) {
+    if !mime_type.starts_with("image/") {
+        return;
+    }
+
+    let result = Mmap::from(data)
+        .map_io_err(|| "Failed to map image entry.")
+        .map_err(ClientError::from)
+        .and_then(|image| store_thumbnail(server, id, mime_type, &image));
+    if let Err(e) = result {
+        warn!("Failed to store thumbnail for entry {id}: {e}");
+    }
+}
@@ -0,0 +1,36 @@
+use clipboard_history_watcher_utils::best_target::BestMimeTypeFinder;
+use ringboard_sdk::core::protocol::MimeType;
+
+fn finder(mimes: &[&str]) -> BestMimeTypeFinder<u32> {
+    let mut finder = BestMimeTypeFinder::default();
+    for (id, mime) in (0..).zip(mimes) {
+        finder.add_mime(&MimeType::from(mime).unwrap(), id);
+    }
+    finder
+}
+
+fn mime(mime: &str) -> MimeType {
+    MimeType::from(mime).unwrap()
+}
+
+#[test]
+fn rich_text_alternates() {
+    let mut finder = finder(&["text/html", "text/plain", "text/rtf", "image/png"]);
+    let mut alternates = finder.take_alternates();
+    assert_eq!(finder.best(), Some((1, mime("text/plain"))));
+
+    let stored = mime("text/plain");
+    assert_eq!(alternates.pop(&stored), Some((0, mime("text/html"))));
+    assert_eq!(alternates.pop(&stored), Some((2, mime("text/rtf"))));
+    assert_eq!(alternates.pop(&stored), None);
+}
+
+#[test]
+fn stored_mime_is_not_an_alternate() {
+    let mut finder = finder(&["text/html;charset=utf-8", "text/uri-list"]);
+    let mut alternates = finder.take_alternates();
+
+    let stored = mime("text/html;charset=utf-8");
+    assert_eq!(alternates.pop(&stored), Some((1, mime("text/uri-list"))));
+    assert_eq!(alternates.pop(&stored), None);
+}
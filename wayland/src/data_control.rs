@@ -0,0 +1,357 @@
+//! The wlr and ext data control protocols are identical apart from their names,
+//! so each object is wrapped in an enum that forwards to whichever protocol the
+//! compositor offers and events are translated into protocol agnostic ones.
+
+use std::os::fd::{BorrowedFd, OwnedFd};
+
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle, backend::ObjectId, event_created_child,
+    protocol::wl_seat::WlSeat,
+};
+use wayland_protocols::ext::data_control::v1::client::{
+    ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+    ext_data_control_manager_v1::ExtDataControlManagerV1,
+    ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+    ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use crate::{App, Destroyable};
+
+#[derive(Debug)]
+pub enum Manager {
+    Wlr(ZwlrDataControlManagerV1),
+    Ext(ExtDataControlManagerV1),
+}
+
+#[derive(Debug)]
+pub enum Device {
+    Wlr(ZwlrDataControlDeviceV1),
+    Ext(ExtDataControlDeviceV1),
+}
+
+#[derive(Debug)]
+pub enum Offer {
+    Wlr(ZwlrDataControlOfferV1),
+    Ext(ExtDataControlOfferV1),
+}
+
+#[derive(Debug)]
+pub enum Source {
+    Wlr(ZwlrDataControlSourceV1),
+    Ext(ExtDataControlSourceV1),
+}
+
+#[derive(Debug)]
+pub enum DeviceEvent {
+    DataOffer(Offer),
+    Selection(Option<Offer>),
+    PrimarySelection(Option<Offer>),
+    Finished,
+}
+
+#[derive(Debug)]
+pub enum SourceEvent {
+    Send { mime_type: String, fd: OwnedFd },
+    Cancelled,
+}
+
+impl Manager {
+    pub fn interface(&self) -> &'static str {
+        match self {
+            Self::Wlr(_) => ZwlrDataControlManagerV1::interface().name,
+            Self::Ext(_) => ExtDataControlManagerV1::interface().name,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            Self::Wlr(m) => m.version(),
+            Self::Ext(m) => m.version(),
+        }
+    }
+
+    pub fn supports_primary_selection(&self) -> bool {
+        match self {
+            Self::Wlr(m) => {
+                m.version() >= zwlr_data_control_device_v1::REQ_SET_PRIMARY_SELECTION_SINCE
+            }
+            Self::Ext(_) => true,
+        }
+    }
+
+    pub fn get_data_device(&self, seat: &WlSeat, qh: &QueueHandle<App>, id: u32) -> Device {
+        match self {
+            Self::Wlr(m) => Device::Wlr(m.get_data_device(seat, qh, id)),
+            Self::Ext(m) => Device::Ext(m.get_data_device(seat, qh, id)),
+        }
+    }
+
+    pub fn create_data_source(&self, qh: &QueueHandle<App>, id: usize) -> Source {
+        match self {
+            Self::Wlr(m) => Source::Wlr(m.create_data_source(qh, id)),
+            Self::Ext(m) => Source::Ext(m.create_data_source(qh, id)),
+        }
+    }
+}
+
+impl Device {
+    pub fn supports_primary_selection(&self) -> bool {
+        match self {
+            Self::Wlr(d) => {
+                d.version() >= zwlr_data_control_device_v1::REQ_SET_PRIMARY_SELECTION_SINCE
+            }
+            Self::Ext(_) => true,
+        }
+    }
+
+    pub fn set_selection(&self, source: Option<&Source>) {
+        match (self, source) {
+            (Self::Wlr(d), None) => d.set_selection(None),
+            (Self::Wlr(d), Some(Source::Wlr(s))) => d.set_selection(Some(s)),
+            (Self::Ext(d), None) => d.set_selection(None),
+            (Self::Ext(d), Some(Source::Ext(s))) => d.set_selection(Some(s)),
+            (Self::Wlr(_), Some(Source::Ext(_))) | (Self::Ext(_), Some(Source::Wlr(_))) => {
+                unreachable!()
+            }
+        }
+    }
+
+    pub fn set_primary_selection(&self, source: Option<&Source>) {
+        match (self, source) {
+            (Self::Wlr(d), None) => d.set_primary_selection(None),
+            (Self::Wlr(d), Some(Source::Wlr(s))) => d.set_primary_selection(Some(s)),
+            (Self::Ext(d), None) => d.set_primary_selection(None),
+            (Self::Ext(d), Some(Source::Ext(s))) => d.set_primary_selection(Some(s)),
+            (Self::Wlr(_), Some(Source::Ext(_))) | (Self::Ext(_), Some(Source::Wlr(_))) => {
+                unreachable!()
+            }
+        }
+    }
+}
+
+impl Offer {
+    pub fn id(&self) -> ObjectId {
+        match self {
+            Self::Wlr(o) => o.id(),
+            Self::Ext(o) => o.id(),
+        }
+    }
+
+    pub fn receive(&self, mime_type: String, fd: BorrowedFd) {
+        match self {
+            Self::Wlr(o) => o.receive(mime_type, fd),
+            Self::Ext(o) => o.receive(mime_type, fd),
+        }
+    }
+}
+
+impl Source {
+    pub fn offer(&self, mime_type: String) {
+        match self {
+            Self::Wlr(s) => s.offer(mime_type),
+            Self::Ext(s) => s.offer(mime_type),
+        }
+    }
+}
+
+impl Destroyable for Manager {
+    fn destroy(&self) {
+        match self {
+            Self::Wlr(m) => m.destroy(),
+            Self::Ext(m) => m.destroy(),
+        }
+    }
+}
+
+impl Destroyable for Device {
+    fn destroy(&self) {
+        match self {
+            Self::Wlr(d) => d.destroy(),
+            Self::Ext(d) => d.destroy(),
+        }
+    }
+}
+
+impl Destroyable for Offer {
+    fn destroy(&self) {
+        match self {
+            Self::Wlr(o) => o.destroy(),
+            Self::Ext(o) => o.destroy(),
+        }
+    }
+}
+
+impl Destroyable for Source {
+    fn destroy(&self) {
+        match self {
+            Self::Wlr(s) => s.destroy(),
+            Self::Ext(s) => s.destroy(),
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for App {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrDataControlManagerV1,
+        event: <ZwlrDataControlManagerV1 as Proxy>::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        debug_assert!(false, "Unhandled data control manager event: {event:?}");
+    }
+}
+
+impl Dispatch<ExtDataControlManagerV1, ()> for App {
+    fn event(
+        _: &mut Self,
+        _: &ExtDataControlManagerV1,
+        event: <ExtDataControlManagerV1 as Proxy>::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        debug_assert!(false, "Unhandled data control manager event: {event:?}");
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, u32> for App {
+    fn event(
+        this: &mut Self,
+        _: &ZwlrDataControlDeviceV1,
+        event: <ZwlrDataControlDeviceV1 as Proxy>::Event,
+        &seat: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_data_control_device_v1::Event;
+        let event = match event {
+            Event::DataOffer { id } => DeviceEvent::DataOffer(Offer::Wlr(id)),
+            Event::Selection { id } => DeviceEvent::Selection(id.map(Offer::Wlr)),
+            Event::PrimarySelection { id } => DeviceEvent::PrimarySelection(id.map(Offer::Wlr)),
+            Event::Finished => DeviceEvent::Finished,
+            _ => {
+                debug_assert!(false, "Unhandled data control device event: {event:?}");
+                return;
+            }
+        };
+        this.handle_device_event(seat, event);
+    }
+
+    event_created_child!(Self, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtDataControlDeviceV1, u32> for App {
+    fn event(
+        this: &mut Self,
+        _: &ExtDataControlDeviceV1,
+        event: <ExtDataControlDeviceV1 as Proxy>::Event,
+        &seat: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_data_control_device_v1::Event;
+        let event = match event {
+            Event::DataOffer { id } => DeviceEvent::DataOffer(Offer::Ext(id)),
+            Event::Selection { id } => DeviceEvent::Selection(id.map(Offer::Ext)),
+            Event::PrimarySelection { id } => DeviceEvent::PrimarySelection(id.map(Offer::Ext)),
+            Event::Finished => DeviceEvent::Finished,
+            _ => {
+                debug_assert!(false, "Unhandled data control device event: {event:?}");
+                return;
+            }
+        };
+        this.handle_device_event(seat, event);
+    }
+
+    event_created_child!(Self, ExtDataControlDeviceV1, [
+        ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for App {
+    fn event(
+        this: &mut Self,
+        id: &ZwlrDataControlOfferV1,
+        event: <ZwlrDataControlOfferV1 as Proxy>::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_data_control_offer_v1::Event;
+        match event {
+            Event::Offer { mime_type } => this.handle_offer_event(id.id(), mime_type),
+            _ => debug_assert!(false, "Unhandled data control offer event: {event:?}"),
+        }
+    }
+}
+
+impl Dispatch<ExtDataControlOfferV1, ()> for App {
+    fn event(
+        this: &mut Self,
+        id: &ExtDataControlOfferV1,
+        event: <ExtDataControlOfferV1 as Proxy>::Event,
+        (): &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_data_control_offer_v1::Event;
+        match event {
+            Event::Offer { mime_type } => this.handle_offer_event(id.id(), mime_type),
+            _ => debug_assert!(false, "Unhandled data control offer event: {event:?}"),
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, usize> for App {
+    fn event(
+        this: &mut Self,
+        _: &ZwlrDataControlSourceV1,
+        event: <ZwlrDataControlSourceV1 as Proxy>::Event,
+        &id: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use zwlr_data_control_source_v1::Event;
+        let event = match event {
+            Event::Send { mime_type, fd } => SourceEvent::Send { mime_type, fd },
+            Event::Cancelled => SourceEvent::Cancelled,
+            _ => {
+                debug_assert!(false, "Unhandled data control source event: {event:?}");
+                return;
+            }
+        };
+        this.handle_source_event(id, event);
+    }
+}
+
+impl Dispatch<ExtDataControlSourceV1, usize> for App {
+    fn event(
+        this: &mut Self,
+        _: &ExtDataControlSourceV1,
+        event: <ExtDataControlSourceV1 as Proxy>::Event,
+        &id: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        use ext_data_control_source_v1::Event;
+        let event = match event {
+            Event::Send { mime_type, fd } => SourceEvent::Send { mime_type, fd },
+            Event::Cancelled => SourceEvent::Cancelled,
+            _ => {
+                debug_assert!(false, "Unhandled data control source event: {event:?}");
+                return;
+            }
+        };
+        this.handle_source_event(id, event);
+    }
+}
@@ -1,53 +1,65 @@
 #![feature(let_chains)]
 
 use std::{
+    cmp::min,
     collections::HashMap,
     convert::identity,
+    env,
     fmt::{Debug, Formatter},
     fs::File,
     hash::BuildHasherDefault,
     io,
     io::ErrorKind::WouldBlock,
     mem,
-    mem::ManuallyDrop,
+    mem::{ManuallyDrop, MaybeUninit},
     ops::Deref,
     os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
     rc::Rc,
+    time::Duration,
 };
 
 use arrayvec::ArrayVec;
 use error_stack::Report;
 use log::{debug, error, info, trace, warn};
 use ringboard_sdk::{
-    api::{AddRequest, MoveToFrontRequest, PasteCommand, connect_to_server},
+    api::{
+        AddAlternateRequest, AddRequest, MoveToFrontRequest, PasteCommand, PasteTarget,
+        connect_to_server,
+    },
     core::{
-        Error, IoErr, create_tmp_file,
+        Error, IoErr, SocketFileGuard, create_tmp_file,
         dirs::{paste_socket_file, socket_file},
-        init_unix_server, is_plaintext_mime,
+        init_unix_server, is_plaintext_mime, looks_like_secret,
         protocol::{
-            AddResponse, IdNotFoundError, MimeType, MoveToFrontResponse, Response, RingKind,
+            ADD_FLAG_ECHO, AddAlternateResponse, IdNotFoundError, MimeType, MoveToFrontResponse,
+            Response, RingKind, mime_type_from_bytes,
         },
         ring::Mmap,
+        socket_addr,
     },
     is_text_mime,
 };
 use ringboard_watcher_utils::{
-    best_target::BestMimeTypeFinder,
+    best_target::{Alternates, BestMimeTypeFinder},
     deduplication::{CopyData, CopyDeduplication},
-    utils::read_paste_command,
+    utils::{add_thumbnail, read_paste_alternates, read_paste_command},
 };
 use rustc_hash::FxHasher;
 use rustix::{
     event::epoll,
     fs::{CWD, MemfdFlags, Mode, OFlags, memfd_create},
-    io::Errno,
-    net::{RecvFlags, SendFlags, SocketAddrUnix, SocketType},
+    io::{Errno, read_uninit},
+    net::{RecvFlags, SendFlags, SocketType},
     pipe::{SpliceFlags, pipe, splice},
+    time::{
+        Itimerspec, TimerfdClockId, TimerfdFlags, TimerfdTimerFlags, Timespec, timerfd_create,
+        timerfd_settime,
+    },
 };
 use thiserror::Error;
 use wayland_client::{
     ConnectError, Connection, Dispatch, DispatchError, Proxy, QueueHandle,
-    backend::WaylandError,
+    backend::{ObjectId, WaylandError},
     event_created_child,
     protocol::{
         wl_keyboard::{KeyState, WlKeyboard},
@@ -65,12 +77,10 @@ use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
     zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
     zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
 };
-use wayland_protocols_wlr::data_control::v1::client::{
-    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
-    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
-    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
-    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
-};
+
+use crate::data_control::{Device, DeviceEvent, Manager, Offer, Source, SourceEvent};
+
+mod data_control;
 
 #[derive(Error, Debug)]
 enum CliError {
@@ -87,6 +97,10 @@ enum CliError {
         message: &'static str,
         interface: &'static str,
     },
+    #[error("invalid arguments")]
+    InvalidArgs { context: String },
+    #[error("clipboard self-test failed")]
+    SelfTestFailed,
 }
 
 impl From<IdNotFoundError> for CliError {
@@ -111,7 +125,7 @@ fn main() -> error_stack::Result<(), Wrapper> {
         env_logger::init();
     }
 
-    run().map_err(into_report)
+    parse_args().and_then(run).map_err(into_report)
 }
 
 fn into_report(cli_err: CliError) -> Report<Wrapper> {
@@ -125,19 +139,84 @@ fn into_report(cli_err: CliError) -> Report<Wrapper> {
             message: _,
             interface: _,
         } => Report::new(wrapper),
+        CliError::InvalidArgs { context } => Report::new(wrapper)
+            .attach_printable(context)
+            .attach_printable(
+                "Usage: ringboard-wayland [--oneshot] [--ignore-secrets] [--self-test <SECONDS> \
+                 [--self-test-restart]]",
+            ),
+        CliError::SelfTestFailed => Report::new(wrapper).attach_printable(
+            "The compositor stopped sending selection events to the data control device.",
+        ),
+    }
+}
+
+#[derive(Default, Debug)]
+struct Args {
+    oneshot: bool,
+    ignore_secrets: bool,
+    self_test: Option<Duration>,
+    self_test_restart: bool,
+}
+
+fn parse_args() -> Result<Args, CliError> {
+    let mut args = Args::default();
+    let mut raw = env::args_os().skip(1);
+    while let Some(arg) = raw.next() {
+        match arg.to_str() {
+            Some("--oneshot") => args.oneshot = true,
+            Some("--ignore-secrets") => args.ignore_secrets = true,
+            Some("--self-test") => {
+                let Some(seconds) = raw
+                    .next()
+                    .and_then(|s| s.to_str().and_then(|s| s.parse().ok()))
+                    .filter(|&s| s > 0)
+                else {
+                    return Err(CliError::InvalidArgs {
+                        context: "--self-test expects a positive number of seconds".to_string(),
+                    });
+                };
+                args.self_test = Some(Duration::from_secs(seconds));
+            }
+            Some("--self-test-restart") => args.self_test_restart = true,
+            _ => {
+                return Err(CliError::InvalidArgs {
+                    context: format!("Unknown argument: {arg:?}"),
+                });
+            }
+        }
+    }
+
+    if args.oneshot && args.self_test.is_some() {
+        return Err(CliError::InvalidArgs {
+            context: "--self-test cannot be used with --oneshot".to_string(),
+        });
+    }
+    if args.self_test_restart && args.self_test.is_none() {
+        return Err(CliError::InvalidArgs {
+            context: "--self-test-restart requires --self-test".to_string(),
+        });
     }
+    Ok(args)
 }
 
-fn run() -> Result<(), CliError> {
+fn run(
+    Args {
+        oneshot,
+        ignore_secrets,
+        self_test,
+        self_test_restart,
+    }: Args,
+) -> Result<(), CliError> {
     info!(
-        "Starting Ringboard Wayland clipboard listener v{}.",
-        env!("CARGO_PKG_VERSION")
+        "Starting Ringboard Wayland clipboard listener v{}{}.",
+        env!("CARGO_PKG_VERSION"),
+        if oneshot { " in oneshot mode" } else { "" }
     );
 
     let server = {
         let socket_file = socket_file();
-        let addr = SocketAddrUnix::new(&socket_file)
-            .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))?;
+        let addr = socket_addr(&socket_file)?;
         connect_to_server(&addr)?
     };
     debug!("Ringboard connection established.");
@@ -145,14 +224,27 @@ fn run() -> Result<(), CliError> {
     let conn = Connection::connect_to_env()?;
     debug!("Wayland connection established.");
 
-    let paste_socket = init_unix_server(paste_socket_file(), SocketType::DGRAM)?;
-    debug!("Initialized paste server");
+    let (paste_socket, _paste_socket_guard) = if oneshot {
+        (None, None)
+    } else {
+        let paste_socket_file = paste_socket_file();
+        let paste_socket = init_unix_server(&paste_socket_file, SocketType::DGRAM)?;
+        debug!("Initialized paste server");
+        (
+            Some(paste_socket),
+            Some(SocketFileGuard::new(paste_socket_file)),
+        )
+    };
 
     let mut ancillary_buf = [0; rustix::cmsg_space!(ScmRights(1))];
 
     let epoll =
         epoll::create(epoll::CreateFlags::empty()).map_io_err(|| "Failed to create epoll.")?;
-    for (i, fd) in [conn.as_fd(), paste_socket.as_fd()].iter().enumerate() {
+    for (i, fd) in [Some(conn.as_fd()), paste_socket.as_ref().map(AsFd::as_fd)]
+        .iter()
+        .enumerate()
+    {
+        let Some(fd) = fd else { continue };
         epoll::add(
             &epoll,
             fd,
@@ -167,6 +259,7 @@ fn run() -> Result<(), CliError> {
         inner: AppDefault::default(),
         epoll,
     };
+    app.inner.pending_offers.ignore_secrets = ignore_secrets;
 
     let mut event_queue = conn.new_event_queue();
     let qh = event_queue.handle();
@@ -177,12 +270,30 @@ fn run() -> Result<(), CliError> {
     if let Some(e) = app.inner.error {
         return Err(e);
     }
-    if app.inner.manager.is_none() {
+    // Fall back to the standardized protocol only on compositors without wlr's.
+    if app.inner.manager.is_some() {
+        app.inner.ext_manager.take();
+    } else {
+        app.inner.manager = app.inner.ext_manager.take();
+    }
+    let Some(manager) = &app.inner.manager else {
         return Err(CliError::BadWaylandGlobal {
             message: "compositor does not implement necessary interface",
-            interface: "zwlr_data_control_manager_v1",
+            interface: "zwlr_data_control_manager_v1 or ext_data_control_manager_v1",
         });
     };
+    let version = manager.version();
+    let primary_supported = manager.supports_primary_selection();
+    info!(
+        "Using data control protocol {} version {version}.",
+        manager.interface()
+    );
+    if !primary_supported {
+        warn!(
+            "Data control protocol version {version} has no primary selection support: pasting \
+             into the primary selection will not work."
+        );
+    }
     if app.inner.virtual_keyboard_manager.is_none() {
         warn!("Virtual keyboard protocol not available: auto-paste will not work.");
     };
@@ -191,6 +302,36 @@ fn run() -> Result<(), CliError> {
     };
     debug!("Wayland globals initialized.");
 
+    let self_test_timer = match self_test {
+        Some(_) if !primary_supported => {
+            warn!("Self-test needs primary selection support, disabling it.");
+            None
+        }
+        Some(interval) => {
+            let timer = timerfd_create(TimerfdClockId::Monotonic, TimerfdFlags::empty())
+                .map_io_err(|| "Failed to create self-test timer.")?;
+            let interval = Timespec {
+                tv_sec: interval.as_secs().try_into().unwrap(),
+                tv_nsec: 0,
+            };
+            timerfd_settime(&timer, TimerfdTimerFlags::empty(), &Itimerspec {
+                it_interval: interval,
+                it_value: interval,
+            })
+            .map_io_err(|| "Failed to arm self-test timer.")?;
+            epoll::add(
+                &app.epoll,
+                &timer,
+                epoll::EventData::new_u64(SELF_TEST_IDX),
+                epoll::EventFlags::IN,
+            )
+            .map_io_err(|| "Failed to register epoll interest in self-test timer.")?;
+            info!("Running clipboard self-test every {}s.", interval.tv_sec);
+            Some(timer)
+        }
+        None => None,
+    };
+
     let mut epoll_events = epoll::EventVec::with_capacity(4);
 
     let mut deduplicator = CopyDeduplication::new()?;
@@ -200,6 +341,10 @@ fn run() -> Result<(), CliError> {
         if let Some(e) = app.inner.error {
             return Err(e);
         }
+        if oneshot && app.inner.selection_resolved {
+            info!("Oneshot capture complete, exiting.");
+            return Ok(());
+        }
         event_queue.flush().map_err(DispatchError::from)?;
 
         trace!("Waiting for event.");
@@ -208,17 +353,17 @@ fn run() -> Result<(), CliError> {
             r => r.map_io_err(|| "Failed to wait for epoll events.")?,
         };
         for epoll::Event { flags: _, data } in &epoll_events {
-            const OUT_START_IDX: u64 = IN_TRANSFER_BUFFERS as u64;
-            const WAYLAND_IDX: u64 = OUT_START_IDX + OUT_TRANSFER_BUFFERS as u64;
-            const PASTE_SERVER_IDX: u64 = WAYLAND_IDX + 1;
             match data.u64() {
-                idx @ ..OUT_START_IDX => app.inner.pending_offers.continue_transfer(
-                    &mut app.inner.tmp_file_unsupported,
-                    &server,
-                    &app.epoll,
-                    &mut deduplicator,
-                    usize::try_from(idx).unwrap(),
-                )?,
+                idx @ ..OUT_START_IDX => {
+                    let finished = app.inner.pending_offers.continue_transfer(
+                        &mut app.inner.tmp_file_unsupported,
+                        &server,
+                        &app.epoll,
+                        &mut deduplicator,
+                        usize::try_from(idx).unwrap(),
+                    )?;
+                    app.inner.selection_resolved |= finished;
+                }
                 idx @ OUT_START_IDX..WAYLAND_IDX => app
                     .inner
                     .outgoing_transfers
@@ -234,7 +379,7 @@ fn run() -> Result<(), CliError> {
                     trace!("Dispatched {count} events.");
                 }
                 PASTE_SERVER_IDX => handle_paste_event(
-                    &paste_socket,
+                    paste_socket.as_ref().unwrap(),
                     &mut ancillary_buf,
                     &qh,
                     app.inner.manager.as_ref(),
@@ -244,12 +389,38 @@ fn run() -> Result<(), CliError> {
                     &server,
                     &mut deduplicator,
                 )?,
+                SELF_TEST_IDX => {
+                    read_uninit(
+                        self_test_timer.as_ref().unwrap(),
+                        &mut [MaybeUninit::uninit(); 8],
+                    )
+                    .map_io_err(|| "Failed to clear self-test timer.")?;
+                    if !app.inner.self_test.tick(
+                        &qh,
+                        app.inner.manager.as_ref().unwrap(),
+                        &app.inner.seats,
+                        &app.inner.sources,
+                    ) {
+                        error!(
+                            "Self-test failed: the compositor did not report our sentinel \
+                             selection, clipboard events are likely no longer being received."
+                        );
+                        if self_test_restart {
+                            return Err(CliError::SelfTestFailed);
+                        }
+                    }
+                }
                 _ => unreachable!(),
             }
         }
     }
 }
 
+const OUT_START_IDX: u64 = IN_TRANSFER_BUFFERS as u64;
+const WAYLAND_IDX: u64 = OUT_START_IDX + OUT_TRANSFER_BUFFERS as u64;
+const PASTE_SERVER_IDX: u64 = WAYLAND_IDX + 1;
+const SELF_TEST_IDX: u64 = PASTE_SERVER_IDX + 1;
+
 trait Destroyable {
     fn destroy(&self);
 }
@@ -260,30 +431,6 @@ impl Destroyable for WlSeat {
     }
 }
 
-impl Destroyable for ZwlrDataControlManagerV1 {
-    fn destroy(&self) {
-        self.destroy();
-    }
-}
-
-impl Destroyable for ZwlrDataControlDeviceV1 {
-    fn destroy(&self) {
-        self.destroy();
-    }
-}
-
-impl Destroyable for ZwlrDataControlOfferV1 {
-    fn destroy(&self) {
-        self.destroy();
-    }
-}
-
-impl Destroyable for ZwlrDataControlSourceV1 {
-    fn destroy(&self) {
-        self.destroy();
-    }
-}
-
 impl Destroyable for WlKeyboard {
     fn destroy(&self) {
         self.release();
@@ -332,7 +479,7 @@ impl<T: Destroyable> Drop for AutoDestroy<T> {
 
 type SeatStore = (
     AutoDestroy<WlSeat>,
-    AutoDestroy<ZwlrDataControlDeviceV1>,
+    AutoDestroy<Device>,
     AutoDestroy<WlKeyboard>,
     Option<AutoDestroy<ZwpVirtualKeyboardV1>>,
 );
@@ -345,13 +492,7 @@ struct Seats {
 }
 
 impl Seats {
-    fn add(
-        &mut self,
-        seat: u32,
-        seat_obj: WlSeat,
-        device: ZwlrDataControlDeviceV1,
-        keyboard: WlKeyboard,
-    ) {
+    fn add(&mut self, seat: u32, seat_obj: WlSeat, device: Device, keyboard: WlKeyboard) {
         let Self {
             active,
             first,
@@ -437,10 +578,13 @@ const IN_TRANSFER_BUFFERS: usize = 4;
 
 #[derive(Default, Debug)]
 struct PendingOffers {
-    offers: [Option<AutoDestroy<ZwlrDataControlOfferV1>>; IN_TRANSFER_BUFFERS],
+    offers: [Option<AutoDestroy<Offer>>; IN_TRANSFER_BUFFERS],
     mimes: [BestMimeTypeFinder<String>; IN_TRANSFER_BUFFERS],
     transfers: [Option<Transfer>; IN_TRANSFER_BUFFERS],
     next: u8,
+    ignore_secrets: bool,
+
+    stats: TransferStats,
 }
 
 #[derive(Debug)]
@@ -450,10 +594,35 @@ struct Transfer {
     len: u64,
 
     mime: MimeType,
+    storage: TransferStorage,
+    alternate: Option<AlternateTransfer>,
+}
+
+/// Tracks the alternate representations still to be fetched for an entry
+/// once its best mime has been stored.
+#[derive(Debug)]
+struct AlternateTransfer {
+    id: u64,
+    stored: MimeType,
+    remaining: Alternates<String>,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum TransferStorage {
+    Memfd,
+    TmpFile,
+}
+
+#[derive(Default, Debug)]
+struct TransferStats {
+    added: u64,
+    promoted: u64,
+    dropped: u64,
+    bytes: u64,
 }
 
 impl PendingOffers {
-    fn init(&mut self, offer: ZwlrDataControlOfferV1) {
+    fn init(&mut self, offer: Offer) {
         const _: () = assert!(IN_TRANSFER_BUFFERS.is_power_of_two());
 
         let Self {
@@ -461,6 +630,8 @@ impl PendingOffers {
             mimes,
             transfers,
             next,
+            ignore_secrets: _,
+            stats: _,
         } = self;
 
         let idx = usize::from(*next) & (IN_TRANSFER_BUFFERS - 1);
@@ -475,16 +646,16 @@ impl PendingOffers {
         *next = next.wrapping_add(1);
     }
 
-    fn add_mime(&mut self, offer: &ZwlrDataControlOfferV1, mime: String) {
-        let Ok(mime_type) = MimeType::from(&mime) else {
-            warn!("Mime {mime:?} too long, ignoring.");
-            return;
+    fn add_mime(&mut self, offer: &ObjectId, mime: String) {
+        let mime_type = match mime_type_from_bytes(mime.as_bytes()) {
+            Ok(mime_type) => mime_type,
+            Err(e) => {
+                warn!("Ignoring mime {mime:?}: {e}");
+                return;
+            }
         };
         let Some(idx) = self.find(offer) else {
-            warn!(
-                "Trying to add mime to offer that does not exist: {:?}",
-                offer.id()
-            );
+            warn!("Trying to add mime to offer that does not exist: {offer:?}");
             return;
         };
 
@@ -495,14 +666,14 @@ impl PendingOffers {
         &mut self,
         tmp_file_unsupported: &mut bool,
         epoll: impl AsFd,
-        offer: &ZwlrDataControlOfferV1,
-    ) -> Result<(), CliError> {
-        let Some(idx) = self.find(offer) else {
+        offer: &Offer,
+    ) -> Result<bool, CliError> {
+        let Some(idx) = self.find(&offer.id()) else {
             error!(
                 "Failed to start transfer for offer that does not exist: {:?}",
                 offer.id()
             );
-            return Ok(());
+            return Ok(true);
         };
 
         self.start_transfer_(tmp_file_unsupported, epoll, idx)
@@ -513,21 +684,28 @@ impl PendingOffers {
         tmp_file_unsupported: &mut bool,
         epoll: impl AsFd,
         idx: usize,
-    ) -> Result<(), CliError> {
+    ) -> Result<bool, CliError> {
         let Some(mime) = self.mimes[idx].pop_best() else {
             warn!("No usable mimes returned, dropping offer.");
             self.reset(idx);
-            return Ok(());
+            return Ok(true);
         };
 
         info!("Starting transfer for peer {idx} of mime {mime:?}.");
         let mime_type = MimeType::from(&mime).unwrap();
 
-        let data = if is_plaintext_mime(&mime) {
-            memfd_create(c"ringboard_wayland_copy", MemfdFlags::empty())
-                .map_io_err(|| "Failed to create copy file.")?
+        let storage = if is_plaintext_mime(&mime) {
+            TransferStorage::Memfd
         } else {
-            create_tmp_file(
+            TransferStorage::TmpFile
+        };
+        debug!("Using {storage:?} storage for peer {idx}.");
+        let data = match storage {
+            TransferStorage::Memfd => {
+                memfd_create(c"ringboard_wayland_copy", MemfdFlags::ALLOW_SEALING)
+                    .map_io_err(|| "Failed to create copy file.")?
+            }
+            TransferStorage::TmpFile => create_tmp_file(
                 tmp_file_unsupported,
                 CWD,
                 c".",
@@ -535,9 +713,58 @@ impl PendingOffers {
                 OFlags::RDWR,
                 Mode::empty(),
             )
-            .map_io_err(|| "Failed to create copy temp file.")?
+            .map_io_err(|| "Failed to create copy temp file.")?,
         };
 
+        let read = self.receive(epoll, idx, mime)?;
+        self.transfers[idx] = Some(Transfer {
+            read,
+            data,
+            len: 0,
+            mime: mime_type,
+            storage,
+            alternate: None,
+        });
+
+        Ok(false)
+    }
+
+    fn start_alternate_transfer(
+        &mut self,
+        epoll: impl AsFd,
+        idx: usize,
+        AlternateTransfer {
+            id,
+            stored,
+            mut remaining,
+        }: AlternateTransfer,
+    ) -> Result<bool, CliError> {
+        let Some((mime, mime_type)) = remaining.pop(&stored) else {
+            self.reset(idx);
+            return Ok(true);
+        };
+
+        info!("Starting transfer for peer {idx} of alternate mime {mime:?}.");
+        let data = memfd_create(c"ringboard_wayland_alternate", MemfdFlags::empty())
+            .map_io_err(|| "Failed to create alternate copy file.")?;
+        let read = self.receive(epoll, idx, mime)?;
+        self.transfers[idx] = Some(Transfer {
+            read,
+            data,
+            len: 0,
+            mime: mime_type,
+            storage: TransferStorage::Memfd,
+            alternate: Some(AlternateTransfer {
+                id,
+                stored,
+                remaining,
+            }),
+        });
+
+        Ok(false)
+    }
+
+    fn receive(&self, epoll: impl AsFd, idx: usize, mime: String) -> Result<OwnedFd, CliError> {
         let (read, write) = pipe().map_io_err(|| "Failed to create pipe.")?;
         self.offers[idx]
             .as_ref()
@@ -551,14 +778,7 @@ impl PendingOffers {
             epoll::EventFlags::IN,
         )
         .map_io_err(|| "Failed to register epoll interest in read end of data transfer pipe.")?;
-        self.transfers[idx] = Some(Transfer {
-            read,
-            data,
-            len: 0,
-            mime: mime_type,
-        });
-
-        Ok(())
+        Ok(read)
     }
 
     fn continue_transfer(
@@ -568,16 +788,18 @@ impl PendingOffers {
         epoll: impl AsFd,
         deduplicator: &mut CopyDeduplication,
         idx: usize,
-    ) -> Result<(), CliError> {
+    ) -> Result<bool, CliError> {
         let Some(Transfer {
             read,
             data,
             len,
             mime,
+            storage,
+            alternate,
         }) = &mut self.transfers[idx]
         else {
             error!("Received poll notification for non-existent peer: {idx}.");
-            return Ok(());
+            return Ok(false);
         };
 
         {
@@ -602,7 +824,7 @@ impl PendingOffers {
                 } {
                     Err(Errno::AGAIN) => {
                         log_bytes_received(total);
-                        return Ok(());
+                        return Ok(false);
                     }
                     r => {
                         let count =
@@ -618,6 +840,22 @@ impl PendingOffers {
         }
         let len = *len;
         debug!("Finished transferring {len} bytes from peer {idx}.");
+        if let Some(alternate) = alternate.take() {
+            let mime = *mime;
+            if len == 0 {
+                warn!("Dropping empty alternate for peer {idx} on mime {mime:?}.");
+            } else if let AddAlternateResponse { error: Some(e) } =
+                AddAlternateRequest::response(&server, alternate.id, mime, &*data)?
+            {
+                warn!(
+                    "Failed to store alternate for peer {idx} on mime {mime:?} of entry {}: {e}",
+                    alternate.id
+                );
+            } else {
+                info!("Stored {len} byte alternate for peer {idx} on mime {mime:?}.");
+            }
+            return self.start_alternate_transfer(epoll, idx, alternate);
+        }
 
         let mmap;
         if len == 0 || {
@@ -626,34 +864,79 @@ impl PendingOffers {
             debug_assert_eq!(mmap.len(), usize::try_from(len).unwrap());
             mmap.iter().all(u8::is_ascii_whitespace)
         } {
-            warn!("Dropping empty or blank selection for peer {idx} on mime {mime:?}.");
-            self.start_transfer_(tmp_file_unsupported, epoll, idx)?;
-            return Ok(());
+            warn!(
+                "Dropping empty or blank selection for peer {idx} on mime {mime:?} ({len} bytes)."
+            );
+            self.stats.dropped += 1;
+            return self.start_transfer_(tmp_file_unsupported, epoll, idx);
+        }
+        if self.ignore_secrets && is_plaintext_mime(mime) && looks_like_secret(&mmap) {
+            info!("Dropping selection for peer {idx} that looks like a secret.");
+            self.stats.dropped += 1;
+            self.reset(idx);
+            return Ok(true);
         }
 
         let data_hash = CopyDeduplication::hash(CopyData::Slice(&mmap), len);
         if let Some(existing) = deduplicator.check(data_hash, CopyData::Slice(&mmap)) {
-            info!("Promoting duplicate entry from peer {idx} on mime {mime:?} to front.");
+            info!(
+                "Promoting duplicate entry of {len} bytes from peer {idx} on mime {mime:?} to \
+                 front."
+            );
             if let MoveToFrontResponse::Success { id } =
                 MoveToFrontRequest::response(&server, existing, None)?
             {
                 deduplicator.remember(data_hash, id);
+                self.stats.promoted += 1;
+                self.stats.log();
                 self.reset(idx);
-                return Ok(());
+                return Ok(true);
             }
         }
 
-        let AddResponse::Success { id } =
-            AddRequest::response_add_unchecked(&server, RingKind::Main, *mime, data)?;
-        deduplicator.remember(data_hash, id);
-        info!("Transfer for peer {idx} on mime {mime:?} complete.");
-        self.reset(idx);
+        // Sealing fails while the data is still mapped.
+        drop(mmap);
+        let response = match storage {
+            TransferStorage::Memfd => AddRequest::response_add_sealed(
+                &server,
+                RingKind::Main,
+                *mime,
+                &*data,
+                ADD_FLAG_ECHO,
+            )?,
+            TransferStorage::TmpFile => AddRequest::response_add_unchecked(
+                &server,
+                RingKind::Main,
+                *mime,
+                &*data,
+                ADD_FLAG_ECHO,
+            )?,
+        };
+        let Some(id) = deduplicator.remember_added(data_hash, len, response) else {
+            warn!("Server rate limited the selection for peer {idx}, dropping it.");
+            self.stats.dropped += 1;
+            self.reset(idx);
+            return Ok(true);
+        };
+        info!(
+            "Transfer for peer {idx} on mime {mime:?} complete: stored {len} bytes via \
+             {storage:?}."
+        );
+        self.stats.added += 1;
+        self.stats.bytes += len;
+        self.stats.log();
+        add_thumbnail(&server, id, mime, &*data);
 
-        Ok(())
+        let alternate = AlternateTransfer {
+            id,
+            stored: *mime,
+            remaining: self.mimes[idx].take_alternates(),
+        };
+        self.start_alternate_transfer(epoll, idx, alternate)
     }
 
-    fn consume(&mut self, offer: &ZwlrDataControlOfferV1) {
-        let Some(idx) = self.find(offer) else {
+    fn consume(&mut self, offer: &Offer) {
+        let Some(idx) = self.find(&offer.id()) else {
             error!(
                 "Failed to consume offer that does not exist: {:?}",
                 offer.id()
@@ -669,6 +952,8 @@ impl PendingOffers {
             mimes,
             transfers,
             next: _,
+            ignore_secrets: _,
+            stats: _,
         } = self;
 
         offers[idx].take();
@@ -676,16 +961,89 @@ impl PendingOffers {
         transfers[idx].take();
     }
 
-    fn find(&self, offer: &ZwlrDataControlOfferV1) -> Option<usize> {
+    fn find(&self, offer: &ObjectId) -> Option<usize> {
         self.offers
             .iter()
-            .position(|id| id.as_ref().map(|id| id.id()) == Some(offer.id()))
+            .position(|id| id.as_ref().map(|id| id.id()).as_ref() == Some(offer))
+    }
+}
+
+impl TransferStats {
+    fn log(&self) {
+        let Self {
+            added,
+            promoted,
+            dropped,
+            bytes,
+        } = self;
+        debug!(
+            "Capture summary: {added} added ({bytes} bytes), {promoted} promoted, {dropped} \
+             dropped."
+        );
+    }
+}
+
+const SELF_TEST_MIME: &str = "application/x-ringboard-self-test";
+const SELF_TEST_SOURCE: usize = 2;
+
+/// Periodically claims the primary selection with a sentinel source and waits
+/// for the compositor to report it back to us, proving that selection events
+/// are still being delivered.
+#[derive(Default, Debug)]
+struct SelfTest {
+    source: Option<AutoDestroy<Source>>,
+    sentinel_offer: Option<ObjectId>,
+}
+
+impl SelfTest {
+    /// Returns false if the previous sentinel was never reported back.
+    fn tick(
+        &mut self,
+        qh: &QueueHandle<App>,
+        manager: &Manager,
+        seats: &Seats,
+        sources: &Sources,
+    ) -> bool {
+        let passed = self.source.take().is_none();
+        self.sentinel_offer = None;
+
+        if sources.open[0].is_some() {
+            debug!("Skipping self-test while pasting into the primary selection.");
+            return passed;
+        }
+        let Some((_, device, _, _)) = seats.get(seats.active) else {
+            debug!("Skipping self-test with no seats.");
+            return passed;
+        };
+
+        trace!("Starting self-test.");
+        let source = AutoDestroy(manager.create_data_source(qh, SELF_TEST_SOURCE));
+        source.offer(SELF_TEST_MIME.to_string());
+        device.set_primary_selection(Some(&source));
+        self.source = Some(source);
+
+        passed
+    }
+
+    fn offer(&mut self, offer: ObjectId) {
+        if self.source.is_some() {
+            self.sentinel_offer = Some(offer);
+        }
+    }
+
+    fn check(&mut self, offer: &Offer) {
+        if self.sentinel_offer.as_ref() == Some(&offer.id()) {
+            debug!("Self-test passed.");
+            self.source.take();
+            self.sentinel_offer = None;
+        }
     }
 }
 
 #[derive(Default, Debug)]
 struct AppDefault {
-    manager: Option<AutoDestroy<ZwlrDataControlManagerV1>>,
+    manager: Option<AutoDestroy<Manager>>,
+    ext_manager: Option<AutoDestroy<Manager>>,
     virtual_keyboard_manager: Option<ZwpVirtualKeyboardManagerV1>,
     foreign_toplevels: Option<AutoDestroy<ExtForeignToplevelListV1>>,
     seats: Seats,
@@ -697,6 +1055,10 @@ struct AppDefault {
 
     tmp_file_unsupported: bool,
 
+    selection_resolved: bool,
+
+    self_test: SelfTest,
+
     error: Option<CliError>,
 }
 
@@ -740,7 +1102,9 @@ impl Dispatch<WlRegistry, ()> for App {
                         interface: T::interface().name,
                     });
                 } else {
-                    let interface = registry.bind(name, version, qh, ());
+                    // Newer compositors may advertise versions we don't know how to speak.
+                    let interface =
+                        registry.bind(name, min(version, T::interface().version), qh, ());
                     *object = Some(map(interface));
                 }
             }
@@ -751,7 +1115,15 @@ impl Dispatch<WlRegistry, ()> for App {
             registry,
             qh,
             &mut this.inner.manager,
-            AutoDestroy,
+            |manager| AutoDestroy(Manager::Wlr(manager)),
+            &mut this.inner.error,
+            &event,
+        );
+        singleton(
+            registry,
+            qh,
+            &mut this.inner.ext_manager,
+            |manager| AutoDestroy(Manager::Ext(manager)),
             &mut this.inner.error,
             &event,
         );
@@ -778,7 +1150,8 @@ impl Dispatch<WlRegistry, ()> for App {
                 version,
             } => {
                 if interface == WlSeat::interface().name {
-                    let _: WlSeat = registry.bind(name, version, qh, name);
+                    let _: WlSeat =
+                        registry.bind(name, min(version, WlSeat::interface().version), qh, name);
                 }
             }
             Event::GlobalRemove { name } => this.inner.seats.remove(name),
@@ -787,19 +1160,6 @@ impl Dispatch<WlRegistry, ()> for App {
     }
 }
 
-impl Dispatch<ZwlrDataControlManagerV1, ()> for App {
-    fn event(
-        _: &mut Self,
-        _: &ZwlrDataControlManagerV1,
-        event: <ZwlrDataControlManagerV1 as Proxy>::Event,
-        (): &(),
-        _: &Connection,
-        _: &QueueHandle<Self>,
-    ) {
-        debug_assert!(false, "Unhandled data control manager event: {event:?}");
-    }
-}
-
 impl Dispatch<ZwpVirtualKeyboardManagerV1, ()> for App {
     fn event(
         _: &mut Self,
@@ -839,84 +1199,60 @@ impl Dispatch<WlSeat, u32> for App {
     }
 }
 
-impl Dispatch<ZwlrDataControlDeviceV1, u32> for App {
-    fn event(
-        this: &mut Self,
-        _: &ZwlrDataControlDeviceV1,
-        event: <ZwlrDataControlDeviceV1 as Proxy>::Event,
-        &seat: &u32,
-        _: &Connection,
-        _: &QueueHandle<Self>,
-    ) {
+impl App {
+    fn handle_device_event(&mut self, seat: u32, event: DeviceEvent) {
         let run = || {
-            use zwlr_data_control_device_v1::Event;
             match event {
-                Event::DataOffer { id } => {
+                DeviceEvent::DataOffer(id) => {
                     trace!("Received data offer event: {:?}", id.id());
-                    this.inner.pending_offers.init(id);
+                    self.inner.pending_offers.init(id);
                 }
-                Event::Selection { id } => {
-                    debug!(
-                        "Received selection event: {:?}",
-                        id.as_ref().map(wayland_client::Proxy::id)
-                    );
-                    let Some(id) = id else { return Ok(()) };
-                    if this.inner.sources.open[1].is_some() {
+                DeviceEvent::Selection(id) => {
+                    debug!("Received selection event: {:?}", id.as_ref().map(Offer::id));
+                    let Some(id) = id else {
+                        debug!("Selection cleared.");
+                        self.inner.selection_resolved = true;
+                        return Ok(());
+                    };
+                    if self.inner.sources.open[1].is_some() {
                         debug!("Ignoring self selection.");
-                        this.inner.pending_offers.consume(&id);
+                        self.inner.pending_offers.consume(&id);
                     } else {
-                        this.inner.pending_offers.start_transfer(
-                            &mut this.inner.tmp_file_unsupported,
-                            &this.epoll,
+                        let finished = self.inner.pending_offers.start_transfer(
+                            &mut self.inner.tmp_file_unsupported,
+                            &self.epoll,
                             &id,
                         )?;
+                        self.inner.selection_resolved |= finished;
                     }
                 }
-                Event::PrimarySelection { id } => {
+                DeviceEvent::PrimarySelection(id) => {
                     trace!(
                         "Received primary selection event: {:?}",
-                        id.as_ref().map(wayland_client::Proxy::id)
+                        id.as_ref().map(Offer::id)
                     );
                     let Some(id) = id else { return Ok(()) };
-                    this.inner.pending_offers.consume(&id);
+                    self.inner.self_test.check(&id);
+                    self.inner.pending_offers.consume(&id);
                 }
-                Event::Finished => this.inner.seats.remove(seat),
-                _ => debug_assert!(false, "Unhandled data control device event: {event:?}"),
+                DeviceEvent::Finished => self.inner.seats.remove(seat),
             }
             Ok(())
         };
 
         let err = run().err();
-        if this.inner.error.is_none() {
-            this.inner.error = err;
+        if self.inner.error.is_none() {
+            self.inner.error = err;
         }
     }
 
-    event_created_child!(Self, ZwlrDataControlDeviceV1, [
-        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
-    ]);
-}
-
-impl Dispatch<ZwlrDataControlOfferV1, ()> for App {
-    fn event(
-        this: &mut Self,
-        id: &ZwlrDataControlOfferV1,
-        event: <ZwlrDataControlOfferV1 as Proxy>::Event,
-        (): &(),
-        _: &Connection,
-        _: &QueueHandle<Self>,
-    ) {
-        use zwlr_data_control_offer_v1::Event;
-        match event {
-            Event::Offer { mime_type } => {
-                trace!(
-                    "Received mime type offer for id {:?}: {mime_type:?}",
-                    id.id()
-                );
-                this.inner.pending_offers.add_mime(id, mime_type);
-            }
-            _ => debug_assert!(false, "Unhandled data control offer event: {event:?}"),
+    fn handle_offer_event(&mut self, id: ObjectId, mime_type: String) {
+        trace!("Received mime type offer for id {id:?}: {mime_type:?}");
+        if mime_type == SELF_TEST_MIME {
+            self.inner.self_test.offer(id);
+            return;
         }
+        self.inner.pending_offers.add_mime(&id, mime_type);
     }
 }
 
@@ -925,7 +1261,8 @@ struct Sources {
     mime: MimeType,
     fd: Option<MaybeRc<OwnedFd>>,
     len: usize,
-    open: [Option<AutoDestroy<ZwlrDataControlSourceV1>>; 2],
+    alternates: Vec<(MimeType, MaybeRc<OwnedFd>, usize)>,
+    open: [Option<AutoDestroy<Source>>; 2],
 }
 
 const OUT_TRANSFER_BUFFERS: usize = 4;
@@ -1099,7 +1436,7 @@ fn handle_paste_event(
     ancillary_buf: &mut [u8; rustix::cmsg_space!(ScmRights(1))],
 
     qh: &QueueHandle<App>,
-    manager: Option<&AutoDestroy<ZwlrDataControlManagerV1>>,
+    manager: Option<&AutoDestroy<Manager>>,
     seats: &Seats,
     pending_paste: &mut bool,
     sources: &mut Sources,
@@ -1135,6 +1472,7 @@ fn handle_paste_event(
     let (
         cmd @ PasteCommand {
             trigger_paste,
+            target,
             id,
             mime,
             ..
@@ -1166,10 +1504,30 @@ fn handle_paste_event(
         return Ok(());
     };
 
+    let primary_supported = device.supports_primary_selection();
+    if target == PasteTarget::Primary && !primary_supported {
+        warn!("Compositor does not support the primary selection, ignoring paste.");
+        return Ok(());
+    }
+
+    // Slot 0 holds the primary selection and slot 1 the clipboard.
+    let targeted = |i| {
+        (i != 0 || primary_supported)
+            && match target {
+                PasteTarget::Both => true,
+                PasteTarget::Clipboard => i == 1,
+                PasteTarget::Primary => i == 0,
+            }
+    };
+
     let Some(fd) = fd else {
         info!("Clearing selections.");
-        device.set_primary_selection(None);
-        device.set_selection(None);
+        if targeted(0) {
+            device.set_primary_selection(None);
+        }
+        if targeted(1) {
+            device.set_selection(None);
+        }
         return Ok(());
     };
 
@@ -1177,6 +1535,7 @@ fn handle_paste_event(
         mime: mime_,
         fd: fd_,
         len,
+        alternates,
         open,
     } = sources;
     *mime_ = mime;
@@ -1184,12 +1543,45 @@ fn handle_paste_event(
     *len = guard.1.as_ref().map_or(0, Mmap::len);
 
     let supported_mimes = generate_supported_mimes(&mime);
-    trace!("Offering mimes: {supported_mimes:?}");
+    alternates.clear();
+    for (alternate_mime, file) in read_paste_alternates(id) {
+        if supported_mimes.contains(&alternate_mime.as_str()) {
+            continue;
+        }
+        let alternate_len = file
+            .metadata()
+            .map_io_err(|| format!("Failed to stat {alternate_mime:?} alternate."))?
+            .len();
+        alternates.push((
+            alternate_mime,
+            MaybeRc::new(OwnedFd::from(file)),
+            usize::try_from(alternate_len).unwrap(),
+        ));
+    }
+    trace!(
+        "Offering mimes: {supported_mimes:?} and alternates: {:?}",
+        alternates
+            .iter()
+            .map(|(mime, _, _)| mime)
+            .collect::<Vec<_>>()
+    );
     for (i, slot) in open.iter_mut().enumerate() {
+        if !targeted(i) {
+            // Both selections share the paste buffer, so a selection we still own
+            // would otherwise start serving the new entry.
+            if slot.take().is_some() {
+                debug!("Releasing ownership of untargeted selection {i}.");
+            }
+            continue;
+        }
+
         let source = AutoDestroy(manager.create_data_source(qh, i));
         for mime in &supported_mimes {
             source.offer((*mime).to_string());
         }
+        for (mime, _, _) in &*alternates {
+            source.offer(mime.to_string());
+        }
         match i {
             0 => device.set_primary_selection(Some(&source)),
             1 => device.set_selection(Some(&source)),
@@ -1225,44 +1617,58 @@ fn generate_supported_mimes(mime: &str) -> ArrayVec<&str, 8> {
     supported_mimes
 }
 
-impl Dispatch<ZwlrDataControlSourceV1, usize> for App {
-    fn event(
-        this: &mut Self,
-        _: &ZwlrDataControlSourceV1,
-        event: <ZwlrDataControlSourceV1 as Proxy>::Event,
-        &id: &usize,
-        _: &Connection,
-        _: &QueueHandle<Self>,
-    ) {
-        use zwlr_data_control_source_v1::Event;
+impl App {
+    fn handle_source_event(&mut self, id: usize, event: SourceEvent) {
+        if id == SELF_TEST_SOURCE {
+            match event {
+                // Dropping the fd hands the peer an empty selection.
+                SourceEvent::Send { .. } => {
+                    debug!("Ignoring transfer request for self-test selection.");
+                }
+                SourceEvent::Cancelled => {
+                    trace!("Self-test selection replaced before it was reported.");
+                    self.inner.self_test.source.take();
+                }
+            }
+            return;
+        }
 
         let Sources {
             mime,
             fd: data,
             len,
+            alternates,
             open,
-        } = &mut this.inner.sources;
+        } = &mut self.inner.sources;
         match event {
-            Event::Send { mime_type, fd } => {
-                if !generate_supported_mimes(mime).contains(&mime_type.as_str()) {
+            SourceEvent::Send { mime_type, fd } => {
+                let (data, len) = if generate_supported_mimes(mime).contains(&mime_type.as_str()) {
+                    let Some(data) = data else {
+                        debug!("Possible bug? No data available, but transfer was requested.");
+                        return;
+                    };
+                    (data, len)
+                } else if let Some((_, data, len)) = alternates
+                    .iter_mut()
+                    .find(|(alternate, _, _)| alternate.as_str() == mime_type)
+                {
+                    debug!("Sending {mime_type:?} alternate.");
+                    (data, len)
+                } else {
                     debug!("Rejecting transfer for mime that was not offered: {mime_type:?}");
                     return;
-                }
-                let Some(data) = data else {
-                    debug!("Possible bug? No data available, but transfer was requested.");
-                    return;
                 };
 
-                let err = this
+                let err = self
                     .inner
                     .outgoing_transfers
-                    .begin(&this.epoll, data, *len, fd)
+                    .begin(&self.epoll, data, *len, fd)
                     .err();
-                if this.inner.error.is_none() {
-                    this.inner.error = err;
+                if self.inner.error.is_none() {
+                    self.inner.error = err;
                 }
             }
-            Event::Cancelled => {
+            SourceEvent::Cancelled => {
                 debug!("Releasing ownership of {} selection.", match id {
                     0 => "primary",
                     1 => "clipboard",
@@ -1271,9 +1677,9 @@ impl Dispatch<ZwlrDataControlSourceV1, usize> for App {
                 open[id].take();
                 if open.iter().all(Option::is_none) {
                     data.take();
+                    alternates.clear();
                 }
             }
-            _ => debug_assert!(false, "Unhandled data control source event: {event:?}"),
         }
     }
 }
@@ -9,10 +9,11 @@ use std::{
     mem,
     mem::MaybeUninit,
     os::{
-        fd::{AsFd, OwnedFd},
+        fd::{AsFd, BorrowedFd, OwnedFd},
         unix::fs::FileExt,
     },
     rc::Rc,
+    thread,
     time::Duration,
 };
 
@@ -20,29 +21,34 @@ use arrayvec::ArrayVec;
 use error_stack::Report;
 use log::{debug, error, info, trace, warn};
 use ringboard_sdk::{
-    api::{AddRequest, MoveToFrontRequest, PasteCommand, connect_to_server},
-    config::{X11Config, X11V1Config, x11_config_file},
+    api::{
+        AddAlternateRequest, AddRequest, MoveToFrontRequest, PasteCommand, PasteTarget,
+        connect_to_server,
+    },
+    config::{X11Config, X11V1Config, x11_chord_keysyms, x11_config_file},
     core::{
-        Error, IoErr, create_tmp_file,
+        Error, IoErr, SocketFileGuard, create_tmp_file,
         dirs::{paste_socket_file, socket_file},
-        init_unix_server,
+        init_unix_server, is_plaintext_mime, looks_like_secret,
         protocol::{
-            AddResponse, IdNotFoundError, MimeType, MoveToFrontResponse, Response, RingKind,
+            ADD_FLAG_ECHO, AddAlternateResponse, IdNotFoundError, MimeType, MoveToFrontResponse,
+            Response, RingKind, mime_type_from_bytes,
         },
         ring::Mmap,
+        socket_addr,
     },
     is_text_mime,
 };
 use ringboard_watcher_utils::{
-    best_target::BestMimeTypeFinder,
+    best_target::{Alternates, BestMimeTypeFinder},
     deduplication::{CopyData, CopyDeduplication},
-    utils::read_paste_command,
+    utils::{add_thumbnail, read_paste_alternates, read_paste_command},
 };
 use rustix::{
     event::epoll,
     fs::{CWD, MemfdFlags, Mode, OFlags, memfd_create},
     io::{Errno, read_uninit},
-    net::{RecvFlags, SendFlags, SocketAddrUnix, SocketType},
+    net::{RecvFlags, SendFlags, SocketType},
     path::Arg,
     time::{
         Itimerspec, TimerfdClockId, TimerfdFlags, TimerfdTimerFlags, Timespec, timerfd_create,
@@ -60,9 +66,9 @@ use x11rb::{
         xfixes::{SelectionEventMask, select_selection_input},
         xproto::{
             Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, CreateWindowAux, EventMask,
-            GetAtomNameReply, GetPropertyType, KEY_PRESS_EVENT, KEY_RELEASE_EVENT, NotifyDetail,
-            PropMode, Property, SELECTION_NOTIFY_EVENT, SelectionNotifyEvent,
-            SelectionRequestEvent, Window, WindowClass,
+            GetAtomNameReply, GetPropertyType, KEY_PRESS_EVENT, KEY_RELEASE_EVENT, Keycode,
+            NotifyDetail, PropMode, Property, SELECTION_NOTIFY_EVENT, SelectionNotifyEvent,
+            SelectionRequestEvent, Setup, Window, WindowClass,
         },
         xtest::ConnectionExt as XTestExt,
     },
@@ -89,6 +95,10 @@ enum CliError {
     X11NoXfixes,
     #[error("Serde TOML deserialization failed")]
     Toml(#[from] toml::de::Error),
+    #[error("invalid paste key: {0:?}")]
+    InvalidPasteKey(String),
+    #[error("paste key is not on the keyboard: keysym {0:#x}")]
+    UnmappedPasteKey(u32),
 }
 
 impl From<X11Error> for CliError {
@@ -151,6 +161,11 @@ fn into_report(cli_err: CliError) -> Report<Wrapper> {
         CliError::X11Error(e) => Report::new(wrapper).attach_printable(format!("{e:?}")),
         CliError::X11IdsExhausted | CliError::X11NoXfixes => Report::new(wrapper),
         CliError::Toml(e) => Report::new(e).change_context(wrapper),
+        CliError::InvalidPasteKey(_) => Report::new(wrapper).attach_printable(
+            "Keys are written as a character or named key optionally prefixed with modifiers, \
+             e.g. \"shift+insert\" or \"ctrl+shift+v\".",
+        ),
+        CliError::UnmappedPasteKey(_) => Report::new(wrapper),
     }
 }
 
@@ -161,19 +176,36 @@ enum State {
     FastPathPendingSelection,
     TargetsRequest {
         allow_plain_text: bool,
+        /// Set when the targets are only queried to find the alternates of an
+        /// entry stored through the fast path.
+        added: Option<(u64, MimeType)>,
     },
     PendingSelection {
         mime_atom: Atom,
         mime_type: MimeType,
+        alternates: AlternateTargets,
     },
     PendingIncr {
         mime_atom: Atom,
         mime_type: MimeType,
         file: Option<File>,
         written: u64,
+        alternates: AlternateTargets,
     },
 }
 
+/// The alternate representations of a selection to fetch once its best target
+/// has been stored.
+#[derive(Debug)]
+struct AlternateTargets {
+    selection: Atom,
+    /// [`None`] until the selection's targets have been queried.
+    remaining: Option<Alternates<Atom>>,
+    /// The stored entry and the mime type it was stored with, set while
+    /// alternates are being transferred.
+    added: Option<(u64, MimeType)>,
+}
+
 const MAX_CONCURRENT_TRANSFERS: usize = 4;
 const BASE_TRANSFER_ATOM: AtomEnum = AtomEnum::CUT_BUFFE_R0;
 
@@ -247,6 +279,18 @@ enum PasteFile {
     Large(Rc<Mmap>),
 }
 
+impl From<Mmap> for PasteFile {
+    fn from(data: Mmap) -> Self {
+        if data.len() > MAX_TRANSFER_SIZE {
+            Self::Large(Rc::new(data))
+        } else {
+            Self::Small(data)
+        }
+    }
+}
+
+type LastPaste = Option<(PasteFile, PasteAtom, Vec<(Atom, PasteFile)>)>;
+
 fn load_config() -> Result<X11V1Config, CliError> {
     let path = x11_config_file();
     let mut file = match File::open(&path) {
@@ -268,17 +312,125 @@ fn run() -> Result<(), CliError> {
         env!("CARGO_PKG_VERSION")
     );
 
-    let ref config @ X11V1Config { auto_paste } = load_config()?;
+    let ref config @ X11V1Config {
+        auto_paste,
+        capture_primary: _,
+        ref paste_key,
+        ignore_secrets: _,
+    } = load_config()?;
     info!("Using configuration {config:?}");
+    let paste_keysyms =
+        x11_chord_keysyms(paste_key).ok_or_else(|| CliError::InvalidPasteKey(paste_key.clone()))?;
 
     let server = {
         let socket_file = socket_file();
-        let addr = SocketAddrUnix::new(&socket_file)
-            .map_io_err(|| format!("Failed to make socket address: {socket_file:?}"))?;
+        let addr = socket_addr(&socket_file)?;
         connect_to_server(&addr)?
     };
     debug!("Ringboard connection established.");
 
+    let paste_socket_file = paste_socket_file();
+    let paste_socket = init_unix_server(&paste_socket_file, SocketType::DGRAM)?;
+    let _paste_socket_guard = SocketFileGuard::new(paste_socket_file);
+    let paste_timer = if auto_paste {
+        Some(
+            timerfd_create(TimerfdClockId::Monotonic, TimerfdFlags::empty())
+                .map_io_err(|| "Failed to create timer fd.")?,
+        )
+    } else {
+        None
+    };
+    debug!("Initialized paste server");
+
+    let epoll =
+        epoll::create(epoll::CreateFlags::empty()).map_io_err(|| "Failed to create epoll.")?;
+    for (i, fd) in [
+        Some(paste_socket.as_fd()),
+        paste_timer.as_ref().map(OwnedFd::as_fd),
+    ]
+    .iter()
+    .flatten()
+    .enumerate()
+    {
+        epoll::add(
+            &epoll,
+            fd,
+            epoll::EventData::new_u64(u64::try_from(i + 1).unwrap()),
+            epoll::EventFlags::IN,
+        )
+        .map_io_err(|| "Failed to register epoll interest.")?;
+    }
+
+    let mut deduplicator = CopyDeduplication::new()?;
+
+    let mut ever_connected = false;
+    let mut failed_attempts = 0;
+    loop {
+        let mut connected = false;
+        let e = match run_x11(
+            config,
+            &paste_keysyms,
+            &PersistentFds {
+                server: server.as_fd(),
+                paste_socket: paste_socket.as_fd(),
+                paste_timer: paste_timer.as_ref().map(OwnedFd::as_fd),
+                epoll: epoll.as_fd(),
+            },
+            &mut deduplicator,
+            &mut connected,
+        ) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        if connected {
+            ever_connected = true;
+            failed_attempts = 0;
+        }
+
+        let recoverable = match e {
+            CliError::X11Connection(ConnectionError::IoError(_)) => true,
+            CliError::X11Connect(_) => ever_connected,
+            _ => false,
+        };
+        if !recoverable || failed_attempts >= MAX_RECONNECT_ATTEMPTS {
+            return Err(e);
+        }
+
+        let delay = RECONNECT_BASE_DELAY * (1 << failed_attempts);
+        failed_attempts += 1;
+        warn!("Lost X11 connection, reconnecting in {delay:?}: {e}");
+        thread::sleep(delay);
+    }
+}
+
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// The file descriptors which survive reconnecting to the X11 server.
+struct PersistentFds<'a> {
+    server: BorrowedFd<'a>,
+    paste_socket: BorrowedFd<'a>,
+    paste_timer: Option<BorrowedFd<'a>>,
+    epoll: BorrowedFd<'a>,
+}
+
+fn run_x11(
+    &X11V1Config {
+        auto_paste: _,
+        capture_primary,
+        paste_key: _,
+        ignore_secrets,
+    }: &X11V1Config,
+    paste_keysyms: &[u32],
+    &PersistentFds {
+        server,
+        paste_socket,
+        paste_timer,
+        epoll,
+    }: &PersistentFds,
+    deduplicator: &mut CopyDeduplication,
+    connected: &mut bool,
+) -> Result<(), CliError> {
     let (conn, root) = {
         let (conn, screen_num) = x11rb::connect(None)?;
         let root = conn.setup().roots[screen_num].root;
@@ -292,6 +444,7 @@ fn run() -> Result<(), CliError> {
         _NET_WM_NAME: window_name_atom,
         UTF8_STRING: utf8_string_atom,
         CLIPBOARD: clipboard_atom,
+        PRIMARY: primary_atom,
         ..
     } = Atoms::new(&conn)?.reply()?;
     debug!("Atom internment complete.");
@@ -346,52 +499,44 @@ fn run() -> Result<(), CliError> {
         clipboard_atom,
         SelectionEventMask::SET_SELECTION_OWNER,
     )?;
+    if capture_primary {
+        select_selection_input(
+            &conn,
+            root,
+            primary_atom,
+            SelectionEventMask::SET_SELECTION_OWNER,
+        )?;
+    }
     debug!("Selection owner listener registered.");
 
-    let paste_socket = init_unix_server(paste_socket_file(), SocketType::DGRAM)?;
-    let paste_timer = if auto_paste {
-        Some(
-            timerfd_create(TimerfdClockId::Monotonic, TimerfdFlags::empty())
-                .map_io_err(|| "Failed to create timer fd.")?,
-        )
+    let paste_keycodes = if paste_timer.is_some() {
+        resolve_keycodes(&conn, paste_keysyms)?
     } else {
-        None
+        Vec::new()
     };
-    debug!("Initialized paste server");
+
+    // The stream is closed when the connection is dropped, which also removes it
+    // from the epoll interest list, so a fresh connection must be registered.
+    epoll::add(
+        epoll,
+        conn.stream(),
+        epoll::EventData::new_u64(0),
+        epoll::EventFlags::IN,
+    )
+    .map_io_err(|| "Failed to register epoll interest.")?;
+    let mut epoll_events = epoll::EventVec::with_capacity(3);
 
     let mut ancillary_buf = [0; rustix::cmsg_space!(ScmRights(1))];
     let mut last_paste = None;
     let mut clear_selection_mask = 0;
 
-    let epoll =
-        epoll::create(epoll::CreateFlags::empty()).map_io_err(|| "Failed to create epoll.")?;
-    for (i, fd) in [
-        Some(conn.stream().as_fd()),
-        Some(paste_socket.as_fd()),
-        paste_timer.as_ref().map(OwnedFd::as_fd),
-    ]
-    .iter()
-    .flatten()
-    .enumerate()
-    {
-        epoll::add(
-            &epoll,
-            fd,
-            epoll::EventData::new_u64(u64::try_from(i).unwrap()),
-            epoll::EventFlags::IN,
-        )
-        .map_io_err(|| "Failed to register epoll interest.")?;
-    }
-    let mut epoll_events = epoll::EventVec::with_capacity(3);
-
     let mut allocator = TransferAtomAllocator {
         windows: transfer_windows.into_inner().unwrap(),
         states: [const { State::Free }; MAX_CONCURRENT_TRANSFERS],
         next: 0,
     };
     let mut paste_allocator = Default::default();
-
-    let mut deduplicator = CopyDeduplication::new()?;
+    *connected = true;
 
     info!("Starting event loop.");
     loop {
@@ -401,11 +546,12 @@ fn run() -> Result<(), CliError> {
                 &conn,
                 &atoms,
                 &mut allocator,
-                &server,
-                &mut deduplicator,
+                server,
+                deduplicator,
+                ignore_secrets,
                 paste_window,
                 root,
-                paste_timer.as_ref(),
+                paste_timer,
                 &mut last_paste,
                 &mut paste_allocator,
                 &mut clear_selection_mask,
@@ -414,7 +560,7 @@ fn run() -> Result<(), CliError> {
         conn.flush()?;
 
         trace!("Waiting for event.");
-        match epoll::wait(&epoll, &mut epoll_events, -1) {
+        match epoll::wait(epoll, &mut epoll_events, -1) {
             Err(Errno::INTR) => continue,
             r => r.map_io_err(|| "Failed to wait for epoll events.")?,
         };
@@ -426,22 +572,20 @@ fn run() -> Result<(), CliError> {
                     &conn,
                     &atoms,
                     root,
-                    &server,
-                    &mut deduplicator,
+                    server,
+                    deduplicator,
                     paste_window,
-                    &paste_socket,
+                    paste_socket,
                     &mut ancillary_buf,
                     &mut last_paste,
                     &mut clear_selection_mask,
                     paste_timer.is_some(),
+                    &paste_keycodes,
                 )?,
                 2 => {
-                    read_uninit(
-                        paste_timer.as_ref().unwrap(),
-                        &mut [MaybeUninit::uninit(); 8],
-                    )
-                    .map_io_err(|| "Failed to clear paste timer.")?;
-                    do_paste(&conn, root)?;
+                    read_uninit(paste_timer.unwrap(), &mut [MaybeUninit::uninit(); 8])
+                        .map_io_err(|| "Failed to clear paste timer.")?;
+                    do_paste(&conn, root, &paste_keycodes)?;
                 }
                 _ => unreachable!(),
             }
@@ -456,11 +600,12 @@ fn handle_x11_event(
     allocator: &mut TransferAtomAllocator,
     server: impl AsFd,
     deduplicator: &mut CopyDeduplication,
+    ignore_secrets: bool,
 
     paste_window: Window,
     root: Window,
     paste_timer: Option<impl AsFd>,
-    last_paste: &mut Option<(PasteFile, PasteAtom)>,
+    last_paste: &mut LastPaste,
     (paste_alloc_next, paste_allocations, tmp_file_unsupported): &mut (
         u8,
         [(Window, Option<(Atom, Rc<Mmap>, usize)>); MAX_CONCURRENT_TRANSFERS],
@@ -545,29 +690,29 @@ fn handle_x11_event(
                 debug!("Unsupported selection type.");
                 return reply(x11rb::NONE);
             }
-            let Some((ref paste_file, PasteAtom { atom, is_text })) = *last_paste else {
+            let Some((ref paste_file, PasteAtom { atom, is_text }, ref alternates)) = *last_paste
+            else {
                 debug!("Nothing to paste.");
                 return reply(x11rb::NONE);
             };
 
-            let mut supported_atoms = ArrayVec::<_, 9>::new_const();
+            let mut supported_atoms = Vec::with_capacity(9 + alternates.len());
             supported_atoms.push(targets_atom);
             if atom != x11rb::NONE {
                 supported_atoms.push(atom);
             }
             if is_text {
-                supported_atoms
-                    .try_extend_from_slice(&[
-                        utf8_string_atom,
-                        atoms.TEXT,
-                        atoms.STRING,
-                        atoms.text_plain,
-                        atoms.text_plain_utf8,
-                        atoms.text_plain_us_ascii,
-                        atoms.text_plain_unicode,
-                    ])
-                    .unwrap();
+                supported_atoms.extend_from_slice(&[
+                    utf8_string_atom,
+                    atoms.TEXT,
+                    atoms.STRING,
+                    atoms.text_plain,
+                    atoms.text_plain_utf8,
+                    atoms.text_plain_us_ascii,
+                    atoms.text_plain_unicode,
+                ]);
             }
+            supported_atoms.extend(alternates.iter().map(|&(atom, _)| atom));
             if !supported_atoms.contains(&target) {
                 debug!("Unsupported target.");
                 return reply(x11rb::NONE);
@@ -585,6 +730,14 @@ fn handle_x11_event(
                 return reply(property);
             }
 
+            let paste_file = if let Some((_, alternate)) =
+                alternates.iter().find(|&&(atom, _)| atom == target)
+            {
+                debug!("Pasting alternate.");
+                alternate
+            } else {
+                paste_file
+            };
             match paste_file {
                 PasteFile::Small(data) => {
                     info!("Responded to paste request with small selection.");
@@ -759,7 +912,10 @@ fn handle_x11_event(
             };
 
             match mem::take(state) {
-                State::TargetsRequest { allow_plain_text } => {
+                State::TargetsRequest {
+                    allow_plain_text,
+                    added,
+                } => {
                     let Some(property) = property else {
                         warn!("Targets response cancelled.");
                         return Ok(());
@@ -789,13 +945,17 @@ fn handle_x11_event(
                         if pending_atom_cookies.is_full() || atom.is_none() {
                             for (cookie, atom) in pending_atom_cookies.drain(..) {
                                 let reply = cookie.reply()?;
-                                let name = reply.name.to_string_lossy();
-                                trace!("Target {name:?} available on atom {atom}.");
-
-                                let Ok(mime) = MimeType::from(&name) else {
-                                    warn!("Target {name:?} name too long, ignoring.");
-                                    continue;
+                                let mime = match mime_type_from_bytes(&reply.name) {
+                                    Ok(mime) => mime,
+                                    Err(e) => {
+                                        warn!(
+                                            "Ignoring target {:?}: {e}",
+                                            reply.name.to_string_lossy()
+                                        );
+                                        continue;
+                                    }
                                 };
+                                trace!("Target {mime:?} available on atom {atom}.");
 
                                 finder.add_mime(&mime, atom);
                             }
@@ -806,6 +966,21 @@ fn handle_x11_event(
                         pending_atom_cookies.push((conn.get_atom_name(atom)?, atom));
                     }
 
+                    let alternates = AlternateTargets {
+                        selection: event.selection,
+                        remaining: Some(finder.take_alternates()),
+                        added: None,
+                    };
+                    if let Some(added) = added {
+                        return next_alternate(
+                            conn,
+                            atoms,
+                            (state, event.requestor, transfer_atom),
+                            added,
+                            alternates,
+                        );
+                    }
+
                     let Some((target, target_mime)) = finder.best() else {
                         warn!("No usable targets returned, dropping selection.");
                         return Ok(());
@@ -815,6 +990,7 @@ fn handle_x11_event(
                     *state = State::PendingSelection {
                         mime_atom: target,
                         mime_type: target_mime,
+                        alternates,
                     };
                     conn.convert_selection(
                         event.requestor,
@@ -834,6 +1010,7 @@ fn handle_x11_event(
                                 );
                                 *state = State::TargetsRequest {
                                     allow_plain_text: true,
+                                    added: None,
                                 };
                                 conn.convert_selection(
                                     event.requestor,
@@ -843,6 +1020,18 @@ fn handle_x11_event(
                                     x11rb::CURRENT_TIME,
                                 )?;
                             }
+                            State::PendingSelection { alternates, .. }
+                                if let Some(added) = alternates.added =>
+                            {
+                                warn!("Alternate transfer cancelled.");
+                                return next_alternate(
+                                    conn,
+                                    atoms,
+                                    (state, event.requestor, transfer_atom),
+                                    added,
+                                    alternates,
+                                );
+                            }
                             State::PendingSelection { .. } => {
                                 warn!("Selection transfer cancelled.");
                             }
@@ -851,14 +1040,22 @@ fn handle_x11_event(
                         return Ok(());
                     };
 
-                    let (mime_atom, mime_type, fast_path) = match s {
-                        State::FastPathPendingSelection => {
-                            (utf8_string_atom, MimeType::new_const(), true)
-                        }
+                    let (mime_atom, mime_type, alternates, fast_path) = match s {
+                        State::FastPathPendingSelection => (
+                            utf8_string_atom,
+                            MimeType::new_const(),
+                            AlternateTargets {
+                                selection: event.selection,
+                                remaining: None,
+                                added: None,
+                            },
+                            true,
+                        ),
                         State::PendingSelection {
                             mime_atom,
                             mime_type,
-                        } => (mime_atom, mime_type, false),
+                            alternates,
+                        } => (mime_atom, mime_type, alternates, false),
                         _ => unreachable!(),
                     };
 
@@ -870,7 +1067,27 @@ fn handle_x11_event(
                             mime_type,
                             file: None,
                             written: 0,
+                            alternates,
                         };
+                    } else if let Some(added) = alternates.added {
+                        if property.value.is_empty() {
+                            warn!("Dropping empty {mime_type:?} alternate.");
+                        } else {
+                            let file = File::from(
+                                memfd_create(c"ringboard_x11_alternate", MemfdFlags::empty())
+                                    .map_io_err(|| "Failed to create alternate temp file.")?,
+                            );
+                            file.write_all_at(&property.value, 0)
+                                .map_io_err(|| "Failed to write data to temp file.")?;
+                            store_alternate(&server, added, mime_type, file)?;
+                        }
+                        return next_alternate(
+                            conn,
+                            atoms,
+                            (state, event.requestor, transfer_atom),
+                            added,
+                            alternates,
+                        );
                     } else {
                         if property.value.is_empty()
                             || property.value.iter().all(u8::is_ascii_whitespace)
@@ -882,6 +1099,7 @@ fn handle_x11_event(
                                 );
                                 *state = State::TargetsRequest {
                                     allow_plain_text: false,
+                                    added: None,
                                 };
                                 conn.convert_selection(
                                     event.requestor,
@@ -895,6 +1113,13 @@ fn handle_x11_event(
                             }
                             return Ok(());
                         }
+                        if ignore_secrets
+                            && is_plaintext_mime(&mime_type)
+                            && looks_like_secret(&property.value)
+                        {
+                            info!("Dropping selection that looks like a secret.");
+                            return Ok(());
+                        }
 
                         let data_hash = CopyDeduplication::hash(
                             CopyData::Slice(&property.value),
@@ -913,20 +1138,36 @@ fn handle_x11_event(
                         }
 
                         let file = File::from(
-                            memfd_create(c"ringboard_x11_selection", MemfdFlags::empty())
+                            memfd_create(c"ringboard_x11_selection", MemfdFlags::ALLOW_SEALING)
                                 .map_io_err(|| "Failed to create selection transfer temp file.")?,
                         );
                         file.write_all_at(&property.value, 0)
                             .map_io_err(|| "Failed to write data to temp file.")?;
 
-                        let AddResponse::Success { id } = AddRequest::response_add_unchecked(
+                        let response = AddRequest::response_add_sealed(
                             &server,
                             RingKind::Main,
                             mime_type,
-                            file,
+                            &file,
+                            ADD_FLAG_ECHO,
                         )?;
-                        deduplicator.remember(data_hash, id);
+                        let Some(id) = deduplicator.remember_added(
+                            data_hash,
+                            u64::try_from(property.value.len()).unwrap(),
+                            response,
+                        ) else {
+                            warn!("Server rate limited the small selection, dropping it.");
+                            return Ok(());
+                        };
                         info!("Small selection transfer complete.");
+                        add_thumbnail(&server, id, &mime_type, &file);
+                        return next_alternate(
+                            conn,
+                            atoms,
+                            (state, event.requestor, transfer_atom),
+                            (id, mime_type),
+                            alternates,
+                        );
                     }
                 }
                 s @ (State::PendingIncr { .. } | State::Free) => {
@@ -955,7 +1196,7 @@ fn handle_x11_event(
                 );
                 return Ok(());
             }
-            let Some((state, _)) = allocator.get(event.window) else {
+            let Some((state, transfer_atom)) = allocator.get(event.window) else {
                 warn!(
                     "Ignoring property notify to unknown requester {}.",
                     event.window
@@ -975,6 +1216,7 @@ fn handle_x11_event(
                         mime_type,
                         file,
                         written,
+                        alternates,
                     } = mem::take(state)
                     else {
                         unreachable!()
@@ -1006,7 +1248,22 @@ fn handle_x11_event(
                     };
 
                     let property = property.reply()?;
-                    if property.value.is_empty() {
+                    if property.value.is_empty()
+                        && let Some(added) = alternates.added
+                    {
+                        if written == 0 {
+                            warn!("Dropping empty INCR {mime_type:?} alternate.");
+                        } else {
+                            store_alternate(&server, added, mime_type, file)?;
+                        }
+                        return next_alternate(
+                            conn,
+                            atoms,
+                            (state, event.window, transfer_atom),
+                            added,
+                            alternates,
+                        );
+                    } else if property.value.is_empty() {
                         if written == 0 {
                             warn!("Dropping empty INCR selection.");
                             return Ok(());
@@ -1024,14 +1281,27 @@ fn handle_x11_event(
                             }
                         }
 
-                        let AddResponse::Success { id } = AddRequest::response_add_unchecked(
+                        let response = AddRequest::response_add_unchecked(
                             &server,
                             RingKind::Main,
                             mime_type,
-                            file,
+                            &file,
+                            ADD_FLAG_ECHO,
                         )?;
-                        deduplicator.remember(data_hash, id);
+                        let Some(id) = deduplicator.remember_added(data_hash, written, response)
+                        else {
+                            warn!("Server rate limited the large selection, dropping it.");
+                            return Ok(());
+                        };
                         info!("Large selection transfer complete.");
+                        add_thumbnail(&server, id, &mime_type, &file);
+                        return next_alternate(
+                            conn,
+                            atoms,
+                            (state, event.window, transfer_atom),
+                            (id, mime_type),
+                            alternates,
+                        );
                     } else {
                         debug!("Writing {} bytes for INCR transfer.", property.value.len());
                         file.write_all_at(&property.value, written)
@@ -1041,6 +1311,7 @@ fn handle_x11_event(
                             mime_type,
                             file: Some(file),
                             written: written + u64::try_from(property.value.len()).unwrap(),
+                            alternates,
                         }
                     }
                 }
@@ -1066,6 +1337,75 @@ fn handle_x11_event(
     Ok(())
 }
 
+/// Requests the next alternate representation of the `added` entry, first
+/// querying the selection's targets if they aren't known yet.
+fn next_alternate(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    (state, requestor, transfer_atom): (&mut State, Window, Atom),
+    added @ (id, stored): (u64, MimeType),
+    AlternateTargets {
+        selection,
+        remaining,
+        added: _,
+    }: AlternateTargets,
+) -> Result<(), CliError> {
+    let Some(mut remaining) = remaining else {
+        debug!("Querying targets to find alternates of entry {id}.");
+        *state = State::TargetsRequest {
+            allow_plain_text: true,
+            added: Some(added),
+        };
+        conn.convert_selection(
+            requestor,
+            selection,
+            atoms.TARGETS,
+            transfer_atom,
+            x11rb::CURRENT_TIME,
+        )?;
+        return Ok(());
+    };
+    let Some((mime_atom, mime_type)) = remaining.pop(&stored) else {
+        debug!("No more alternates to store for entry {id}.");
+        return Ok(());
+    };
+
+    info!("Requesting {mime_type:?} alternate on atom {mime_atom} for entry {id}.");
+    *state = State::PendingSelection {
+        mime_atom,
+        mime_type,
+        alternates: AlternateTargets {
+            selection,
+            remaining: Some(remaining),
+            added: Some(added),
+        },
+    };
+    conn.convert_selection(
+        requestor,
+        selection,
+        mime_atom,
+        transfer_atom,
+        x11rb::CURRENT_TIME,
+    )?;
+    Ok(())
+}
+
+fn store_alternate(
+    server: impl AsFd,
+    (id, _): (u64, MimeType),
+    mime_type: MimeType,
+    data: File,
+) -> Result<(), CliError> {
+    if let AddAlternateResponse { error: Some(e) } =
+        AddAlternateRequest::response(server, id, mime_type, data)?
+    {
+        warn!("Failed to store {mime_type:?} alternate of entry {id}: {e}");
+    } else {
+        info!("Stored {mime_type:?} alternate of entry {id}.");
+    }
+    Ok(())
+}
+
 fn handle_paste_event(
     conn: &RustConnection,
     atoms: &Atoms,
@@ -1075,13 +1415,14 @@ fn handle_paste_event(
     paste_window: Window,
     paste_socket: impl AsFd,
     ancillary_buf: &mut [u8; rustix::cmsg_space!(ScmRights(1))],
-    last_paste: &mut Option<(PasteFile, PasteAtom)>,
+    last_paste: &mut LastPaste,
     clear_selection_mask: &mut u8,
     auto_paste: bool,
+    paste_keycodes: &[Keycode],
 ) -> Result<(), CliError> {
     struct MoveToFrontGuard<'a, 'b, Server: AsFd>(
         Server,
-        &'a mut Option<(PasteFile, PasteAtom)>,
+        &'a mut LastPaste,
         &'b mut CopyDeduplication,
     );
 
@@ -1097,7 +1438,7 @@ fn handle_paste_event(
             else {
                 return;
             };
-            let Some((file, _)) = self.1 else {
+            let Some((file, _, _)) = self.1 else {
                 return;
             };
 
@@ -1115,6 +1456,7 @@ fn handle_paste_event(
     let (
         cmd @ PasteCommand {
             trigger_paste,
+            target,
             id,
             mime,
             ..
@@ -1139,11 +1481,7 @@ fn handle_paste_event(
         let data = Mmap::from(fd).map_io_err(|| "Failed to mmap paste file.")?;
         info!("Received paste buffer of length {}.", data.len());
         *move_to_front_guard.1 = Some((
-            if data.len() > MAX_TRANSFER_SIZE {
-                PasteFile::Large(Rc::new(data))
-            } else {
-                PasteFile::Small(data)
-            },
+            PasteFile::from(data),
             if let Some(a) = mime_atom {
                 a
             } else if let Some(r) = mime_atom_req.take() {
@@ -1157,8 +1495,31 @@ fn handle_paste_event(
                     is_text: true,
                 }
             },
+            Vec::new(),
         ));
     }
+    if let Some((_, PasteAtom { atom, .. }, alternates)) = move_to_front_guard.1 {
+        let mut cookies = Vec::new();
+        for (mime_type, file) in read_paste_alternates(id) {
+            cookies.push((
+                conn.intern_atom(false, mime_type.as_bytes())?,
+                mime_type,
+                file,
+            ));
+        }
+        conn.flush()?;
+        for (cookie, mime_type, file) in cookies {
+            let alternate_atom = cookie.reply()?.atom;
+            if alternate_atom == *atom {
+                continue;
+            }
+            match Mmap::from(&file) {
+                Ok(data) => alternates.push((alternate_atom, PasteFile::from(data))),
+                Err(e) => warn!("Failed to mmap {mime_type:?} alternate, not offering it: {e}"),
+            }
+        }
+        debug!("Offering {} alternates.", alternates.len());
+    }
 
     let Atoms {
         CLIPBOARD: clipboard_atom,
@@ -1167,10 +1528,25 @@ fn handle_paste_event(
         ..
     } = *atoms;
 
-    debug!("Claiming selection ownership.");
-    conn.set_selection_owner(paste_window, clipboard_atom, x11rb::CURRENT_TIME)?;
-    conn.set_selection_owner(paste_window, primary_atom, x11rb::CURRENT_TIME)?;
+    debug!("Claiming {target:?} selection ownership.");
     *clear_selection_mask = 0;
+    for (bit, selection, targeted) in [
+        (0, clipboard_atom, target != PasteTarget::Primary),
+        (1, primary_atom, target != PasteTarget::Clipboard),
+    ] {
+        if targeted {
+            conn.set_selection_owner(paste_window, selection, x11rb::CURRENT_TIME)?;
+            continue;
+        }
+
+        // Both selections share the paste buffer, so a selection we still own
+        // would otherwise start serving the new entry.
+        if conn.get_selection_owner(selection)?.reply()?.owner == paste_window {
+            debug!("Releasing ownership of untargeted selection {selection}.");
+            conn.set_selection_owner(x11rb::NONE, selection, x11rb::CURRENT_TIME)?;
+        }
+        *clear_selection_mask |= 1 << bit;
+    }
 
     if auto_paste && trigger_paste {
         trace!("Preparing to send paste command.");
@@ -1203,21 +1579,46 @@ fn handle_paste_event(
         if should_defer().ok() == Some(true) {
             debug!("Waiting for focus event to send paste command.");
         } else {
-            do_paste(conn, root)?;
+            do_paste(conn, root, paste_keycodes)?;
         }
     }
 
     Ok(())
 }
 
-fn do_paste(conn: &RustConnection, root: Window) -> Result<(), CliError> {
+fn resolve_keycodes(conn: &RustConnection, keysyms: &[u32]) -> Result<Vec<Keycode>, CliError> {
+    let Setup {
+        min_keycode,
+        max_keycode,
+        ..
+    } = *conn.setup();
+    let mapping = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+        .reply()?;
+    let keysyms_per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+
+    keysyms
+        .iter()
+        .map(|&keysym| {
+            mapping
+                .keysyms
+                .chunks_exact(keysyms_per_keycode)
+                .position(|syms| syms.contains(&keysym))
+                .map(|i| min_keycode + u8::try_from(i).unwrap())
+                .ok_or(CliError::UnmappedPasteKey(keysym))
+        })
+        .collect()
+}
+
+fn do_paste(conn: &RustConnection, root: Window, keycodes: &[Keycode]) -> Result<(), CliError> {
     let key = |type_, code| conn.xtest_fake_input(type_, code, x11rb::CURRENT_TIME, root, 1, 1, 0);
 
-    // Shift + Insert
-    key(KEY_PRESS_EVENT, 50)?;
-    key(KEY_PRESS_EVENT, 118)?;
-    key(KEY_RELEASE_EVENT, 118)?;
-    key(KEY_RELEASE_EVENT, 50)?;
+    for &code in keycodes {
+        key(KEY_PRESS_EVENT, code)?;
+    }
+    for &code in keycodes.iter().rev() {
+        key(KEY_RELEASE_EVENT, code)?;
+    }
     conn.flush()?;
     info!("Sent paste command.");
 